@@ -48,6 +48,7 @@ pub mod handlers;
 mod queue;
 mod rayon;
 mod rules;
+pub mod schema;
 pub mod stdlib;
 pub mod types;
 mod validation;
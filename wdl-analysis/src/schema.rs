@@ -0,0 +1,703 @@
+//! Generation of JSON Schemas for workflow and task inputs.
+//!
+//! The inputs of a workflow or task, along with their constant defaults and
+//! `parameter_meta` descriptions, are also available via [`inputs`].
+//!
+//! The generated schema describes an inputs JSON object as accepted by the
+//! engine: each input is a property named by its dotted path (e.g.
+//! `workflow.input` or `workflow.call.input`).
+//!
+//! Only constant default expressions (i.e. literals) are represented as
+//! schema defaults as other expressions cannot be evaluated without the
+//! values of other inputs.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use indexmap::IndexMap;
+use serde_json::Map;
+use serde_json::Value;
+use serde_json::json;
+use wdl_ast::AstNode;
+use wdl_ast::AstToken;
+use wdl_ast::SyntaxNode;
+use wdl_ast::v1::Expr;
+use wdl_ast::v1::InputSection;
+use wdl_ast::v1::LiteralExpr;
+use wdl_ast::v1::LiteralString;
+use wdl_ast::v1::MetadataValue;
+use wdl_ast::v1::ParameterMetadataSection;
+use wdl_ast::v1::StructDefinition;
+
+use crate::Document;
+use crate::document::Input;
+use crate::types::CallKind;
+use crate::types::CompoundType;
+use crate::types::Optional;
+use crate::types::PrimitiveType;
+use crate::types::Type;
+
+/// The URI of the JSON Schema dialect of generated schemas.
+pub const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Gets the text of a string literal.
+///
+/// Returns `None` if the string is interpolated.
+fn string_text(s: &LiteralString) -> Option<String> {
+    let text = s.text()?;
+    let mut buffer = String::new();
+    text.unescape_to(&mut buffer);
+    Some(buffer)
+}
+
+/// Gets the description from a `parameter_meta` value.
+///
+/// The description is either the value itself, if a string, or the
+/// `description` member of an object value.
+fn description(value: MetadataValue) -> Option<String> {
+    match value {
+        MetadataValue::String(s) => string_text(&s),
+        MetadataValue::Object(o) => o
+            .items()
+            .find(|i| i.name().text() == "description")
+            .and_then(|i| match i.value() {
+                MetadataValue::String(s) => string_text(&s),
+                _ => None,
+            }),
+        _ => None,
+    }
+}
+
+/// Collects the descriptions of the given `parameter_meta` sections.
+fn descriptions(
+    sections: impl Iterator<Item = ParameterMetadataSection>,
+) -> HashMap<String, String> {
+    let mut descriptions = HashMap::new();
+    for section in sections {
+        for item in section.items() {
+            if let Some(description) = description(item.value()) {
+                descriptions.insert(item.name().text().to_string(), description);
+            }
+        }
+    }
+
+    descriptions
+}
+
+/// Converts a constant expression to its JSON value.
+///
+/// Returns `None` if the expression is not constant.
+fn constant_value(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Literal(literal) => match literal {
+            LiteralExpr::Boolean(b) => Some(b.value().into()),
+            LiteralExpr::Integer(i) => i.value().map(Into::into),
+            LiteralExpr::Float(f) => f
+                .value()
+                .and_then(|v| serde_json::Number::from_f64(v).map(Value::Number)),
+            LiteralExpr::String(s) => string_text(s).map(Into::into),
+            LiteralExpr::Array(a) => a
+                .elements()
+                .map(|e| constant_value(&e))
+                .collect::<Option<_>>()
+                .map(Value::Array),
+            LiteralExpr::Pair(p) => {
+                let (left, right) = p.exprs();
+                Some(json!({
+                    "left": constant_value(&left)?,
+                    "right": constant_value(&right)?,
+                }))
+            }
+            LiteralExpr::Map(m) => m
+                .items()
+                .map(|i| {
+                    let (key, value) = i.key_value();
+                    let key = match constant_value(&key)? {
+                        Value::String(s) => s,
+                        Value::Null => return None,
+                        key => key.to_string(),
+                    };
+                    Some((key, constant_value(&value)?))
+                })
+                .collect::<Option<_>>()
+                .map(Value::Object),
+            LiteralExpr::Object(o) => o
+                .items()
+                .map(|i| {
+                    let (name, value) = i.name_value();
+                    Some((name.text().to_string(), constant_value(&value)?))
+                })
+                .collect::<Option<_>>()
+                .map(Value::Object),
+            LiteralExpr::Struct(s) => s
+                .items()
+                .map(|i| {
+                    let (name, value) = i.name_value();
+                    Some((name.text().to_string(), constant_value(&value)?))
+                })
+                .collect::<Option<_>>()
+                .map(Value::Object),
+            LiteralExpr::None(_) => Some(Value::Null),
+            LiteralExpr::Hints(_) | LiteralExpr::Input(_) | LiteralExpr::Output(_) => None,
+        },
+        Expr::Negation(e) => match e.operand() {
+            Expr::Literal(LiteralExpr::Integer(i)) => i.negate().map(Into::into),
+            Expr::Literal(LiteralExpr::Float(f)) => f
+                .value()
+                .and_then(|v| serde_json::Number::from_f64(-v).map(Value::Number)),
+            _ => None,
+        },
+        Expr::Parenthesized(e) => constant_value(&e.expr()),
+        _ => None,
+    }
+}
+
+/// Represents the annotations of the inputs of a workflow or task from its
+/// definition.
+#[derive(Default)]
+struct Annotations {
+    /// The default expressions of the inputs.
+    defaults: HashMap<String, Expr>,
+    /// The `parameter_meta` descriptions of the inputs.
+    descriptions: HashMap<String, String>,
+}
+
+impl Annotations {
+    /// Gets the annotations of the workflow or task with the given name.
+    fn new(document: &Document, name: &str) -> Self {
+        /// Creates annotations from a definition's sections.
+        fn from_sections(
+            input: Option<InputSection>,
+            parameter_meta: Option<ParameterMetadataSection>,
+        ) -> Annotations {
+            Annotations {
+                defaults: input
+                    .map(|s| {
+                        s.declarations()
+                            .filter_map(|d| Some((d.name().text().to_string(), d.expr()?)))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                descriptions: descriptions(parameter_meta.into_iter()),
+            }
+        }
+
+        let Some(ast) = document.root().ast().into_v1() else {
+            return Self::default();
+        };
+
+        if let Some(workflow) = ast.workflows().find(|w| w.name().text() == name) {
+            return from_sections(workflow.input(), workflow.parameter_metadata());
+        }
+
+        match ast.tasks().find(|t| t.name().text() == name) {
+            Some(task) => from_sections(task.input(), task.parameter_metadata()),
+            None => Self::default(),
+        }
+    }
+}
+
+/// Represents an input of a workflow or task along with its annotations.
+///
+/// The input may also be the input of a call within a workflow that allows
+/// nested inputs.
+#[derive(Debug, Clone)]
+pub struct AnnotatedInput {
+    /// The dotted path of the input (e.g. `workflow.call.input`).
+    path: String,
+    /// The type of the input.
+    ty: Type,
+    /// Whether or not the input is required.
+    required: bool,
+    /// Whether or not the input has a default expression.
+    has_default: bool,
+    /// The value of the input's default expression, if constant.
+    default: Option<Value>,
+    /// The `parameter_meta` description of the input.
+    description: Option<String>,
+}
+
+impl AnnotatedInput {
+    /// Gets the dotted path of the input (e.g. `workflow.call.input`).
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Gets the type of the input.
+    pub fn ty(&self) -> &Type {
+        &self.ty
+    }
+
+    /// Whether or not the input is required.
+    pub fn required(&self) -> bool {
+        self.required
+    }
+
+    /// Whether or not the input has a default expression.
+    pub fn has_default(&self) -> bool {
+        self.has_default
+    }
+
+    /// Gets the JSON value of the input's default expression.
+    ///
+    /// Returns `None` if the input has no default expression or if the default
+    /// expression is not constant.
+    pub fn default(&self) -> Option<&Value> {
+        self.default.as_ref()
+    }
+
+    /// Gets the `parameter_meta` description of the input.
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+/// Visits the inputs of a workflow or task.
+///
+/// The visitor is passed the document that defines each input.
+///
+/// Inputs that are specified by a call statement are skipped.
+fn visit_inputs(
+    document: &Document,
+    name: &str,
+    prefix: &str,
+    inputs: &IndexMap<String, Input>,
+    specified: Option<&HashSet<String>>,
+    visitor: &mut dyn FnMut(&Document, AnnotatedInput),
+) {
+    let mut annotations = Annotations::new(document, name);
+    for (name, input) in inputs {
+        if specified.map(|s| s.contains(name)).unwrap_or(false) {
+            continue;
+        }
+
+        visitor(
+            document,
+            AnnotatedInput {
+                path: format!("{prefix}.{name}"),
+                ty: input.ty().clone(),
+                required: input.required(),
+                has_default: annotations.defaults.contains_key(name),
+                default: annotations.defaults.get(name).and_then(constant_value),
+                description: annotations.descriptions.remove(name),
+            },
+        );
+    }
+}
+
+/// Visits the inputs of a workflow, including the inputs of its calls if the
+/// workflow allows nested inputs.
+fn visit_workflow_inputs(
+    document: &Document,
+    prefix: &str,
+    specified: Option<&HashSet<String>>,
+    visitor: &mut dyn FnMut(&Document, AnnotatedInput),
+) {
+    let workflow = document
+        .workflow()
+        .expect("document should have a workflow");
+    visit_inputs(
+        document,
+        workflow.name(),
+        prefix,
+        workflow.inputs(),
+        specified,
+        visitor,
+    );
+
+    if !workflow.allows_nested_inputs() {
+        return;
+    }
+
+    // Sort the calls so that the inputs are visited in a deterministic order
+    let mut calls: Vec<_> = workflow.calls().iter().collect();
+    calls.sort_by_key(|(a, _)| *a);
+    for (alias, call) in calls {
+        let document = call
+            .namespace()
+            .and_then(|ns| document.namespace(ns))
+            .map(|ns| ns.document())
+            .unwrap_or(document);
+        let prefix = format!("{prefix}.{alias}");
+        match call.kind() {
+            CallKind::Task => visit_inputs(
+                document,
+                call.name(),
+                &prefix,
+                call.inputs(),
+                Some(call.specified()),
+                visitor,
+            ),
+            CallKind::Workflow => {
+                visit_workflow_inputs(document, &prefix, Some(call.specified()), visitor)
+            }
+        }
+    }
+}
+
+/// Visits the inputs of the workflow or task with the given name.
+///
+/// Returns `false` if the document does not contain a workflow or task with
+/// the given name.
+fn visit(
+    document: &Document,
+    name: &str,
+    visitor: &mut dyn FnMut(&Document, AnnotatedInput),
+) -> bool {
+    match document.workflow() {
+        Some(workflow) if workflow.name() == name => {
+            visit_workflow_inputs(document, name, None, visitor)
+        }
+        _ => match document.task_by_name(name) {
+            Some(task) => visit_inputs(document, name, name, task.inputs(), None, visitor),
+            None => return false,
+        },
+    }
+
+    true
+}
+
+/// Generates the schema of a set of inputs.
+#[derive(Default)]
+struct Generator {
+    /// The properties of the inputs object.
+    properties: Map<String, Value>,
+    /// The names of the required properties.
+    required: Vec<Value>,
+    /// The schemas of the structs referenced by the inputs.
+    defs: Map<String, Value>,
+}
+
+impl Generator {
+    /// Adds an input to the schema.
+    fn add_input(&mut self, document: &Document, input: AnnotatedInput) {
+        let mut schema = self.type_schema(document, &input.ty);
+        if let Some(description) = input.description {
+            schema.insert("description".to_string(), description.into());
+        }
+
+        if let Some(default) = input.default {
+            schema.insert("default".to_string(), default);
+        }
+
+        if input.required {
+            self.required.push(input.path.clone().into());
+        }
+
+        self.properties.insert(input.path, schema.into());
+    }
+
+    /// Gets the schema of a type.
+    ///
+    /// The schemas of structs are added to `$defs` and referenced.
+    fn type_schema(&mut self, document: &Document, ty: &Type) -> Map<String, Value> {
+        let (schema, optional) = match ty {
+            Type::Primitive(ty, optional) => {
+                let ty = match ty {
+                    PrimitiveType::Boolean => "boolean",
+                    PrimitiveType::Integer => "integer",
+                    PrimitiveType::Float => "number",
+                    PrimitiveType::String | PrimitiveType::File | PrimitiveType::Directory => {
+                        "string"
+                    }
+                };
+                (json!({ "type": ty }), *optional)
+            }
+            Type::Compound(ty, optional) => {
+                let schema = match ty {
+                    CompoundType::Array(ty) => {
+                        let mut schema = json!({
+                            "type": "array",
+                            "items": self.type_schema(document, ty.element_type()),
+                        });
+                        if ty.is_non_empty() {
+                            schema["minItems"] = 1.into();
+                        }
+                        schema
+                    }
+                    CompoundType::Pair(ty) => json!({
+                        "type": "object",
+                        "properties": {
+                            "left": self.type_schema(document, ty.left_type()),
+                            "right": self.type_schema(document, ty.right_type()),
+                        },
+                        "required": ["left", "right"],
+                        "additionalProperties": false,
+                    }),
+                    CompoundType::Map(ty) => json!({
+                        "type": "object",
+                        "additionalProperties": self.type_schema(document, ty.value_type()),
+                    }),
+                    CompoundType::Struct(ty) => {
+                        let name = ty.name().as_str();
+                        if !self.defs.contains_key(name) {
+                            // Insert a placeholder so the struct is only generated once
+                            self.defs.insert(name.to_string(), Value::Null);
+                            let schema = self.struct_schema(document, name, ty.members());
+                            self.defs.insert(name.to_string(), schema);
+                        }
+
+                        json!({ "$ref": format!("#/$defs/{name}") })
+                    }
+                };
+                (schema, *optional)
+            }
+            Type::Object => (json!({ "type": "object" }), false),
+            Type::OptionalObject => (json!({ "type": "object" }), true),
+            _ => (json!({}), false),
+        };
+
+        let schema = if optional {
+            json!({ "anyOf": [schema, { "type": "null" }] })
+        } else {
+            schema
+        };
+
+        match schema {
+            Value::Object(schema) => schema,
+            _ => unreachable!("schema should be an object"),
+        }
+    }
+
+    /// Gets the schema of a struct with the given members.
+    fn struct_schema(
+        &mut self,
+        document: &Document,
+        name: &str,
+        members: &IndexMap<String, Type>,
+    ) -> Value {
+        let descriptions = document
+            .struct_by_name(name)
+            .and_then(|s| StructDefinition::cast(SyntaxNode::new_root(s.node().clone())))
+            .map(|s| descriptions(s.parameter_metadata()))
+            .unwrap_or_default();
+
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for (name, ty) in members {
+            let mut schema = self.type_schema(document, ty);
+            if let Some(description) = descriptions.get(name) {
+                schema.insert("description".to_string(), description.clone().into());
+            }
+
+            if !ty.is_optional() {
+                required.push(Value::from(name.as_str()));
+            }
+
+            properties.insert(name.clone(), schema.into());
+        }
+
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        })
+    }
+}
+
+/// Gets the inputs of the workflow or task with the given name.
+///
+/// If the workflow allows nested inputs, the inputs of its calls that are not
+/// specified by the call statements are included.
+///
+/// Returns `None` if the document does not contain a workflow or task with
+/// the given name.
+pub fn inputs(document: &Document, name: &str) -> Option<Vec<AnnotatedInput>> {
+    let mut inputs = Vec::new();
+    if !visit(document, name, &mut |_, input| inputs.push(input)) {
+        return None;
+    }
+
+    Some(inputs)
+}
+
+/// Generates a JSON Schema for the inputs of the workflow or task with the
+/// given name.
+///
+/// The schema describes an inputs JSON object for the workflow or task; if the
+/// workflow allows nested inputs, the inputs of its calls that are not
+/// specified by the call statements are included. Structs are described in
+/// the schema's `$defs`.
+///
+/// Returns `None` if the document does not contain a workflow or task with
+/// the given name.
+pub fn input_schema(document: &Document, name: &str) -> Option<Value> {
+    let mut generator = Generator::default();
+    if !visit(document, name, &mut |document, input| {
+        generator.add_input(document, input)
+    }) {
+        return None;
+    }
+
+    let mut schema = json!({
+        "$schema": SCHEMA_DIALECT,
+        "title": name,
+        "type": "object",
+        "properties": generator.properties,
+        "required": generator.required,
+        "additionalProperties": false,
+    });
+
+    if !generator.defs.is_empty() {
+        schema["$defs"] = generator.defs.into();
+    }
+
+    Some(schema)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::Analyzer;
+    use crate::path_to_uri;
+
+    #[tokio::test]
+    async fn it_generates_input_schemas() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let path = dir.path().join("foo.wdl");
+        fs::write(
+            &path,
+            r#"version 1.2
+
+struct Sample {
+    String name
+    Array[File]+ reads
+    Int? lane
+
+    parameter_meta {
+        name: "The name of the sample"
+    }
+}
+
+task align {
+    input {
+        Sample sample
+        Int threads = 4
+        Map[String, Float] weights = { "a": -1.5 }
+        String? prefix
+        String suffix = sample.name
+    }
+
+    parameter_meta {
+        threads: { description: "The number of threads", help: "ignored" }
+    }
+
+    command <<<>>>
+}
+
+workflow test {
+    input {
+        Array[Sample] samples
+        Pair[Boolean, Directory] options = (true, "out")
+    }
+
+    parameter_meta {
+        samples: "The samples to align"
+    }
+
+    hints {
+        allow_nested_inputs: true
+    }
+
+    call align { sample = samples[0] }
+}
+"#,
+        )
+        .expect("failed to create test file");
+
+        let analyzer = Analyzer::default();
+        analyzer
+            .add_document(path_to_uri(&path).expect("should convert to URI"))
+            .await
+            .expect("should add document");
+        let results = analyzer.analyze(()).await.unwrap();
+        let document = results[0].document();
+        assert!(!document.has_errors(), "document should not have errors");
+
+        let sample = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "description": "The name of the sample" },
+                "reads": { "type": "array", "items": { "type": "string" }, "minItems": 1 },
+                "lane": { "anyOf": [{ "type": "integer" }, { "type": "null" }] },
+            },
+            "required": ["name", "reads"],
+            "additionalProperties": false,
+        });
+
+        assert_eq!(
+            input_schema(document, "test").unwrap(),
+            json!({
+                "$schema": SCHEMA_DIALECT,
+                "title": "test",
+                "type": "object",
+                "properties": {
+                    "test.samples": {
+                        "type": "array",
+                        "items": { "$ref": "#/$defs/Sample" },
+                        "description": "The samples to align",
+                    },
+                    "test.options": {
+                        "type": "object",
+                        "properties": {
+                            "left": { "type": "boolean" },
+                            "right": { "type": "string" },
+                        },
+                        "required": ["left", "right"],
+                        "additionalProperties": false,
+                        "default": { "left": true, "right": "out" },
+                    },
+                    "test.align.threads": {
+                        "type": "integer",
+                        "description": "The number of threads",
+                        "default": 4,
+                    },
+                    "test.align.weights": {
+                        "type": "object",
+                        "additionalProperties": { "type": "number" },
+                        "default": { "a": -1.5 },
+                    },
+                    "test.align.prefix": {
+                        "anyOf": [{ "type": "string" }, { "type": "null" }],
+                    },
+                    "test.align.suffix": { "type": "string" },
+                },
+                "required": ["test.samples"],
+                "additionalProperties": false,
+                "$defs": { "Sample": sample },
+            })
+        );
+
+        let schema = input_schema(document, "align").unwrap();
+        assert_eq!(schema["required"], json!(["align.sample"]));
+        assert_eq!(
+            schema["properties"]["align.sample"],
+            json!({ "$ref": "#/$defs/Sample" })
+        );
+        assert_eq!(schema["$defs"]["Sample"], sample);
+
+        let inputs = inputs(document, "align").unwrap();
+        assert_eq!(
+            inputs.iter().map(|i| i.path()).collect::<Vec<_>>(),
+            [
+                "align.sample",
+                "align.threads",
+                "align.weights",
+                "align.prefix",
+                "align.suffix"
+            ]
+        );
+        assert_eq!(inputs[1].description(), Some("The number of threads"));
+        assert!(!inputs[3].has_default());
+        assert!(inputs[3].default().is_none());
+        assert!(inputs[4].default().is_none());
+
+        assert!(input_schema(document, "missing").is_none());
+    }
+}
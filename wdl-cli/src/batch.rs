@@ -0,0 +1,648 @@
+//! Facilities for evaluating a WDL task or workflow over the rows of a sample
+//! sheet.
+//!
+//! A sample sheet is a tab-separated values (TSV) file where the header line
+//! names the inputs and each subsequent line is a row of input values for a
+//! single run. The inputs of each row are merged on top of a set of base
+//! inputs; empty values are omitted so that the base or default value of the
+//! input is used.
+//!
+//! All rows are evaluated concurrently with a single evaluator, so the runs
+//! share one task execution backend and its task concurrency limits.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::path::absolute;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use futures::future::join_all;
+use tokio_util::sync::CancellationToken;
+use wdl_analysis::Document;
+use wdl_analysis::document::Task;
+use wdl_analysis::document::Workflow;
+use wdl_analysis::types::PrimitiveType;
+use wdl_analysis::types::Type;
+use wdl_engine::EvaluatedTask;
+use wdl_engine::EvaluationError;
+use wdl_engine::EvaluationResult;
+use wdl_engine::Events;
+use wdl_engine::Inputs as EngineInputs;
+use wdl_engine::Outputs;
+use wdl_engine::ValueSerializer;
+use wdl_engine::config::Config;
+use wdl_engine::v1::TaskEvaluator;
+use wdl_engine::v1::WorkflowEvaluator;
+
+use crate::Inputs;
+use crate::inputs::OriginPaths;
+
+/// The name of the file, within the output directory of a batch, containing
+/// the status of each row.
+pub const SUMMARY_FILE_NAME: &str = "summary.tsv";
+
+/// The name of the file, within the output directory of a batch, containing
+/// the outputs of each row.
+pub const OUTPUTS_FILE_NAME: &str = "outputs.tsv";
+
+/// Formats a value for a TSV cell.
+///
+/// Strings containing tabs or line breaks are written as JSON strings so that
+/// the cell remains on a single line.
+fn cell(value: serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) if !s.contains(['\t', '\n', '\r']) => s,
+        value => value.to_string(),
+    }
+}
+
+/// Represents a sample sheet.
+#[derive(Debug, Clone)]
+pub struct SampleSheet {
+    /// The directory that relative paths in the sample sheet are relative to.
+    origin: PathBuf,
+    /// The input names of the columns.
+    columns: Vec<String>,
+    /// The values of each row.
+    rows: Vec<Vec<String>>,
+}
+
+impl SampleSheet {
+    /// Reads a sample sheet from the given path.
+    ///
+    /// Relative paths in the sample sheet are relative to the directory
+    /// containing the sample sheet.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).with_context(|| {
+            format!(
+                "failed to read sample sheet `{path}`",
+                path = path.display()
+            )
+        })?;
+
+        let origin = absolute(path)
+            .ok()
+            .and_then(|p| p.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+
+        Self::parse(&contents, origin).with_context(|| {
+            format!(
+                "failed to parse sample sheet `{path}`",
+                path = path.display()
+            )
+        })
+    }
+
+    /// Parses a sample sheet from the given contents.
+    ///
+    /// Relative paths in the sample sheet are relative to the given origin
+    /// directory.
+    ///
+    /// Blank lines are ignored.
+    pub fn parse(contents: &str, origin: impl Into<PathBuf>) -> Result<Self> {
+        let mut lines = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+
+        let columns: Vec<String> = match lines.next() {
+            Some((_, header)) => header.split('\t').map(|c| c.trim().to_string()).collect(),
+            None => bail!("the sample sheet is missing a header line"),
+        };
+
+        for (i, column) in columns.iter().enumerate() {
+            if column.is_empty() {
+                bail!(
+                    "column {column} of the sample sheet header is empty",
+                    column = i + 1
+                );
+            }
+
+            if columns[..i].contains(column) {
+                bail!("the sample sheet header contains duplicate column `{column}`");
+            }
+        }
+
+        let rows = lines
+            .map(|(i, line)| {
+                let values: Vec<_> = line.split('\t').map(|v| v.trim().to_string()).collect();
+                if values.len() != columns.len() {
+                    bail!(
+                        "line {line} of the sample sheet has {values} value(s) but the header has \
+                         {columns} column(s)",
+                        line = i + 1,
+                        values = values.len(),
+                        columns = columns.len()
+                    );
+                }
+
+                Ok(values)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            origin: origin.into(),
+            columns,
+            rows,
+        })
+    }
+
+    /// Gets the input names of the columns of the sample sheet.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Gets the number of rows in the sample sheet.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Determines if the sample sheet has no rows.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Gets the declared types of the inputs of the given document that the
+    /// columns of the sample sheet may name, keyed by dotted path.
+    ///
+    /// If `entrypoint` is `None`, the task or workflow of each column is the
+    /// first component of the column name.
+    fn input_types(&self, document: &Document, entrypoint: Option<&str>) -> HashMap<String, Type> {
+        let mut names: Vec<_> = match entrypoint {
+            Some(entrypoint) => vec![entrypoint],
+            None => self
+                .columns
+                .iter()
+                .filter_map(|c| c.split_once('.').map(|(name, _)| name))
+                .collect(),
+        };
+        names.sort();
+        names.dedup();
+
+        names
+            .into_iter()
+            .filter_map(|name| wdl_analysis::schema::inputs(document, name))
+            .flatten()
+            .map(|input| (input.path().to_string(), input.ty().clone()))
+            .collect()
+    }
+
+    /// Gets the inputs of each row merged on top of the given base inputs.
+    ///
+    /// Values of inputs declared in the document as a `String`, `File`, or
+    /// `Directory` are used as is so that, for example, an identifier of `42`
+    /// remains a string; other values are coalesced as key-value pairs (see
+    /// [`Inputs::coalesce`]). If `entrypoint` is `Some(_)`, it is prefixed to
+    /// each column name.
+    pub fn inputs(
+        &self,
+        document: &Document,
+        base: &Inputs,
+        entrypoint: Option<&str>,
+    ) -> Result<Vec<Inputs>> {
+        let types = self.input_types(document, entrypoint);
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(i, values)| {
+                let mut strings = Vec::new();
+                let mut pairs = Vec::new();
+                for (column, value) in self
+                    .columns
+                    .iter()
+                    .zip(values)
+                    .filter(|(_, value)| !value.is_empty())
+                {
+                    let key = match entrypoint {
+                        Some(entrypoint) => format!("{entrypoint}.{column}"),
+                        None => column.clone(),
+                    };
+
+                    match types.get(&key).and_then(Type::as_primitive) {
+                        Some(
+                            PrimitiveType::String | PrimitiveType::File | PrimitiveType::Directory,
+                        ) => strings.push((key, value.clone())),
+                        _ => pairs.push(format!("{column}={value}")),
+                    }
+                }
+
+                let row = Inputs::coalesce(pairs, entrypoint.map(ToString::to_string))
+                    .with_context(|| format!("invalid inputs in row {row}", row = i + 1))?;
+
+                let mut inputs = base.clone();
+                inputs.extend(
+                    row.into_inner()
+                        .into_iter()
+                        .map(|(key, (_, value))| (key, (self.origin.clone(), value))),
+                );
+                inputs.extend(
+                    strings
+                        .into_iter()
+                        .map(|(key, value)| (key, (self.origin.clone(), value.into()))),
+                );
+                Ok(inputs)
+            })
+            .collect()
+    }
+}
+
+/// Represents the status of an evaluated row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowStatus {
+    /// The evaluation of the row succeeded.
+    Succeeded,
+    /// The evaluation of the row failed.
+    Failed,
+    /// The evaluation of the row was canceled.
+    Canceled,
+}
+
+impl fmt::Display for RowStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Succeeded => write!(f, "succeeded"),
+            Self::Failed => write!(f, "failed"),
+            Self::Canceled => write!(f, "canceled"),
+        }
+    }
+}
+
+/// Represents the result of evaluating a row.
+#[derive(Debug)]
+pub struct RowResult {
+    /// The one-based number of the row.
+    row: usize,
+    /// The output directory of the row.
+    dir: PathBuf,
+    /// The status of the row.
+    status: RowStatus,
+    /// The result of the row's evaluation.
+    result: EvaluationResult<Outputs>,
+}
+
+impl RowResult {
+    /// Gets the one-based number of the row.
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// Gets the output directory of the row.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Gets the status of the row.
+    pub fn status(&self) -> RowStatus {
+        self.status
+    }
+
+    /// Gets the result of the row's evaluation.
+    pub fn result(&self) -> &EvaluationResult<Outputs> {
+        &self.result
+    }
+
+    /// Gets the error message of the row.
+    ///
+    /// Returns `None` if the row's evaluation succeeded.
+    pub fn error(&self) -> Option<String> {
+        match &self.result {
+            Ok(_) => None,
+            Err(EvaluationError::Source(e)) => Some(format!(
+                "{path}: {message}",
+                path = e.document.path(),
+                message = e.diagnostic.message()
+            )),
+            Err(EvaluationError::Other(e)) => Some(format!("{e:#}")),
+        }
+    }
+}
+
+/// Represents the results of evaluating a batch of rows.
+#[derive(Debug)]
+pub struct BatchResults {
+    /// The results of each row, in row order.
+    rows: Vec<RowResult>,
+}
+
+impl BatchResults {
+    /// Gets the results of each row, in row order.
+    pub fn rows(&self) -> &[RowResult] {
+        &self.rows
+    }
+
+    /// Counts the rows with the given status.
+    pub fn count(&self, status: RowStatus) -> usize {
+        self.rows.iter().filter(|r| r.status == status).count()
+    }
+
+    /// Formats the status summary of the rows as TSV.
+    ///
+    /// The summary has a line for each row with the row's number, status,
+    /// output directory, and error message (if the row failed).
+    pub fn summary(&self) -> String {
+        let mut summary = String::from("row\tstatus\tdirectory\terror\n");
+        for row in &self.rows {
+            writeln!(
+                &mut summary,
+                "{number}\t{status}\t{dir}\t{error}",
+                number = row.row,
+                status = row.status,
+                dir = row.dir.display(),
+                error = row.error().map(|e| cell(e.into())).unwrap_or_default()
+            )
+            .expect("writing to a string should not fail");
+        }
+
+        summary
+    }
+
+    /// Formats the outputs of the rows as TSV.
+    ///
+    /// The table has a column for each output; the outputs of rows that did
+    /// not succeed are empty.
+    pub fn outputs(&self) -> String {
+        let names = self
+            .rows
+            .iter()
+            .find_map(|r| r.result.as_ref().ok())
+            .map(|o| o.iter().map(|(name, _)| name).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let mut table = String::from("row");
+        for name in &names {
+            table.push('\t');
+            table.push_str(name);
+        }
+        table.push('\n');
+
+        for row in &self.rows {
+            table.push_str(&row.row.to_string());
+            for name in &names {
+                table.push('\t');
+                if let Some(value) = row.result.as_ref().ok().and_then(|o| o.get(name)) {
+                    let value =
+                        serde_json::to_value(ValueSerializer::new(value, true)).unwrap_or_default();
+                    table.push_str(&cell(value));
+                }
+            }
+            table.push('\n');
+        }
+
+        table
+    }
+}
+
+/// Represents the task or workflow evaluated for each row.
+enum Target<'a> {
+    /// The target is a task.
+    Task(&'a Task, TaskEvaluator),
+    /// The target is a workflow.
+    Workflow(&'a Workflow, WorkflowEvaluator),
+}
+
+impl Target<'_> {
+    /// Evaluates the inputs of a single row.
+    async fn evaluate(
+        &self,
+        document: &Document,
+        name: &str,
+        inputs: Inputs,
+        dir: &Path,
+    ) -> EvaluationResult<Outputs> {
+        let (derived, inputs, origins) = match inputs.into_engine_inputs(document)? {
+            Some(inputs) => inputs,
+            None => (
+                name.to_string(),
+                match self {
+                    Target::Task(..) => EngineInputs::Task(Default::default()),
+                    Target::Workflow(..) => EngineInputs::Workflow(Default::default()),
+                },
+                OriginPaths::Map(Default::default()),
+            ),
+        };
+
+        if derived != name {
+            return Err(EvaluationError::Other(anyhow!(
+                "inputs are for `{derived}` but the batch is evaluating `{name}`"
+            )));
+        }
+
+        // Ensure all the paths specified in the inputs are relative to their
+        // respective origin paths.
+        let origin = |key: &str| {
+            origins
+                .get(key)
+                .ok_or(anyhow!("unable to find origin path for key `{key}`"))
+        };
+
+        match (self, inputs) {
+            (Target::Task(task, evaluator), EngineInputs::Task(mut inputs)) => {
+                inputs.join_paths(task, origin)?;
+                evaluator
+                    .evaluate(document, task, &inputs, dir)
+                    .await
+                    .and_then(EvaluatedTask::into_result)
+            }
+            (Target::Workflow(workflow, evaluator), EngineInputs::Workflow(mut inputs)) => {
+                inputs.join_paths(workflow, origin)?;
+                evaluator.evaluate(document, inputs, dir).await
+            }
+            _ => Err(EvaluationError::Other(anyhow!(
+                "inputs do not match the kind of `{name}`"
+            ))),
+        }
+    }
+}
+
+/// An evaluator of a WDL task or workflow over a batch of inputs.
+pub struct BatchEvaluator<'a> {
+    /// The document that contains the task or workflow to run.
+    document: &'a Document,
+
+    /// The name of the task or workflow to run.
+    name: &'a str,
+
+    /// The configuration for the WDL engine.
+    config: Config,
+
+    /// The output directory.
+    ///
+    /// Each row is evaluated in a subdirectory named by the row's number.
+    output_dir: &'a Path,
+}
+
+impl<'a> BatchEvaluator<'a> {
+    /// Creates a new batch evaluator.
+    pub fn new(
+        document: &'a Document,
+        name: &'a str,
+        config: Config,
+        output_dir: &'a Path,
+    ) -> Self {
+        Self {
+            document,
+            name,
+            config,
+            output_dir,
+        }
+    }
+
+    /// Evaluates the task or workflow for each of the given row inputs.
+    ///
+    /// The rows are evaluated concurrently; the failure of a row does not
+    /// affect the evaluation of the other rows.
+    ///
+    /// Upon completion, the status summary and outputs table of the rows are
+    /// written to the output directory.
+    pub async fn run(
+        self,
+        rows: Vec<Inputs>,
+        token: CancellationToken,
+        events: Events,
+    ) -> Result<BatchResults> {
+        let Self {
+            document,
+            name,
+            config,
+            output_dir,
+        } = self;
+
+        let target = match document.task_by_name(name) {
+            Some(task) => Target::Task(
+                task,
+                TaskEvaluator::new(config, token.clone(), events).await?,
+            ),
+            None => match document.workflow() {
+                Some(workflow) if workflow.name() == name => Target::Workflow(
+                    workflow,
+                    WorkflowEvaluator::new(config, token.clone(), events).await?,
+                ),
+                _ => bail!("document does not contain a task or workflow named `{name}`"),
+            },
+        };
+
+        let rows = join_all(rows.into_iter().enumerate().map(|(i, inputs)| {
+            let target = &target;
+            let token = &token;
+            async move {
+                let row = i + 1;
+                let dir = output_dir.join(row.to_string());
+                let result = target.evaluate(document, name, inputs, &dir).await;
+                let status = match &result {
+                    Ok(_) => RowStatus::Succeeded,
+                    Err(_) if token.is_cancelled() => RowStatus::Canceled,
+                    Err(_) => RowStatus::Failed,
+                };
+
+                RowResult {
+                    row,
+                    dir,
+                    status,
+                    result,
+                }
+            }
+        }))
+        .await;
+
+        let results = BatchResults { rows };
+        fs::create_dir_all(output_dir).with_context(|| {
+            format!(
+                "failed to create output directory `{dir}`",
+                dir = output_dir.display()
+            )
+        })?;
+
+        for (file_name, contents) in [
+            (SUMMARY_FILE_NAME, results.summary()),
+            (OUTPUTS_FILE_NAME, results.outputs()),
+        ] {
+            let path = output_dir.join(file_name);
+            fs::write(&path, contents)
+                .with_context(|| format!("failed to write `{path}`", path = path.display()))?;
+        }
+
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::Value;
+    use tempfile::TempDir;
+    use wdl_analysis::Analyzer;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_parses_sample_sheets() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            dir.path().join("source.wdl"),
+            r#"
+version 1.2
+
+task test {
+  input {
+    String name
+    File? reads
+    Int count = 0
+    Int threads = 1
+  }
+
+  command <<<>>>
+}
+"#,
+        )
+        .unwrap();
+
+        let analyzer = Analyzer::default();
+        analyzer
+            .add_directory(dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer.analyze(()).await.expect("failed to analyze");
+        let document = results[0].document();
+
+        let sheet = SampleSheet::parse(
+            "name\treads\tcount\n\nfoo\tfoo.fastq\t1\r\n42\t\t2\n",
+            "/data",
+        )
+        .unwrap();
+        assert_eq!(sheet.columns(), ["name", "reads", "count"]);
+        assert_eq!(sheet.len(), 2);
+
+        let base = Inputs::coalesce(["count=0", "threads=4"], Some("test".to_string())).unwrap();
+        let rows = sheet.inputs(document, &base, Some("test")).unwrap();
+        let (origin, value) = &rows[0]["test.reads"];
+        assert_eq!(origin, Path::new("/data"));
+        assert_eq!(value, &Value::from("foo.fastq"));
+        assert_eq!(rows[0]["test.count"].1, Value::from(1));
+        assert_eq!(rows[0]["test.threads"].1, Value::from(4));
+
+        // Empty values fall back to the base inputs and values of string inputs
+        // are not parsed
+        assert!(!rows[1].contains_key("test.reads"));
+        assert_eq!(rows[1]["test.name"].1, Value::from("42"));
+        assert_eq!(rows[1]["test.count"].1, Value::from(2));
+
+        let error = SampleSheet::parse("name\tname\n", "/data").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the sample sheet header contains duplicate column `name`"
+        );
+
+        let error = SampleSheet::parse("name\tcount\nfoo\n", "/data").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 2 of the sample sheet has 1 value(s) but the header has 2 column(s)"
+        );
+    }
+}
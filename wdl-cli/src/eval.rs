@@ -1,6 +1,7 @@
 //! Facilities for performing a typical WDL evaluation using the `wdl-*` crates.
 
 use std::path::Path;
+use std::sync::Arc;
 
 use anyhow::anyhow;
 use tokio_util::sync::CancellationToken;
@@ -11,6 +12,7 @@ use wdl_engine::EvaluationResult;
 use wdl_engine::Events;
 use wdl_engine::Inputs;
 use wdl_engine::Outputs;
+use wdl_engine::TaskExecutionBackend;
 use wdl_engine::config::Config;
 use wdl_engine::v1::TaskEvaluator;
 use wdl_engine::v1::WorkflowEvaluator;
@@ -36,6 +38,11 @@ pub struct Evaluator<'a> {
 
     /// The output directory.
     output_dir: &'a Path,
+
+    /// The task execution backend to use.
+    ///
+    /// If `None`, a backend is created from the configuration.
+    backend: Option<Arc<dyn TaskExecutionBackend>>,
 }
 
 impl<'a> Evaluator<'a> {
@@ -55,9 +62,19 @@ impl<'a> Evaluator<'a> {
             origins,
             config,
             output_dir,
+            backend: None,
         }
     }
 
+    /// Sets the task execution backend to use for the evaluation.
+    ///
+    /// The backend is expected to have been created from the evaluator's
+    /// configuration.
+    pub fn with_backend(mut self, backend: Arc<dyn TaskExecutionBackend>) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
     /// Runs a WDL task or workflow evaluation.
     pub async fn run(
         mut self,
@@ -81,7 +98,12 @@ impl<'a> Evaluator<'a> {
                         .ok_or(anyhow!("unable to find origin path for key `{key}`"))
                 })?;
 
-                let evaluator = TaskEvaluator::new(self.config, token, events).await?;
+                let evaluator = match self.backend {
+                    Some(backend) => {
+                        TaskEvaluator::with_backend(Arc::new(self.config), backend, token, events)?
+                    }
+                    None => TaskEvaluator::new(self.config, token, events).await?,
+                };
 
                 evaluator
                     .evaluate(self.document, task, inputs, self.output_dir)
@@ -109,7 +131,15 @@ impl<'a> Evaluator<'a> {
                         .ok_or(anyhow!("unable to find origin path for key `{key}`"))
                 })?;
 
-                let evaluator = WorkflowEvaluator::new(self.config, token, events).await?;
+                let evaluator = match self.backend {
+                    Some(backend) => WorkflowEvaluator::with_backend(
+                        Arc::new(self.config),
+                        backend,
+                        token,
+                        events,
+                    )?,
+                    None => WorkflowEvaluator::new(self.config, token, events).await?,
+                };
                 evaluator
                     .evaluate(self.document, inputs, self.output_dir)
                     .await
@@ -2,10 +2,13 @@
 //! command line.
 
 pub mod analysis;
+pub mod batch;
 pub mod eval;
 pub mod inputs;
+pub mod wes;
 
 pub use analysis::Analysis;
+pub use batch::BatchEvaluator;
 pub use eval::Evaluator;
 pub use inputs::Input;
 pub use inputs::Inputs;
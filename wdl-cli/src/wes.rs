@@ -0,0 +1,209 @@
+//! An implementation of the GA4GH Workflow Execution Service (WES) API.
+//!
+//! The [`Server`] accepts WDL runs over HTTP, evaluates them with the engine,
+//! and records their state, logs, and outputs in a local run store.
+//!
+//! See the [WES API specification](https://ga4gh.github.io/workflow-execution-service-schemas/)
+//! for more information.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+
+mod run;
+mod server;
+mod store;
+
+pub use server::Server;
+
+/// The base path of the WES API routes.
+pub const BASE_PATH: &str = "/ga4gh/wes/v1";
+
+/// The workflow type of WDL runs.
+pub const WORKFLOW_TYPE: &str = "WDL";
+
+/// The supported versions of WDL.
+pub const WORKFLOW_TYPE_VERSIONS: &[&str] = &["1.0", "1.1", "1.2"];
+
+/// Represents the state of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum State {
+    /// The state of the run is unknown.
+    Unknown,
+    /// The run is queued.
+    Queued,
+    /// The run's document is being analyzed and its inputs are being parsed.
+    Initializing,
+    /// The run is being evaluated.
+    Running,
+    /// The run is paused.
+    ///
+    /// This state is never reported by the server.
+    Paused,
+    /// The run completed successfully.
+    Complete,
+    /// The run failed because of its document, inputs, or a task's command.
+    ExecutorError,
+    /// The run failed because of an error unrelated to the workflow, such as a
+    /// failure to execute a task.
+    SystemError,
+    /// The run was canceled.
+    Canceled,
+    /// The run is being canceled.
+    Canceling,
+    /// The run was preempted.
+    ///
+    /// This state is never reported by the server.
+    Preempted,
+}
+
+impl State {
+    /// Determines if the state is terminal.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            Self::Complete | Self::ExecutorError | Self::SystemError | Self::Canceled
+        )
+    }
+}
+
+/// Represents a request to run a workflow or task.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RunRequest {
+    /// The inputs of the run in the JSON inputs format.
+    ///
+    /// If the inputs are empty, the document's workflow is run without inputs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workflow_params: Option<serde_json::Map<String, serde_json::Value>>,
+    /// The workflow type; must be `WDL`.
+    pub workflow_type: String,
+    /// The version of WDL of the run's document.
+    pub workflow_type_version: String,
+    /// The tags of the run.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+    /// The engine parameters of the run.
+    ///
+    /// Engine parameters are recorded with the run but are otherwise unused.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub workflow_engine_parameters: BTreeMap<String, String>,
+    /// The URL of the WDL document to run.
+    ///
+    /// This may be a remote URL, a local path, or a path relative to the
+    /// run's attachments.
+    pub workflow_url: String,
+}
+
+/// Represents the log of a run or a task attempt.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Log {
+    /// The name of the workflow or the identifier of the task.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The command line of the task.
+    ///
+    /// This is always empty as tasks are not run with a command line.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cmd: Vec<String>,
+    /// The time the run or attempt started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<DateTime<Utc>>,
+    /// The time the run or attempt ended.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<DateTime<Utc>>,
+    /// The URL of the attempt's standard output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<String>,
+    /// The URL of the attempt's standard error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+    /// The exit code of the attempt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// The messages of any errors that caused the run to fail.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub system_logs: Vec<String>,
+}
+
+/// Represents the full log of a run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunLog {
+    /// The identifier of the run.
+    pub run_id: String,
+    /// The request of the run.
+    pub request: RunRequest,
+    /// The state of the run.
+    pub state: State,
+    /// The log of the run.
+    pub run_log: Log,
+    /// The logs of the run's task attempts.
+    pub task_logs: Vec<Log>,
+    /// The outputs of the run.
+    ///
+    /// This is `None` until the run completes.
+    pub outputs: Option<serde_json::Value>,
+}
+
+/// Represents the state of a run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunStatus {
+    /// The identifier of the run.
+    pub run_id: String,
+    /// The state of the run.
+    pub state: State,
+}
+
+/// Represents the identifier of a run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunId {
+    /// The identifier of the run.
+    pub run_id: String,
+}
+
+/// Represents a page of runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunListResponse {
+    /// The runs, most recently submitted first.
+    pub runs: Vec<RunStatus>,
+    /// The token of the next page of runs.
+    ///
+    /// This is empty if there are no more runs.
+    pub next_page_token: String,
+}
+
+/// Represents the supported versions of a workflow type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkflowTypeVersion {
+    /// The supported versions.
+    pub workflow_type_version: Vec<String>,
+}
+
+/// Represents information about the service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceInfo {
+    /// The supported workflow types and their versions.
+    pub workflow_type_versions: HashMap<String, WorkflowTypeVersion>,
+    /// The supported versions of the WES API.
+    pub supported_wes_versions: Vec<String>,
+    /// The supported URL schemes of workflow URLs and inputs.
+    pub supported_filesystem_protocols: Vec<String>,
+    /// The versions of the workflow engine.
+    pub workflow_engine_versions: HashMap<String, String>,
+    /// The number of runs in each state.
+    pub system_state_counts: HashMap<State, u64>,
+}
+
+/// Represents an error response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    /// The error message.
+    pub msg: String,
+    /// The HTTP status code of the error.
+    pub status_code: u16,
+}
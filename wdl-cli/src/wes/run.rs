@@ -0,0 +1,290 @@
+//! Execution of WES runs.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::DateTime;
+use chrono::Utc;
+use tokio::sync::broadcast::error::TryRecvError;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+use wdl_analysis::Analyzer;
+use wdl_ast::Severity;
+use wdl_engine::EngineEvent;
+use wdl_engine::EngineEventKind;
+use wdl_engine::EvaluationError;
+use wdl_engine::Events;
+use wdl_engine::FailureKind;
+use wdl_engine::Inputs as EngineInputs;
+
+use super::Log;
+use super::RunLog;
+use super::State;
+use super::server::Shared;
+use super::store::RunStore;
+use crate::Evaluator;
+use crate::inputs::OriginPaths;
+
+/// The name of the directory, within a run's directory, containing the run's
+/// attachments.
+pub(super) const ATTACHMENTS_DIR_NAME: &str = "attachments";
+
+/// The name of the directory, within a run's directory, in which the run is
+/// evaluated.
+const OUTPUTS_DIR_NAME: &str = "outputs";
+
+/// The capacity of the engine events channel of a run.
+const EVENTS_CAPACITY: usize = 1000;
+
+/// Represents the failure of a run.
+struct Failure {
+    /// The terminal state of the run.
+    state: State,
+    /// The failure message.
+    message: String,
+}
+
+impl Failure {
+    /// Constructs a failure caused by the run's document, inputs, or a task's
+    /// command.
+    fn executor(message: impl Into<String>) -> Self {
+        Self {
+            state: State::ExecutorError,
+            message: message.into(),
+        }
+    }
+
+    /// Constructs a failure caused by an error unrelated to the workflow.
+    fn system(message: impl Into<String>) -> Self {
+        Self {
+            state: State::SystemError,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<EvaluationError> for Failure {
+    fn from(e: EvaluationError) -> Self {
+        match e {
+            EvaluationError::Source(e) => {
+                let message = format!(
+                    "{path}: {message}",
+                    path = e.document.path(),
+                    message = e.diagnostic.message()
+                );
+                match e.failure {
+                    Some(FailureKind::System) => Self::system(message),
+                    Some(FailureKind::Command) | None => Self::executor(message),
+                }
+            }
+            EvaluationError::Other(e) => Self::system(format!("{e:#}")),
+        }
+    }
+}
+
+/// Gets the URL of a file in an attempt directory.
+fn file_url(attempt_dir: &Path, name: &str) -> Option<String> {
+    Url::from_file_path(attempt_dir.join(name))
+        .ok()
+        .map(Into::into)
+}
+
+/// Records the end of the most recent attempt of the given task.
+fn finish_attempt(log: &mut RunLog, id: &str, time: DateTime<Utc>, exit_code: Option<i32>) {
+    if let Some(attempt) = log
+        .task_logs
+        .iter_mut()
+        .rev()
+        .find(|l| l.name.as_deref() == Some(id))
+        && attempt.end_time.is_none()
+    {
+        attempt.end_time = Some(time);
+        attempt.exit_code = exit_code;
+    }
+}
+
+/// Records the task attempts of a run from an engine event.
+fn record_event(store: &RunStore, id: &str, event: EngineEvent) {
+    store.update(id, |log| match event.kind {
+        EngineEventKind::TaskStarted { attempt_dir, .. } => log.task_logs.push(Log {
+            name: Some(event.id),
+            start_time: Some(event.time),
+            stdout: file_url(&attempt_dir, "stdout"),
+            stderr: file_url(&attempt_dir, "stderr"),
+            ..Default::default()
+        }),
+        EngineEventKind::TaskReused {
+            attempt_dir,
+            exit_code,
+            ..
+        } => log.task_logs.push(Log {
+            name: Some(event.id),
+            start_time: Some(event.time),
+            end_time: Some(event.time),
+            stdout: file_url(&attempt_dir, "stdout"),
+            stderr: file_url(&attempt_dir, "stderr"),
+            exit_code: Some(exit_code),
+            ..Default::default()
+        }),
+        EngineEventKind::TaskCompleted { exit_code, .. } => {
+            finish_attempt(log, &event.id, event.time, Some(exit_code))
+        }
+        EngineEventKind::TaskRetried { exit_code, .. }
+        | EngineEventKind::TaskFailed { exit_code, .. } => {
+            finish_attempt(log, &event.id, event.time, exit_code)
+        }
+        EngineEventKind::TaskTimedOut { .. } => finish_attempt(log, &event.id, event.time, None),
+        _ => {}
+    });
+}
+
+/// Analyzes and evaluates a run's document.
+///
+/// Returns the outputs of the run.
+async fn evaluate(
+    shared: &Shared,
+    id: &str,
+    url: Url,
+    params: serde_json::Map<String, serde_json::Value>,
+    token: CancellationToken,
+) -> Result<serde_json::Value, Failure> {
+    let analyzer = Analyzer::default();
+    analyzer
+        .add_document(url.clone())
+        .await
+        .map_err(|e| Failure::system(format!("failed to add document `{url}`: {e:#}")))?;
+    let results = analyzer
+        .analyze(())
+        .await
+        .map_err(|e| Failure::system(format!("failed to analyze document `{url}`: {e:#}")))?;
+    let result = results
+        .iter()
+        .find(|r| r.document().uri().as_ref() == &url)
+        .ok_or_else(|| Failure::system(format!("document `{url}` was not analyzed")))?;
+
+    if let Some(e) = result.error() {
+        return Err(Failure::system(format!(
+            "failed to read document `{url}`: {e:#}"
+        )));
+    }
+
+    let document = result.document();
+    if document.has_errors() {
+        let errors = results
+            .iter()
+            .flat_map(|r| {
+                r.document()
+                    .diagnostics()
+                    .iter()
+                    .filter(|d| d.severity() == Severity::Error)
+                    .map(|d| {
+                        format!(
+                            "{path}: {message}",
+                            path = r.document().path(),
+                            message = d.message()
+                        )
+                    })
+            })
+            .collect::<Vec<_>>();
+        return Err(Failure::executor(format!(
+            "document `{url}` has errors:\n{errors}",
+            errors = errors.join("\n")
+        )));
+    }
+
+    let (name, inputs) = match EngineInputs::parse_object(document, params)
+        .map_err(|e| Failure::executor(format!("invalid inputs: {e:#}")))?
+    {
+        Some(inputs) => inputs,
+        None => {
+            let workflow = document.workflow().ok_or_else(|| {
+                Failure::executor("inputs must be provided to run a document without a workflow")
+            })?;
+            (
+                workflow.name().to_string(),
+                EngineInputs::Workflow(Default::default()),
+            )
+        }
+    };
+
+    shared.store.update(id, |log| {
+        log.state = State::Running;
+        log.run_log.name = Some(name.clone());
+    });
+
+    let dir = shared.store.run_dir(id);
+    let outputs_dir = dir.join(OUTPUTS_DIR_NAME);
+    let events = Events::engine_only(EVENTS_CAPACITY);
+    let mut receiver = events
+        .subscribe_engine()
+        .expect("engine events should be enabled");
+    let evaluation = Evaluator::new(
+        document,
+        &name,
+        inputs,
+        OriginPaths::from(dir.join(ATTACHMENTS_DIR_NAME)),
+        shared.config.clone(),
+        &outputs_dir,
+    )
+    .with_backend(shared.backend.clone())
+    .run(token, events);
+    tokio::pin!(evaluation);
+
+    // Record the task attempts of the run while it is evaluated
+    let result = loop {
+        tokio::select! {
+            result = &mut evaluation => break result,
+            Ok(event) = receiver.recv() => record_event(&shared.store, id, event),
+        }
+    };
+
+    loop {
+        match receiver.try_recv() {
+            Ok(event) => record_event(&shared.store, id, event),
+            Err(TryRecvError::Lagged(_)) => continue,
+            Err(_) => break,
+        }
+    }
+
+    let outputs = result?;
+    serde_json::to_value(outputs.with_name(name.as_str()))
+        .map_err(|e| Failure::system(format!("failed to serialize outputs: {e}")))
+}
+
+/// Executes a submitted run.
+///
+/// The document of the run is at the given URL.
+pub(super) async fn execute(shared: Arc<Shared>, id: String, url: Url, token: CancellationToken) {
+    let params = shared
+        .store
+        .get(&id)
+        .and_then(|log| log.request.workflow_params)
+        .unwrap_or_default();
+
+    shared.store.update(&id, |log| {
+        log.state = State::Initializing;
+        log.run_log.start_time = Some(Utc::now());
+    });
+
+    let result = evaluate(&shared, &id, url, params, token.clone()).await;
+    shared.store.update(&id, |log| {
+        log.run_log.end_time = Some(Utc::now());
+        match result {
+            Ok(outputs) => {
+                log.state = State::Complete;
+                log.outputs = Some(outputs);
+            }
+            Err(_) if token.is_cancelled() => log.state = State::Canceled,
+            Err(failure) => {
+                log.state = failure.state;
+                log.run_log.system_logs.push(failure.message);
+            }
+        }
+    });
+
+    shared
+        .tokens
+        .lock()
+        .expect("failed to lock tokens")
+        .remove(&id);
+}
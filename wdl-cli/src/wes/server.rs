@@ -0,0 +1,512 @@
+//! The WES API server.
+
+use std::collections::HashMap;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use anyhow::Result;
+use axum::Json;
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::FromRequest;
+use axum::extract::Multipart;
+use axum::extract::Path as UrlPath;
+use axum::extract::Query;
+use axum::extract::Request;
+use axum::extract::State as AppState;
+use axum::http::StatusCode;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use axum::routing::post;
+use serde::Deserialize;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+use url::Url;
+use wdl_engine::TaskExecutionBackend;
+use wdl_engine::config::Config;
+use wdl_engine::path::parse_url;
+
+use super::BASE_PATH;
+use super::ErrorResponse;
+use super::RunId;
+use super::RunListResponse;
+use super::RunLog;
+use super::RunRequest;
+use super::RunStatus;
+use super::ServiceInfo;
+use super::State;
+use super::WORKFLOW_TYPE;
+use super::WORKFLOW_TYPE_VERSIONS;
+use super::WorkflowTypeVersion;
+use super::run::ATTACHMENTS_DIR_NAME;
+use super::run::execute;
+use super::store::RunStore;
+
+/// The default number of runs in a page of runs.
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// The supported WES API versions.
+const SUPPORTED_WES_VERSIONS: &[&str] = &["1.1.0"];
+
+/// The URL schemes supported for workflow URLs and inputs.
+const SUPPORTED_FILESYSTEM_PROTOCOLS: &[&str] = &["file", "http", "https", "s3", "gs", "az"];
+
+/// Represents the state shared by the server's request handlers and runs.
+pub(super) struct Shared {
+    /// The engine configuration used to evaluate runs.
+    pub config: Config,
+    /// The task execution backend shared by every run.
+    ///
+    /// Sharing the backend applies its concurrency and resource limits across
+    /// runs rather than to each run separately.
+    pub backend: Arc<dyn TaskExecutionBackend>,
+    /// The run store.
+    pub store: RunStore,
+    /// The cancellation tokens of the runs that have not finished.
+    pub tokens: Mutex<HashMap<String, CancellationToken>>,
+}
+
+/// Represents an error response of the API.
+#[derive(Debug)]
+struct ApiError {
+    /// The HTTP status code of the error.
+    status: StatusCode,
+    /// The error message.
+    msg: String,
+}
+
+impl ApiError {
+    /// Constructs an error for an invalid request.
+    fn bad_request(msg: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            msg: msg.into(),
+        }
+    }
+
+    /// Constructs an error for a run that does not exist.
+    fn not_found(id: &str) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            msg: format!("run `{id}` does not exist"),
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            msg: format!("{e:#}"),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let response = ErrorResponse {
+            msg: self.msg,
+            status_code: self.status.as_u16(),
+        };
+        (self.status, Json(response)).into_response()
+    }
+}
+
+/// The result type of the request handlers.
+type ApiResult<T> = std::result::Result<Json<T>, ApiError>;
+
+/// Represents the query parameters for listing runs.
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    /// The maximum number of runs to list.
+    page_size: Option<usize>,
+    /// The token of the page of runs to list.
+    page_token: Option<String>,
+}
+
+/// Represents an attachment of a run request.
+struct Attachment {
+    /// The path of the attachment relative to the run's attachments directory.
+    path: PathBuf,
+    /// The contents of the attachment.
+    data: Bytes,
+}
+
+/// Parses the path of an attachment.
+///
+/// The path must be relative and must not contain parent directory components.
+fn attachment_path(name: &str) -> std::result::Result<PathBuf, ApiError> {
+    let path = Path::new(name);
+    if name.is_empty() || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(ApiError::bad_request(format!(
+            "invalid attachment path `{name}`: the path must be relative and cannot contain `..`"
+        )));
+    }
+
+    Ok(path.to_path_buf())
+}
+
+/// Parses a JSON-encoded form field.
+fn json_field<T: serde::de::DeserializeOwned>(
+    name: &str,
+    data: &[u8],
+) -> std::result::Result<T, ApiError> {
+    serde_json::from_slice(data)
+        .map_err(|e| ApiError::bad_request(format!("invalid form field `{name}`: {e}")))
+}
+
+/// Parses a `multipart/form-data` run request.
+async fn parse_form(
+    mut form: Multipart,
+) -> std::result::Result<(RunRequest, Vec<Attachment>), ApiError> {
+    let mut request = RunRequest::default();
+    let mut attachments = Vec::new();
+    while let Some(field) = form
+        .next_field()
+        .await
+        .map_err(|e| ApiError::bad_request(format!("invalid multipart form: {e}")))?
+    {
+        let name = field
+            .name()
+            .ok_or_else(|| ApiError::bad_request("multipart part is missing a field name"))?
+            .to_string();
+        let filename = field.file_name().map(ToString::to_string);
+        let data = field.bytes().await.map_err(|e| {
+            ApiError::bad_request(format!("failed to read form field `{name}`: {e}"))
+        })?;
+        let text = || {
+            String::from_utf8(data.to_vec())
+                .map_err(|_| ApiError::bad_request(format!("form field `{name}` is not UTF-8")))
+        };
+
+        match name.as_str() {
+            "workflow_params" if data.iter().all(u8::is_ascii_whitespace) => {}
+            "workflow_params" => request.workflow_params = Some(json_field(&name, &data)?),
+            "workflow_type" => request.workflow_type = text()?,
+            "workflow_type_version" => request.workflow_type_version = text()?,
+            "tags" => request.tags = json_field(&name, &data)?,
+            "workflow_engine_parameters" => {
+                request.workflow_engine_parameters = json_field(&name, &data)?
+            }
+            "workflow_url" => request.workflow_url = text()?,
+            "workflow_attachment" => {
+                let filename = filename.as_deref().ok_or_else(|| {
+                    ApiError::bad_request("form field `workflow_attachment` must be a file")
+                })?;
+                attachments.push(Attachment {
+                    path: attachment_path(filename)?,
+                    data,
+                });
+            }
+            name => {
+                return Err(ApiError::bad_request(format!(
+                    "unknown form field `{name}`"
+                )));
+            }
+        }
+    }
+
+    Ok((request, attachments))
+}
+
+/// Gets the URL of a run request's document.
+///
+/// Returns `Ok(None)` if the document is an attachment of the run.
+fn workflow_url(
+    request: &RunRequest,
+    attachments: &[Attachment],
+) -> std::result::Result<Option<Url>, ApiError> {
+    let url = &request.workflow_url;
+    if let Some(url) = parse_url(url) {
+        return Ok(Some(url));
+    }
+
+    let path = Path::new(url);
+    if path.is_absolute() {
+        return Url::from_file_path(path)
+            .map(Some)
+            .map_err(|_| ApiError::bad_request(format!("invalid workflow URL `{url}`")));
+    }
+
+    if attachments.iter().any(|a| a.path == path) {
+        return Ok(None);
+    }
+
+    Err(ApiError::bad_request(format!(
+        "workflow URL `{url}` is not a URL, an absolute path, or the path of an attachment"
+    )))
+}
+
+/// Handles a request for information about the service.
+async fn service_info(AppState(shared): AppState<Arc<Shared>>) -> Json<ServiceInfo> {
+    Json(ServiceInfo {
+        workflow_type_versions: HashMap::from([(
+            WORKFLOW_TYPE.to_string(),
+            WorkflowTypeVersion {
+                workflow_type_version: WORKFLOW_TYPE_VERSIONS
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+            },
+        )]),
+        supported_wes_versions: SUPPORTED_WES_VERSIONS
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        supported_filesystem_protocols: SUPPORTED_FILESYSTEM_PROTOCOLS
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        workflow_engine_versions: HashMap::from([(
+            env!("CARGO_PKG_NAME").to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        )]),
+        system_state_counts: shared.store.state_counts(),
+    })
+}
+
+/// Handles a request to list runs.
+async fn list_runs(
+    AppState(shared): AppState<Arc<Shared>>,
+    Query(query): Query<ListQuery>,
+) -> ApiResult<RunListResponse> {
+    let skip = match query.page_token.as_deref() {
+        None | Some("") => 0,
+        Some(token) => token
+            .parse()
+            .map_err(|_| ApiError::bad_request(format!("invalid page token `{token}`")))?,
+    };
+
+    let count = query.page_size.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+    let (runs, more) = shared.store.list(skip, count);
+    Ok(Json(RunListResponse {
+        runs,
+        next_page_token: if more {
+            (skip + count).to_string()
+        } else {
+            String::new()
+        },
+    }))
+}
+
+/// Handles a request to submit a run.
+///
+/// Runs may be submitted as `multipart/form-data`, as required by the WES
+/// specification, or as a JSON run request without attachments.
+async fn submit_run(AppState(shared): AppState<Arc<Shared>>, request: Request) -> ApiResult<RunId> {
+    let content_type = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    let (request, attachments) = if content_type.starts_with("multipart/form-data") {
+        let form = Multipart::from_request(request, &())
+            .await
+            .map_err(|e| ApiError::bad_request(e.body_text()))?;
+        parse_form(form).await?
+    } else if content_type.starts_with("application/json") {
+        let body = Bytes::from_request(request, &())
+            .await
+            .map_err(|e| ApiError::bad_request(e.body_text()))?;
+        let request = serde_json::from_slice(&body)
+            .map_err(|e| ApiError::bad_request(format!("invalid run request: {e}")))?;
+        (request, Vec::new())
+    } else {
+        return Err(ApiError::bad_request(format!(
+            "unsupported content type `{content_type}`: expected `multipart/form-data` or \
+             `application/json`"
+        )));
+    };
+
+    if !request.workflow_type.eq_ignore_ascii_case(WORKFLOW_TYPE) {
+        return Err(ApiError::bad_request(format!(
+            "unsupported workflow type `{ty}`: expected `{WORKFLOW_TYPE}`",
+            ty = request.workflow_type
+        )));
+    }
+
+    if !WORKFLOW_TYPE_VERSIONS.contains(&request.workflow_type_version.as_str()) {
+        return Err(ApiError::bad_request(format!(
+            "unsupported workflow type version `{version}`",
+            version = request.workflow_type_version
+        )));
+    }
+
+    let url = workflow_url(&request, &attachments)?;
+    let path = request.workflow_url.clone();
+    let id = shared.store.create(request)?;
+    let attachments_dir = shared.store.run_dir(&id).join(ATTACHMENTS_DIR_NAME);
+    for attachment in attachments {
+        let path = attachments_dir.join(&attachment.path);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.with_context(|| {
+                format!(
+                    "failed to create directory `{parent}`",
+                    parent = parent.display()
+                )
+            })?;
+        }
+
+        tokio::fs::write(&path, attachment.data)
+            .await
+            .with_context(|| format!("failed to write file `{path}`", path = path.display()))?;
+    }
+
+    let url = match url {
+        Some(url) => url,
+        None => Url::from_file_path(attachments_dir.join(path))
+            .map_err(|_| anyhow::anyhow!("invalid attachments directory"))?,
+    };
+
+    info!("submitted run `{id}` of `{url}`");
+
+    let token = CancellationToken::new();
+    shared
+        .tokens
+        .lock()
+        .expect("failed to lock tokens")
+        .insert(id.clone(), token.clone());
+    tokio::spawn(execute(shared.clone(), id.clone(), url, token));
+    Ok(Json(RunId { run_id: id }))
+}
+
+/// Handles a request for the log of a run.
+async fn get_run(
+    AppState(shared): AppState<Arc<Shared>>,
+    UrlPath(id): UrlPath<String>,
+) -> ApiResult<RunLog> {
+    shared
+        .store
+        .get(&id)
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found(&id))
+}
+
+/// Handles a request for the state of a run.
+async fn get_run_status(
+    AppState(shared): AppState<Arc<Shared>>,
+    UrlPath(id): UrlPath<String>,
+) -> ApiResult<RunStatus> {
+    let log = shared
+        .store
+        .get(&id)
+        .ok_or_else(|| ApiError::not_found(&id))?;
+    Ok(Json(RunStatus {
+        run_id: log.run_id,
+        state: log.state,
+    }))
+}
+
+/// Handles a request to cancel a run.
+///
+/// Canceling a run that has finished has no effect.
+async fn cancel_run(
+    AppState(shared): AppState<Arc<Shared>>,
+    UrlPath(id): UrlPath<String>,
+) -> ApiResult<RunId> {
+    if shared.store.get(&id).is_none() {
+        return Err(ApiError::not_found(&id));
+    }
+
+    let token = shared
+        .tokens
+        .lock()
+        .expect("failed to lock tokens")
+        .get(&id)
+        .cloned();
+    if let Some(token) = token {
+        info!("canceling run `{id}`");
+        token.cancel();
+        shared.store.update(&id, |log| {
+            if !log.state.is_terminal() {
+                log.state = State::Canceling;
+            }
+        });
+    }
+
+    Ok(Json(RunId { run_id: id }))
+}
+
+/// Represents a WES API server.
+///
+/// Runs are evaluated with the engine configuration of the server, using a
+/// single task execution backend, and are recorded in a local run store; the
+/// run store is reloaded when a server is created for the same directory.
+#[derive(Clone)]
+pub struct Server {
+    /// The state shared with the request handlers.
+    shared: Arc<Shared>,
+}
+
+impl Server {
+    /// Creates a new WES server with the given engine configuration that stores
+    /// runs in the given directory.
+    pub async fn new(config: Config, dir: impl AsRef<Path>) -> Result<Self> {
+        config.validate()?;
+
+        let dir = dir.as_ref();
+        let dir = std::path::absolute(dir).with_context(|| {
+            format!(
+                "failed to determine absolute path of `{dir}`",
+                dir = dir.display()
+            )
+        })?;
+
+        let store = RunStore::open(dir)?;
+        let backend = Arc::new(config.clone()).create_backend(None).await?;
+
+        Ok(Self {
+            shared: Arc::new(Shared {
+                config,
+                backend,
+                store,
+                tokens: Default::default(),
+            }),
+        })
+    }
+
+    /// Gets the router of the server's API.
+    ///
+    /// The API routes are nested under [`BASE_PATH`].
+    pub fn router(&self) -> Router {
+        Router::new().nest(
+            BASE_PATH,
+            Router::new()
+                .route("/service-info", get(service_info))
+                .route("/runs", get(list_runs).post(submit_run))
+                .route("/runs/{run_id}", get(get_run))
+                .route("/runs/{run_id}/status", get(get_run_status))
+                .route("/runs/{run_id}/cancel", post(cancel_run))
+                .with_state(self.shared.clone()),
+        )
+    }
+
+    /// Serves the API on the given listener.
+    pub async fn serve(&self, listener: TcpListener) -> Result<()> {
+        axum::serve(listener, self.router())
+            .await
+            .context("failed to serve the WES API")
+    }
+
+    /// Cancels every run that has not finished.
+    pub fn cancel_all(&self) {
+        for token in self
+            .shared
+            .tokens
+            .lock()
+            .expect("failed to lock tokens")
+            .values()
+        {
+            token.cancel();
+        }
+    }
+}
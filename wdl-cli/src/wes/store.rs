@@ -0,0 +1,357 @@
+//! The local store of WES runs.
+//!
+//! Each run has a directory in the store's directory named by the run's
+//! identifier; the directory contains a `run.json` record of the run along
+//! with the run's attachments and outputs.
+//!
+//! Runs are kept in memory; changes to a run are persisted by a dedicated
+//! writer thread so that updating a run never blocks on the file system. The
+//! writer coalesces the changes made to a run while it was busy into a single
+//! write of the run's record.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::io::BufWriter;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::thread;
+use std::thread::JoinHandle;
+
+use anyhow::Context;
+use anyhow::Result;
+use chrono::DateTime;
+use chrono::Utc;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::Log;
+use super::RunLog;
+use super::RunRequest;
+use super::RunStatus;
+use super::State;
+
+/// The name of the file, within a run's directory, containing the run's
+/// record.
+const RECORD_FILE_NAME: &str = "run.json";
+
+/// Represents the persisted record of a run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RunRecord {
+    /// The time the run was submitted.
+    submitted: DateTime<Utc>,
+    /// The log of the run.
+    #[serde(flatten)]
+    log: RunLog,
+}
+
+/// The runs of a store in the order they were submitted.
+type Runs = Mutex<IndexMap<String, RunRecord>>;
+
+/// Persists the records of the runs with the identifiers received by the run
+/// store writer thread until every sender has been dropped.
+///
+/// Pending writes of the same run are coalesced so that its record is written
+/// once with its latest changes.
+///
+/// Failing to persist a run does not fail the run, so errors are logged as
+/// warnings.
+fn write_runs(dir: &Path, runs: &Runs, receiver: mpsc::Receiver<String>) {
+    while let Ok(id) = receiver.recv() {
+        let ids: HashSet<_> = std::iter::once(id).chain(receiver.try_iter()).collect();
+        for id in ids {
+            // Only hold the lock long enough to copy the record
+            let Some(record) = runs.lock().expect("failed to lock runs").get(&id).cloned() else {
+                continue;
+            };
+
+            if let Err(e) = write_record(&dir.join(&id), &record) {
+                warn!("failed to persist run `{id}`: {e:#}");
+            }
+        }
+    }
+}
+
+/// Represents a local store of runs.
+#[derive(Debug)]
+pub(super) struct RunStore {
+    /// The directory of the store.
+    dir: PathBuf,
+    /// The runs in the store in the order they were submitted.
+    runs: Arc<Runs>,
+    /// The sender of run identifiers to persist to the writer thread.
+    ///
+    /// This is only `None` when the store is dropped.
+    sender: Option<mpsc::Sender<String>>,
+    /// The handle of the writer thread.
+    ///
+    /// This is only `None` when the store is dropped.
+    writer: Option<JoinHandle<()>>,
+}
+
+impl RunStore {
+    /// Opens the run store in the given directory.
+    ///
+    /// The directory is created if it does not exist.
+    ///
+    /// Runs that had not finished when the store was last used are marked as
+    /// having failed with a system error.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).with_context(|| {
+            format!(
+                "failed to create run store directory `{dir}`",
+                dir = dir.display()
+            )
+        })?;
+
+        let mut records = Vec::new();
+        for entry in fs::read_dir(&dir).with_context(|| {
+            format!(
+                "failed to read run store directory `{dir}`",
+                dir = dir.display()
+            )
+        })? {
+            let path = entry?.path().join(RECORD_FILE_NAME);
+            if !path.is_file() {
+                continue;
+            }
+
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read `{path}`", path = path.display()))?;
+            match serde_json::from_str::<RunRecord>(&contents) {
+                Ok(record) => records.push(record),
+                Err(e) => warn!(
+                    "ignoring invalid run record `{path}`: {e}",
+                    path = path.display()
+                ),
+            }
+        }
+
+        records.sort_by_key(|r| r.submitted);
+
+        let runs: Arc<Runs> = Arc::new(Mutex::new(
+            records
+                .into_iter()
+                .map(|r| (r.log.run_id.clone(), r))
+                .collect(),
+        ));
+        let (sender, receiver) = mpsc::channel();
+        let writer = thread::Builder::new()
+            .name("wdl-wes-store".to_string())
+            .spawn({
+                let dir = dir.clone();
+                let runs = runs.clone();
+                move || write_runs(&dir, &runs, receiver)
+            })
+            .context("failed to spawn run store writer thread")?;
+
+        let store = Self {
+            dir,
+            runs,
+            sender: Some(sender),
+            writer: Some(writer),
+        };
+
+        let unfinished: Vec<_> = store
+            .runs
+            .lock()
+            .expect("failed to lock runs")
+            .values()
+            .filter(|r| !r.log.state.is_terminal())
+            .map(|r| r.log.run_id.clone())
+            .collect();
+        for id in unfinished {
+            store.update(&id, |log| {
+                log.state = State::SystemError;
+                log.run_log
+                    .system_logs
+                    .push("the server stopped before the run finished".to_string());
+            });
+        }
+
+        Ok(store)
+    }
+
+    /// Gets the directory of the given run.
+    pub fn run_dir(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    /// Creates a new queued run for the given request.
+    ///
+    /// Returns the identifier of the run.
+    pub fn create(&self, request: RunRequest) -> Result<String> {
+        let id = Uuid::new_v4().to_string();
+        let dir = self.run_dir(&id);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create directory `{dir}`", dir = dir.display()))?;
+
+        let record = RunRecord {
+            submitted: Utc::now(),
+            log: RunLog {
+                run_id: id.clone(),
+                request,
+                state: State::Queued,
+                run_log: Log::default(),
+                task_logs: Vec::new(),
+                outputs: None,
+            },
+        };
+
+        self.runs
+            .lock()
+            .expect("failed to lock runs")
+            .insert(id.clone(), record);
+        self.persist(&id);
+        Ok(id)
+    }
+
+    /// Gets the log of the given run.
+    ///
+    /// Returns `None` if the run does not exist.
+    pub fn get(&self, id: &str) -> Option<RunLog> {
+        self.runs
+            .lock()
+            .expect("failed to lock runs")
+            .get(id)
+            .map(|r| r.log.clone())
+    }
+
+    /// Lists the states of up to `count` runs, most recently submitted first,
+    /// skipping the first `skip` runs.
+    ///
+    /// Returns the states and whether or not there are more runs.
+    pub fn list(&self, skip: usize, count: usize) -> (Vec<RunStatus>, bool) {
+        let runs = self.runs.lock().expect("failed to lock runs");
+        let statuses = runs
+            .values()
+            .rev()
+            .skip(skip)
+            .take(count)
+            .map(|r| RunStatus {
+                run_id: r.log.run_id.clone(),
+                state: r.log.state,
+            })
+            .collect();
+        (statuses, runs.len() > skip.saturating_add(count))
+    }
+
+    /// Counts the runs in each state.
+    pub fn state_counts(&self) -> HashMap<State, u64> {
+        let mut counts = HashMap::new();
+        for record in self.runs.lock().expect("failed to lock runs").values() {
+            *counts.entry(record.log.state).or_default() += 1;
+        }
+
+        counts
+    }
+
+    /// Updates the log of the given run and persists the change.
+    ///
+    /// The change is persisted by the writer thread; failing to persist the
+    /// change is logged as a warning.
+    ///
+    /// Updates of a run that does not exist are ignored.
+    pub fn update(&self, id: &str, update: impl FnOnce(&mut RunLog)) {
+        {
+            let mut runs = self.runs.lock().expect("failed to lock runs");
+            let Some(record) = runs.get_mut(id) else {
+                return;
+            };
+
+            update(&mut record.log);
+        }
+
+        self.persist(id);
+    }
+
+    /// Sends a write of the given run's record to the writer thread.
+    fn persist(&self, id: &str) {
+        let sender = self.sender.as_ref().expect("store should have a sender");
+        if sender.send(id.to_string()).is_err() {
+            warn!("failed to persist run `{id}`: the writer thread has stopped");
+        }
+    }
+}
+
+impl Drop for RunStore {
+    fn drop(&mut self) {
+        // Dropping the sender stops the writer thread once it has persisted the
+        // remaining changes
+        drop(self.sender.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Writes a run record to the given run directory.
+///
+/// The record is written to a temporary file that is then renamed so that a
+/// partially written record is never observed.
+fn write_record(dir: &Path, record: &RunRecord) -> Result<()> {
+    let path = dir.join(RECORD_FILE_NAME);
+    let temp = path.with_extension("json.tmp");
+    let file = fs::File::create(&temp)
+        .with_context(|| format!("failed to create file `{path}`", path = temp.display()))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), record)
+        .with_context(|| format!("failed to write file `{path}`", path = temp.display()))?;
+    fs::rename(&temp, &path)
+        .with_context(|| format!("failed to write file `{path}`", path = path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn it_persists_runs() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = RunStore::open(dir.path()).unwrap();
+        let first = store.create(RunRequest::default()).unwrap();
+        let second = store.create(RunRequest::default()).unwrap();
+        store.update(&first, |log| log.state = State::Complete);
+
+        let (runs, more) = store.list(0, 1);
+        assert_eq!(
+            runs,
+            [RunStatus {
+                run_id: second.clone(),
+                state: State::Queued
+            }]
+        );
+        assert!(more);
+
+        // Reopening the store should fail the unfinished run
+        drop(store);
+        let store = RunStore::open(dir.path()).unwrap();
+        let (runs, more) = store.list(0, 10);
+        assert_eq!(
+            runs,
+            [
+                RunStatus {
+                    run_id: second.clone(),
+                    state: State::SystemError
+                },
+                RunStatus {
+                    run_id: first,
+                    state: State::Complete
+                }
+            ]
+        );
+        assert!(!more);
+        assert_eq!(
+            store.get(&second).unwrap().run_log.system_logs,
+            ["the server stopped before the run finished"]
+        );
+    }
+}
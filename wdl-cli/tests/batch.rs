@@ -0,0 +1,129 @@
+//! The sample sheet batch evaluation tests.
+//!
+//! These tests evaluate the rows of a sample sheet with the local backend.
+
+use std::fs;
+
+use pretty_assertions::assert_eq;
+use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
+use wdl_analysis::Analyzer;
+use wdl_cli::BatchEvaluator;
+use wdl_cli::Inputs;
+use wdl_cli::batch::OUTPUTS_FILE_NAME;
+use wdl_cli::batch::RowStatus;
+use wdl_cli::batch::SUMMARY_FILE_NAME;
+use wdl_cli::batch::SampleSheet;
+use wdl_engine::Events;
+use wdl_engine::config::BackendConfig;
+use wdl_engine::config::Config;
+
+/// The source of the workflow used by the tests.
+const SOURCE: &str = r#"
+version 1.2
+
+task greet {
+  input {
+    String greeting
+    String name
+    File suffix
+    Int code = 0
+  }
+
+  command <<<
+    echo "~{greeting} ~{name}~{read_string(suffix)}"
+    exit ~{code}
+  >>>
+
+  output {
+    String message = read_string(stdout())
+  }
+}
+
+workflow test {
+  input {
+    String greeting
+    String name
+    File suffix
+    Int code = 0
+  }
+
+  call greet { greeting, name, suffix, code }
+
+  output {
+    String message = greet.message
+  }
+}
+"#;
+
+#[tokio::test]
+async fn it_evaluates_sample_sheets() {
+    let dir = TempDir::new().expect("failed to create temporary directory");
+    fs::write(dir.path().join("main.wdl"), SOURCE).unwrap();
+
+    // The paths in the sample sheet are relative to the sample sheet
+    fs::create_dir(dir.path().join("samples")).unwrap();
+    fs::write(dir.path().join("samples/bang.txt"), "!").unwrap();
+    fs::write(
+        dir.path().join("samples/sheet.tsv"),
+        "name\tsuffix\tcode\nworld\tbang.txt\t\nfailure\tbang.txt\t1\n42\tbang.txt\t\n",
+    )
+    .unwrap();
+
+    let analyzer = Analyzer::default();
+    analyzer
+        .add_directory(dir.path().to_path_buf())
+        .await
+        .expect("failed to add directory");
+    let results = analyzer.analyze(()).await.expect("failed to analyze");
+    let document = results[0].document();
+
+    let sheet = SampleSheet::read(dir.path().join("samples/sheet.tsv")).unwrap();
+    let base = Inputs::coalesce(["greeting=hello"], Some("test".to_string())).unwrap();
+    let rows = sheet.inputs(document, &base, Some("test")).unwrap();
+
+    let config = Config {
+        backends: [(
+            "default".to_string(),
+            BackendConfig::Local(Default::default()),
+        )]
+        .into(),
+        ..Default::default()
+    };
+
+    let output_dir = dir.path().join("out");
+    let results = BatchEvaluator::new(document, "test", config, &output_dir)
+        .run(rows, CancellationToken::new(), Events::none())
+        .await
+        .unwrap();
+
+    assert_eq!(results.count(RowStatus::Succeeded), 2);
+    assert_eq!(results.count(RowStatus::Failed), 1);
+
+    let rows = results.rows();
+    assert_eq!(rows[0].status(), RowStatus::Succeeded);
+    assert_eq!(rows[0].dir(), output_dir.join("1"));
+    assert_eq!(rows[1].status(), RowStatus::Failed);
+    assert!(
+        rows[1].error().unwrap().contains("exit code 1"),
+        "unexpected error: {error}",
+        error = rows[1].error().unwrap()
+    );
+
+    assert_eq!(
+        fs::read_to_string(output_dir.join(OUTPUTS_FILE_NAME)).unwrap(),
+        "row\tmessage\n1\thello world!\n2\t\n3\thello 42!\n"
+    );
+
+    let summary = fs::read_to_string(output_dir.join(SUMMARY_FILE_NAME)).unwrap();
+    let lines: Vec<_> = summary.lines().collect();
+    assert_eq!(lines[0], "row\tstatus\tdirectory\terror");
+    assert_eq!(
+        lines[1],
+        format!(
+            "1\tsucceeded\t{dir}\t",
+            dir = output_dir.join("1").display()
+        )
+    );
+    assert!(lines[2].starts_with("2\tfailed\t"));
+}
@@ -0,0 +1,327 @@
+//! The WES API server tests.
+//!
+//! These tests serve the API on a localhost port and submit runs evaluated
+//! with the local backend.
+
+use std::fs;
+use std::time::Duration;
+
+use pretty_assertions::assert_eq;
+use reqwest::Client;
+use reqwest::StatusCode;
+use reqwest::header::CONTENT_TYPE;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+use wdl_cli::wes::BASE_PATH;
+use wdl_cli::wes::ErrorResponse;
+use wdl_cli::wes::RunId;
+use wdl_cli::wes::RunListResponse;
+use wdl_cli::wes::RunLog;
+use wdl_cli::wes::RunStatus;
+use wdl_cli::wes::Server;
+use wdl_cli::wes::ServiceInfo;
+use wdl_cli::wes::State;
+use wdl_engine::config::BackendConfig;
+use wdl_engine::config::Config;
+
+/// The source of the workflow used by the tests.
+const SOURCE: &str = r#"
+version 1.2
+
+task greet {
+  input {
+    String name
+    Int sleep = 0
+  }
+
+  command <<<
+    sleep ~{sleep}
+    echo "hello ~{name}"
+  >>>
+
+  output {
+    String message = read_string(stdout())
+  }
+}
+
+workflow test {
+  input {
+    String name
+    Int sleep = 0
+  }
+
+  call greet { name, sleep }
+
+  output {
+    String message = greet.message
+  }
+}
+"#;
+
+/// Represents a WES server serving on a localhost port.
+struct TestServer {
+    /// The temporary directory of the server.
+    dir: TempDir,
+    /// The base URL of the API.
+    url: String,
+    /// The HTTP client.
+    client: Client,
+}
+
+impl TestServer {
+    /// Starts a new server.
+    async fn start() -> Self {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(dir.path().join("main.wdl"), SOURCE).unwrap();
+
+        let config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Local(Default::default()),
+            )]
+            .into(),
+            ..Default::default()
+        };
+
+        let server = Server::new(config, dir.path().join("runs"))
+            .await
+            .expect("failed to create server");
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{addr}{BASE_PATH}",
+            addr = listener.local_addr().unwrap()
+        );
+        tokio::spawn(async move { server.serve(listener).await });
+
+        Self {
+            dir,
+            url,
+            client: Client::new(),
+        }
+    }
+
+    /// Gets the path of the test workflow.
+    fn source_path(&self) -> String {
+        self.dir.path().join("main.wdl").display().to_string()
+    }
+
+    /// Sends a GET request and deserializes the response.
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> (StatusCode, T) {
+        let response = self
+            .client
+            .get(format!("{url}{path}", url = self.url))
+            .send()
+            .await
+            .unwrap();
+        let status = response.status();
+        (
+            status,
+            serde_json::from_str(&response.text().await.unwrap()).unwrap(),
+        )
+    }
+
+    /// Sends a POST request and deserializes the response.
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        content_type: &str,
+        body: impl Into<reqwest::Body>,
+    ) -> (StatusCode, T) {
+        let response = self
+            .client
+            .post(format!("{url}{path}", url = self.url))
+            .header(CONTENT_TYPE, content_type)
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        let status = response.status();
+        (
+            status,
+            serde_json::from_str(&response.text().await.unwrap()).unwrap(),
+        )
+    }
+
+    /// Submits a run as JSON.
+    async fn submit(&self, params: serde_json::Value) -> String {
+        let request = json!({
+            "workflow_params": params,
+            "workflow_type": "WDL",
+            "workflow_type_version": "1.2",
+            "workflow_url": self.source_path(),
+        });
+        let (status, id) = self
+            .post::<RunId>("/runs", "application/json", request.to_string())
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        id.run_id
+    }
+
+    /// Waits for a run to reach the given state.
+    async fn wait_for(&self, id: &str, state: State) -> RunLog {
+        for _ in 0..600 {
+            let (_, log) = self.get::<RunLog>(&format!("/runs/{id}")).await;
+            if log.state == state {
+                return log;
+            }
+
+            assert!(
+                !log.state.is_terminal(),
+                "run finished in unexpected state: {log:#?}"
+            );
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        panic!("timed out waiting for run `{id}` to reach state {state:?}");
+    }
+}
+
+#[tokio::test]
+async fn it_runs_workflows() {
+    let server = TestServer::start().await;
+
+    let (status, info) = server.get::<ServiceInfo>("/service-info").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        info.workflow_type_versions["WDL"].workflow_type_version,
+        ["1.0", "1.1", "1.2"]
+    );
+
+    let id = server.submit(json!({ "test.name": "world" })).await;
+    let log = server.wait_for(&id, State::Complete).await;
+    assert_eq!(log.run_log.name.as_deref(), Some("test"));
+    assert_eq!(log.outputs, Some(json!({ "test.message": "hello world" })));
+
+    // The task's attempt should be logged with the URL of its stdout
+    assert_eq!(log.task_logs.len(), 1);
+    let task = &log.task_logs[0];
+    assert_eq!(task.name.as_deref(), Some("greet"));
+    assert_eq!(task.exit_code, Some(0));
+    let stdout = url::Url::parse(task.stdout.as_deref().unwrap())
+        .unwrap()
+        .to_file_path()
+        .unwrap();
+    assert_eq!(fs::read_to_string(stdout).unwrap(), "hello world\n");
+
+    let (status, run) = server.get::<RunStatus>(&format!("/runs/{id}/status")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(run.state, State::Complete);
+
+    // Invalid inputs fail the run
+    let id = server.submit(json!({ "test.nam": "world" })).await;
+    let log = server.wait_for(&id, State::ExecutorError).await;
+    assert!(
+        log.run_log.system_logs[0].starts_with("invalid inputs"),
+        "unexpected logs: {logs:?}",
+        logs = log.run_log.system_logs
+    );
+
+    let (status, runs) = server.get::<RunListResponse>("/runs?page_size=1").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        runs.runs,
+        [RunStatus {
+            run_id: id,
+            state: State::ExecutorError
+        }]
+    );
+    assert_eq!(runs.next_page_token, "1");
+
+    let (_, runs) = server.get::<RunListResponse>("/runs?page_token=1").await;
+    assert_eq!(runs.runs.len(), 1);
+    assert_eq!(runs.next_page_token, "");
+
+    let (status, error) = server.get::<ErrorResponse>("/runs/missing").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(error.msg, "run `missing` does not exist");
+}
+
+#[tokio::test]
+async fn it_runs_attachments() {
+    let server = TestServer::start().await;
+
+    let body = [
+        ("workflow_type", None, "WDL".to_string()),
+        ("workflow_type_version", None, "1.2".to_string()),
+        ("workflow_url", None, "wdl/main.wdl".to_string()),
+        (
+            "workflow_params",
+            None,
+            json!({ "greet.name": "attachment" }).to_string(),
+        ),
+        (
+            "workflow_attachment",
+            Some("wdl/main.wdl"),
+            SOURCE.to_string(),
+        ),
+    ]
+    .iter()
+    .map(|(name, filename, value)| {
+        format!(
+            "--boundary\r\nContent-Disposition: form-data; \
+             name=\"{name}\"{filename}\r\n\r\n{value}\r\n",
+            filename = filename
+                .map(|f| format!("; filename=\"{f}\""))
+                .unwrap_or_default()
+        )
+    })
+    .collect::<String>()
+        + "--boundary--\r\n";
+
+    let (status, id) = server
+        .post::<RunId>("/runs", "multipart/form-data; boundary=boundary", body)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The inputs are for the task, so the task is run
+    let log = server.wait_for(&id.run_id, State::Complete).await;
+    assert_eq!(
+        log.outputs,
+        Some(json!({ "greet.message": "hello attachment" }))
+    );
+    assert!(
+        server
+            .dir
+            .path()
+            .join("runs")
+            .join(&id.run_id)
+            .join("attachments/wdl/main.wdl")
+            .is_file()
+    );
+
+    let (status, error) = server
+        .post::<ErrorResponse>(
+            "/runs",
+            "application/json",
+            json!({
+                "workflow_type": "CWL",
+                "workflow_type_version": "1.0",
+                "workflow_url": "main.cwl",
+            })
+            .to_string(),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error.msg, "unsupported workflow type `CWL`: expected `WDL`");
+}
+
+#[tokio::test]
+async fn it_cancels_runs() {
+    let server = TestServer::start().await;
+
+    let id = server
+        .submit(json!({ "test.name": "world", "test.sleep": 60 }))
+        .await;
+    server.wait_for(&id, State::Running).await;
+
+    let (status, canceled) = server
+        .post::<RunId>(&format!("/runs/{id}/cancel"), "application/json", "")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(canceled.run_id, id);
+
+    let log = server.wait_for(&id, State::Canceled).await;
+    assert!(log.outputs.is_none());
+}
@@ -10,6 +10,7 @@ use std::ops::Sub;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use anyhow::anyhow;
@@ -28,13 +29,25 @@ use crate::Value;
 use crate::http::Transferer;
 use crate::path::EvaluationPath;
 
+mod apptainer;
 mod docker;
+mod hpc;
 mod local;
+mod noop;
+mod rerun;
+mod scheduler;
+mod slurm;
 mod tes;
+mod usage;
 
+pub use apptainer::*;
 pub use docker::*;
 pub use local::*;
+pub use noop::*;
+pub use scheduler::*;
+pub use slurm::*;
 pub use tes::*;
+pub use usage::*;
 
 /// The default work directory name.
 pub(crate) const WORK_DIR_NAME: &str = "work";
@@ -212,6 +225,28 @@ impl TaskSpawnRequest {
     }
 }
 
+/// Represents an error for a task execution that exceeded its timeout.
+///
+/// The task's execution is cancelled when the timeout elapses.
+#[derive(Debug, Clone, Copy)]
+pub struct TaskTimeoutError {
+    /// The timeout that was exceeded.
+    pub timeout: Duration,
+}
+
+impl fmt::Display for TaskTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.timeout.as_secs();
+        write!(
+            f,
+            "task timed out after {secs} second{s}",
+            s = if secs == 1 { "" } else { "s" }
+        )
+    }
+}
+
+impl std::error::Error for TaskTimeoutError {}
+
 /// Represents the result of a task's execution.
 #[derive(Debug)]
 pub struct TaskExecutionResult {
@@ -223,6 +258,14 @@ pub struct TaskExecutionResult {
     pub stdout: Value,
     /// The value of the task's stderr file.
     pub stderr: Value,
+    /// The resources used by the task's execution.
+    ///
+    /// This is `None` if the result was not produced by executing the task.
+    pub usage: Option<ResourceUsage>,
+    /// The identifiers of the GPU devices reserved for the task's execution.
+    ///
+    /// This is empty if the backend did not reserve specific GPU devices.
+    pub gpus: Vec<String>,
 }
 
 /// Represents a task execution backend.
@@ -274,6 +317,18 @@ pub trait TaskExecutionBackend: Send + Sync {
         let _ = token;
         None
     }
+
+    /// Resolves a container image to an immutable reference by digest (e.g.
+    /// `ubuntu@sha256:...`).
+    ///
+    /// Returns `None` if the backend does not support resolving containers.
+    fn resolve_container<'a>(
+        &'a self,
+        container: &'a str,
+    ) -> Option<BoxFuture<'a, Result<String>>> {
+        let _ = container;
+        None
+    }
 }
 
 /// A trait implemented by backend requests.
@@ -284,6 +339,28 @@ trait TaskManagerRequest: Send + Sync + 'static {
     /// Gets the requested memory allocation from the request, in bytes.
     fn memory(&self) -> u64;
 
+    /// Gets the requested number of GPU devices from the request.
+    fn gpu(&self) -> u64 {
+        0
+    }
+
+    /// Sets the identifiers of the GPU devices reserved for the request.
+    ///
+    /// This is called before the request is run.
+    fn set_gpus(&mut self, gpus: Vec<String>) {
+        let _ = gpus;
+    }
+
+    /// Gets the maximum amount of time the request may run for.
+    ///
+    /// Returns `None` if the request's run time is not limited.
+    fn timeout(&self) -> Option<Duration>;
+
+    /// Gets the cancellation token for the request.
+    ///
+    /// The token is cancelled when the request exceeds its timeout.
+    fn token(&self) -> &CancellationToken;
+
     /// Runs the request.
     fn run(self) -> impl Future<Output = Result<TaskExecutionResult>> + Send;
 }
@@ -294,6 +371,8 @@ struct TaskManagerResponse {
     cpu: f64,
     /// The previous memory allocation from the request.
     memory: u64,
+    /// The GPU devices previously reserved for the request.
+    gpus: Vec<String>,
     /// The result of the task's execution.
     result: Result<TaskExecutionResult>,
     /// The channel to send the task's execution result back on.
@@ -306,6 +385,8 @@ struct TaskManagerState<Req> {
     cpu: OrderedFloat<f64>,
     /// The amount of available memory remaining, in bytes.
     memory: u64,
+    /// The identifiers of the available GPU devices.
+    gpus: Vec<String>,
     /// The set of spawned tasks.
     spawned: JoinSet<TaskManagerResponse>,
     /// The queue of parked spawn requests.
@@ -313,11 +394,13 @@ struct TaskManagerState<Req> {
 }
 
 impl<Req> TaskManagerState<Req> {
-    /// Constructs a new task manager state with the given total CPU and memory.
-    fn new(cpu: u64, memory: u64) -> Self {
+    /// Constructs a new task manager state with the given total CPU, memory,
+    /// and GPU devices.
+    fn new(cpu: u64, memory: u64, gpus: Vec<String>) -> Self {
         Self {
             cpu: OrderedFloat(cpu as f64),
             memory,
+            gpus,
             spawned: Default::default(),
             parked: Default::default(),
         }
@@ -342,10 +425,19 @@ where
     /// Constructs a new task manager with the given total CPU, maximum CPU per
     /// request, total memory, and maximum memory per request.
     fn new(cpu: u64, max_cpu: u64, memory: u64, max_memory: u64) -> Self {
+        Self::with_gpus(cpu, max_cpu, memory, max_memory, Vec::new())
+    }
+
+    /// Constructs a new task manager with the given total CPU, maximum CPU per
+    /// request, total memory, maximum memory per request, and the identifiers
+    /// of the GPU devices to reserve for requests.
+    ///
+    /// Each running request is reserved a disjoint set of GPU devices.
+    fn with_gpus(cpu: u64, max_cpu: u64, memory: u64, max_memory: u64, gpus: Vec<String>) -> Self {
         let (tx, rx) = mpsc::unbounded_channel();
 
         tokio::spawn(async move {
-            Self::run_request_queue(rx, cpu, max_cpu, memory, max_memory).await;
+            Self::run_request_queue(rx, cpu, max_cpu, memory, max_memory, gpus).await;
         });
 
         Self { tx }
@@ -369,8 +461,10 @@ where
         max_cpu: u64,
         memory: u64,
         max_memory: u64,
+        gpus: Vec<String>,
     ) {
-        let mut state = TaskManagerState::new(cpu, memory);
+        let max_gpu = gpus.len() as u64;
+        let mut state = TaskManagerState::new(cpu, memory, gpus);
 
         loop {
             // If there aren't any spawned tasks, wait for a spawn request only
@@ -381,7 +475,9 @@ where
                 );
                 match rx.recv().await {
                     Some((req, completed)) => {
-                        Self::handle_spawn_request(&mut state, max_cpu, max_memory, req, completed);
+                        Self::handle_spawn_request(
+                            &mut state, max_cpu, max_memory, max_gpu, req, completed,
+                        );
                         continue;
                     }
                     None => break,
@@ -393,7 +489,7 @@ where
                 request = rx.recv() => {
                     match request {
                         Some((req, completed)) => {
-                            Self::handle_spawn_request(&mut state, max_cpu, max_memory, req, completed);
+                            Self::handle_spawn_request(&mut state, max_cpu, max_memory, max_gpu, req, completed);
                         }
                         None => break,
                     }
@@ -404,8 +500,10 @@ where
                         state.memory += response.memory;
                     }
 
+                    state.gpus.extend(response.gpus);
+
                     response.tx.send(response.result).ok();
-                    Self::spawn_parked_tasks(&mut state, max_cpu, max_memory);
+                    Self::spawn_parked_tasks(&mut state, max_cpu, max_memory, max_gpu);
                 }
             }
         }
@@ -413,13 +511,16 @@ where
 
     /// Handles a spawn request by either parking it (not enough resources
     /// currently available) or by spawning it.
+    ///
+    /// Returns `true` if the request was parked.
     fn handle_spawn_request(
         state: &mut TaskManagerState<Req>,
         max_cpu: u64,
         max_memory: u64,
-        request: Req,
+        max_gpu: u64,
+        mut request: Req,
         completed: oneshot::Sender<Result<TaskExecutionResult>>,
-    ) {
+    ) -> bool {
         // Ensure the request does not exceed the maximum CPU
         let cpu = request.cpu();
         if cpu > max_cpu as f64 {
@@ -428,7 +529,7 @@ where
                     "requested task CPU count of {cpu} exceeds the maximum CPU count of {max_cpu}",
                 )))
                 .ok();
-            return;
+            return false;
         }
 
         // Ensure the request does not exceed the maximum memory
@@ -441,7 +542,29 @@ where
                     s = if memory == 1 { "" } else { "s" }
                 )))
                 .ok();
-            return;
+            return false;
+        }
+
+        // Ensure the request does not exceed the number of GPU devices
+        let gpu = request.gpu();
+        if gpu > max_gpu {
+            completed
+                .send(Err(anyhow!(
+                    "requested task GPU count of {gpu} exceeds the maximum GPU count of {max_gpu}",
+                )))
+                .ok();
+            return false;
+        }
+
+        // GPU devices are reserved even if CPU and memory are unlimited
+        if gpu > state.gpus.len() as u64 {
+            debug!(
+                "parking task due to insufficient GPUs: task reserves {gpu} GPU(s) but there are \
+                 only {gpu_remaining} GPU(s) available",
+                gpu_remaining = state.gpus.len()
+            );
+            state.parked.push_back((request, completed));
+            return true;
         }
 
         if !state.unlimited() {
@@ -457,7 +580,7 @@ where
                     memory_remaining = state.memory
                 );
                 state.parked.push_back((request, completed));
-                return;
+                return true;
             }
 
             // Decrement the resource counts and spawn the task
@@ -470,18 +593,53 @@ where
             );
         }
 
+        let gpus: Vec<_> = state.gpus.drain(..gpu as usize).collect();
+        if !gpus.is_empty() {
+            debug!("reserving GPU device(s) {gpus:?} for task");
+            request.set_gpus(gpus.clone());
+        }
+
         state.spawned.spawn(async move {
+            let cpu = request.cpu();
+            let memory = request.memory();
+            let timeout = request.timeout();
+            let token = request.token().clone();
+            let run = request.run();
+            let result = match timeout {
+                Some(timeout) => {
+                    tokio::pin!(run);
+                    tokio::select! {
+                        result = &mut run => result,
+                        _ = tokio::time::sleep(timeout) => {
+                            // Cancel the request and wait for the backend to clean up
+                            token.cancel();
+                            run.await.ok();
+                            Err(TaskTimeoutError { timeout }.into())
+                        }
+                    }
+                }
+                None => run.await,
+            };
+
             TaskManagerResponse {
-                cpu: request.cpu(),
-                memory: request.memory(),
-                result: request.run().await,
+                cpu,
+                memory,
+                gpus,
+                result,
                 tx: completed,
             }
         });
+
+        false
     }
 
     /// Responsible for spawning parked tasks.
-    fn spawn_parked_tasks(state: &mut TaskManagerState<Req>, max_cpu: u64, max_memory: u64) {
+    fn spawn_parked_tasks(
+        state: &mut TaskManagerState<Req>,
+        max_cpu: u64,
+        max_memory: u64,
+        max_gpu: u64,
+    ) {
         if state.parked.is_empty() {
             return;
         }
@@ -567,6 +725,10 @@ where
                 range.start, 0,
                 "expected the fit tasks to be at the front of the queue"
             );
+            // Tasks that fit the available CPU and memory may still be parked again due
+            // to insufficient GPUs; stop when no task could be unparked
+            let len = range.len();
+            let mut reparked = 0;
             for _ in range {
                 let (request, completed) = state.parked.pop_front().unwrap();
 
@@ -576,7 +738,15 @@ where
                     memory = request.memory(),
                 );
 
-                Self::handle_spawn_request(state, max_cpu, max_memory, request, completed);
+                if Self::handle_spawn_request(
+                    state, max_cpu, max_memory, max_gpu, request, completed,
+                ) {
+                    reparked += 1;
+                }
+            }
+
+            if reparked == len {
+                break;
             }
         }
     }
@@ -770,7 +940,102 @@ mod test {
 
     #[test]
     fn unlimited_state() {
-        let manager_state = TaskManagerState::<()>::new(u64::MAX, u64::MAX);
+        let manager_state = TaskManagerState::<()>::new(u64::MAX, u64::MAX, Vec::new());
         assert!(manager_state.unlimited());
     }
+
+    /// A task manager request that reports its reserved GPU devices.
+    struct GpuRequest {
+        /// The cancellation token for the request.
+        token: CancellationToken,
+        /// The GPU devices reserved for the request.
+        gpus: Vec<String>,
+        /// The barrier that is waited on once the request is running.
+        barrier: Arc<tokio::sync::Barrier>,
+        /// The sender for the reserved GPU devices.
+        tx: mpsc::UnboundedSender<Vec<String>>,
+    }
+
+    impl TaskManagerRequest for GpuRequest {
+        fn cpu(&self) -> f64 {
+            1.0
+        }
+
+        fn memory(&self) -> u64 {
+            0
+        }
+
+        fn gpu(&self) -> u64 {
+            1
+        }
+
+        fn set_gpus(&mut self, gpus: Vec<String>) {
+            self.gpus = gpus;
+        }
+
+        fn timeout(&self) -> Option<Duration> {
+            None
+        }
+
+        fn token(&self) -> &CancellationToken {
+            &self.token
+        }
+
+        async fn run(self) -> Result<TaskExecutionResult> {
+            self.tx.send(self.gpus.clone()).unwrap();
+
+            // Wait for the other request so that both run concurrently
+            self.barrier.wait().await;
+
+            Ok(TaskExecutionResult {
+                exit_code: 0,
+                work_dir: EvaluationPath::Local(PathBuf::new()),
+                stdout: crate::PrimitiveValue::new_file("stdout").into(),
+                stderr: crate::PrimitiveValue::new_file("stderr").into(),
+                usage: None,
+                gpus: self.gpus,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn reserves_disjoint_gpus() {
+        let manager =
+            TaskManager::with_gpus(4, 4, 1024, 1024, vec!["0".to_string(), "1".to_string()]);
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let mut completed = Vec::new();
+        for _ in 0..2 {
+            let (completed_tx, completed_rx) = oneshot::channel();
+            manager.send(
+                GpuRequest {
+                    token: CancellationToken::new(),
+                    gpus: Vec::new(),
+                    barrier: barrier.clone(),
+                    tx: tx.clone(),
+                },
+                completed_tx,
+            );
+            completed.push(completed_rx);
+        }
+
+        let mut gpus = vec![rx.recv().await.unwrap(), rx.recv().await.unwrap()];
+        gpus.sort();
+        assert_eq!(gpus, [["0"], ["1"]]);
+
+        // The results should report the devices reserved for each request
+        let mut reported = Vec::new();
+        for rx in completed {
+            let result = tokio::time::timeout(Duration::from_secs(10), rx)
+                .await
+                .expect("requests should run concurrently")
+                .unwrap()
+                .unwrap();
+            reported.push(result.gpus);
+        }
+
+        reported.sort();
+        assert_eq!(reported, gpus);
+    }
 }
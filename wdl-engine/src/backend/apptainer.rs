@@ -0,0 +1,801 @@
+//! Implementation of the Apptainer backend.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+use std::path::absolute;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use crankshaft::engine::service::name::GeneratorIterator;
+use crankshaft::engine::service::name::UniqueAlphanumeric;
+use crankshaft::events::Event;
+use crankshaft::events::next_task_id;
+use crankshaft::events::send_event;
+use nonempty::NonEmpty;
+use tokio::process::Command;
+use tokio::select;
+use tokio::sync::OnceCell;
+use tokio::sync::broadcast;
+use tokio::sync::oneshot;
+use tokio::sync::oneshot::Receiver;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+use tracing::warn;
+use wdl_ast::v1::common::container::value::uri::Kind;
+
+use super::TaskExecutionBackend;
+use super::TaskExecutionConstraints;
+use super::TaskManager;
+use super::TaskManagerRequest;
+use super::TaskSpawnRequest;
+use super::rerun::RerunContainer;
+use super::rerun::write_container_rerun_script;
+use super::usage::monitor_process;
+use crate::COMMAND_FILE_NAME;
+use crate::ONE_GIBIBYTE;
+use crate::PrimitiveValue;
+use crate::STDERR_FILE_NAME;
+use crate::STDOUT_FILE_NAME;
+use crate::SYSTEM;
+use crate::TaskExecutionResult;
+use crate::Value;
+use crate::WORK_DIR_NAME;
+use crate::backend::INITIAL_EXPECTED_NAMES;
+use crate::config::ApptainerBackendConfig;
+use crate::config::Config;
+use crate::config::DEFAULT_TASK_SHELL;
+use crate::config::TaskResourceLimitBehavior;
+use crate::convert_unit_string;
+use crate::path::EvaluationPath;
+use crate::v1::container;
+use crate::v1::cpu;
+use crate::v1::memory;
+use crate::v1::timeout;
+
+/// The root guest path for inputs.
+const GUEST_INPUTS_DIR: &str = "/mnt/task/inputs/";
+
+/// The guest working directory.
+const GUEST_WORK_DIR: &str = "/mnt/task/work";
+
+/// The guest path for the command file.
+const GUEST_COMMAND_PATH: &str = "/mnt/task/command";
+
+/// The default Apptainer executable.
+const DEFAULT_APPTAINER_EXECUTABLE: &str = "apptainer";
+
+/// The default image cache subdirectory that is appended to the system cache
+/// directory.
+const DEFAULT_IMAGE_CACHE_SUBDIR: &str = "wdl/apptainer";
+
+/// The file extension of Singularity Image Format (SIF) files.
+const SIF_EXTENSION: &str = ".sif";
+
+/// Represents the source of a container image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ImageSource {
+    /// The image is a local SIF file.
+    Local(PathBuf),
+    /// The image is pulled from the given URI (e.g. `docker://ubuntu:latest`).
+    Remote(String),
+}
+
+impl ImageSource {
+    /// Parses an image source from the value of a task's `container`
+    /// requirement.
+    ///
+    /// Container URIs without a protocol are treated as Docker images unless
+    /// they refer to a SIF file.
+    fn parse(container: &str) -> Result<Self> {
+        let entry = match container
+            .parse::<Kind>()
+            .with_context(|| format!("invalid container URI `{container}`"))?
+        {
+            Kind::Any => bail!("container URI `{container}` does not specify an image"),
+            Kind::Entry(entry) => entry,
+        };
+
+        let location = entry.location().as_str();
+        match entry.protocol().map(|p| p.as_str()) {
+            Some("file") => Ok(Self::Local(location.into())),
+            None if location.ends_with(SIF_EXTENSION) => Ok(Self::Local(location.into())),
+            None => Ok(Self::Remote(format!("docker://{location}"))),
+            Some(protocol) => Ok(Self::Remote(format!("{protocol}://{location}"))),
+        }
+    }
+}
+
+/// Responsible for pulling remote images into the image cache directory.
+#[derive(Debug)]
+struct ImageCache {
+    /// The Apptainer executable.
+    executable: String,
+    /// The image cache directory.
+    dir: PathBuf,
+    /// The images that have been pulled, keyed by URI.
+    images: Mutex<HashMap<String, Arc<OnceCell<PathBuf>>>>,
+}
+
+impl ImageCache {
+    /// Gets the path to the SIF file for the given image source.
+    ///
+    /// Remote images are pulled into the cache directory if not already
+    /// present.
+    async fn get(&self, source: ImageSource) -> Result<PathBuf> {
+        let uri = match source {
+            ImageSource::Local(path) => {
+                let path = absolute(&path).with_context(|| {
+                    format!(
+                        "failed to determine absolute path of `{path}`",
+                        path = path.display()
+                    )
+                })?;
+
+                if !path.is_file() {
+                    bail!(
+                        "container image `{path}` does not exist",
+                        path = path.display()
+                    );
+                }
+
+                return Ok(path);
+            }
+            ImageSource::Remote(uri) => uri,
+        };
+
+        let cell = self
+            .images
+            .lock()
+            .expect("failed to lock images")
+            .entry(uri.clone())
+            .or_default()
+            .clone();
+
+        cell.get_or_try_init(|| self.pull(&uri)).await.cloned()
+    }
+
+    /// Pulls the given image URI into the cache directory.
+    async fn pull(&self, uri: &str) -> Result<PathBuf> {
+        let path = self.dir.join(format!(
+            "{digest}{SIF_EXTENSION}",
+            digest = blake3::hash(uri.as_bytes()).to_hex()
+        ));
+        if path.is_file() {
+            return Ok(path);
+        }
+
+        fs::create_dir_all(&self.dir).with_context(|| {
+            format!(
+                "failed to create image cache directory `{path}`",
+                path = self.dir.display()
+            )
+        })?;
+
+        // Pull to a temporary file first and then persist it to prevent partially
+        // pulled images from being used
+        let temp = tempfile::Builder::new()
+            .suffix(SIF_EXTENSION)
+            .tempfile_in(&self.dir)
+            .with_context(|| {
+                format!(
+                    "failed to create temporary file in `{path}`",
+                    path = self.dir.display()
+                )
+            })?;
+
+        info!("pulling container image `{uri}`");
+        let output = Command::new(&self.executable)
+            .arg("pull")
+            .arg("--force")
+            .arg(temp.path())
+            .arg(uri)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .with_context(|| {
+                format!("failed to run `{executable}`", executable = self.executable)
+            })?;
+
+        if !output.status.success() {
+            bail!(
+                "failed to pull container image `{uri}` ({status}): {stderr}",
+                status = output.status,
+                stderr = String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        temp.persist(&path).with_context(|| {
+            format!(
+                "failed to persist container image `{path}`",
+                path = path.display()
+            )
+        })?;
+
+        Ok(path)
+    }
+}
+
+/// Formats an `--env` argument to `apptainer exec` for the given environment
+/// variable.
+///
+/// Returns an error if the name or value cannot be passed to Apptainer
+/// unambiguously.
+fn env_arg(name: &str, value: &str) -> Result<String> {
+    if name.is_empty() || name.contains(['=', ',', '"', '\n', '\0']) {
+        bail!("environment variable name `{name}` is not supported by the Apptainer backend");
+    }
+
+    if value.contains(['\n', '\r', '\0']) {
+        bail!(
+            "the value of environment variable `{name}` contains a newline or NUL character, \
+             which is not supported by the Apptainer backend"
+        );
+    }
+
+    // Apptainer splits the argument on commas, so the value is quoted as a CSV
+    // field if needed
+    if value.contains([',', '"']) {
+        return Ok(format!(
+            "--env=\"{name}={value}\"",
+            value = value.replace('"', "\"\"")
+        ));
+    }
+
+    Ok(format!("--env={name}={value}"))
+}
+
+/// Represents an Apptainer task request.
+///
+/// This request contains the requested cpu and memory reservations for the task
+/// as well as the result receiver channel.
+#[derive(Debug)]
+struct ApptainerTaskRequest {
+    /// The engine configuration.
+    config: Arc<Config>,
+    /// The backend configuration.
+    backend_config: Arc<ApptainerBackendConfig>,
+    /// The Apptainer executable.
+    executable: String,
+    /// The inner task spawn request.
+    inner: TaskSpawnRequest,
+    /// The name of the task.
+    name: String,
+    /// The requested container for the task.
+    container: String,
+    /// The path to the SIF file of the task's container image.
+    image: PathBuf,
+    /// The requested CPU reservation for the task.
+    ///
+    /// Note that CPU isn't actually reserved for the task process.
+    cpu: f64,
+    /// The requested memory reservation for the task.
+    ///
+    /// Note that memory isn't actually reserved for the task process.
+    memory: u64,
+    /// The cancellation token for the request.
+    token: CancellationToken,
+    /// The maximum amount of time the task may run for.
+    timeout: Option<Duration>,
+    /// The sender for events.
+    events: Option<broadcast::Sender<Event>>,
+}
+
+impl ApptainerTaskRequest {
+    /// Gets the shell to use for the request.
+    fn shell(&self) -> &str {
+        self.config
+            .task
+            .shell
+            .as_deref()
+            .unwrap_or(DEFAULT_TASK_SHELL)
+    }
+
+    /// Gets the arguments to `apptainer exec` for the task's environment
+    /// variables followed by the additional arguments from the backend
+    /// configuration.
+    fn extra_args(&self) -> Result<Vec<String>> {
+        let mut args = self
+            .inner
+            .env()
+            .iter()
+            .map(|(k, v)| env_arg(k, v))
+            .collect::<Result<Vec<_>>>()?;
+        args.extend(self.backend_config.args.iter().cloned());
+        Ok(args)
+    }
+
+    /// Gets the arguments to `apptainer exec` for the request.
+    fn exec_args(&self, work_dir: &Path, command_path: &Path) -> Result<Vec<String>> {
+        let mut args = vec![
+            "exec".to_string(),
+            "--containall".to_string(),
+            format!("--pwd={GUEST_WORK_DIR}"),
+            format!("--bind={path}:{GUEST_WORK_DIR}", path = work_dir.display()),
+            format!(
+                "--bind={path}:{GUEST_COMMAND_PATH}:ro",
+                path = command_path.display()
+            ),
+        ];
+
+        for input in self.inner.inputs() {
+            let guest_path = input.guest_path().expect("input should have guest path");
+            let local_path = input.local_path().expect("input should be localized");
+
+            // The local path must exist for Apptainer to bind
+            if !local_path.exists() {
+                bail!(
+                    "cannot bind input `{path}` as it does not exist",
+                    path = local_path.display()
+                );
+            }
+
+            args.push(format!(
+                "--bind={path}:{guest_path}:ro",
+                path = local_path.display()
+            ));
+        }
+
+        args.extend(self.extra_args()?);
+        args.push(self.image.display().to_string());
+        args.push(self.shell().to_string());
+        args.push(GUEST_COMMAND_PATH.to_string());
+        Ok(args)
+    }
+}
+
+impl TaskManagerRequest for ApptainerTaskRequest {
+    fn cpu(&self) -> f64 {
+        self.cpu
+    }
+
+    fn memory(&self) -> u64 {
+        self.memory
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    async fn run(self) -> Result<TaskExecutionResult> {
+        let id = next_task_id();
+        let work_dir = self.inner.attempt_dir().join(WORK_DIR_NAME);
+        let stdout_path = self.inner.attempt_dir().join(STDOUT_FILE_NAME);
+        let stderr_path = self.inner.attempt_dir().join(STDERR_FILE_NAME);
+
+        let run = async {
+            // Create the working directory
+            fs::create_dir_all(&work_dir).with_context(|| {
+                format!(
+                    "failed to create directory `{path}`",
+                    path = work_dir.display()
+                )
+            })?;
+
+            // Write the evaluated command to disk
+            let command_path = self.inner.attempt_dir().join(COMMAND_FILE_NAME);
+            fs::write(&command_path, self.inner.command()).with_context(|| {
+                format!(
+                    "failed to write command contents to `{path}`",
+                    path = command_path.display()
+                )
+            })?;
+
+            let args = self.exec_args(&work_dir, &command_path)?;
+
+            write_container_rerun_script(
+                &self.inner,
+                RerunContainer::Apptainer {
+                    executable: &self.executable,
+                    image: &self.image,
+                    args: &self.extra_args()?,
+                },
+                self.shell(),
+                (&work_dir, GUEST_WORK_DIR),
+                (&command_path, GUEST_COMMAND_PATH),
+            )?;
+
+            // Create a file for the stdout
+            let stdout = File::create(&stdout_path).with_context(|| {
+                format!(
+                    "failed to create stdout file `{path}`",
+                    path = stdout_path.display()
+                )
+            })?;
+
+            // Create a file for the stderr
+            let stderr = File::create(&stderr_path).with_context(|| {
+                format!(
+                    "failed to create stderr file `{path}`",
+                    path = stderr_path.display()
+                )
+            })?;
+
+            let mut child = Command::new(&self.executable)
+                .args(args.iter().map(OsStr::new))
+                .stdin(Stdio::null())
+                .stdout(stdout)
+                .stderr(stderr)
+                .kill_on_drop(true)
+                .spawn()
+                .with_context(|| {
+                    format!(
+                        "failed to spawn `{executable}`",
+                        executable = self.executable
+                    )
+                })?;
+
+            // Notify that the process has spawned
+            send_event!(self.events, Event::TaskStarted { id });
+
+            let id = child.id().expect("should have id");
+            info!(
+                "spawned Apptainer process {id} for execution of task `{name}` in container \
+                 `{container}`",
+                name = self.name,
+                container = self.container
+            );
+
+            let (status, usage) = monitor_process(id, child.wait()).await;
+            let status = status.with_context(|| {
+                format!("failed to wait for termination of task child process {id}")
+            })?;
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::process::ExitStatusExt;
+                if let Some(signal) = status.signal() {
+                    tracing::warn!("task process {id} has terminated with signal {signal}");
+
+                    bail!(
+                        "task child process {id} has terminated with signal {signal}; see stderr \
+                         file `{path}` for more details",
+                        path = stderr_path.display()
+                    );
+                }
+            }
+
+            Ok((status, usage))
+        };
+
+        // Send the created event
+        send_event!(
+            self.events,
+            Event::TaskCreated {
+                id,
+                name: self.name.clone(),
+                tes_id: None,
+                token: self.token.clone(),
+            }
+        );
+
+        select! {
+            // Poll the cancellation token before the child future
+            biased;
+
+            _ = self.token.cancelled() => {
+                send_event!(self.events, Event::TaskCanceled { id });
+                bail!("task was cancelled");
+            }
+            result = run => {
+                match result {
+                    Ok((status, usage)) => {
+                        send_event!(self.events, Event::TaskCompleted { id, exit_statuses: NonEmpty::new(status) });
+
+                        let exit_code = status.code().expect("process should have exited");
+                        info!("process {id} for task `{name}` has terminated with status code {exit_code}", name = self.name);
+                        Ok(TaskExecutionResult {
+                            exit_code,
+                            work_dir: EvaluationPath::Local(work_dir),
+                            stdout: PrimitiveValue::new_file(stdout_path.into_os_string().into_string().expect("path should be UTF-8")).into(),
+                            stderr: PrimitiveValue::new_file(stderr_path.into_os_string().into_string().expect("path should be UTF-8")).into(),
+                            usage: Some(usage),
+                            gpus: Vec::new(),
+                        })
+                    }
+                    Err(e) => {
+                        send_event!(self.events, Event::TaskFailed { id, message: format!("{e:#}") });
+                        Err(e)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Represents a task execution backend that executes tasks in Apptainer
+/// (formerly Singularity) containers on the host.
+pub struct ApptainerBackend {
+    /// The engine configuration.
+    config: Arc<Config>,
+    /// The backend configuration.
+    backend_config: Arc<ApptainerBackendConfig>,
+    /// The total CPU of the host.
+    cpu: u64,
+    /// The total memory of the host.
+    memory: u64,
+    /// The image cache of the backend.
+    images: Arc<ImageCache>,
+    /// The underlying task manager.
+    manager: Arc<TaskManager<ApptainerTaskRequest>>,
+    /// The name generator for tasks.
+    names: Arc<Mutex<GeneratorIterator<UniqueAlphanumeric>>>,
+    /// The sender for events.
+    events: Option<broadcast::Sender<Event>>,
+}
+
+impl ApptainerBackend {
+    /// Constructs a new Apptainer task execution backend with the given
+    /// configuration.
+    ///
+    /// The provided configuration is expected to have already been validated.
+    pub fn new(
+        config: Arc<Config>,
+        backend_config: &ApptainerBackendConfig,
+        events: Option<broadcast::Sender<Event>>,
+    ) -> Result<Self> {
+        info!("initializing Apptainer backend");
+
+        let names = Arc::new(Mutex::new(GeneratorIterator::new(
+            UniqueAlphanumeric::default_with_expected_generations(INITIAL_EXPECTED_NAMES),
+            INITIAL_EXPECTED_NAMES,
+        )));
+
+        let dir = match &backend_config.image_cache_dir {
+            Some(dir) => dir.clone(),
+            None => dirs::cache_dir()
+                .context("failed to determine system cache directory")?
+                .join(DEFAULT_IMAGE_CACHE_SUBDIR),
+        };
+
+        let images = Arc::new(ImageCache {
+            executable: backend_config
+                .executable
+                .clone()
+                .unwrap_or_else(|| DEFAULT_APPTAINER_EXECUTABLE.to_string()),
+            dir,
+            images: Default::default(),
+        });
+
+        let cpu = backend_config
+            .cpu
+            .unwrap_or_else(|| SYSTEM.cpus().len() as u64);
+        let memory = backend_config
+            .memory
+            .as_ref()
+            .map(|s| convert_unit_string(s).expect("value should be valid"))
+            .unwrap_or_else(|| SYSTEM.total_memory());
+        let manager = Arc::new(TaskManager::new(cpu, cpu, memory, memory));
+
+        Ok(Self {
+            config,
+            backend_config: Arc::new(backend_config.clone()),
+            cpu,
+            memory,
+            images,
+            manager,
+            names,
+            events,
+        })
+    }
+}
+
+impl TaskExecutionBackend for ApptainerBackend {
+    fn max_concurrency(&self) -> u64 {
+        self.cpu
+    }
+
+    fn constraints(
+        &self,
+        requirements: &HashMap<String, Value>,
+        _: &HashMap<String, Value>,
+    ) -> Result<TaskExecutionConstraints> {
+        let container = container(requirements, self.config.task.container.as_deref());
+
+        let mut cpu = cpu(requirements);
+        if (self.cpu as f64) < cpu {
+            let env_specific = if self.config.suppress_env_specific_output {
+                String::new()
+            } else {
+                format!(
+                    ", but the host only has {total_cpu} available",
+                    total_cpu = self.cpu
+                )
+            };
+            match self.config.task.cpu_limit_behavior {
+                TaskResourceLimitBehavior::TryWithMax => {
+                    warn!(
+                        "task requires at least {cpu} CPU{s}{env_specific}",
+                        s = if cpu == 1.0 { "" } else { "s" },
+                    );
+                    // clamp the reported constraint to what's available
+                    cpu = self.cpu as f64;
+                }
+                TaskResourceLimitBehavior::Deny => {
+                    bail!(
+                        "task requires at least {cpu} CPU{s}{env_specific}",
+                        s = if cpu == 1.0 { "" } else { "s" },
+                    );
+                }
+            }
+        }
+
+        let mut memory = memory(requirements)?;
+        if self.memory < memory as u64 {
+            let env_specific = if self.config.suppress_env_specific_output {
+                String::new()
+            } else {
+                format!(
+                    ", but the host only has {total_memory} GiB available",
+                    total_memory = self.memory as f64 / ONE_GIBIBYTE,
+                )
+            };
+            match self.config.task.memory_limit_behavior {
+                TaskResourceLimitBehavior::TryWithMax => {
+                    warn!(
+                        "task requires at least {memory} GiB of memory{env_specific}",
+                        // Display the error in GiB, as it is the most common unit for memory
+                        memory = memory as f64 / ONE_GIBIBYTE,
+                    );
+                    // clamp the reported constraint to what's available
+                    memory = self.memory.try_into().unwrap_or(i64::MAX);
+                }
+                TaskResourceLimitBehavior::Deny => {
+                    bail!(
+                        "task requires at least {memory} GiB of memory{env_specific}",
+                        // Display the error in GiB, as it is the most common unit for memory
+                        memory = memory as f64 / ONE_GIBIBYTE,
+                    );
+                }
+            }
+        }
+
+        Ok(TaskExecutionConstraints {
+            container: Some(container.into_owned()),
+            cpu,
+            memory,
+            gpu: Default::default(),
+            fpga: Default::default(),
+            disks: Default::default(),
+        })
+    }
+
+    fn guest_inputs_dir(&self) -> Option<&'static str> {
+        Some(GUEST_INPUTS_DIR)
+    }
+
+    fn needs_local_inputs(&self) -> bool {
+        true
+    }
+
+    fn spawn(
+        &self,
+        request: TaskSpawnRequest,
+        token: CancellationToken,
+    ) -> Result<Receiver<Result<TaskExecutionResult>>> {
+        let (completed_tx, completed_rx) = oneshot::channel();
+
+        let requirements = request.requirements();
+        let container = container(requirements, self.config.task.container.as_deref()).into_owned();
+        let mut cpu = cpu(requirements);
+        if let TaskResourceLimitBehavior::TryWithMax = self.config.task.cpu_limit_behavior {
+            cpu = std::cmp::min(cpu.ceil() as u64, self.cpu) as f64;
+        }
+        let mut memory = memory(requirements)? as u64;
+        if let TaskResourceLimitBehavior::TryWithMax = self.config.task.memory_limit_behavior {
+            memory = std::cmp::min(memory, self.memory);
+        }
+
+        let timeout = timeout(request.hints(), &self.config)?;
+
+        let name = format!(
+            "{id}-{generated}",
+            id = request.id(),
+            generated = self
+                .names
+                .lock()
+                .expect("generator should always acquire")
+                .next()
+                .expect("generator should never be exhausted")
+        );
+
+        let source = ImageSource::parse(&container)?;
+        let request = ApptainerTaskRequest {
+            config: self.config.clone(),
+            backend_config: self.backend_config.clone(),
+            executable: self.images.executable.clone(),
+            inner: request,
+            name,
+            container,
+            image: PathBuf::new(),
+            cpu,
+            memory,
+            token,
+            timeout,
+            events: self.events.clone(),
+        };
+
+        // Resolve the image before the request is sent to the task manager so that
+        // pulling an image does not hold the task's reservation or count against its
+        // timeout
+        let images = self.images.clone();
+        let manager = self.manager.clone();
+        tokio::spawn(async move {
+            let image = select! {
+                biased;
+
+                _ = request.token.cancelled() => Err(anyhow!("task was cancelled")),
+                image = images.get(source) => image,
+            };
+
+            match image {
+                Ok(image) => manager.send(ApptainerTaskRequest { image, ..request }, completed_tx),
+                Err(e) => {
+                    let _ = completed_tx.send(Err(e));
+                }
+            }
+        });
+
+        Ok(completed_rx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn image_sources() {
+        assert_eq!(
+            ImageSource::parse("ubuntu:latest").unwrap(),
+            ImageSource::Remote("docker://ubuntu:latest".to_string())
+        );
+        assert_eq!(
+            ImageSource::parse("docker://ubuntu@sha256:abcd").unwrap(),
+            ImageSource::Remote("docker://ubuntu@sha256:abcd".to_string())
+        );
+        assert_eq!(
+            ImageSource::parse("library://alpine:3").unwrap(),
+            ImageSource::Remote("library://alpine:3".to_string())
+        );
+        assert_eq!(
+            ImageSource::parse("/images/ubuntu.sif").unwrap(),
+            ImageSource::Local("/images/ubuntu.sif".into())
+        );
+        assert_eq!(
+            ImageSource::parse("file:///images/ubuntu.sif").unwrap(),
+            ImageSource::Local("/images/ubuntu.sif".into())
+        );
+        assert!(ImageSource::parse("*").is_err());
+        assert!(ImageSource::parse("ubuntu:").is_err());
+    }
+
+    #[test]
+    fn env_args() {
+        assert_eq!(env_arg("FOO", "bar baz").unwrap(), "--env=FOO=bar baz");
+        assert_eq!(env_arg("FOO", "a=b").unwrap(), "--env=FOO=a=b");
+        assert_eq!(
+            env_arg("FOO", r#"a,"b""#).unwrap(),
+            r#"--env="FOO=a,""b""""#
+        );
+        assert!(env_arg("", "bar").is_err());
+        assert!(env_arg("FOO=BAR", "baz").is_err());
+        assert!(env_arg("FOO\nBAR", "baz").is_err());
+        assert!(env_arg("FOO", "bar\nbaz").is_err());
+    }
+}
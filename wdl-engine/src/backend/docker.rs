@@ -2,13 +2,27 @@
 
 use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
+use std::pin::pin;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
 
 use anyhow::Context;
 use anyhow::Result;
 use anyhow::bail;
+use bollard::auth::DockerCredentials;
+use bollard::models::ContainerStatsResponse;
+use bollard::models::DeviceRequest;
+use bollard::models::HostConfig;
+use bollard::models::Mount;
+use bollard::models::MountTypeEnum;
+use bollard::query_parameters::CreateImageOptions;
+use bollard::query_parameters::StatsOptionsBuilder;
 use crankshaft::config::backend;
+use crankshaft::docker::Docker;
+use crankshaft::docker::EventOptions;
 use crankshaft::engine::Task;
 use crankshaft::engine::service::name::GeneratorIterator;
 use crankshaft::engine::service::name::UniqueAlphanumeric;
@@ -22,7 +36,16 @@ use crankshaft::engine::task::input::Contents;
 use crankshaft::engine::task::input::Type as InputType;
 use crankshaft::engine::task::output::Type as OutputType;
 use crankshaft::events::Event;
+use crankshaft::events::next_task_id;
+use crankshaft::events::send_event;
+use futures::FutureExt;
+use futures::StreamExt;
+use futures::future::BoxFuture;
+use indexmap::IndexMap;
 use nonempty::NonEmpty;
+use secrecy::ExposeSecret;
+use tokio::process::Command;
+use tokio::select;
 use tokio::sync::broadcast;
 use tokio::sync::oneshot;
 use tokio::sync::oneshot::Receiver;
@@ -31,12 +54,15 @@ use tracing::info;
 use tracing::warn;
 use url::Url;
 
+use super::ResourceUsage;
 use super::TaskExecutionBackend;
 use super::TaskExecutionConstraints;
 use super::TaskExecutionResult;
 use super::TaskManager;
 use super::TaskManagerRequest;
 use super::TaskSpawnRequest;
+use super::rerun::RerunContainer;
+use super::rerun::write_container_rerun_script;
 use crate::COMMAND_FILE_NAME;
 use crate::ONE_GIBIBYTE;
 use crate::PrimitiveValue;
@@ -48,13 +74,17 @@ use crate::backend::INITIAL_EXPECTED_NAMES;
 use crate::config::Config;
 use crate::config::DEFAULT_TASK_SHELL;
 use crate::config::DockerBackendConfig;
+use crate::config::RegistryAuthConfig;
 use crate::config::TaskResourceLimitBehavior;
 use crate::path::EvaluationPath;
+use crate::pinning::repository;
 use crate::v1::container;
 use crate::v1::cpu;
+use crate::v1::gpu;
 use crate::v1::max_cpu;
 use crate::v1::max_memory;
 use crate::v1::memory;
+use crate::v1::timeout;
 
 /// The root guest path for inputs.
 const GUEST_INPUTS_DIR: &str = "/mnt/task/inputs/";
@@ -71,6 +101,112 @@ const GUEST_STDOUT_PATH: &str = "/mnt/task/stdout";
 /// The path to the container's stderr.
 const GUEST_STDERR_PATH: &str = "/mnt/task/stderr";
 
+/// The device driver (and Docker runtime) used for GPU device requests.
+const GPU_DRIVER: &str = "nvidia";
+
+/// The timeout, in seconds, for requests made to a configured Docker socket.
+const SOCKET_TIMEOUT_SECS: u64 = 120;
+
+/// The registry host of images that do not specify one.
+const DEFAULT_REGISTRY: &str = "docker.io";
+
+/// Gets the registry host of the given image.
+///
+/// The first component of an image's name is only a registry host if it
+/// contains a `.` or `:` or is `localhost`.
+fn registry(image: &str) -> &str {
+    match image.split_once('/') {
+        Some((host, _)) if host.contains(['.', ':']) || host == "localhost" => host,
+        _ => DEFAULT_REGISTRY,
+    }
+}
+
+/// Represents settings of the Docker daemon that apply to task containers.
+#[derive(Debug)]
+struct DaemonSettings {
+    /// The configured socket of the Docker daemon.
+    ///
+    /// This is `None` when connecting to the default Docker daemon.
+    socket: Option<String>,
+    /// Whether or not the daemon is running in rootless mode.
+    ///
+    /// A rootless daemon maps the container's root user to the current user.
+    rootless: bool,
+    /// The user namespace mode for task containers.
+    userns_mode: Option<String>,
+    /// The authentication to use when pulling images, keyed by registry host.
+    registries: IndexMap<String, RegistryAuthConfig>,
+}
+
+impl DaemonSettings {
+    /// Gets the credentials to use when pulling the given image.
+    ///
+    /// Returns `None` if no authentication is configured for the image's
+    /// registry.
+    fn credentials(&self, image: &str) -> Option<DockerCredentials> {
+        let registry = registry(image);
+        let credentials = match self.registries.get(registry)? {
+            RegistryAuthConfig::Basic(auth) => DockerCredentials {
+                username: Some(auth.username.clone()),
+                password: Some(auth.password.inner().expose_secret().to_string()),
+                ..Default::default()
+            },
+            RegistryAuthConfig::Bearer(auth) => DockerCredentials {
+                registrytoken: Some(auth.token.inner().expose_secret().to_string()),
+                ..Default::default()
+            },
+        };
+
+        Some(DockerCredentials {
+            serveraddress: Some(registry.to_string()),
+            ..credentials
+        })
+    }
+
+    /// Ensures the given image exists on the daemon, pulling it if necessary.
+    ///
+    /// The image is pulled with the authentication configured for its
+    /// registry.
+    async fn ensure_image(&self, client: &Docker, image: &str) -> Result<()> {
+        let Some(credentials) = self.credentials(image) else {
+            return client
+                .ensure_image(image)
+                .await
+                .with_context(|| format!("failed to pull image `{image}`"));
+        };
+
+        if client.inner().inspect_image(image).await.is_ok() {
+            return Ok(());
+        }
+
+        info!(
+            "pulling image `{image}` with the credentials for registry `{registry}`",
+            registry = registry(image)
+        );
+
+        // An image without a tag or digest is pulled with the `latest` tag
+        let tag = (repository(image) == image).then(|| "latest".to_string());
+        let mut stream = client.inner().create_image(
+            Some(CreateImageOptions {
+                from_image: Some(image.to_string()),
+                tag,
+                ..Default::default()
+            }),
+            None,
+            Some(credentials),
+        );
+
+        while let Some(info) = stream.next().await {
+            let info = info.with_context(|| format!("failed to pull image `{image}`"))?;
+            if let Some(e) = info.error_detail.and_then(|d| d.message).or(info.error) {
+                bail!("failed to pull image `{image}`: {e}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
 /// This request contains the requested cpu and memory reservations for the task
 /// as well as the result receiver channel.
 #[derive(Debug)]
@@ -79,8 +215,10 @@ struct DockerTaskRequest {
     config: Arc<Config>,
     /// The inner task spawn request.
     inner: TaskSpawnRequest,
-    /// The underlying Crankshaft backend.
-    backend: Arc<docker::Backend>,
+    /// The underlying Crankshaft backend used to run the task as a service.
+    ///
+    /// This is `Some` only when the Docker daemon is part of a swarm.
+    service: Option<Arc<docker::Backend>>,
     /// The name of the task.
     name: String,
     /// The requested container for the task.
@@ -93,8 +231,212 @@ struct DockerTaskRequest {
     max_cpu: Option<f64>,
     /// The requested maximum memory limit for the task, in bytes.
     max_memory: Option<u64>,
+    /// The requested number of GPU devices for the task.
+    gpu: u64,
+    /// The identifiers of the GPU devices reserved for the task.
+    ///
+    /// This is set by the task manager before the task runs.
+    gpus: Vec<String>,
+    /// The Docker client used to run task containers.
+    client: Docker,
+    /// The daemon settings for the task's container.
+    daemon: Arc<DaemonSettings>,
+    /// Whether or not to remove the task's container after it completes.
+    cleanup: bool,
+    /// The sender for events.
+    events: Option<broadcast::Sender<Event>>,
     /// The cancellation token for the request.
     token: CancellationToken,
+    /// The maximum amount of time the task may run for.
+    timeout: Option<Duration>,
+}
+
+impl DockerTaskRequest {
+    /// Gets the shell to use for running the task's command.
+    fn shell(&self) -> &str {
+        self.config
+            .task
+            .shell
+            .as_deref()
+            .unwrap_or(DEFAULT_TASK_SHELL)
+    }
+
+    /// Runs the task in a container, with any assigned GPU devices, using the
+    /// Docker client directly.
+    ///
+    /// The resource usage of the container is sampled from its stats while it
+    /// runs.
+    ///
+    /// Returns the exit code and resource usage of the container.
+    async fn run_container(
+        &self,
+        inputs: &[Input],
+        stdout_path: &Path,
+        stderr_path: &Path,
+        resources: &Resources,
+    ) -> Result<(i32, ResourceUsage)> {
+        let mut mounts = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let Contents::Path(source) = input.contents() else {
+                bail!(
+                    "input `{path}` must be a local path to be mounted",
+                    path = input.path()
+                );
+            };
+
+            mounts.push(Mount {
+                target: Some(input.path().to_string()),
+                source: Some(
+                    source
+                        .to_str()
+                        .with_context(|| {
+                            format!("path `{source}` is not UTF-8", source = source.display())
+                        })?
+                        .to_string(),
+                ),
+                typ: Some(MountTypeEnum::BIND),
+                read_only: Some(input.read_only()),
+                ..Default::default()
+            });
+        }
+
+        let id = next_task_id();
+        let run = async {
+            self.daemon
+                .ensure_image(&self.client, &self.container)
+                .await?;
+
+            let container = self
+                .client
+                .container_builder()
+                .name(&self.name)
+                .image(&self.container)
+                .program(self.shell())
+                .args([GUEST_COMMAND_PATH])
+                .envs(self.inner.env().clone())
+                .work_dir(GUEST_WORK_DIR)
+                .stdout(stdout_path)
+                .stderr(stderr_path)
+                .host_config(HostConfig {
+                    mounts: Some(mounts),
+                    // Ensure the caller's group id is added so that the container can access
+                    // the mounts and working directory; a rootless daemon does not map the
+                    // caller's groups into the container's user namespace
+                    #[cfg(unix)]
+                    group_add: (!self.daemon.rootless)
+                        .then(|| vec![unsafe { libc::getegid() }.to_string()]),
+                    userns_mode: self.daemon.userns_mode.clone(),
+                    device_requests: (!self.gpus.is_empty()).then(|| {
+                        vec![DeviceRequest {
+                            driver: Some(GPU_DRIVER.to_string()),
+                            device_ids: Some(self.gpus.clone()),
+                            capabilities: Some(vec![vec!["gpu".to_string()]]),
+                            ..Default::default()
+                        }]
+                    }),
+                    ..resources.into()
+                })
+                .try_build()
+                .await
+                .context("failed to create Docker container")?;
+
+            if self.gpus.is_empty() {
+                info!("created container `{name}`", name = container.name());
+            } else {
+                info!(
+                    "created container `{name}` with GPU devices {gpus:?}",
+                    name = container.name(),
+                    gpus = self.gpus,
+                );
+            }
+
+            let options = self.events.clone().map(|sender| EventOptions {
+                sender,
+                task_id: id,
+                send_start: true,
+            });
+
+            let start = Instant::now();
+            let mut usage = ResourceUsage::new(Duration::ZERO);
+            let mut stats = self.client.inner().stats(
+                &self.name,
+                Some(StatsOptionsBuilder::new().stream(true).build()),
+            );
+            let mut run = pin!(container.run(&self.name, options));
+            let result = loop {
+                select! {
+                    // Poll the cancellation token before the container future
+                    biased;
+
+                    _ = self.token.cancelled() => break None,
+                    result = &mut run => break Some(result),
+                    Some(Ok(stats)) = stats.next() => record_stats(&mut usage, stats),
+                }
+            };
+            usage.wall_time = start.elapsed();
+
+            if self.cleanup {
+                let cleanup = if result.is_none() {
+                    container.force_remove().await
+                } else {
+                    container.remove().await
+                };
+
+                if let Err(e) = cleanup {
+                    warn!(
+                        "failed to remove container `{name}`: {e:#}",
+                        name = container.name()
+                    );
+                }
+            }
+
+            result
+                .map(|r| {
+                    r.map(|status| (status, usage))
+                        .context("failed to run Docker container")
+                })
+                .transpose()
+        };
+
+        // Send the created event
+        send_event!(
+            self.events,
+            Event::TaskCreated {
+                id,
+                name: self.name.clone(),
+                tes_id: None,
+                token: self.token.clone(),
+            }
+        );
+
+        match run.await {
+            Ok(Some((status, usage))) => {
+                send_event!(
+                    self.events,
+                    Event::TaskCompleted {
+                        id,
+                        exit_statuses: NonEmpty::new(status),
+                    }
+                );
+
+                Ok((status.code().expect("should have exit code"), usage))
+            }
+            Ok(None) => {
+                send_event!(self.events, Event::TaskCanceled { id });
+                bail!("task was cancelled");
+            }
+            Err(e) => {
+                send_event!(
+                    self.events,
+                    Event::TaskFailed {
+                        id,
+                        message: format!("{e:#}"),
+                    }
+                );
+                Err(e)
+            }
+        }
+    }
 }
 
 impl TaskManagerRequest for DockerTaskRequest {
@@ -106,6 +448,22 @@ impl TaskManagerRequest for DockerTaskRequest {
         self.memory
     }
 
+    fn gpu(&self) -> u64 {
+        self.gpu
+    }
+
+    fn set_gpus(&mut self, gpus: Vec<String>) {
+        self.gpus = gpus;
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
     async fn run(self) -> Result<TaskExecutionResult> {
         // Create the working directory
         let work_dir = self.inner.attempt_dir().join(WORK_DIR_NAME);
@@ -117,10 +475,11 @@ impl TaskManagerRequest for DockerTaskRequest {
         })?;
 
         // On Unix, the work directory must be group writable in case the container uses
-        // a different user/group; the Crankshaft docker backend will automatically add
-        // the current user's egid to the container
+        // a different user/group; the current user's egid is added to the container.
+        // A rootless daemon maps the container's root user to the current user, so the
+        // directory's owner permissions apply instead
         #[cfg(unix)]
-        {
+        if !self.daemon.rootless {
             use std::fs::Permissions;
             use std::fs::set_permissions;
             use std::os::unix::fs::PermissionsExt;
@@ -142,6 +501,24 @@ impl TaskManagerRequest for DockerTaskRequest {
             )
         })?;
 
+        write_container_rerun_script(
+            &self.inner,
+            RerunContainer::Docker {
+                image: &self.container,
+                host: self.daemon.socket.as_deref(),
+            },
+            self.shell(),
+            (&work_dir, GUEST_WORK_DIR),
+            (&command_path, GUEST_COMMAND_PATH),
+        )?;
+
+        // Inputs localized as independent copies may be written to by the task
+        let writable = self
+            .config
+            .task
+            .localization
+            .is_some_and(|s| s.is_writable());
+
         // Allocate the inputs, which will always be, at most, the number of inputs plus
         // the working directory and command
         let mut inputs = Vec::with_capacity(self.inner.inputs().len() + 2);
@@ -162,7 +539,7 @@ impl TaskManagerRequest for DockerTaskRequest {
                     .path(guest_path.as_str())
                     .contents(Contents::Path(local_path.into()))
                     .ty(input.kind())
-                    .read_only(true)
+                    .read_only(!writable)
                     .build(),
             );
         }
@@ -190,57 +567,63 @@ impl TaskManagerRequest for DockerTaskRequest {
         let stdout_path = self.inner.attempt_dir().join(STDOUT_FILE_NAME);
         let stderr_path = self.inner.attempt_dir().join(STDERR_FILE_NAME);
 
-        let outputs = vec![
-            Output::builder()
-                .path(GUEST_STDOUT_PATH)
-                .url(Url::from_file_path(&stdout_path).expect("path should be absolute"))
-                .ty(OutputType::File)
-                .build(),
-            Output::builder()
-                .path(GUEST_STDERR_PATH)
-                .url(Url::from_file_path(&stderr_path).expect("path should be absolute"))
-                .ty(OutputType::File)
-                .build(),
-        ];
-
-        let task = Task::builder()
-            .name(self.name)
-            .executions(NonEmpty::new(
-                Execution::builder()
-                    .image(self.container)
-                    .program(
-                        self.config
-                            .task
-                            .shell
-                            .as_deref()
-                            .unwrap_or(DEFAULT_TASK_SHELL),
-                    )
-                    .args([GUEST_COMMAND_PATH.to_string()])
-                    .work_dir(GUEST_WORK_DIR)
-                    .env(self.inner.env().clone())
-                    .stdout(GUEST_STDOUT_PATH)
-                    .stderr(GUEST_STDERR_PATH)
+        let resources = Resources::builder()
+            .cpu(self.cpu)
+            .maybe_cpu_limit(self.max_cpu)
+            .ram(self.memory as f64 / ONE_GIBIBYTE)
+            .maybe_ram_limit(self.max_memory.map(|m| m as f64 / ONE_GIBIBYTE))
+            .build();
+
+        // The Crankshaft backend does not support device requests or expose the
+        // containers it creates, so tasks are run with the Docker client directly
+        // unless the daemon is part of a swarm
+        let (exit_code, usage) = if let Some(service) = &self.service {
+            let start = Instant::now();
+            let outputs = vec![
+                Output::builder()
+                    .path(GUEST_STDOUT_PATH)
+                    .url(Url::from_file_path(&stdout_path).expect("path should be absolute"))
+                    .ty(OutputType::File)
                     .build(),
-            ))
-            .inputs(inputs)
-            .outputs(outputs)
-            .resources(
-                Resources::builder()
-                    .cpu(self.cpu)
-                    .maybe_cpu_limit(self.max_cpu)
-                    .ram(self.memory as f64 / ONE_GIBIBYTE)
-                    .maybe_ram_limit(self.max_memory.map(|m| m as f64 / ONE_GIBIBYTE))
+                Output::builder()
+                    .path(GUEST_STDERR_PATH)
+                    .url(Url::from_file_path(&stderr_path).expect("path should be absolute"))
+                    .ty(OutputType::File)
                     .build(),
-            )
-            .build();
+            ];
 
-        let statuses = self.backend.run(task, self.token.clone())?.await?;
+            let task = Task::builder()
+                .name(&self.name)
+                .executions(NonEmpty::new(
+                    Execution::builder()
+                        .image(&self.container)
+                        .program(self.shell())
+                        .args([GUEST_COMMAND_PATH.to_string()])
+                        .work_dir(GUEST_WORK_DIR)
+                        .env(self.inner.env().clone())
+                        .stdout(GUEST_STDOUT_PATH)
+                        .stderr(GUEST_STDERR_PATH)
+                        .build(),
+                ))
+                .inputs(inputs)
+                .outputs(outputs)
+                .resources(resources)
+                .build();
 
-        assert_eq!(statuses.len(), 1, "there should only be one exit status");
-        let status = statuses.first();
+            let statuses = service.run(task, self.token.clone())?.await?;
+
+            assert_eq!(statuses.len(), 1, "there should only be one exit status");
+            (
+                statuses.first().code().expect("should have exit code"),
+                ResourceUsage::new(start.elapsed()),
+            )
+        } else {
+            self.run_container(&inputs, &stdout_path, &stderr_path, &resources)
+                .await?
+        };
 
         Ok(TaskExecutionResult {
-            exit_code: status.code().expect("should have exit code"),
+            exit_code,
             work_dir: EvaluationPath::Local(work_dir),
             stdout: PrimitiveValue::new_file(
                 stdout_path
@@ -256,16 +639,43 @@ impl TaskManagerRequest for DockerTaskRequest {
                     .expect("path should be UTF-8"),
             )
             .into(),
+            usage: Some(usage),
+            gpus: self.gpus,
         })
     }
 }
 
+/// Records the memory and CPU usage reported by a container's stats.
+///
+/// The peak memory is the maximum usage reported by the stats; the CPU time is
+/// cumulative, so the latest reported value is used.
+fn record_stats(usage: &mut ResourceUsage, stats: ContainerStatsResponse) {
+    if let Some(memory) = stats
+        .memory_stats
+        .and_then(|s| s.max_usage.or(s.usage))
+        .filter(|m| *m > 0)
+    {
+        usage.peak_memory = Some(usage.peak_memory.map_or(memory, |m| m.max(memory)));
+    }
+
+    if let Some(cpu) = stats
+        .cpu_stats
+        .and_then(|s| s.cpu_usage)
+        .and_then(|u| u.total_usage)
+        .filter(|c| *c > 0)
+    {
+        usage.cpu_time = Some(Duration::from_nanos(cpu));
+    }
+}
+
 /// Represents the Docker backend.
 pub struct DockerBackend {
     /// The engine configuration.
     config: Arc<Config>,
-    /// The underlying Crankshaft backend.
-    inner: Arc<docker::Backend>,
+    /// The underlying Crankshaft backend used to run tasks as services.
+    ///
+    /// This is `Some` only when the Docker daemon is part of a swarm.
+    service: Option<Arc<docker::Backend>>,
     /// The maximum amount of concurrency supported.
     max_concurrency: u64,
     /// The maximum CPUs for any of one node.
@@ -276,6 +686,16 @@ pub struct DockerBackend {
     manager: TaskManager<DockerTaskRequest>,
     /// The name generator for tasks.
     names: Arc<Mutex<GeneratorIterator<UniqueAlphanumeric>>>,
+    /// The Docker client used to run task containers.
+    client: Docker,
+    /// The daemon settings for task containers.
+    daemon: Arc<DaemonSettings>,
+    /// The identifiers of the GPU devices available to tasks.
+    gpus: Vec<String>,
+    /// Whether or not to remove a task's container after the task completes.
+    cleanup: bool,
+    /// The sender for events.
+    events: Option<broadcast::Sender<Event>>,
 }
 
 impl DockerBackend {
@@ -295,43 +715,169 @@ impl DockerBackend {
             INITIAL_EXPECTED_NAMES,
         )));
 
-        let backend = docker::Backend::initialize_default_with(
-            backend::docker::Config::builder()
-                .cleanup(backend_config.cleanup)
-                .build(),
-            names.clone(),
-            events,
-        )
-        .await
-        .context("failed to initialize Docker backend")?;
+        let client = match &backend_config.socket {
+            Some(socket) => Docker::new(
+                bollard::Docker::connect_with_socket(
+                    socket,
+                    SOCKET_TIMEOUT_SECS,
+                    bollard::API_DEFAULT_VERSION,
+                )
+                .with_context(|| format!("failed to connect to Docker socket `{socket}`"))?
+                .negotiate_version()
+                .await
+                .with_context(|| {
+                    format!("failed to negotiate API version with Docker socket `{socket}`")
+                })?,
+            ),
+            None => {
+                Docker::with_defaults().context("failed to connect to the local Docker daemon")?
+            }
+        };
 
-        let resources = *backend.resources();
-        let cpu = resources.cpu();
-        let max_cpu = resources.max_cpu();
-        let memory = resources.memory();
-        let max_memory = resources.max_memory();
+        let info = client
+            .info()
+            .await
+            .context("failed to retrieve Docker daemon information")?;
+
+        // A rootless daemon (e.g. rootless Docker or Podman) reports itself in its
+        // security options
+        let rootless = info
+            .security_options
+            .iter()
+            .flatten()
+            .any(|o| o.split(',').any(|o| o == "name=rootless"));
+        if rootless {
+            info!("Docker daemon is running in rootless mode");
+        }
+
+        // The Crankshaft backend only connects to the default Docker daemon and is
+        // only used to run tasks as services when the daemon is part of a swarm
+        let (service, cpu, max_cpu, memory, max_memory) = match &backend_config.socket {
+            Some(_) => {
+                let cpu: u64 = info
+                    .ncpu
+                    .context("Docker daemon did not report a CPU count")?
+                    .try_into()
+                    .context("Docker daemon reported a negative CPU count")?;
+                let memory: u64 = info
+                    .mem_total
+                    .context("Docker daemon did not report a memory total")?
+                    .try_into()
+                    .context("Docker daemon reported a negative total memory")?;
+                (None, cpu, cpu, memory, memory)
+            }
+            None => {
+                let backend = docker::Backend::initialize_default_with(
+                    backend::docker::Config::builder()
+                        .cleanup(backend_config.cleanup)
+                        .build(),
+                    names.clone(),
+                    events.clone(),
+                )
+                .await
+                .context("failed to initialize Docker backend")?;
+
+                let resources = *backend.resources();
+                (
+                    resources.use_service().then(|| Arc::new(backend)),
+                    resources.cpu(),
+                    resources.max_cpu(),
+                    resources.memory(),
+                    resources.max_memory(),
+                )
+            }
+        };
+
+        // Device requests are only supported for containers and not for services
+        let gpus = if service.is_some() {
+            if backend_config.gpus.is_some() {
+                warn!("GPUs are not supported when the Docker daemon is part of a swarm");
+            }
+
+            Vec::new()
+        } else {
+            match &backend_config.gpus {
+                Some(gpus) => gpus.clone(),
+                None => discover_gpus(&client).await,
+            }
+        };
 
         // If a service is being used, then we're going to be spawning into a cluster
         // For the purposes of resource tracking, treat it as unlimited resources and
         // let Docker handle resource allocation
-        let manager = if resources.use_service() {
+        let manager = if service.is_some() {
             TaskManager::new_unlimited(max_cpu, max_memory)
         } else {
-            TaskManager::new(cpu, max_cpu, memory, max_memory)
+            TaskManager::with_gpus(cpu, max_cpu, memory, max_memory, gpus.clone())
         };
 
+        if !gpus.is_empty() {
+            info!(
+                "Docker backend has {count} GPU{s} available",
+                count = gpus.len(),
+                s = if gpus.len() == 1 { "" } else { "s" }
+            );
+        }
+
         Ok(Self {
             config,
-            inner: Arc::new(backend),
+            service,
             max_concurrency: cpu,
             max_cpu,
             max_memory,
             manager,
             names,
+            client,
+            daemon: Arc::new(DaemonSettings {
+                socket: backend_config.socket.clone(),
+                rootless,
+                userns_mode: backend_config.userns_mode.clone(),
+                registries: backend_config.registries.clone(),
+            }),
+            gpus,
+            cleanup: backend_config.cleanup,
+            events,
         })
     }
 }
 
+/// Discovers the GPUs available to the Docker daemon.
+///
+/// GPUs are only discovered when the daemon has an `nvidia` runtime; the device
+/// identifiers are the GPU indexes reported by `nvidia-smi`.
+async fn discover_gpus(client: &Docker) -> Vec<String> {
+    let has_runtime = client
+        .info()
+        .await
+        .ok()
+        .and_then(|info| info.runtimes)
+        .map(|runtimes| runtimes.contains_key(GPU_DRIVER))
+        .unwrap_or(false);
+    if !has_runtime {
+        return Vec::new();
+    }
+
+    match Command::new("nvidia-smi")
+        .args(["--query-gpu=index", "--format=csv,noheader"])
+        .output()
+        .await
+    {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(ToString::to_string)
+            .collect(),
+        _ => {
+            warn!(
+                "the Docker daemon has an `{GPU_DRIVER}` runtime but its GPUs could not be \
+                 discovered with `nvidia-smi`"
+            );
+            Vec::new()
+        }
+    }
+}
+
 impl TaskExecutionBackend for DockerBackend {
     fn max_concurrency(&self) -> u64 {
         self.max_concurrency
@@ -340,7 +886,7 @@ impl TaskExecutionBackend for DockerBackend {
     fn constraints(
         &self,
         requirements: &HashMap<String, Value>,
-        _: &HashMap<String, Value>,
+        hints: &HashMap<String, Value>,
     ) -> Result<TaskExecutionConstraints> {
         let container = container(requirements, self.config.task.container.as_deref());
 
@@ -402,11 +948,40 @@ impl TaskExecutionBackend for DockerBackend {
             }
         }
 
+        let mut gpu = gpu(requirements, hints);
+        let max_gpu = self.gpus.len() as u64;
+        if max_gpu < gpu {
+            let env_specific = if self.config.suppress_env_specific_output {
+                String::new()
+            } else {
+                format!(", but the execution backend has a maximum of {max_gpu}")
+            };
+            match self.config.task.gpu_limit_behavior {
+                TaskResourceLimitBehavior::TryWithMax => {
+                    warn!(
+                        "task requires at least {gpu} GPU{s}{env_specific}",
+                        s = if gpu == 1 { "" } else { "s" },
+                    );
+                    // clamp the reported constraint to what's available
+                    gpu = max_gpu;
+                }
+                TaskResourceLimitBehavior::Deny => {
+                    bail!(
+                        "task requires at least {gpu} GPU{s}{env_specific}",
+                        s = if gpu == 1 { "" } else { "s" },
+                    );
+                }
+            }
+        }
+
         Ok(TaskExecutionConstraints {
             container: Some(container.into_owned()),
             cpu,
             memory,
-            gpu: Default::default(),
+            // The devices are reserved when the task is spawned, so only the driver is
+            // known for each GPU until the task's execution result reports the reserved
+            // device identifiers
+            gpu: vec![GPU_DRIVER.to_string(); gpu as usize],
             fpga: Default::default(),
             disks: Default::default(),
         })
@@ -441,6 +1016,11 @@ impl TaskExecutionBackend for DockerBackend {
         }
         let max_cpu = max_cpu(hints);
         let max_memory = max_memory(hints)?.map(|i| i as u64);
+        // Tasks requiring more GPUs than are available were denied when determining
+        // the task's constraints, so clamp to what's available
+        let gpu = std::cmp::min(gpu(requirements, hints), self.gpus.len() as u64);
+
+        let timeout = timeout(hints, &self.config)?;
 
         let name = format!(
             "{id}-{generated}",
@@ -456,14 +1036,21 @@ impl TaskExecutionBackend for DockerBackend {
             DockerTaskRequest {
                 config: self.config.clone(),
                 inner: request,
-                backend: self.inner.clone(),
+                service: self.service.clone(),
                 name,
                 container,
                 cpu,
                 memory,
                 max_cpu,
                 max_memory,
+                gpu,
+                gpus: Vec::new(),
+                client: self.client.clone(),
+                daemon: self.daemon.clone(),
+                cleanup: self.cleanup,
+                events: self.events.clone(),
                 token,
+                timeout,
             },
             completed_tx,
         );
@@ -476,29 +1063,34 @@ impl TaskExecutionBackend for DockerBackend {
         &'a self,
         work_dir: &'a EvaluationPath,
         token: CancellationToken,
-    ) -> Option<futures::future::BoxFuture<'a, ()>> {
-        use futures::FutureExt;
+    ) -> Option<BoxFuture<'a, ()>> {
         use tracing::debug;
 
         /// The guest path for the work directory.
         const GUEST_WORK_DIR: &str = "/mnt/work";
-        /// Amount of CPU to reserve for the cleanup task.
-        const CLEANUP_CPU: f64 = 0.1;
-        /// Amount of memory to reserve for the cleanup task.
-        const CLEANUP_MEMORY: f64 = 0.05;
+        /// The image used for the cleanup container.
+        const CLEANUP_IMAGE: &str = "alpine:latest";
 
         // SAFETY: the work directory is always local for the Docker backend
         let work_dir = work_dir.as_local().expect("path should be local");
         assert!(work_dir.is_absolute(), "work directory should be absolute");
 
-        let backend = self.inner.clone();
+        let client = self.client.clone();
+        let daemon = self.daemon.clone();
         let names = self.names.clone();
 
         Some(
             async move {
                 let result = async {
-                    let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
-                    let ownership = format!("{uid}:{gid}");
+                    // A rootless daemon maps the current user to the container's root user; the
+                    // cleanup container uses the daemon's default user namespace so that the
+                    // mapping applies regardless of the configured mode
+                    let ownership = if daemon.rootless {
+                        "0:0".to_string()
+                    } else {
+                        let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+                        format!("{uid}:{gid}")
+                    };
 
                     let name = format!(
                         "docker-backend-cleanup-{id}",
@@ -509,53 +1101,58 @@ impl TaskExecutionBackend for DockerBackend {
                             .expect("generator should never be exhausted")
                     );
 
-                    let task = Task::builder()
+                    daemon.ensure_image(&client, CLEANUP_IMAGE).await?;
+
+                    let container = client
+                        .container_builder()
                         .name(&name)
-                        .executions(NonEmpty::new(
-                            Execution::builder()
-                                .image("alpine:latest")
-                                .program("chown")
-                                .args([
-                                    "-R".to_string(),
-                                    ownership.clone(),
-                                    GUEST_WORK_DIR.to_string(),
-                                ])
-                                .build(),
-                        ))
-                        .inputs([Input::builder()
-                            .path(GUEST_WORK_DIR)
-                            .contents(Contents::Path(work_dir.to_path_buf()))
-                            .ty(InputType::Directory)
-                            // need write access to chown
-                            .read_only(false)
-                            .build()])
-                        .resources(
-                            Resources::builder()
-                                .cpu(CLEANUP_CPU)
-                                .ram(CLEANUP_MEMORY)
-                                .build(),
-                        )
-                        .build();
+                        .image(CLEANUP_IMAGE)
+                        .program("chown")
+                        .args(["-R", &ownership, GUEST_WORK_DIR])
+                        .host_config(HostConfig {
+                            mounts: Some(vec![Mount {
+                                target: Some(GUEST_WORK_DIR.to_string()),
+                                source: Some(
+                                    work_dir
+                                        .to_str()
+                                        .context("work directory path is not UTF-8")?
+                                        .to_string(),
+                                ),
+                                typ: Some(MountTypeEnum::BIND),
+                                // need write access to chown
+                                read_only: Some(false),
+                                ..Default::default()
+                            }]),
+                            ..Default::default()
+                        })
+                        .try_build()
+                        .await
+                        .context("failed to create cleanup container")?;
 
                     debug!(
-                        "running cleanup task `{name}` to change ownership of `{path}` to \
+                        "running cleanup container `{name}` to change ownership of `{path}` to \
                          `{ownership}`",
                         path = work_dir.display(),
                     );
 
-                    let statuses = backend
-                        .run(task, token)
-                        .context("failed to submit cleanup task")?
-                        .await
-                        .context("failed to run cleanup task")?;
-                    let status = statuses.first();
-                    if status.success() {
-                        Ok(())
-                    } else {
-                        bail!(
+                    let result = select! {
+                        biased;
+
+                        _ = token.cancelled() => None,
+                        result = container.run(&name, None) => Some(result),
+                    };
+
+                    if let Err(e) = container.force_remove().await {
+                        warn!("failed to remove cleanup container `{name}`: {e:#}");
+                    }
+
+                    match result {
+                        Some(Ok(status)) if status.success() => Ok(()),
+                        Some(Err(e)) => Err(e).context("failed to run cleanup container"),
+                        _ => bail!(
                             "failed to chown task work directory `{path}`",
                             path = work_dir.display()
-                        );
+                        ),
                     }
                 }
                 .await;
@@ -567,4 +1164,54 @@ impl TaskExecutionBackend for DockerBackend {
             .boxed(),
         )
     }
+
+    fn resolve_container<'a>(
+        &'a self,
+        container: &'a str,
+    ) -> Option<BoxFuture<'a, Result<String>>> {
+        Some(
+            async move {
+                self.daemon.ensure_image(&self.client, container).await?;
+
+                let image = self
+                    .client
+                    .inner()
+                    .inspect_image(container)
+                    .await
+                    .with_context(|| format!("failed to inspect image `{container}`"))?;
+
+                // Prefer the digest of the container's repository as an image may have been
+                // pulled from more than one repository
+                let name = repository(container);
+                let digests = image.repo_digests.unwrap_or_default();
+                let digest = digests
+                    .iter()
+                    .filter_map(|d| d.split_once('@'))
+                    .find(|(repo, _)| *repo == name)
+                    .or_else(|| digests.iter().find_map(|d| d.split_once('@')))
+                    .map(|(_, digest)| digest)
+                    .with_context(|| {
+                        format!("image `{container}` does not have a repository digest")
+                    })?;
+
+                Ok(format!("{name}@{digest}"))
+            }
+            .boxed(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn registries() {
+        assert_eq!(registry("ubuntu"), "docker.io");
+        assert_eq!(registry("ubuntu:22.04"), "docker.io");
+        assert_eq!(registry("stjude/wdl:latest"), "docker.io");
+        assert_eq!(registry("ghcr.io/stjude/wdl:latest"), "ghcr.io");
+        assert_eq!(registry("localhost/ubuntu"), "localhost");
+        assert_eq!(registry("localhost:5000/ubuntu"), "localhost:5000");
+    }
 }
@@ -0,0 +1,740 @@
+//! Shared implementation of backends that submit tasks as jobs to an HPC
+//! scheduler.
+//!
+//! A scheduler implements submitting, querying, and cancelling jobs; the
+//! writing of a task's job script, the polling of a job until it finishes, and
+//! the reading of the command's exit code are common to every scheduler.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::process::Output;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use crankshaft::engine::service::name::GeneratorIterator;
+use crankshaft::engine::service::name::UniqueAlphanumeric;
+use crankshaft::events::Event;
+use crankshaft::events::next_task_id;
+use crankshaft::events::send_event;
+use nonempty::NonEmpty;
+use tokio::process::Command;
+use tokio::select;
+use tokio::sync::broadcast;
+use tokio::sync::oneshot;
+use tokio::sync::oneshot::Receiver;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+use tracing::info;
+use tracing::warn;
+
+use super::ResourceUsage;
+use super::TaskExecutionBackend;
+use super::TaskExecutionConstraints;
+use super::TaskManager;
+use super::TaskManagerRequest;
+use super::TaskSpawnRequest;
+use super::rerun::shell_quote;
+use super::rerun::write_local_rerun_script;
+use crate::COMMAND_FILE_NAME;
+use crate::ONE_GIBIBYTE;
+use crate::PrimitiveValue;
+use crate::STDERR_FILE_NAME;
+use crate::STDOUT_FILE_NAME;
+use crate::TaskExecutionResult;
+use crate::Value;
+use crate::WORK_DIR_NAME;
+use crate::backend::INITIAL_EXPECTED_NAMES;
+use crate::config::Config;
+use crate::config::DEFAULT_TASK_SHELL;
+use crate::config::TaskResourceLimitBehavior;
+use crate::convert_unit_string;
+use crate::path::EvaluationPath;
+use crate::v1::cpu;
+use crate::v1::disks;
+use crate::v1::memory;
+use crate::v1::timeout;
+
+/// The name of the job script file submitted to the scheduler.
+pub(crate) const JOB_SCRIPT_FILE_NAME: &str = "job.sh";
+
+/// The name of the file the job script writes the command's exit code to.
+pub(crate) const EXIT_CODE_FILE_NAME: &str = "rc";
+
+/// The default poll interval, in seconds, for checking the state of a job.
+pub(crate) const DEFAULT_INTERVAL: u64 = 30;
+
+/// The number of bytes in a mebibyte.
+pub(crate) const ONE_MEBIBYTE: f64 = 1024.0 * 1024.0;
+
+/// The number of consecutive failures to query the state of a job after which
+/// the job is considered lost.
+const MAX_STATE_QUERY_FAILURES: u32 = 3;
+
+/// Represents the state of a submitted job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum JobState {
+    /// The job is waiting to run.
+    ///
+    /// The value is the scheduler's name for the state.
+    Pending(String),
+    /// The job is running.
+    ///
+    /// The value is the scheduler's name for the state.
+    Running(String),
+    /// The job has finished.
+    Finished,
+}
+
+/// A trait implemented by HPC schedulers.
+pub(crate) trait Scheduler: fmt::Debug + Send + Sync + Sized + 'static {
+    /// Gets the name of the scheduler used in log and error messages.
+    fn name(&self) -> &str;
+
+    /// Gets the interval for polling the state of a job.
+    fn interval(&self) -> Duration;
+
+    /// Determines if the job script redirects the command's stdout and stderr
+    /// to the attempt directory.
+    ///
+    /// Schedulers that redirect a job's output themselves return `false`.
+    fn redirects_output(&self) -> bool;
+
+    /// Submits the job for the given request.
+    ///
+    /// Returns the identifier of the submitted job.
+    fn submit(&self, request: &JobRequest<Self>) -> impl Future<Output = Result<String>> + Send;
+
+    /// Queries the state of the given job.
+    ///
+    /// An error is returned if the state could not be determined; the state
+    /// is queried again at the next interval.
+    fn state(&self, job: &str, name: &str) -> impl Future<Output = Result<JobState>> + Send;
+
+    /// Cancels the given job.
+    fn cancel(&self, job: &str, name: &str) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets the final state of a finished job for error messages.
+    ///
+    /// Returns `None` if the final state cannot be determined.
+    fn final_state(&self, job: &str) -> impl Future<Output = Option<String>> + Send {
+        let _ = job;
+        async { None }
+    }
+
+    /// Gets the resource usage of a finished job.
+    ///
+    /// Returns `None` if the usage cannot be determined.
+    fn usage(&self, job: &str) -> impl Future<Output = Option<ResourceUsage>> + Send {
+        let _ = job;
+        async { None }
+    }
+}
+
+/// Represents a request to run a task as a scheduler job.
+///
+/// This request contains the requested cpu and memory reservations for the task
+/// as well as the result receiver channel.
+#[derive(Debug)]
+pub(crate) struct JobRequest<S> {
+    /// The engine configuration.
+    pub config: Arc<Config>,
+    /// The scheduler to submit the job to.
+    pub scheduler: Arc<S>,
+    /// The inner task spawn request.
+    pub inner: TaskSpawnRequest,
+    /// The name of the task.
+    pub name: String,
+    /// The requested CPU reservation for the task.
+    pub cpu: f64,
+    /// The requested memory reservation for the task, in bytes.
+    pub memory: u64,
+    /// The cancellation token for the request.
+    token: CancellationToken,
+    /// The maximum amount of time the task may run for.
+    timeout: Option<Duration>,
+    /// The sender for events.
+    events: Option<broadcast::Sender<Event>>,
+}
+
+impl<S: Scheduler> JobRequest<S> {
+    /// Gets the path to the working directory of the job.
+    pub fn work_dir(&self) -> PathBuf {
+        self.inner.attempt_dir().join(WORK_DIR_NAME)
+    }
+
+    /// Gets the path to the stdout file of the job.
+    pub fn stdout_path(&self) -> PathBuf {
+        self.inner.attempt_dir().join(STDOUT_FILE_NAME)
+    }
+
+    /// Gets the path to the stderr file of the job.
+    pub fn stderr_path(&self) -> PathBuf {
+        self.inner.attempt_dir().join(STDERR_FILE_NAME)
+    }
+
+    /// Gets the path to the job script.
+    pub fn job_script_path(&self) -> PathBuf {
+        self.inner.attempt_dir().join(JOB_SCRIPT_FILE_NAME)
+    }
+
+    /// Gets the path to the file the job script writes the command's exit code
+    /// to.
+    fn exit_code_path(&self) -> PathBuf {
+        self.inner.attempt_dir().join(EXIT_CODE_FILE_NAME)
+    }
+
+    /// Gets the shell used to run the task's command.
+    fn shell(&self) -> &str {
+        self.config
+            .task
+            .shell
+            .as_deref()
+            .unwrap_or(DEFAULT_TASK_SHELL)
+    }
+
+    /// Writes the job script for the request.
+    ///
+    /// The job script runs the task's command with the configured shell in the
+    /// working directory and writes the command's exit code to the attempt
+    /// directory.
+    fn write_job_script(&self) -> Result<()> {
+        let quote = |p: PathBuf| shell_quote(&p.display().to_string());
+
+        let mut script = String::from("#!/bin/sh\n");
+        for (k, v) in self.inner.env() {
+            writeln!(&mut script, "export {k}={v}", v = shell_quote(v)).unwrap();
+        }
+
+        writeln!(
+            &mut script,
+            "cd {dir} || exit 1",
+            dir = quote(self.work_dir())
+        )
+        .unwrap();
+        write!(
+            &mut script,
+            "{shell} {command}",
+            shell = self.shell(),
+            command = quote(self.inner.attempt_dir().join(COMMAND_FILE_NAME)),
+        )
+        .unwrap();
+
+        if self.scheduler.redirects_output() {
+            write!(
+                &mut script,
+                " > {stdout} 2> {stderr}",
+                stdout = quote(self.stdout_path()),
+                stderr = quote(self.stderr_path()),
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            &mut script,
+            "\necho $? > {rc}",
+            rc = quote(self.exit_code_path())
+        )
+        .unwrap();
+
+        let path = self.job_script_path();
+        fs::write(&path, script).with_context(|| {
+            format!(
+                "failed to write job script to `{path}`",
+                path = path.display()
+            )
+        })?;
+
+        // Some schedulers execute the script directly rather than with a shell
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).with_context(|| {
+                format!(
+                    "failed to set permissions for job script `{path}`",
+                    path = path.display()
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits for the given job to finish.
+    ///
+    /// Returns `Ok(None)` if the request was cancelled or the exit code of the
+    /// task's command once the job has finished.
+    async fn wait(&self, id: u64, job: &str) -> Result<Option<i32>> {
+        let scheduler = self.scheduler.name();
+        let exit_code_path = self.exit_code_path();
+        let mut started = false;
+        let mut failures = 0;
+        loop {
+            select! {
+                // Poll the cancellation token before sleeping
+                biased;
+
+                _ = self.token.cancelled() => return Ok(None),
+                _ = sleep(self.scheduler.interval()) => {}
+            }
+
+            let state = match self.scheduler.state(job, &self.name).await {
+                Ok(state) => {
+                    failures = 0;
+                    state
+                }
+                // The job script writes the exit code file last, so the job has finished if
+                // it exists
+                Err(_) if exit_code_path.exists() => JobState::Finished,
+                Err(e) => {
+                    failures += 1;
+                    if failures >= MAX_STATE_QUERY_FAILURES {
+                        return Err(e.context(format!(
+                            "failed to query the state of {scheduler} job {job} \
+                             {MAX_STATE_QUERY_FAILURES} consecutive times"
+                        )));
+                    }
+
+                    warn!("{e:#}");
+                    continue;
+                }
+            };
+
+            if !started && !matches!(state, JobState::Pending(_)) {
+                // Notify that the job has started
+                send_event!(self.events, Event::TaskStarted { id });
+                started = true;
+            }
+
+            match state {
+                JobState::Pending(state) | JobState::Running(state) => {
+                    debug!(
+                        "{scheduler} job {job} for task `{name}` is {state}",
+                        name = self.name
+                    )
+                }
+                JobState::Finished => break,
+            }
+        }
+
+        // The job has finished, so read the exit code written by the job script
+        match fs::read_to_string(&exit_code_path) {
+            Ok(contents) => contents.trim().parse::<i32>().map(Some).with_context(|| {
+                format!(
+                    "invalid exit code in file `{path}`",
+                    path = exit_code_path.display()
+                )
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let state = match self.scheduler.final_state(job).await {
+                    Some(state) => format!(" (job state `{state}`)"),
+                    None => String::new(),
+                };
+
+                if self.scheduler.redirects_output() {
+                    bail!(
+                        "{scheduler} job {job} terminated without running the task's \
+                         command{state}; see the scheduler's logs for the job for more details"
+                    );
+                }
+
+                bail!(
+                    "{scheduler} job {job} terminated without running the task's command{state}; \
+                     see stderr file `{path}` for more details",
+                    path = self.stderr_path().display()
+                );
+            }
+            Err(e) => Err(e).with_context(|| {
+                format!(
+                    "failed to read file `{path}`",
+                    path = exit_code_path.display()
+                )
+            }),
+        }
+    }
+}
+
+impl<S: Scheduler> TaskManagerRequest for JobRequest<S> {
+    fn cpu(&self) -> f64 {
+        self.cpu
+    }
+
+    fn memory(&self) -> u64 {
+        self.memory
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    async fn run(self) -> Result<TaskExecutionResult> {
+        let id = next_task_id();
+        let scheduler = self.scheduler.name();
+        let work_dir = self.work_dir();
+        let exit_code_path = self.exit_code_path();
+
+        // Create the working directory
+        fs::create_dir_all(&work_dir).with_context(|| {
+            format!(
+                "failed to create directory `{path}`",
+                path = work_dir.display()
+            )
+        })?;
+
+        // Write the evaluated command to disk
+        let command_path = self.inner.attempt_dir().join(COMMAND_FILE_NAME);
+        fs::write(&command_path, self.inner.command()).with_context(|| {
+            format!(
+                "failed to write command contents to `{path}`",
+                path = command_path.display()
+            )
+        })?;
+
+        // Remove any exit code file from a previous submission
+        if exit_code_path.exists() {
+            fs::remove_file(&exit_code_path).with_context(|| {
+                format!(
+                    "failed to remove file `{path}`",
+                    path = exit_code_path.display()
+                )
+            })?;
+        }
+
+        self.write_job_script()?;
+        write_local_rerun_script(&self.inner, self.shell(), &work_dir, &command_path)?;
+
+        // Send the created event
+        send_event!(
+            self.events,
+            Event::TaskCreated {
+                id,
+                name: self.name.clone(),
+                tes_id: None,
+                token: self.token.clone(),
+            }
+        );
+
+        let start = Instant::now();
+        let job = match self.scheduler.submit(&self).await {
+            Ok(job) => job,
+            Err(e) => {
+                send_event!(
+                    self.events,
+                    Event::TaskFailed {
+                        id,
+                        message: format!("{e:#}")
+                    }
+                );
+                return Err(e);
+            }
+        };
+
+        info!(
+            "submitted {scheduler} job {job} for execution of task `{name}`",
+            name = self.name
+        );
+
+        match self.wait(id, &job).await {
+            Ok(Some(exit_code)) => {
+                send_event!(
+                    self.events,
+                    Event::TaskCompleted {
+                        id,
+                        exit_statuses: NonEmpty::new(exit_status(exit_code))
+                    }
+                );
+
+                info!(
+                    "{scheduler} job {job} for task `{name}` has terminated with status code \
+                     {exit_code}",
+                    name = self.name
+                );
+
+                // Fall back to the time since submission if the scheduler does not report
+                // the job's usage
+                let usage = self
+                    .scheduler
+                    .usage(&job)
+                    .await
+                    .unwrap_or_else(|| ResourceUsage::new(start.elapsed()));
+
+                Ok(TaskExecutionResult {
+                    exit_code,
+                    work_dir: EvaluationPath::Local(work_dir),
+                    stdout: PrimitiveValue::new_file(
+                        self.stdout_path()
+                            .into_os_string()
+                            .into_string()
+                            .expect("path should be UTF-8"),
+                    )
+                    .into(),
+                    stderr: PrimitiveValue::new_file(
+                        self.stderr_path()
+                            .into_os_string()
+                            .into_string()
+                            .expect("path should be UTF-8"),
+                    )
+                    .into(),
+                    usage: Some(usage),
+                    gpus: Vec::new(),
+                })
+            }
+            Ok(None) => {
+                if let Err(e) = self.scheduler.cancel(&job, &self.name).await {
+                    warn!("{e:#}");
+                }
+
+                send_event!(self.events, Event::TaskCanceled { id });
+                bail!("task was cancelled");
+            }
+            Err(e) => {
+                send_event!(
+                    self.events,
+                    Event::TaskFailed {
+                        id,
+                        message: format!("{e:#}")
+                    }
+                );
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Converts an exit code into an exit status.
+pub(crate) fn exit_status(code: i32) -> ExitStatus {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        ExitStatus::from_raw(code << 8)
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::ExitStatusExt;
+        ExitStatus::from_raw(code as u32)
+    }
+}
+
+/// Runs a scheduler command and returns its output, regardless of its exit
+/// status.
+///
+/// The first element of the command is the program to run.
+pub(crate) async fn command_output(command: &[String]) -> Result<Output> {
+    let (program, args) = command.split_first().context("command cannot be empty")?;
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .with_context(|| format!("failed to run `{program}`"))
+}
+
+/// Runs a scheduler command and returns its stdout.
+///
+/// The first element of the command is the program to run.
+pub(crate) async fn run_command(command: &[String]) -> Result<String> {
+    let output = command_output(command).await?;
+    let program = &command[0];
+    if !output.status.success() {
+        bail!(
+            "`{program}` failed with {status}: {stderr}",
+            status = output.status,
+            stderr = String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    String::from_utf8(output.stdout).with_context(|| format!("`{program}` output is not UTF-8"))
+}
+
+/// Represents a task execution backend that submits tasks as jobs to an HPC
+/// scheduler.
+pub(crate) struct JobBackend<S> {
+    /// The engine configuration.
+    config: Arc<Config>,
+    /// The scheduler to submit jobs to.
+    scheduler: Arc<S>,
+    /// The maximum amount of concurrency supported.
+    max_concurrency: u64,
+    /// The maximum CPUs for any of one node.
+    max_cpu: u64,
+    /// The maximum memory for any of one node.
+    max_memory: u64,
+    /// The underlying task manager.
+    manager: TaskManager<JobRequest<S>>,
+    /// The name generator for tasks.
+    names: Arc<Mutex<GeneratorIterator<UniqueAlphanumeric>>>,
+    /// The sender for events.
+    events: Option<broadcast::Sender<Event>>,
+}
+
+impl<S: Scheduler> JobBackend<S> {
+    /// Constructs a new job backend for the given scheduler.
+    ///
+    /// The maximum memory is expected to be a valid unit string.
+    pub fn new(
+        config: Arc<Config>,
+        scheduler: S,
+        max_concurrency: Option<u64>,
+        max_cpu: Option<u64>,
+        max_memory: Option<&str>,
+        events: Option<broadcast::Sender<Event>>,
+    ) -> Self {
+        let names = Arc::new(Mutex::new(GeneratorIterator::new(
+            UniqueAlphanumeric::default_with_expected_generations(INITIAL_EXPECTED_NAMES),
+            INITIAL_EXPECTED_NAMES,
+        )));
+
+        let max_cpu = max_cpu.unwrap_or(u64::MAX);
+        let max_memory = max_memory
+            .map(|s| convert_unit_string(s).expect("value should be valid"))
+            .unwrap_or(u64::MAX);
+        let manager = TaskManager::new_unlimited(max_cpu, max_memory);
+
+        Self {
+            config,
+            scheduler: Arc::new(scheduler),
+            max_concurrency: max_concurrency.unwrap_or(u64::MAX),
+            max_cpu,
+            max_memory,
+            manager,
+            names,
+            events,
+        }
+    }
+}
+
+impl<S: Scheduler> TaskExecutionBackend for JobBackend<S> {
+    fn max_concurrency(&self) -> u64 {
+        self.max_concurrency
+    }
+
+    fn constraints(
+        &self,
+        requirements: &HashMap<String, Value>,
+        hints: &HashMap<String, Value>,
+    ) -> Result<TaskExecutionConstraints> {
+        let mut cpu = cpu(requirements);
+        if (self.max_cpu as f64) < cpu {
+            let message = format!(
+                "task requires at least {cpu} CPU{s}, but the execution backend has a maximum of \
+                 {max_cpu}",
+                s = if cpu == 1.0 { "" } else { "s" },
+                max_cpu = self.max_cpu,
+            );
+            match self.config.task.cpu_limit_behavior {
+                TaskResourceLimitBehavior::TryWithMax => {
+                    warn!("{message}");
+                    // clamp the reported constraint to what's available
+                    cpu = self.max_cpu as f64;
+                }
+                TaskResourceLimitBehavior::Deny => bail!("{message}"),
+            }
+        }
+
+        let mut memory = memory(requirements)?;
+        if self.max_memory < memory as u64 {
+            // Display the error in GiB, as it is the most common unit for memory
+            let message = format!(
+                "task requires at least {memory} GiB of memory, but the execution backend has a \
+                 maximum of {max_memory} GiB",
+                memory = memory as f64 / ONE_GIBIBYTE,
+                max_memory = self.max_memory as f64 / ONE_GIBIBYTE,
+            );
+            match self.config.task.memory_limit_behavior {
+                TaskResourceLimitBehavior::TryWithMax => {
+                    warn!("{message}");
+                    // clamp the reported constraint to what's available
+                    memory = self.max_memory.try_into().unwrap_or(i64::MAX);
+                }
+                TaskResourceLimitBehavior::Deny => bail!("{message}"),
+            }
+        }
+
+        let disks = disks(requirements, hints)?
+            .into_iter()
+            .map(|(mp, disk)| (mp.to_string(), disk.size))
+            .collect();
+
+        Ok(TaskExecutionConstraints {
+            container: None,
+            cpu,
+            memory,
+            gpu: Default::default(),
+            fpga: Default::default(),
+            disks,
+        })
+    }
+
+    fn guest_inputs_dir(&self) -> Option<&'static str> {
+        // Scheduler jobs do not use a container
+        None
+    }
+
+    fn needs_local_inputs(&self) -> bool {
+        true
+    }
+
+    fn spawn(
+        &self,
+        request: TaskSpawnRequest,
+        token: CancellationToken,
+    ) -> Result<Receiver<Result<TaskExecutionResult>>> {
+        let (completed_tx, completed_rx) = oneshot::channel();
+
+        let requirements = request.requirements();
+        let mut cpu = cpu(requirements);
+        if let TaskResourceLimitBehavior::TryWithMax = self.config.task.cpu_limit_behavior {
+            cpu = cpu.min(self.max_cpu as f64);
+        }
+        let mut memory = memory(requirements)? as u64;
+        if let TaskResourceLimitBehavior::TryWithMax = self.config.task.memory_limit_behavior {
+            memory = std::cmp::min(memory, self.max_memory);
+        }
+
+        let timeout = timeout(request.hints(), &self.config)?;
+
+        let name = format!(
+            "{id}-{generated}",
+            id = request.id(),
+            generated = self
+                .names
+                .lock()
+                .expect("generator should always acquire")
+                .next()
+                .expect("generator should never be exhausted")
+        );
+
+        self.manager.send(
+            JobRequest {
+                config: self.config.clone(),
+                scheduler: self.scheduler.clone(),
+                inner: request,
+                name,
+                cpu,
+                memory,
+                token,
+                timeout,
+                events: self.events.clone(),
+            },
+            completed_tx,
+        );
+
+        Ok(completed_rx)
+    }
+}
@@ -7,6 +7,7 @@ use std::fs::File;
 use std::process::Stdio;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
 use anyhow::Context;
 use anyhow::Result;
@@ -31,6 +32,8 @@ use super::TaskExecutionConstraints;
 use super::TaskManager;
 use super::TaskManagerRequest;
 use super::TaskSpawnRequest;
+use super::rerun::write_local_rerun_script;
+use super::usage::monitor_process;
 use crate::COMMAND_FILE_NAME;
 use crate::ONE_GIBIBYTE;
 use crate::PrimitiveValue;
@@ -49,6 +52,7 @@ use crate::convert_unit_string;
 use crate::path::EvaluationPath;
 use crate::v1::cpu;
 use crate::v1::memory;
+use crate::v1::timeout;
 
 /// Represents a local task request.
 ///
@@ -72,6 +76,8 @@ struct LocalTaskRequest {
     memory: u64,
     /// The cancellation token for the request.
     token: CancellationToken,
+    /// The maximum amount of time the task may run for.
+    timeout: Option<Duration>,
     /// The sender for events.
     events: Option<broadcast::Sender<Event>>,
 }
@@ -85,6 +91,14 @@ impl TaskManagerRequest for LocalTaskRequest {
         self.memory
     }
 
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
     async fn run(self) -> Result<TaskExecutionResult> {
         let id = next_task_id();
         let work_dir = self.inner.attempt_dir().join(WORK_DIR_NAME);
@@ -109,6 +123,14 @@ impl TaskManagerRequest for LocalTaskRequest {
                 )
             })?;
 
+            let shell = self
+                .config
+                .task
+                .shell
+                .as_deref()
+                .unwrap_or(DEFAULT_TASK_SHELL);
+            write_local_rerun_script(&self.inner, shell, &work_dir, &command_path)?;
+
             // Create a file for the stdout
             let stdout = File::create(&stdout_path).with_context(|| {
                 format!(
@@ -125,13 +147,7 @@ impl TaskManagerRequest for LocalTaskRequest {
                 )
             })?;
 
-            let mut command = Command::new(
-                self.config
-                    .task
-                    .shell
-                    .as_deref()
-                    .unwrap_or(DEFAULT_TASK_SHELL),
-            );
+            let mut command = Command::new(shell);
             command
                 .current_dir(&work_dir)
                 .arg(command_path)
@@ -164,7 +180,8 @@ impl TaskManagerRequest for LocalTaskRequest {
                 name = self.name
             );
 
-            let status = child.wait().await.with_context(|| {
+            let (status, usage) = monitor_process(id, child.wait()).await;
+            let status = status.with_context(|| {
                 format!("failed to wait for termination of task child process {id}")
             })?;
 
@@ -182,7 +199,7 @@ impl TaskManagerRequest for LocalTaskRequest {
                 }
             }
 
-            Ok(status)
+            Ok((status, usage))
         };
 
         // Send the created event
@@ -206,7 +223,7 @@ impl TaskManagerRequest for LocalTaskRequest {
             }
             result = run => {
                 match result {
-                    Ok(status) => {
+                    Ok((status, usage)) => {
                         send_event!(self.events, Event::TaskCompleted { id, exit_statuses: NonEmpty::new(status) });
 
                         let exit_code = status.code().expect("process should have exited");
@@ -216,6 +233,8 @@ impl TaskManagerRequest for LocalTaskRequest {
                             work_dir: EvaluationPath::Local(work_dir),
                             stdout: PrimitiveValue::new_file(stdout_path.into_os_string().into_string().expect("path should be UTF-8")).into(),
                             stderr: PrimitiveValue::new_file(stderr_path.into_os_string().into_string().expect("path should be UTF-8")).into(),
+                            usage: Some(usage),
+                            gpus: Vec::new(),
                         })
                     }
                     Err(e) => {
@@ -391,6 +410,8 @@ impl TaskExecutionBackend for LocalBackend {
             memory = std::cmp::min(memory, self.memory);
         }
 
+        let timeout = timeout(request.hints(), &self.config)?;
+
         let name = format!(
             "{id}-{generated}",
             id = request.id(),
@@ -410,6 +431,7 @@ impl TaskExecutionBackend for LocalBackend {
                 cpu,
                 memory,
                 token,
+                timeout,
                 events: self.events.clone(),
             },
             completed_tx,
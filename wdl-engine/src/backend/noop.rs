@@ -0,0 +1,81 @@
+//! Implementation of the no-op backend.
+//!
+//! The no-op backend never executes tasks; it is used to plan the execution of
+//! a workflow without submitting anything to an execution backend.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use anyhow::bail;
+use tokio::sync::oneshot::Receiver;
+use tokio_util::sync::CancellationToken;
+
+use super::TaskExecutionBackend;
+use super::TaskExecutionConstraints;
+use super::TaskSpawnRequest;
+use crate::TaskExecutionResult;
+use crate::Value;
+use crate::config::Config;
+use crate::v1::container;
+use crate::v1::cpu;
+use crate::v1::memory;
+
+/// Represents a task execution backend that does not execute tasks.
+///
+/// The backend reports the constraints requested by a task as-is; attempting
+/// to spawn a task with the backend is an error.
+pub struct NoopBackend {
+    /// The engine configuration.
+    config: Arc<Config>,
+}
+
+impl NoopBackend {
+    /// Constructs a new no-op task execution backend with the given
+    /// configuration.
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config }
+    }
+}
+
+impl TaskExecutionBackend for NoopBackend {
+    fn max_concurrency(&self) -> u64 {
+        u64::MAX
+    }
+
+    fn constraints(
+        &self,
+        requirements: &HashMap<String, Value>,
+        _: &HashMap<String, Value>,
+    ) -> Result<TaskExecutionConstraints> {
+        Ok(TaskExecutionConstraints {
+            container: Some(
+                container(requirements, self.config.task.container.as_deref()).into_owned(),
+            ),
+            cpu: cpu(requirements),
+            memory: memory(requirements)?,
+            gpu: Default::default(),
+            fpga: Default::default(),
+            disks: Default::default(),
+        })
+    }
+
+    fn guest_inputs_dir(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn needs_local_inputs(&self) -> bool {
+        false
+    }
+
+    fn spawn(
+        &self,
+        request: TaskSpawnRequest,
+        _: CancellationToken,
+    ) -> Result<Receiver<Result<TaskExecutionResult>>> {
+        bail!(
+            "task `{id}` cannot be executed by the no-op backend",
+            id = request.id()
+        )
+    }
+}
@@ -0,0 +1,462 @@
+//! Implementation of re-run scripts for task attempts.
+//!
+//! A re-run script is written to each attempt directory so that a task's
+//! execution can be reproduced outside of the engine.
+//!
+//! Running the script executes the task's command again with the same inputs,
+//! environment variables, and (for container backends) mounts; passing the
+//! `--shell` option starts an interactive shell in the task's environment
+//! instead.
+//!
+//! Container re-run scripts download remote file inputs that were not
+//! localized by the backend (e.g. for TES) with `curl` before running the
+//! container.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use anyhow::Result;
+
+use super::TaskSpawnRequest;
+use crate::InputKind;
+
+/// The name of the re-run script written to each attempt directory.
+pub(crate) const RERUN_FILE_NAME: &str = "rerun.sh";
+
+/// The name of the directory in the attempt directory that a re-run script
+/// downloads remote inputs to.
+const RERUN_INPUTS_DIR_NAME: &str = "rerun-inputs";
+
+/// Quotes a string for use in a POSIX shell script.
+pub(crate) fn shell_quote(s: &str) -> String {
+    format!("'{s}'", s = s.replace('\'', r"'\''"))
+}
+
+/// Represents the container runtime used by a re-run script.
+#[derive(Clone, Copy)]
+pub(crate) enum RerunContainer<'a> {
+    /// The command is executed in a Docker container.
+    Docker {
+        /// The container image to use.
+        image: &'a str,
+        /// The socket of the Docker daemon to use.
+        ///
+        /// This is `None` for the default Docker daemon.
+        host: Option<&'a str>,
+    },
+    /// The command is executed in an Apptainer container.
+    Apptainer {
+        /// The `apptainer` executable to use.
+        executable: &'a str,
+        /// The path to the image to use.
+        image: &'a Path,
+        /// The additional arguments to pass to `apptainer exec`.
+        ///
+        /// These include the task's environment variables, as Apptainer
+        /// requires them to be escaped.
+        args: &'a [String],
+    },
+}
+
+/// Writes the header of a re-run script.
+fn write_header(script: &mut String, request: &TaskSpawnRequest) {
+    writeln!(
+        script,
+        "#!/bin/sh\n# Re-runs attempt {attempt} of task `{id}`.\n#\n# Usage: {RERUN_FILE_NAME} \
+         [--shell]\n#\n# Pass `--shell` to start an interactive shell in the task's environment \
+         instead of\n# executing the task's command.\nset -e\n",
+        attempt = request.attempt(),
+        id = request.id(),
+    )
+    .unwrap();
+}
+
+/// Writes a re-run script to the request's attempt directory.
+fn write_script(request: &TaskSpawnRequest, script: String) -> Result<()> {
+    let path = request.attempt_dir().join(RERUN_FILE_NAME);
+    fs::write(&path, script).with_context(|| {
+        format!(
+            "failed to write re-run script to `{path}`",
+            path = path.display()
+        )
+    })?;
+
+    #[cfg(unix)]
+    {
+        use std::fs::Permissions;
+        use std::fs::set_permissions;
+        use std::os::unix::fs::PermissionsExt;
+        set_permissions(&path, Permissions::from_mode(0o755)).with_context(|| {
+            format!(
+                "failed to set permissions for re-run script `{path}`",
+                path = path.display()
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Writes a re-run script for a task executed on the host.
+pub(crate) fn write_local_rerun_script(
+    request: &TaskSpawnRequest,
+    shell: &str,
+    work_dir: &Path,
+    command_path: &Path,
+) -> Result<()> {
+    let mut script = String::new();
+    write_header(&mut script, request);
+
+    writeln!(
+        &mut script,
+        "cd {dir}",
+        dir = shell_quote(&work_dir.display().to_string())
+    )
+    .unwrap();
+    for (k, v) in request.env() {
+        writeln!(&mut script, "export {k}={v}", v = shell_quote(v)).unwrap();
+    }
+
+    writeln!(
+        &mut script,
+        "\nif [ \"$1\" = \"--shell\" ]; then\n  exec {shell}\nfi\n\nexec {shell} {command}",
+        shell = shell_quote(shell),
+        command = shell_quote(&command_path.display().to_string()),
+    )
+    .unwrap();
+
+    write_script(request, script)
+}
+
+/// Writes a re-run script for a task executed in a container.
+///
+/// The script mounts the work directory, the command file, and each input that
+/// has a local path at the same guest paths used by the backend.
+///
+/// Remote file inputs with an HTTP(S) URL are downloaded to the attempt
+/// directory the first time the script runs and mounted in the same way;
+/// authentication is not applied to the URLs.
+pub(crate) fn write_container_rerun_script(
+    request: &TaskSpawnRequest,
+    container: RerunContainer<'_>,
+    shell: &str,
+    work_dir: (&Path, &str),
+    command_path: (&Path, &str),
+) -> Result<()> {
+    let mut script = String::new();
+    write_header(&mut script, request);
+
+    let mut args = Vec::new();
+    let mut mount = |host: &Path, guest: &str, read_only: bool| {
+        let host = host.display();
+        let ro = if read_only { ":ro" } else { "" };
+        match container {
+            RerunContainer::Docker { .. } => {
+                args.push(shell_quote(&format!("--volume={host}:{guest}{ro}")))
+            }
+            RerunContainer::Apptainer { .. } => {
+                args.push(shell_quote(&format!("--bind={host}:{guest}{ro}")))
+            }
+        }
+    };
+
+    mount(work_dir.0, work_dir.1, false);
+    mount(command_path.0, command_path.1, true);
+    let downloads_dir = request.attempt_dir().join(RERUN_INPUTS_DIR_NAME);
+    let mut downloads = 0;
+    for input in request.inputs() {
+        let Some(guest_path) = input.guest_path() else {
+            continue;
+        };
+
+        if let Some(path) = input.local_path() {
+            mount(path, guest_path.as_str(), true);
+            continue;
+        }
+
+        // Remote inputs are not localized by the backend, so download remote files
+        let url = input
+            .path()
+            .as_remote()
+            .filter(|u| input.kind() == InputKind::File && matches!(u.scheme(), "http" | "https"));
+        match url {
+            Some(url) => {
+                let path = downloads_dir.join(downloads.to_string());
+                downloads += 1;
+
+                if downloads == 1 {
+                    writeln!(
+                        &mut script,
+                        "mkdir -p {dir}",
+                        dir = shell_quote(&downloads_dir.display().to_string())
+                    )
+                    .unwrap();
+                }
+
+                // Download to a temporary file so that a failed download is retried
+                let path_str = path.display().to_string();
+                writeln!(
+                    &mut script,
+                    "if [ ! -f {path} ]; then\n  curl -fsSL -o {part} {url}\n  mv {part} \
+                     {path}\nfi",
+                    path = shell_quote(&path_str),
+                    part = shell_quote(&format!("{path_str}.part")),
+                    url = shell_quote(url.as_str())
+                )
+                .unwrap();
+                mount(&path, guest_path.as_str(), true);
+            }
+            None => {
+                writeln!(
+                    &mut script,
+                    "# Input `{path}` is remote and is not mounted at `{guest_path}`",
+                    path = input.path().display(),
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    if let RerunContainer::Docker { .. } = container {
+        for (k, v) in request.env() {
+            args.push(shell_quote(&format!("--env={k}={v}")));
+        }
+    }
+
+    let (program, image, tty) = match container {
+        RerunContainer::Docker { image, host } => {
+            let mut prefix = Vec::new();
+            if let Some(host) = host {
+                let host = if host.contains("://") {
+                    host.to_string()
+                } else {
+                    format!("unix://{host}")
+                };
+
+                prefix.push(shell_quote(&format!("--host={host}")));
+            }
+
+            prefix.extend([
+                "run".to_string(),
+                "--rm".to_string(),
+                "--interactive".to_string(),
+                format!("--workdir={dir}", dir = shell_quote(work_dir.1)),
+            ]);
+            prefix.append(&mut args);
+            args = prefix;
+            ("docker".to_string(), image.to_string(), "--tty ")
+        }
+        RerunContainer::Apptainer {
+            executable,
+            image,
+            args: extra,
+        } => {
+            let mut prefix = vec![
+                "exec".to_string(),
+                "--containall".to_string(),
+                format!("--pwd={dir}", dir = shell_quote(work_dir.1)),
+            ];
+            prefix.append(&mut args);
+            prefix.extend(extra.iter().map(|a| shell_quote(a)));
+            args = prefix;
+            (executable.to_string(), image.display().to_string(), "")
+        }
+    };
+
+    writeln!(
+        &mut script,
+        "\nif [ \"$1\" = \"--shell\" ]; then\n  set -- {tty}{image} {shell}\nelse\n  set -- \
+         {image} {shell} {command}\nfi\n\nexec {program} \\\n  {args} \\\n  \"$@\"",
+        image = shell_quote(&image),
+        shell = shell_quote(shell),
+        command = shell_quote(command_path.1),
+        program = shell_quote(&program),
+        args = args.join(" \\\n  "),
+    )
+    .unwrap();
+
+    write_script(request, script)
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use futures::future::BoxFuture;
+    use indexmap::IndexMap;
+    use url::Url;
+
+    use super::*;
+    use crate::Input;
+    use crate::TaskSpawnInfo;
+    use crate::http::Location;
+    use crate::http::Transferer;
+    use crate::path::EvaluationPath;
+
+    /// A transferer that is not expected to be used.
+    struct UnusedTransferer;
+
+    impl Transferer for UnusedTransferer {
+        fn download<'a>(&'a self, _: &'a Url) -> BoxFuture<'a, Result<Location>> {
+            unimplemented!()
+        }
+
+        fn upload<'a>(&'a self, _: &'a Path, _: &'a Url) -> BoxFuture<'a, Result<()>> {
+            unimplemented!()
+        }
+
+        fn size<'a>(&'a self, _: &'a Url) -> BoxFuture<'a, Result<Option<u64>>> {
+            unimplemented!()
+        }
+
+        fn apply_auth<'a>(&self, url: &'a Url) -> Result<Cow<'a, Url>> {
+            Ok(Cow::Borrowed(url))
+        }
+    }
+
+    #[test]
+    fn quote() {
+        assert_eq!(shell_quote("foo"), "'foo'");
+        assert_eq!(shell_quote("foo bar"), "'foo bar'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn container_rerun_script() {
+        use std::io::Read;
+        use std::io::Write;
+        use std::net::TcpListener;
+        use std::os::unix::fs::PermissionsExt;
+        use std::process::Command;
+
+        let dir = tempfile::tempdir().unwrap();
+
+        // Serve the remote input once; a second run of the script should not download
+        // it again
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf).unwrap();
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\nremote",
+                )
+                .unwrap();
+        });
+
+        // Use a stub `docker` that records its arguments
+        let bin = dir.path().join("bin");
+        fs::create_dir(&bin).unwrap();
+        let docker = bin.join("docker");
+        fs::write(
+            &docker,
+            "#!/bin/sh\nprintf '%s\\n' \"$@\" > \"$(dirname \"$0\")/docker.args\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&docker, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let local = dir.path().join("local.txt");
+        fs::write(&local, "local").unwrap();
+        let inputs = vec![
+            Input::new(
+                InputKind::File,
+                EvaluationPath::Local(local.clone()),
+                Some(Arc::new("/mnt/task/inputs/0/local.txt".to_string()).into()),
+            ),
+            Input::new(
+                InputKind::File,
+                EvaluationPath::Remote(format!("http://{addr}/remote.txt").parse().unwrap()),
+                Some(Arc::new("/mnt/task/inputs/1/remote.txt".to_string()).into()),
+            ),
+        ];
+
+        let attempt_dir = dir.path().join("attempt");
+        fs::create_dir(&attempt_dir).unwrap();
+        let request = TaskSpawnRequest::new(
+            "t".to_string(),
+            TaskSpawnInfo::new(
+                "echo hello".to_string(),
+                inputs,
+                Arc::new(HashMap::new()),
+                Arc::new(HashMap::new()),
+                Arc::new(IndexMap::from([("FOO".to_string(), "bar baz".to_string())])),
+                Arc::new(UnusedTransferer),
+            ),
+            0,
+            attempt_dir.clone(),
+        );
+
+        let work_dir = attempt_dir.join("work");
+        let command = attempt_dir.join("command");
+        write_container_rerun_script(
+            &request,
+            RerunContainer::Docker {
+                image: "ubuntu:22.04",
+                host: None,
+            },
+            "bash",
+            (&work_dir, "/mnt/task/work"),
+            (&command, "/mnt/task/command"),
+        )
+        .unwrap();
+
+        let run = || {
+            let output = Command::new(attempt_dir.join(RERUN_FILE_NAME))
+                .env(
+                    "PATH",
+                    format!(
+                        "{bin}:{path}",
+                        bin = bin.display(),
+                        path = std::env::var("PATH").unwrap_or_default()
+                    ),
+                )
+                .output()
+                .unwrap();
+            assert!(
+                output.status.success(),
+                "re-run script failed: {stderr}",
+                stderr = String::from_utf8_lossy(&output.stderr)
+            );
+            fs::read_to_string(bin.join("docker.args")).unwrap()
+        };
+
+        let downloaded = attempt_dir.join(RERUN_INPUTS_DIR_NAME).join("0");
+        let expected = [
+            "run".to_string(),
+            "--rm".to_string(),
+            "--interactive".to_string(),
+            "--workdir=/mnt/task/work".to_string(),
+            format!("--volume={path}:/mnt/task/work", path = work_dir.display()),
+            format!(
+                "--volume={path}:/mnt/task/command:ro",
+                path = command.display()
+            ),
+            format!(
+                "--volume={path}:/mnt/task/inputs/0/local.txt:ro",
+                path = local.display()
+            ),
+            format!(
+                "--volume={path}:/mnt/task/inputs/1/remote.txt:ro",
+                path = downloaded.display()
+            ),
+            "--env=FOO=bar baz".to_string(),
+            "ubuntu:22.04".to_string(),
+            "bash".to_string(),
+            "/mnt/task/command".to_string(),
+        ];
+
+        assert_eq!(run().lines().collect::<Vec<_>>(), expected);
+        server.join().unwrap();
+        assert_eq!(fs::read_to_string(&downloaded).unwrap(), "remote");
+
+        // The remote input was already downloaded
+        assert_eq!(run().lines().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,404 @@
+//! Implementation of the generic HPC scheduler backend.
+//!
+//! The backend submits tasks to schedulers such as LSF, PBS/Torque, and SGE
+//! with the command templates of its configuration rather than implementing
+//! each scheduler's interface.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use crankshaft::events::Event;
+use regex::Regex;
+use tokio::sync::broadcast;
+use tokio::sync::oneshot::Receiver;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+use tracing::info;
+
+use super::TaskExecutionBackend;
+use super::TaskExecutionConstraints;
+use super::TaskSpawnRequest;
+use super::hpc::DEFAULT_INTERVAL;
+use super::hpc::JobBackend;
+use super::hpc::JobRequest;
+use super::hpc::JobState;
+use super::hpc::ONE_MEBIBYTE;
+use super::hpc::Scheduler;
+use super::hpc::command_output;
+use super::hpc::run_command;
+use crate::ONE_GIBIBYTE;
+use crate::TaskExecutionResult;
+use crate::Value;
+use crate::config::Config;
+use crate::config::SchedulerBackendConfig;
+use crate::v1::disks;
+
+/// The variables available to the templates used to submit a job.
+pub(crate) const SUBMIT_TEMPLATE_VARIABLES: &[&str] = &[
+    "name",
+    "attempt_dir",
+    "work_dir",
+    "cpu",
+    "memory",
+    "memory_mb",
+    "memory_gb",
+    "disk_gb",
+];
+
+/// The variables available to the templates used to query and cancel a job.
+pub(crate) const JOB_TEMPLATE_VARIABLES: &[&str] = &["name", "job_id"];
+
+/// Expands the variables in a command template argument.
+///
+/// Variables are in the form `{name}`; `{{` and `}}` expand to literal braces.
+///
+/// Returns an error if the template references a variable for which the
+/// lookup returns `None` or if the template has an unmatched brace.
+pub(crate) fn expand_template(
+    template: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<String> {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        expanded.push_str(&rest[..i]);
+        let brace = rest.as_bytes()[i];
+        rest = &rest[i + 1..];
+
+        // Check for an escaped brace
+        if rest.as_bytes().first() == Some(&brace) {
+            expanded.push(brace as char);
+            rest = &rest[1..];
+            continue;
+        }
+
+        if brace == b'}' {
+            bail!("unmatched `}}` in template");
+        }
+
+        let end = rest.find('}').context("unmatched `{` in template")?;
+        let name = &rest[..end];
+        expanded
+            .push_str(&lookup(name).ok_or_else(|| anyhow!("unknown template variable `{name}`"))?);
+        rest = &rest[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Expands the variables in each of the given command template arguments.
+fn expand_templates<'a>(
+    templates: impl IntoIterator<Item = &'a String>,
+    variables: &HashMap<&str, String>,
+) -> Result<Vec<String>> {
+    templates
+        .into_iter()
+        .map(|t| expand_template(t, |v| variables.get(v).cloned()))
+        .collect()
+}
+
+/// Represents a scheduler driven by the command templates of the generic HPC
+/// scheduler backend configuration.
+#[derive(Debug)]
+struct TemplateScheduler {
+    /// The backend configuration.
+    config: SchedulerBackendConfig,
+}
+
+impl TemplateScheduler {
+    /// Gets the command to submit the given request's job.
+    fn submit_command(&self, request: &JobRequest<Self>) -> Result<Vec<String>> {
+        let disks = disks(request.inner.requirements(), request.inner.hints())?;
+        let memory = request.memory as f64;
+        let variables = HashMap::from([
+            ("name", request.name.clone()),
+            (
+                "attempt_dir",
+                request.inner.attempt_dir().display().to_string(),
+            ),
+            ("work_dir", request.work_dir().display().to_string()),
+            ("cpu", (request.cpu.ceil() as u64).to_string()),
+            ("memory", request.memory.to_string()),
+            (
+                "memory_mb",
+                ((memory / ONE_MEBIBYTE).ceil() as u64).to_string(),
+            ),
+            (
+                "memory_gb",
+                ((memory / ONE_GIBIBYTE).ceil() as u64).to_string(),
+            ),
+            (
+                "disk_gb",
+                disks.values().map(|d| d.size).sum::<i64>().to_string(),
+            ),
+        ]);
+
+        let config = &self.config;
+        let mut command = expand_templates(&config.submit, &variables)?;
+        command.extend(expand_templates(&config.cpu_args, &variables)?);
+        command.extend(expand_templates(&config.memory_args, &variables)?);
+        if !disks.is_empty() {
+            command.extend(expand_templates(&config.disk_args, &variables)?);
+        }
+
+        command.extend(expand_templates(&config.args, &variables)?);
+        command.push(request.job_script_path().display().to_string());
+        Ok(command)
+    }
+
+    /// Gets a command to query or cancel a job from the given templates.
+    fn job_command(&self, templates: &[String], job: &str, name: &str) -> Result<Vec<String>> {
+        let variables = HashMap::from([("name", name.to_string()), ("job_id", job.to_string())]);
+        expand_templates(templates, &variables)
+    }
+}
+
+impl Scheduler for TemplateScheduler {
+    fn name(&self) -> &str {
+        self.config.scheduler_name()
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(self.config.interval.unwrap_or(DEFAULT_INTERVAL))
+    }
+
+    fn redirects_output(&self) -> bool {
+        // The submit command template may not redirect the job's output, so the job
+        // script does so
+        true
+    }
+
+    async fn submit(&self, request: &JobRequest<Self>) -> Result<String> {
+        let scheduler = self.name();
+        let output = run_command(&self.submit_command(request)?)
+            .await
+            .with_context(|| format!("failed to submit {scheduler} job"))?;
+
+        parse_job_id(self.config.job_id_pattern.as_deref(), &output).with_context(|| {
+            format!(
+                "failed to determine the job identifier from the output of the {scheduler} submit \
+                 command: {output}",
+                output = output.trim()
+            )
+        })
+    }
+
+    async fn state(&self, job: &str, name: &str) -> Result<JobState> {
+        let command = self.job_command(&self.config.status, job, name)?;
+        let output = command_output(&command).await?;
+        if !output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+
+            // Status commands commonly fail for jobs that are no longer known to the
+            // scheduler, but may also fail transiently
+            if let Some(pattern) = &self.config.unknown_job_pattern
+                && let Ok(regex) = Regex::new(pattern)
+                && (regex.is_match(&stdout) || regex.is_match(&stderr))
+            {
+                debug!(
+                    "{scheduler} job {job} for task `{name}` is no longer known to the scheduler",
+                    scheduler = self.name()
+                );
+                return Ok(JobState::Finished);
+            }
+
+            bail!(
+                "failed to query state of {scheduler} job {job}: `{program}` failed with \
+                 {status}: {stderr}",
+                scheduler = self.name(),
+                program = command[0],
+                status = output.status,
+                stderr = stderr.trim()
+            );
+        }
+
+        let output = String::from_utf8(output.stdout)
+            .with_context(|| format!("`{program}` output is not UTF-8", program = command[0]))?;
+
+        let state = output.trim();
+        if state.is_empty() || self.config.finished_states.iter().any(|s| s == state) {
+            return Ok(JobState::Finished);
+        }
+
+        // The job's state is not interpreted beyond whether it has finished
+        Ok(JobState::Running(state.to_string()))
+    }
+
+    async fn cancel(&self, job: &str, name: &str) -> Result<()> {
+        run_command(&self.job_command(&self.config.cancel, job, name)?)
+            .await
+            .with_context(|| {
+                format!(
+                    "failed to cancel {scheduler} job {job}",
+                    scheduler = self.name()
+                )
+            })?;
+        Ok(())
+    }
+}
+
+/// Parses the identifier of a submitted job from the output of the submit
+/// command.
+///
+/// If a pattern is not given, the identifier is the first line of the output.
+fn parse_job_id(pattern: Option<&str>, output: &str) -> Result<String> {
+    let job = match pattern {
+        Some(pattern) => {
+            let regex = Regex::new(pattern)?;
+            let captures = regex
+                .captures(output)
+                .context("the output does not match the job identifier pattern")?;
+            captures
+                .get(1)
+                .or_else(|| captures.get(0))
+                .expect("captures should have a match")
+                .as_str()
+                .trim()
+        }
+        None => output.lines().next().unwrap_or_default().trim(),
+    };
+
+    if job.is_empty() {
+        bail!("the job identifier is empty");
+    }
+
+    Ok(job.to_string())
+}
+
+/// Represents a task execution backend that submits tasks to an HPC scheduler
+/// with configured command templates.
+///
+/// Tasks are executed on the cluster's nodes without the use of a container;
+/// the output directory is expected to be on a file system shared with the
+/// nodes.
+pub struct SchedulerBackend {
+    /// The underlying job backend.
+    inner: JobBackend<TemplateScheduler>,
+}
+
+impl SchedulerBackend {
+    /// Constructs a new generic HPC scheduler task execution backend with the
+    /// given configuration.
+    ///
+    /// The provided configuration is expected to have already been validated.
+    pub fn new(
+        config: Arc<Config>,
+        backend_config: &SchedulerBackendConfig,
+        events: Option<broadcast::Sender<Event>>,
+    ) -> Result<Self> {
+        info!(
+            "initializing {scheduler} backend",
+            scheduler = backend_config.scheduler_name()
+        );
+
+        Ok(Self {
+            inner: JobBackend::new(
+                config,
+                TemplateScheduler {
+                    config: backend_config.clone(),
+                },
+                backend_config.max_concurrency,
+                backend_config.max_cpu,
+                backend_config.max_memory.as_deref(),
+                events,
+            ),
+        })
+    }
+}
+
+impl TaskExecutionBackend for SchedulerBackend {
+    fn max_concurrency(&self) -> u64 {
+        self.inner.max_concurrency()
+    }
+
+    fn constraints(
+        &self,
+        requirements: &HashMap<String, Value>,
+        hints: &HashMap<String, Value>,
+    ) -> Result<TaskExecutionConstraints> {
+        self.inner.constraints(requirements, hints)
+    }
+
+    fn guest_inputs_dir(&self) -> Option<&'static str> {
+        self.inner.guest_inputs_dir()
+    }
+
+    fn needs_local_inputs(&self) -> bool {
+        self.inner.needs_local_inputs()
+    }
+
+    fn spawn(
+        &self,
+        request: TaskSpawnRequest,
+        token: CancellationToken,
+    ) -> Result<Receiver<Result<TaskExecutionResult>>> {
+        self.inner.spawn(request, token)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn it_expands_templates() {
+        let lookup = |v: &str| (v == "cpu").then(|| "4".to_string());
+        assert_eq!(expand_template("-n", lookup).unwrap(), "-n");
+        assert_eq!(expand_template("{cpu}", lookup).unwrap(), "4");
+        assert_eq!(
+            expand_template("span[ptile={cpu}]", lookup).unwrap(),
+            "span[ptile=4]"
+        );
+        assert_eq!(expand_template("{{cpu}}={cpu}", lookup).unwrap(), "{cpu}=4");
+        assert_eq!(
+            expand_template("{memory}", lookup).unwrap_err().to_string(),
+            "unknown template variable `memory`"
+        );
+        assert_eq!(
+            expand_template("{cpu", lookup).unwrap_err().to_string(),
+            "unmatched `{` in template"
+        );
+        assert_eq!(
+            expand_template("cpu}", lookup).unwrap_err().to_string(),
+            "unmatched `}` in template"
+        );
+    }
+
+    #[test]
+    fn it_parses_job_ids() {
+        // LSF
+        assert_eq!(
+            parse_job_id(
+                Some(r"Job <(\d+)>"),
+                "Job <1234> is submitted to queue <normal>.\n"
+            )
+            .unwrap(),
+            "1234"
+        );
+
+        // PBS/Torque
+        assert_eq!(parse_job_id(None, "1234.server\n").unwrap(), "1234.server");
+
+        // SGE
+        assert_eq!(
+            parse_job_id(
+                Some(r"\d+"),
+                "Your job 1234 (\"name\") has been submitted\n"
+            )
+            .unwrap(),
+            "1234"
+        );
+
+        assert!(parse_job_id(Some(r"Job <(\d+)>"), "error\n").is_err());
+        assert!(parse_job_id(None, "").is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! Implementation of the Slurm backend.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use crankshaft::events::Event;
+use tokio::sync::broadcast;
+use tokio::sync::oneshot::Receiver;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+use tracing::info;
+
+use super::ResourceUsage;
+use super::TaskExecutionBackend;
+use super::TaskExecutionConstraints;
+use super::TaskSpawnRequest;
+use super::hpc::DEFAULT_INTERVAL;
+use super::hpc::JobBackend;
+use super::hpc::JobRequest;
+use super::hpc::JobState;
+use super::hpc::ONE_MEBIBYTE;
+use super::hpc::Scheduler;
+use super::hpc::run_command;
+use crate::TaskExecutionResult;
+use crate::Value;
+use crate::config::Config;
+use crate::config::SlurmBackendConfig;
+use crate::v1::disks;
+use crate::v1::max_retries;
+
+/// The Slurm job states that indicate a job is finished.
+const FINISHED_STATES: &[&str] = &[
+    "COMPLETED",
+    "FAILED",
+    "CANCELLED",
+    "TIMEOUT",
+    "OUT_OF_MEMORY",
+    "NODE_FAIL",
+    "BOOT_FAIL",
+    "DEADLINE",
+    "PREEMPTED",
+];
+
+/// Runs a Slurm command and returns its stdout.
+async fn run_slurm_command(program: &str, args: &[String]) -> Result<String> {
+    let mut command = Vec::with_capacity(args.len() + 1);
+    command.push(program.to_string());
+    command.extend(args.iter().cloned());
+    run_command(&command).await
+}
+
+/// Represents the Slurm scheduler.
+#[derive(Debug)]
+struct Slurm {
+    /// The backend configuration.
+    config: SlurmBackendConfig,
+}
+
+impl Slurm {
+    /// Gets the arguments to pass to `sbatch` for the given request.
+    fn sbatch_args(&self, request: &JobRequest<Self>) -> Result<Vec<String>> {
+        let requirements = request.inner.requirements();
+        let mut args = vec![
+            "--parsable".to_string(),
+            format!("--job-name={name}", name = request.name),
+            format!("--chdir={path}", path = request.work_dir().display()),
+            format!("--output={path}", path = request.stdout_path().display()),
+            format!("--error={path}", path = request.stderr_path().display()),
+            format!("--cpus-per-task={cpu}", cpu = request.cpu.ceil() as u64),
+            format!(
+                "--mem={memory}M",
+                memory = (request.memory as f64 / ONE_MEBIBYTE).ceil() as u64
+            ),
+        ];
+
+        // Request temporary disk space for the total of the requested disks
+        let disks = disks(requirements, request.inner.hints())?;
+        if !disks.is_empty() {
+            args.push(format!(
+                "--tmp={size}G",
+                size = disks.values().map(|d| d.size).sum::<i64>()
+            ));
+        }
+
+        // Allow Slurm to requeue the job (e.g. upon node failure) if the task may be
+        // retried
+        if max_retries(requirements, &request.config) > 0 {
+            args.push("--requeue".to_string());
+        } else {
+            args.push("--no-requeue".to_string());
+        }
+
+        if let Some(partition) = &self.config.partition {
+            args.push(format!("--partition={partition}"));
+        }
+
+        args.extend(self.config.args.iter().cloned());
+        args.push(request.job_script_path().display().to_string());
+        Ok(args)
+    }
+}
+
+impl Scheduler for Slurm {
+    fn name(&self) -> &str {
+        "Slurm"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(self.config.interval.unwrap_or(DEFAULT_INTERVAL))
+    }
+
+    fn redirects_output(&self) -> bool {
+        // The job's output is redirected with the `--output` and `--error` options
+        false
+    }
+
+    async fn submit(&self, request: &JobRequest<Self>) -> Result<String> {
+        let output = run_slurm_command("sbatch", &self.sbatch_args(request)?)
+            .await
+            .context("failed to submit Slurm job")?;
+
+        // The parsable output is in the form `<job id>[;<cluster name>]`
+        match output.trim().split(';').next() {
+            Some(job) if !job.is_empty() => Ok(job.to_string()),
+            _ => bail!("`sbatch` did not output a job identifier"),
+        }
+    }
+
+    /// Gets the state of a job with `squeue`.
+    ///
+    /// If `squeue` fails, such as for a job that has been purged from the queue
+    /// or when the controller is temporarily unavailable, the state is queried
+    /// from job accounting with `sacct` instead.
+    async fn state(&self, job: &str, _: &str) -> Result<JobState> {
+        let state = match run_slurm_command(
+            "squeue",
+            &[
+                "--noheader".to_string(),
+                "--format=%T".to_string(),
+                format!("--jobs={job}"),
+            ],
+        )
+        .await
+        {
+            Ok(output) => output.trim().to_string(),
+            Err(e) => {
+                debug!("failed to query state of Slurm job {job} from the queue: {e:#}");
+                accounted_job_state(job)
+                    .await
+                    .with_context(|| format!("failed to query state of Slurm job {job}"))?
+            }
+        };
+
+        if state.is_empty() || FINISHED_STATES.contains(&state.as_str()) {
+            return Ok(JobState::Finished);
+        }
+
+        if state == "RUNNING" {
+            return Ok(JobState::Running(state));
+        }
+
+        Ok(JobState::Pending(state))
+    }
+
+    async fn cancel(&self, job: &str, _: &str) -> Result<()> {
+        run_slurm_command("scancel", &[job.to_string()])
+            .await
+            .with_context(|| format!("failed to cancel Slurm job {job}"))?;
+        Ok(())
+    }
+
+    async fn final_state(&self, job: &str) -> Option<String> {
+        Some(
+            accounted_job_state(job)
+                .await
+                .unwrap_or_else(|_| "unknown".to_string()),
+        )
+    }
+
+    async fn usage(&self, job: &str) -> Option<ResourceUsage> {
+        accounted_job_usage(job).await
+    }
+}
+
+/// Gets the state of a job from job accounting with `sacct`.
+///
+/// Returns an error if job accounting is not available or the job has not
+/// been accounted.
+async fn accounted_job_state(job: &str) -> Result<String> {
+    let output = run_slurm_command(
+        "sacct",
+        &[
+            "--noheader".to_string(),
+            "--parsable2".to_string(),
+            "--allocations".to_string(),
+            "--format=State".to_string(),
+            format!("--jobs={job}"),
+        ],
+    )
+    .await?;
+
+    // Cancelled jobs have a state in the form `CANCELLED by <uid>`
+    match output
+        .lines()
+        .next()
+        .and_then(|l| l.split_whitespace().next())
+    {
+        Some(state) => Ok(state.to_string()),
+        None => bail!("Slurm job {job} has not been accounted"),
+    }
+}
+
+/// Gets the resource usage of a completed job with `sacct`.
+///
+/// Returns `None` if the job's usage could not be determined.
+async fn accounted_job_usage(job: &str) -> Option<ResourceUsage> {
+    let output = run_slurm_command(
+        "sacct",
+        &[
+            "--noheader".to_string(),
+            "--parsable2".to_string(),
+            "--format=MaxRSS,TotalCPU,ElapsedRaw".to_string(),
+            format!("--jobs={job}"),
+        ],
+    )
+    .await
+    .ok()?;
+
+    parse_accounted_usage(&output)
+}
+
+/// Parses the output of `sacct` for a job's resource usage.
+///
+/// The first line is expected to be for the job's allocation, which reports
+/// the total CPU time and elapsed time of the job; the peak memory is only
+/// reported for the job's steps.
+fn parse_accounted_usage(output: &str) -> Option<ResourceUsage> {
+    /// Parses a memory size in the form `<size>[KMGT]`, where the default unit
+    /// is kibibytes.
+    fn parse_memory(s: &str) -> Option<u64> {
+        let (size, multiplier) = match s.char_indices().last()? {
+            (i, 'K') => (&s[..i], 1u64 << 10),
+            (i, 'M') => (&s[..i], 1 << 20),
+            (i, 'G') => (&s[..i], 1 << 30),
+            (i, 'T') => (&s[..i], 1 << 40),
+            _ => (s, 1 << 10),
+        };
+
+        Some((size.parse::<f64>().ok()? * multiplier as f64) as u64)
+    }
+
+    /// Parses a CPU time in the form `[DD-[HH:]]MM:SS[.mmm]`.
+    fn parse_cpu_time(s: &str) -> Option<Duration> {
+        let (days, rest) = match s.split_once('-') {
+            Some((days, rest)) => (days.parse::<u64>().ok()?, rest),
+            None => (0, s),
+        };
+
+        let mut secs = 0.0;
+        for part in rest.split(':') {
+            secs = secs * 60.0 + part.parse::<f64>().ok()?;
+        }
+
+        Some(Duration::from_secs_f64(secs + (days * 24 * 60 * 60) as f64))
+    }
+
+    let mut lines = output.lines().filter(|l| !l.trim().is_empty());
+    let mut fields = lines.next()?.trim().split('|');
+    let mut peak_memory = fields.next().and_then(parse_memory);
+    let cpu_time = fields.next().and_then(parse_cpu_time);
+    let wall_time = Duration::from_secs(fields.next()?.parse().ok()?);
+
+    for line in lines {
+        if let Some(memory) = line.trim().split('|').next().and_then(parse_memory) {
+            peak_memory = Some(peak_memory.map_or(memory, |m| m.max(memory)));
+        }
+    }
+
+    Some(ResourceUsage {
+        peak_memory,
+        cpu_time,
+        wall_time,
+    })
+}
+
+/// Represents a task execution backend that submits tasks to a Slurm cluster.
+///
+/// Tasks are executed on the cluster's nodes without the use of a container;
+/// the output directory is expected to be on a file system shared with the
+/// nodes.
+pub struct SlurmBackend {
+    /// The underlying job backend.
+    inner: JobBackend<Slurm>,
+}
+
+impl SlurmBackend {
+    /// Constructs a new Slurm task execution backend with the given
+    /// configuration.
+    ///
+    /// The provided configuration is expected to have already been validated.
+    pub fn new(
+        config: Arc<Config>,
+        backend_config: &SlurmBackendConfig,
+        events: Option<broadcast::Sender<Event>>,
+    ) -> Result<Self> {
+        info!("initializing Slurm backend");
+
+        Ok(Self {
+            inner: JobBackend::new(
+                config,
+                Slurm {
+                    config: backend_config.clone(),
+                },
+                backend_config.max_concurrency,
+                backend_config.max_cpu,
+                backend_config.max_memory.as_deref(),
+                events,
+            ),
+        })
+    }
+}
+
+impl TaskExecutionBackend for SlurmBackend {
+    fn max_concurrency(&self) -> u64 {
+        self.inner.max_concurrency()
+    }
+
+    fn constraints(
+        &self,
+        requirements: &HashMap<String, Value>,
+        hints: &HashMap<String, Value>,
+    ) -> Result<TaskExecutionConstraints> {
+        self.inner.constraints(requirements, hints)
+    }
+
+    fn guest_inputs_dir(&self) -> Option<&'static str> {
+        self.inner.guest_inputs_dir()
+    }
+
+    fn needs_local_inputs(&self) -> bool {
+        self.inner.needs_local_inputs()
+    }
+
+    fn spawn(
+        &self,
+        request: TaskSpawnRequest,
+        token: CancellationToken,
+    ) -> Result<Receiver<Result<TaskExecutionResult>>> {
+        self.inner.spawn(request, token)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_parses_accounted_usage() {
+        let usage =
+            parse_accounted_usage("|1-01:02:03|90061\n1.50M|00:01.500|90061\n2048K|01:00|90000\n")
+                .expect("should parse usage");
+        assert_eq!(usage.peak_memory, Some(2 * 1024 * 1024));
+        assert_eq!(usage.cpu_time, Some(Duration::from_secs(90123)));
+        assert_eq!(usage.wall_time, Duration::from_secs(90061));
+
+        let usage = parse_accounted_usage("||5\n").expect("should parse usage");
+        assert_eq!(usage.peak_memory, None);
+        assert_eq!(usage.cpu_time, None);
+        assert_eq!(usage.wall_time, Duration::from_secs(5));
+
+        assert!(parse_accounted_usage("").is_none());
+        assert!(parse_accounted_usage("1K|00:01|bad\n").is_none());
+    }
+}
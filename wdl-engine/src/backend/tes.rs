@@ -5,6 +5,8 @@ use std::collections::HashMap;
 use std::fs;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
 
 use anyhow::Context;
 use anyhow::Result;
@@ -36,12 +38,15 @@ use tokio_util::sync::CancellationToken;
 use tracing::info;
 use wdl_ast::v1::TASK_REQUIREMENT_DISKS;
 
+use super::ResourceUsage;
 use super::TaskExecutionBackend;
 use super::TaskExecutionConstraints;
 use super::TaskExecutionResult;
 use super::TaskManager;
 use super::TaskManagerRequest;
 use super::TaskSpawnRequest;
+use super::rerun::RerunContainer;
+use super::rerun::write_container_rerun_script;
 use crate::COMMAND_FILE_NAME;
 use crate::ONE_GIBIBYTE;
 use crate::PrimitiveValue;
@@ -65,6 +70,7 @@ use crate::v1::max_cpu;
 use crate::v1::max_memory;
 use crate::v1::memory;
 use crate::v1::preemptible;
+use crate::v1::timeout;
 
 /// The root guest path for inputs.
 const GUEST_INPUTS_DIR: &str = "/mnt/task/inputs/";
@@ -118,6 +124,8 @@ struct TesTaskRequest {
     preemptible: i64,
     /// The cancellation token for the request.
     token: CancellationToken,
+    /// The maximum amount of time the task may run for.
+    timeout: Option<Duration>,
 }
 
 impl TesTaskRequest {
@@ -157,6 +165,14 @@ impl TaskManagerRequest for TesTaskRequest {
         self.memory
     }
 
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
     async fn run(self) -> Result<TaskExecutionResult> {
         // Create the attempt directory
         let attempt_dir = self.inner.attempt_dir();
@@ -177,6 +193,23 @@ impl TaskManagerRequest for TesTaskRequest {
             )
         })?;
 
+        // The re-run script executes the task locally with Docker; inputs that were
+        // local are mounted and remote file inputs are downloaded by the script
+        write_container_rerun_script(
+            &self.inner,
+            RerunContainer::Docker {
+                image: &self.container,
+                host: None,
+            },
+            self.config
+                .task
+                .shell
+                .as_deref()
+                .unwrap_or(DEFAULT_TASK_SHELL),
+            (&attempt_dir.join(WORK_DIR_NAME), GUEST_WORK_DIR),
+            (&command_path, GUEST_COMMAND_PATH),
+        )?;
+
         // SAFETY: currently `inputs` is required by configuration validation, so it
         // should always unwrap
         let inputs_url = Arc::new(
@@ -330,6 +363,9 @@ impl TaskManagerRequest for TesTaskRequest {
                 .build(),
         ];
 
+        // The TES backend does not report resource usage, so only the wall time
+        // (including any time spent queued or preempted) is recorded
+        let start = Instant::now();
         let mut preemptible = self.preemptible;
         loop {
             let task = Task::builder()
@@ -389,6 +425,8 @@ impl TaskManagerRequest for TesTaskRequest {
                 work_dir: EvaluationPath::Remote(work_dir_url),
                 stdout: PrimitiveValue::new_file(stdout_url).into(),
                 stderr: PrimitiveValue::new_file(stderr_url).into(),
+                usage: Some(ResourceUsage::new(start.elapsed())),
+                gpus: Vec::new(),
             });
         }
     }
@@ -553,6 +591,8 @@ impl TaskExecutionBackend for TesBackend {
         let max_memory = max_memory(hints)?.map(|i| i as u64);
         let preemptible = preemptible(hints);
 
+        let timeout = timeout(hints, &self.config)?;
+
         let name = format!(
             "{id}-{generated}",
             id = request.id(),
@@ -576,6 +616,7 @@ impl TaskExecutionBackend for TesBackend {
                 max_cpu,
                 max_memory,
                 token,
+                timeout,
                 preemptible,
             },
             completed_tx,
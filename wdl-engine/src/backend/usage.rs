@@ -0,0 +1,241 @@
+//! Implementation of task resource usage monitoring.
+//!
+//! The resources used by each attempt to execute a task are written to a
+//! `usage.json` file in the attempt directory; memory is reported in bytes and
+//! times are reported in seconds.
+
+use std::future::Future;
+use std::path::Path;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::Result;
+use serde::Serialize;
+use serde::Serializer;
+use tokio::select;
+
+use crate::v1::write_json_file;
+
+/// The name of the resource usage file written to each attempt directory.
+pub(crate) const USAGE_FILE_NAME: &str = "usage.json";
+
+/// The interval at which the resource usage of a process is sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Serializes a duration as seconds.
+fn serialize_secs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Serializes an optional duration as seconds.
+fn serialize_optional_secs<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serialize_secs(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Represents the resources used by an attempt to execute a task.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ResourceUsage {
+    /// The peak memory (resident set size) used by the task, in bytes.
+    ///
+    /// This is `None` if the backend could not measure the task's memory
+    /// usage.
+    pub peak_memory: Option<u64>,
+    /// The CPU time (user and system) used by the task.
+    ///
+    /// This is `None` if the backend could not measure the task's CPU usage.
+    #[serde(serialize_with = "serialize_optional_secs")]
+    pub cpu_time: Option<Duration>,
+    /// The wall time of the task's execution.
+    ///
+    /// For backends that queue tasks remotely, this may include the time the
+    /// task spent queued.
+    #[serde(serialize_with = "serialize_secs")]
+    pub wall_time: Duration,
+}
+
+impl ResourceUsage {
+    /// Constructs a new resource usage with only a wall time.
+    pub(crate) fn new(wall_time: Duration) -> Self {
+        Self {
+            peak_memory: None,
+            cpu_time: None,
+            wall_time,
+        }
+    }
+
+    /// Writes the resource usage to the given attempt directory.
+    pub(crate) fn write(&self, attempt_dir: &Path) -> Result<()> {
+        write_json_file(attempt_dir.join(USAGE_FILE_NAME), self)
+    }
+}
+
+/// Samples the resource usage of a process and its descendants.
+#[derive(Debug, Default)]
+struct ProcessSampler {
+    /// The peak memory of the process tree, in bytes.
+    peak_memory: Option<u64>,
+    /// The CPU time of the process tree.
+    cpu_time: Option<Duration>,
+}
+
+impl ProcessSampler {
+    /// Samples the resource usage of the given process and its descendants.
+    ///
+    /// The process tree is read from `/proc`; the CPU time of a process
+    /// includes the CPU time of its descendants that have already exited.
+    #[cfg(target_os = "linux")]
+    fn sample(&mut self, pid: u32) {
+        use std::fs;
+
+        /// Reads the CPU time, in clock ticks, and resident set size, in pages,
+        /// of a process.
+        fn read_stat(pid: u32) -> Option<(u64, u64)> {
+            let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+
+            // The command name may contain spaces, so parse the fields after it; the
+            // fields are numbered as in `proc(5)`, starting with the state as field 3
+            let fields: Vec<_> = stat
+                .get(stat.rfind(')')? + 1..)?
+                .split_whitespace()
+                .collect();
+            let field = |n: usize| fields.get(n - 3)?.parse::<u64>().ok();
+            Some((
+                field(14)? + field(15)? + field(16)? + field(17)?,
+                field(24)?,
+            ))
+        }
+
+        /// Reads the children of each thread of a process.
+        fn read_children(pid: u32) -> Vec<u32> {
+            let Ok(tasks) = fs::read_dir(format!("/proc/{pid}/task")) else {
+                return Vec::new();
+            };
+
+            tasks
+                .filter_map(|t| fs::read_to_string(t.ok()?.path().join("children")).ok())
+                .flat_map(|c| {
+                    c.split_whitespace()
+                        .filter_map(|p| p.parse().ok())
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        }
+
+        // SAFETY: `sysconf` is safe to call with valid names
+        let (ticks_per_sec, page_size) = unsafe {
+            (
+                libc::sysconf(libc::_SC_CLK_TCK),
+                libc::sysconf(libc::_SC_PAGESIZE),
+            )
+        };
+        if ticks_per_sec <= 0 || page_size <= 0 {
+            return;
+        }
+
+        let mut ticks = 0;
+        let mut pages = 0;
+        let mut found = false;
+        let mut pids = vec![pid];
+        while let Some(pid) = pids.pop() {
+            let Some((t, p)) = read_stat(pid) else {
+                continue;
+            };
+
+            ticks += t;
+            pages += p;
+            found = true;
+            pids.extend(read_children(pid));
+        }
+
+        if !found {
+            return;
+        }
+
+        let memory = pages * page_size as u64;
+        let cpu_time = Duration::from_secs_f64(ticks as f64 / ticks_per_sec as f64);
+        self.peak_memory = Some(self.peak_memory.map_or(memory, |m| m.max(memory)));
+        self.cpu_time = Some(self.cpu_time.map_or(cpu_time, |t| t.max(cpu_time)));
+    }
+
+    /// Samples the resource usage of the given process and its descendants.
+    ///
+    /// Sampling is only supported on Linux.
+    #[cfg(not(target_os = "linux"))]
+    fn sample(&mut self, _: u32) {}
+}
+
+/// Monitors the resource usage of a process and its descendants until the
+/// given future completes.
+///
+/// The usage is sampled periodically, so CPU time used by the process shortly
+/// before it exits may not be included.
+pub(crate) async fn monitor_process<F: Future>(pid: u32, future: F) -> (F::Output, ResourceUsage) {
+    let start = Instant::now();
+    let mut sampler = ProcessSampler::default();
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+    let mut future = std::pin::pin!(future);
+    let output = loop {
+        select! {
+            // Poll the future before sampling
+            biased;
+
+            output = &mut future => break output,
+            _ = interval.tick() => sampler.sample(pid),
+        }
+    };
+
+    (
+        output,
+        ResourceUsage {
+            peak_memory: sampler.peak_memory,
+            cpu_time: sampler.cpu_time,
+            wall_time: start.elapsed(),
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn it_samples_process_usage() {
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", "sleep 1.5"])
+            .spawn()
+            .expect("failed to spawn process");
+
+        let pid = child.id().expect("should have pid");
+        let (status, usage) = monitor_process(pid, child.wait()).await;
+        assert!(status.unwrap().success());
+        assert!(usage.peak_memory.unwrap() > 0);
+        assert!(usage.cpu_time.is_some());
+        assert!(usage.wall_time >= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn it_serializes_usage() {
+        let usage = ResourceUsage {
+            peak_memory: Some(1024),
+            cpu_time: Some(Duration::from_millis(1500)),
+            wall_time: Duration::from_secs(2),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&usage).unwrap(),
+            r#"{"peak_memory":1024,"cpu_time":1.5,"wall_time":2.0}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&ResourceUsage::new(Duration::from_secs(1))).unwrap(),
+            r#"{"peak_memory":null,"cpu_time":null,"wall_time":1.0}"#
+        );
+    }
+}
@@ -0,0 +1,889 @@
+//! Implementation of the call cache.
+//!
+//! The call cache maps a key calculated from a task's evaluated command,
+//! requirements, hints, container, environment variables, and input digests to
+//! the result of a previous successful execution of the task.
+//!
+//! This module also implements the completion records written to a task's
+//! root directory upon success; the records allow evaluation to be resumed
+//! against an existing output directory.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use blake3::Hash;
+use blake3::Hasher;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use serde::Serialize;
+use tempfile::NamedTempFile;
+use tracing::debug;
+use walkdir::WalkDir;
+use wdl_ast::v1::TASK_HINT_INPUTS;
+use wdl_ast::v1::TASK_HINT_OUTPUTS;
+
+use crate::Input;
+use crate::InputKind;
+use crate::PrimitiveValue;
+use crate::TaskExecutionResult;
+use crate::TaskInputs;
+use crate::Value;
+use crate::ValueSerializer;
+use crate::config::CallCacheConfig;
+use crate::config::CallCacheMode;
+use crate::config::LocalizationStrategy;
+use crate::eval::v1::COMPLETION_FILE;
+use crate::hash::Digest;
+use crate::hash::InputDigests;
+use crate::localization;
+use crate::path::EvaluationPath;
+
+/// The default call cache subdirectory that is appended to the system cache
+/// directory.
+const DEFAULT_CACHE_SUBDIR: &str = "wdl/calls";
+
+/// The version of the call cache key.
+///
+/// This must be incremented whenever the calculation of the key changes so that
+/// existing entries are not incorrectly reused.
+const KEY_VERSION: u32 = 2;
+
+/// Represents an entry in the call cache.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) struct CallCacheEntry {
+    /// The attempt directory of the cached execution.
+    pub attempt_dir: PathBuf,
+    /// The exit code of the cached execution.
+    pub exit_code: i32,
+    /// The working directory of the cached execution.
+    pub work_dir: String,
+    /// The path to the stdout of the cached execution.
+    pub stdout: String,
+    /// The path to the stderr of the cached execution.
+    pub stderr: String,
+}
+
+impl CallCacheEntry {
+    /// Constructs a new call cache entry from an execution result.
+    ///
+    /// Returns `None` if the result contains paths that are not UTF-8.
+    pub fn new(attempt_dir: &Path, result: &TaskExecutionResult) -> Option<Self> {
+        Some(Self {
+            attempt_dir: attempt_dir.to_path_buf(),
+            exit_code: result.exit_code,
+            work_dir: result.work_dir.to_str()?.to_string(),
+            stdout: result.stdout.as_file()?.as_str().to_string(),
+            stderr: result.stderr.as_file()?.as_str().to_string(),
+        })
+    }
+
+    /// Converts the entry into an attempt directory and execution result.
+    pub fn into_result(self) -> Result<(PathBuf, TaskExecutionResult)> {
+        let work_dir: EvaluationPath = self.work_dir.parse()?;
+        Ok((
+            self.attempt_dir,
+            TaskExecutionResult {
+                exit_code: self.exit_code,
+                work_dir,
+                stdout: PrimitiveValue::new_file(self.stdout).into(),
+                stderr: PrimitiveValue::new_file(self.stderr).into(),
+                usage: None,
+                gpus: Vec::new(),
+            },
+        ))
+    }
+
+    /// Determines if the entry is still usable.
+    ///
+    /// An entry is unusable if any of its local paths no longer exist.
+    fn is_usable(&self) -> bool {
+        if !self.attempt_dir.is_dir() {
+            return false;
+        }
+
+        [&self.work_dir, &self.stdout, &self.stderr]
+            .into_iter()
+            .all(|p| match p.parse::<EvaluationPath>() {
+                Ok(EvaluationPath::Local(path)) => path.exists(),
+                Ok(EvaluationPath::Remote(_)) => true,
+                Err(_) => false,
+            })
+    }
+}
+
+/// Represents an on-disk call cache.
+#[derive(Debug)]
+pub(crate) struct CallCache {
+    /// The directory containing the cache entries.
+    dir: PathBuf,
+    /// The mode of the cache.
+    mode: CallCacheMode,
+    /// The strategy used to localize cached executions.
+    strategy: LocalizationStrategy,
+}
+
+impl CallCache {
+    /// Creates a new call cache from the given configuration.
+    ///
+    /// Returns `Ok(None)` if call caching is disabled.
+    pub fn new(config: &CallCacheConfig) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        let dir = match &config.dir {
+            Some(dir) => dir.clone(),
+            None => dirs::cache_dir()
+                .context("failed to determine system cache directory")?
+                .join(DEFAULT_CACHE_SUBDIR),
+        };
+
+        Ok(Some(Self {
+            dir,
+            mode: config.mode,
+            strategy: config
+                .localization
+                .unwrap_or(LocalizationStrategy::Hardlink),
+        }))
+    }
+
+    /// Calculates a call cache key.
+    ///
+    /// The key is calculated from the task's evaluated command, evaluated
+    /// requirements and hints, container, environment variables, and the
+    /// digests of the task's inputs.
+    ///
+    /// The `inputs` and `outputs` hints only describe the task's inputs and
+    /// outputs and are not part of the key.
+    ///
+    /// Inputs that are not local (i.e. remote inputs for backends that do not
+    /// require local inputs) contribute their URL to the key.
+    ///
+    /// Returns an error if the digest of an input could not be calculated.
+    pub fn key(
+        command: &str,
+        requirements: &HashMap<String, Value>,
+        hints: &HashMap<String, Value>,
+        container: &str,
+        env: &IndexMap<String, String>,
+        inputs: &[Input],
+        digests: &InputDigests,
+    ) -> Result<Hash> {
+        let mut hasher = Hasher::new();
+        hasher.update(&KEY_VERSION.to_le_bytes());
+        update_str(&mut hasher, command);
+        update_str(&mut hasher, container);
+
+        update_values(&mut hasher, requirements)
+            .context("failed to serialize task requirements")?;
+
+        let hints: HashMap<_, _> = hints
+            .iter()
+            .filter(|(k, _)| *k != TASK_HINT_INPUTS && *k != TASK_HINT_OUTPUTS)
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        update_values(&mut hasher, &hints).context("failed to serialize task hints")?;
+
+        let env: BTreeMap<_, _> = env.iter().collect();
+        hasher.update(&(env.len() as u64).to_le_bytes());
+        for (name, value) in env {
+            update_str(&mut hasher, name);
+            update_str(&mut hasher, value);
+        }
+
+        hasher.update(&(inputs.len() as u64).to_le_bytes());
+        for (input, digest) in inputs.iter().zip(digests.iter()) {
+            match digest.map_err(|e| anyhow!("{e}"))? {
+                Some(Digest::File(digest)) => {
+                    hasher.update(b"file");
+                    hasher.update(digest.as_bytes());
+                }
+                Some(Digest::Directory(digest)) => {
+                    hasher.update(b"directory");
+                    hasher.update(digest.as_bytes());
+                }
+                None => {
+                    hasher.update(match input.kind() {
+                        InputKind::File => b"file-url",
+                        InputKind::Directory => b"directory-url",
+                    });
+                    update_str(&mut hasher, &input.path().display().to_string());
+                }
+            }
+
+            // The guest path of the input is part of the key as the command may
+            // observe it
+            if let Some(guest_path) = input.guest_path() {
+                update_str(&mut hasher, guest_path.as_str());
+            }
+        }
+
+        Ok(hasher.finalize())
+    }
+
+    /// Looks up an entry in the call cache.
+    ///
+    /// Returns `Ok(None)` if there is no usable entry for the given key.
+    pub fn get(&self, key: &Hash) -> Result<Option<CallCacheEntry>> {
+        let path = self.entry_path(key);
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!(
+                        "failed to open call cache entry `{path}`",
+                        path = path.display()
+                    )
+                });
+            }
+        };
+
+        let entry: CallCacheEntry = match serde_json::from_reader(BufReader::new(file)) {
+            Ok(entry) => entry,
+            Err(e) => {
+                debug!(
+                    "ignoring invalid call cache entry `{path}`: {e}",
+                    path = path.display()
+                );
+                return Ok(None);
+            }
+        };
+
+        if !entry.is_usable() {
+            debug!(
+                "ignoring call cache entry `{path}` as its execution results no longer exist",
+                path = path.display()
+            );
+            return Ok(None);
+        }
+
+        Ok(Some(entry))
+    }
+
+    /// Localizes the results of a cached execution into the given attempt
+    /// directory.
+    ///
+    /// The local working directory, stdout, and stderr of the cached execution
+    /// are localized with the cache's localization strategy so that the
+    /// outputs of the reusing call do not refer to another evaluation's output
+    /// directory.
+    ///
+    /// Returns an entry for the localized execution.
+    pub async fn localize(
+        &self,
+        entry: CallCacheEntry,
+        attempt_dir: &Path,
+    ) -> Result<CallCacheEntry> {
+        let strategy = self.strategy;
+        let attempt_dir = attempt_dir.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let localize = |path: String| -> Result<String> {
+                let source = match path.parse::<EvaluationPath>()? {
+                    EvaluationPath::Local(source) => source,
+                    EvaluationPath::Remote(_) => return Ok(path),
+                };
+
+                let target = match source.strip_prefix(&entry.attempt_dir) {
+                    Ok(relative) => attempt_dir.join(relative),
+                    Err(_) => attempt_dir.join(source.file_name().with_context(|| {
+                        format!(
+                            "cached path `{path}` has no file name",
+                            path = source.display()
+                        )
+                    })?),
+                };
+
+                localization::localize(strategy, &source, &target).with_context(|| {
+                    format!(
+                        "failed to localize cached execution result `{source}` to `{target}`",
+                        source = source.display(),
+                        target = target.display()
+                    )
+                })?;
+
+                target
+                    .into_os_string()
+                    .into_string()
+                    .map_err(|p| anyhow!("path `{p}` is not UTF-8", p = Path::new(&p).display()))
+            };
+
+            Ok(CallCacheEntry {
+                work_dir: localize(entry.work_dir.clone())?,
+                stdout: localize(entry.stdout.clone())?,
+                stderr: localize(entry.stderr.clone())?,
+                exit_code: entry.exit_code,
+                attempt_dir,
+            })
+        })
+        .await
+        .context("call cache localization task failed")?
+    }
+
+    /// Inserts an entry into the call cache.
+    ///
+    /// This is a no-op if the cache is read-only.
+    pub fn put(&self, key: &Hash, entry: &CallCacheEntry) -> Result<()> {
+        if self.mode == CallCacheMode::ReadOnly {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir).with_context(|| {
+            format!(
+                "failed to create call cache directory `{path}`",
+                path = self.dir.display()
+            )
+        })?;
+
+        // Write to a temporary file first and then persist it to prevent partially
+        // written entries from being observed
+        let path = self.entry_path(key);
+        let mut file = NamedTempFile::new_in(&self.dir).with_context(|| {
+            format!(
+                "failed to create temporary file in `{path}`",
+                path = self.dir.display()
+            )
+        })?;
+        serde_json::to_writer_pretty(&mut file, entry).with_context(|| {
+            format!(
+                "failed to write call cache entry `{path}`",
+                path = path.display()
+            )
+        })?;
+        file.persist(&path).with_context(|| {
+            format!(
+                "failed to persist call cache entry `{path}`",
+                path = path.display()
+            )
+        })?;
+
+        Ok(())
+    }
+
+    /// Gets the path to the entry file for the given key.
+    fn entry_path(&self, key: &Hash) -> PathBuf {
+        self.dir.join(format!("{key}.json", key = key.to_hex()))
+    }
+}
+
+/// Represents a record of a task's successful completion.
+///
+/// A completion record is written to the task's root directory after the
+/// task's outputs are written.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) struct CompletionRecord {
+    /// The digest of the task's source.
+    source: String,
+    /// The fingerprint of the task's inputs.
+    inputs: String,
+    /// The successful execution of the task.
+    #[serde(flatten)]
+    execution: CallCacheEntry,
+}
+
+impl CompletionRecord {
+    /// Constructs a new completion record.
+    pub fn new(source: &Hash, inputs: &Hash, execution: CallCacheEntry) -> Self {
+        Self {
+            source: source.to_hex().to_string(),
+            inputs: inputs.to_hex().to_string(),
+            execution,
+        }
+    }
+
+    /// Calculates the digest of a task's source from the given source texts.
+    pub fn source_digest(texts: impl IntoIterator<Item = impl AsRef<str>>) -> Hash {
+        let mut hasher = Hasher::new();
+        hasher.update(&KEY_VERSION.to_le_bytes());
+        for text in texts {
+            update_str(&mut hasher, text.as_ref());
+        }
+
+        hasher.finalize()
+    }
+
+    /// Calculates the fingerprint of a task's inputs.
+    ///
+    /// The fingerprint is calculated from the task's inputs, evaluated command,
+    /// and evaluated requirements.
+    ///
+    /// Unlike call cache keys, the contents of local input files are not
+    /// hashed; instead, the size and modification time of each file is used.
+    pub fn fingerprint(
+        inputs: &TaskInputs,
+        command: &str,
+        requirements: &HashMap<String, Value>,
+        backend_inputs: &[Input],
+    ) -> Result<Hash> {
+        let mut hasher = Hasher::new();
+        hasher.update(&KEY_VERSION.to_le_bytes());
+        let inputs = serde_json::to_string(inputs).context("failed to serialize task inputs")?;
+        update_str(&mut hasher, &inputs);
+        update_str(&mut hasher, command);
+        update_values(&mut hasher, requirements)
+            .context("failed to serialize task requirements")?;
+
+        hasher.update(&(backend_inputs.len() as u64).to_le_bytes());
+        for input in backend_inputs {
+            update_str(&mut hasher, &input.path().display().to_string());
+            if let Some(path) = input.local_path() {
+                for entry in WalkDir::new(path).sort_by_file_name() {
+                    let entry = entry.with_context(|| {
+                        format!(
+                            "failed to read metadata of input `{path}`",
+                            path = path.display()
+                        )
+                    })?;
+                    let metadata = entry.metadata().with_context(|| {
+                        format!(
+                            "failed to read metadata of input `{path}`",
+                            path = entry.path().display()
+                        )
+                    })?;
+
+                    update_str(&mut hasher, &entry.path().display().to_string());
+                    hasher.update(&metadata.len().to_le_bytes());
+                    if let Ok(modified) = metadata.modified()
+                        && let Ok(duration) = modified.duration_since(UNIX_EPOCH)
+                    {
+                        hasher.update(&duration.as_nanos().to_le_bytes());
+                    }
+                }
+            }
+        }
+
+        Ok(hasher.finalize())
+    }
+
+    /// Reads the completion record from the given task root directory.
+    ///
+    /// Returns `Ok(None)` if the record does not exist or is invalid.
+    pub fn read(root_dir: &Path) -> Result<Option<Self>> {
+        let path = root_dir.join(COMPLETION_FILE);
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!(
+                        "failed to open completion record `{path}`",
+                        path = path.display()
+                    )
+                });
+            }
+        };
+
+        match serde_json::from_reader(BufReader::new(file)) {
+            Ok(record) => Ok(Some(record)),
+            Err(e) => {
+                debug!(
+                    "ignoring invalid completion record `{path}`: {e}",
+                    path = path.display()
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    /// Writes the completion record to the given task root directory.
+    pub fn write(&self, root_dir: &Path) -> Result<()> {
+        let path = root_dir.join(COMPLETION_FILE);
+        let mut file = NamedTempFile::new_in(root_dir).with_context(|| {
+            format!(
+                "failed to create temporary file in `{path}`",
+                path = root_dir.display()
+            )
+        })?;
+        serde_json::to_writer_pretty(&mut file, self).with_context(|| {
+            format!(
+                "failed to write completion record `{path}`",
+                path = path.display()
+            )
+        })?;
+        file.persist(&path).with_context(|| {
+            format!(
+                "failed to persist completion record `{path}`",
+                path = path.display()
+            )
+        })?;
+
+        Ok(())
+    }
+
+    /// Removes any completion record from the given task root directory.
+    pub fn remove(root_dir: &Path) -> Result<()> {
+        let path = root_dir.join(COMPLETION_FILE);
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).with_context(|| {
+                format!(
+                    "failed to remove completion record `{path}`",
+                    path = path.display()
+                )
+            }),
+        }
+    }
+
+    /// Converts the record into the successful execution of the task.
+    ///
+    /// Returns `None` if the record does not match the given source digest and
+    /// inputs fingerprint or if the execution results no longer exist.
+    pub fn into_execution(self, source: &Hash, inputs: &Hash) -> Option<CallCacheEntry> {
+        if self.source != source.to_hex().as_str() {
+            debug!("ignoring completion record as the task's source has changed");
+            return None;
+        }
+
+        if self.inputs != inputs.to_hex().as_str() {
+            debug!("ignoring completion record as the task's inputs have changed");
+            return None;
+        }
+
+        if !self.execution.is_usable() {
+            debug!("ignoring completion record as its execution results no longer exist");
+            return None;
+        }
+
+        Some(self.execution)
+    }
+}
+
+/// Updates the hasher with a map of values serialized in a consistent order.
+fn update_values(hasher: &mut Hasher, values: &HashMap<String, Value>) -> Result<()> {
+    let values: BTreeMap<_, _> = values
+        .iter()
+        .map(|(k, v)| (k.as_str(), ValueSerializer::new(v, true)))
+        .collect();
+    update_str(hasher, &serde_json::to_string(&values)?);
+    Ok(())
+}
+
+/// Updates the hasher with a length-prefixed string.
+fn update_str(hasher: &mut Hasher, s: &str) {
+    hasher.update(&(s.len() as u64).to_le_bytes());
+    hasher.update(s.as_bytes());
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn key_changes() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let file = dir.path().join("input.txt");
+        fs::write(&file, "hello").unwrap();
+
+        let requirements: HashMap<_, _> =
+            [("cpu".to_string(), Value::from(PrimitiveValue::Integer(1)))].into();
+        let inputs = [Input::new(
+            InputKind::File,
+            EvaluationPath::Local(file.clone()),
+            None,
+        )];
+
+        let digests = InputDigests::calculate(&inputs).await;
+        fn key(
+            command: &str,
+            requirements: &HashMap<String, Value>,
+            container: &str,
+            inputs: &[Input],
+            digests: &InputDigests,
+        ) -> Hash {
+            CallCache::key(
+                command,
+                requirements,
+                &Default::default(),
+                container,
+                &Default::default(),
+                inputs,
+                digests,
+            )
+            .unwrap()
+        }
+
+        let expected = key("echo hi", &requirements, "ubuntu:latest", &inputs, &digests);
+        assert_eq!(
+            expected,
+            key("echo hi", &requirements, "ubuntu:latest", &inputs, &digests)
+        );
+        assert_ne!(
+            expected,
+            key(
+                "echo bye",
+                &requirements,
+                "ubuntu:latest",
+                &inputs,
+                &digests
+            )
+        );
+        assert_ne!(
+            expected,
+            key("echo hi", &requirements, "alpine:latest", &inputs, &digests)
+        );
+        assert_ne!(
+            expected,
+            key(
+                "echo hi",
+                &Default::default(),
+                "ubuntu:latest",
+                &inputs,
+                &digests
+            )
+        );
+        assert_ne!(
+            expected,
+            key(
+                "echo hi",
+                &requirements,
+                "ubuntu:latest",
+                &[],
+                &Default::default()
+            )
+        );
+
+        // Environment variables and hints are part of the key
+        let env: IndexMap<_, _> = [("MODE".to_string(), "fast".to_string())].into();
+        let with_env = CallCache::key(
+            "echo hi",
+            &requirements,
+            &Default::default(),
+            "ubuntu:latest",
+            &env,
+            &inputs,
+            &digests,
+        )
+        .unwrap();
+        assert_ne!(expected, with_env);
+        let env: IndexMap<_, _> = [("MODE".to_string(), "slow".to_string())].into();
+        assert_ne!(
+            with_env,
+            CallCache::key(
+                "echo hi",
+                &requirements,
+                &Default::default(),
+                "ubuntu:latest",
+                &env,
+                &inputs,
+                &digests,
+            )
+            .unwrap()
+        );
+        let hints: HashMap<_, _> = [(
+            "max_cpu".to_string(),
+            Value::from(PrimitiveValue::Integer(4)),
+        )]
+        .into();
+        assert_ne!(
+            expected,
+            CallCache::key(
+                "echo hi",
+                &requirements,
+                &hints,
+                "ubuntu:latest",
+                &Default::default(),
+                &inputs,
+                &digests,
+            )
+            .unwrap()
+        );
+
+        // A failure to calculate the digest of an input is an error
+        let missing = [Input::new(
+            InputKind::File,
+            EvaluationPath::Local(dir.path().join("missing.txt")),
+            None,
+        )];
+        let digests = InputDigests::calculate(&missing).await;
+        assert!(
+            CallCache::key(
+                "echo hi",
+                &requirements,
+                &Default::default(),
+                "ubuntu:latest",
+                &Default::default(),
+                &missing,
+                &digests
+            )
+            .unwrap_err()
+            .to_string()
+            .starts_with("failed to calculate digest of input")
+        );
+    }
+
+    #[test]
+    fn get_and_put() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let attempt_dir = dir.path().join("attempts").join("0");
+        let work_dir = attempt_dir.join("work");
+        fs::create_dir_all(&work_dir).unwrap();
+        fs::write(attempt_dir.join("stdout"), "").unwrap();
+        fs::write(attempt_dir.join("stderr"), "").unwrap();
+
+        let result = TaskExecutionResult {
+            exit_code: 0,
+            work_dir: EvaluationPath::Local(work_dir.clone()),
+            stdout: PrimitiveValue::new_file(attempt_dir.join("stdout").to_str().unwrap()).into(),
+            stderr: PrimitiveValue::new_file(attempt_dir.join("stderr").to_str().unwrap()).into(),
+            usage: None,
+            gpus: Vec::new(),
+        };
+        let entry = CallCacheEntry::new(&attempt_dir, &result).unwrap();
+        let key = blake3::hash(b"key");
+
+        // A read-only cache should not store the entry
+        let cache = CallCache::new(&CallCacheConfig {
+            enabled: true,
+            dir: Some(dir.path().join("cache")),
+            mode: CallCacheMode::ReadOnly,
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap();
+        cache.put(&key, &entry).unwrap();
+        assert!(cache.get(&key).unwrap().is_none());
+
+        // A read-write cache should store the entry
+        let cache = CallCache::new(&CallCacheConfig {
+            enabled: true,
+            dir: Some(dir.path().join("cache")),
+            mode: CallCacheMode::ReadWrite,
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap();
+        cache.put(&key, &entry).unwrap();
+        let (cached_dir, cached) = cache.get(&key).unwrap().unwrap().into_result().unwrap();
+        assert_eq!(cached_dir, attempt_dir);
+        assert_eq!(cached.exit_code, 0);
+        assert_eq!(cached.work_dir.as_local(), Some(work_dir.as_path()));
+
+        // The entry is unusable once the execution results are removed
+        fs::remove_dir_all(&work_dir).unwrap();
+        assert!(cache.get(&key).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn localize() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let attempt_dir = dir.path().join("first").join("attempts").join("0");
+        let work_dir = attempt_dir.join("work");
+        fs::create_dir_all(work_dir.join("nested")).unwrap();
+        fs::write(work_dir.join("nested").join("out.txt"), "out").unwrap();
+        fs::write(attempt_dir.join("stdout"), "stdout").unwrap();
+        fs::write(attempt_dir.join("stderr"), "stderr").unwrap();
+
+        let result = TaskExecutionResult {
+            exit_code: 0,
+            work_dir: EvaluationPath::Local(work_dir.clone()),
+            stdout: PrimitiveValue::new_file(attempt_dir.join("stdout").to_str().unwrap()).into(),
+            stderr: PrimitiveValue::new_file(attempt_dir.join("stderr").to_str().unwrap()).into(),
+            usage: None,
+            gpus: Vec::new(),
+        };
+        let entry = CallCacheEntry::new(&attempt_dir, &result).unwrap();
+
+        for strategy in [
+            LocalizationStrategy::Symlink,
+            LocalizationStrategy::Hardlink,
+            LocalizationStrategy::Reflink,
+            LocalizationStrategy::Copy,
+        ] {
+            let cache = CallCache::new(&CallCacheConfig {
+                enabled: true,
+                dir: Some(dir.path().join("cache")),
+                localization: Some(strategy),
+                ..Default::default()
+            })
+            .unwrap()
+            .unwrap();
+
+            let target = dir.path().join("second").join("attempts").join("0");
+            let (localized_dir, localized) = cache
+                .localize(entry.clone(), &target)
+                .await
+                .unwrap()
+                .into_result()
+                .unwrap();
+            assert_eq!(localized_dir, target);
+            assert_eq!(
+                localized.work_dir.as_local(),
+                Some(target.join("work").as_path())
+            );
+            assert_eq!(
+                fs::read_to_string(target.join("work").join("nested").join("out.txt")).unwrap(),
+                "out"
+            );
+            assert_eq!(
+                localized.stdout.as_file().unwrap().as_str(),
+                target.join("stdout").to_str().unwrap()
+            );
+            assert_eq!(fs::read_to_string(target.join("stderr")).unwrap(), "stderr");
+        }
+    }
+
+    #[test]
+    fn completion_record() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let attempt_dir = dir.path().join("attempts").join("0");
+        let work_dir = attempt_dir.join("work");
+        fs::create_dir_all(&work_dir).unwrap();
+        fs::write(attempt_dir.join("stdout"), "").unwrap();
+        fs::write(attempt_dir.join("stderr"), "").unwrap();
+
+        let result = TaskExecutionResult {
+            exit_code: 0,
+            work_dir: EvaluationPath::Local(work_dir.clone()),
+            stdout: PrimitiveValue::new_file(attempt_dir.join("stdout").to_str().unwrap()).into(),
+            stderr: PrimitiveValue::new_file(attempt_dir.join("stderr").to_str().unwrap()).into(),
+            usage: None,
+            gpus: Vec::new(),
+        };
+        let source = CompletionRecord::source_digest(["task t {}"]);
+        let inputs =
+            CompletionRecord::fingerprint(&Default::default(), "echo hi", &Default::default(), &[])
+                .unwrap();
+
+        assert!(CompletionRecord::read(dir.path()).unwrap().is_none());
+        CompletionRecord::new(
+            &source,
+            &inputs,
+            CallCacheEntry::new(&attempt_dir, &result).unwrap(),
+        )
+        .write(dir.path())
+        .unwrap();
+
+        // The record should only match the same source and inputs
+        let read = || CompletionRecord::read(dir.path()).unwrap().unwrap();
+        assert!(read().into_execution(&source, &inputs).is_some());
+        assert!(
+            read()
+                .into_execution(&CompletionRecord::source_digest(["task u {}"]), &inputs)
+                .is_none()
+        );
+        assert!(read().into_execution(&source, &source).is_none());
+
+        // The record should be removed
+        CompletionRecord::remove(dir.path()).unwrap();
+        assert!(CompletionRecord::read(dir.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn disabled() {
+        assert!(CallCache::new(&Default::default()).unwrap().is_none());
+    }
+}
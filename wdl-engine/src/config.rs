@@ -10,6 +10,7 @@ use anyhow::anyhow;
 use anyhow::bail;
 use crankshaft::events::Event;
 use indexmap::IndexMap;
+use regex::Regex;
 use secrecy::ExposeSecret;
 use serde::Deserialize;
 use serde::Serialize;
@@ -17,13 +18,21 @@ use tokio::sync::broadcast;
 use tracing::warn;
 use url::Url;
 
+use crate::ApptainerBackend;
 use crate::DockerBackend;
 use crate::LocalBackend;
 use crate::SYSTEM;
+use crate::SchedulerBackend;
+use crate::SlurmBackend;
 use crate::TaskExecutionBackend;
 use crate::TesBackend;
+use crate::backend::JOB_TEMPLATE_VARIABLES;
+use crate::backend::SUBMIT_TEMPLATE_VARIABLES;
+use crate::backend::expand_template;
 use crate::convert_unit_string;
+use crate::path::is_file_url;
 use crate::path::is_url;
+use crate::path::parse_url;
 
 /// The inclusive maximum number of task retries the engine supports.
 pub const MAX_RETRIES: u64 = 100;
@@ -152,6 +161,12 @@ pub struct Config {
     /// Task evaluation configuration.
     #[serde(default)]
     pub task: TaskConfig,
+    /// Call cache configuration.
+    #[serde(default)]
+    pub call_cache: CallCacheConfig,
+    /// Run history configuration.
+    #[serde(default)]
+    pub history: HistoryConfig,
     /// The name of the backend to use.
     ///
     /// If not specified and `backends` has multiple entries, it will use a name
@@ -193,6 +208,8 @@ impl Config {
         self.http.validate()?;
         self.workflow.validate()?;
         self.task.validate()?;
+        self.call_cache.validate()?;
+        self.history.validate()?;
 
         if self.backend.is_none() && self.backends.len() < 2 {
             // This is OK, we'll use either the singular backends entry (1) or
@@ -286,6 +303,30 @@ impl Config {
             BackendConfig::Tes(config) => Ok(Arc::new(
                 TesBackend::new(self.clone(), config, events).await?,
             )),
+            BackendConfig::Slurm(config) => {
+                warn!(
+                    "the engine is configured to use the Slurm backend: tasks will not be run \
+                     inside of a container"
+                );
+                Ok(Arc::new(SlurmBackend::new(self.clone(), config, events)?))
+            }
+            BackendConfig::Scheduler(config) => {
+                warn!(
+                    "the engine is configured to use the {name} backend: tasks will not be run \
+                     inside of a container",
+                    name = config.scheduler_name()
+                );
+                Ok(Arc::new(SchedulerBackend::new(
+                    self.clone(),
+                    config,
+                    events,
+                )?))
+            }
+            BackendConfig::Apptainer(config) => Ok(Arc::new(ApptainerBackend::new(
+                self.clone(),
+                config,
+                events,
+            )?)),
         }
     }
 }
@@ -517,16 +558,97 @@ pub struct WorkflowConfig {
     /// Scatter statement evaluation configuration.
     #[serde(default)]
     pub scatter: ScatterConfig,
+    /// Whether or not to write a provenance document to the workflow's root
+    /// directory upon completion of evaluation.
+    ///
+    /// The provenance document records the source digest, evaluated command,
+    /// container, requirements, input and output digests, and attempts of
+    /// each task call made by the workflow.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub provenance: bool,
+    /// The configuration for relocating the final outputs of the workflow upon
+    /// successful completion of evaluation.
+    ///
+    /// Defaults to `None` (outputs remain in the attempt directories of the
+    /// calls that produced them).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<OutputsConfig>,
 }
 
 impl WorkflowConfig {
     /// Validates the workflow configuration.
     pub fn validate(&self) -> Result<()> {
         self.scatter.validate()?;
+
+        if let Some(outputs) = &self.outputs {
+            outputs.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Represents configuration for relocating the final outputs of a workflow.
+///
+/// The `File` and `Directory` values of the relocated outputs are rewritten to
+/// their relocated paths in the workflow's `outputs.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct OutputsConfig {
+    /// The local directory or cloud storage URL to relocate outputs to.
+    pub dir: String,
+    /// The layout of the relocated outputs.
+    #[serde(default)]
+    pub layout: OutputLayout,
+    /// The strategy for relocating outputs to a local directory.
+    ///
+    /// Outputs relocated to a cloud storage URL are always uploaded.
+    ///
+    /// Defaults to `copy`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<LocalizationStrategy>,
+}
+
+impl OutputsConfig {
+    /// Validates the outputs configuration.
+    pub fn validate(&self) -> Result<()> {
+        if self.dir.is_empty() {
+            bail!("configuration value `workflow.outputs.dir` cannot be empty");
+        }
+
+        if is_url(&self.dir) && (is_file_url(&self.dir) || parse_url(&self.dir).is_none()) {
+            bail!(
+                "configuration value `workflow.outputs.dir` is not a supported cloud storage URL"
+            );
+        }
+
         Ok(())
     }
 }
 
+/// The layout of relocated workflow outputs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum OutputLayout {
+    /// Relocate outputs directly into the output directory by file name.
+    ///
+    /// Outputs of different calls with the same file name (e.g. the outputs of
+    /// a scattered call) have their file names prefixed with the name of the
+    /// directory of the call that produced them, such as `t-1-out.txt`.
+    ///
+    /// This is the default layout.
+    #[default]
+    Flat,
+    /// Relocate outputs into a directory for each call that produced them.
+    ///
+    /// An output's path within its call's work directory is preserved, with
+    /// the directories of calls made by nested workflows nested accordingly.
+    /// Outputs not produced by a call are relocated by file name.
+    Call,
+}
+
 /// Represents scatter statement evaluation configuration.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
@@ -632,6 +754,57 @@ pub struct TaskConfig {
     /// The behavior when a task's `memory` requirement cannot be met.
     #[serde(default)]
     pub memory_limit_behavior: TaskResourceLimitBehavior,
+    /// The behavior when a task's `gpu` requirement cannot be met.
+    ///
+    /// When trying with the maximum, the task is executed with the GPUs that
+    /// are available, which may be none.
+    #[serde(default)]
+    pub gpu_limit_behavior: TaskResourceLimitBehavior,
+    /// The default maximum amount of time, in seconds, a task may execute for.
+    ///
+    /// A task's `timeout` hint will override this value.
+    ///
+    /// Defaults to `None` (no timeout).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// The behavior when a task exceeds its timeout.
+    #[serde(default)]
+    pub timeout_behavior: TaskTimeoutBehavior,
+    /// Whether or not to resume evaluation from an existing output directory.
+    ///
+    /// When enabled, a task that previously completed successfully in the same
+    /// output directory is not executed again provided its source and inputs
+    /// have not changed.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub resume: bool,
+    /// The policy for pinning task containers to references by digest.
+    #[serde(default)]
+    pub container_digest_policy: ContainerDigestPolicy,
+    /// The strategy for localizing task inputs into each attempt directory.
+    ///
+    /// This only applies to backends that execute tasks with local inputs.
+    ///
+    /// Defaults to `None` (inputs are used where they are and mounted
+    /// read-only into containers).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub localization: Option<LocalizationStrategy>,
+    /// The factor to multiply a task's memory by when retrying an attempt that
+    /// was killed for exceeding its memory (i.e. exited with status code
+    /// `137`).
+    ///
+    /// The escalated memory is capped by the task's `max_memory` hint and by
+    /// the memory the backend can allocate; the new value is reflected in
+    /// `task.memory` for the retried attempt. An attempt killed for exceeding
+    /// its memory counts against the task's `max_retries` requirement.
+    ///
+    /// Defaults to `None` (retried attempts use the same memory).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_retry_multiplier: Option<f64>,
+    /// The policy for retrying failed task attempts by the kind of failure.
+    #[serde(default)]
+    pub retry_policy: TaskRetryPolicy,
 }
 
 impl TaskConfig {
@@ -641,6 +814,63 @@ impl TaskConfig {
             bail!("configuration value `task.retries` cannot exceed {MAX_RETRIES}");
         }
 
+        if let Some(0) = self.timeout {
+            bail!("configuration value `task.timeout` cannot be zero");
+        }
+
+        if let Some(multiplier) = self.memory_retry_multiplier
+            && !(multiplier.is_finite() && multiplier > 1.0)
+        {
+            bail!("configuration value `task.memory_retry_multiplier` must be greater than 1");
+        }
+
+        self.retry_policy.validate()?;
+        Ok(())
+    }
+}
+
+/// Represents the policy for retrying failed task attempts by the kind of
+/// failure.
+///
+/// Retries of attempts whose command failed and retries of attempts the
+/// backend failed to execute are budgeted separately.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct TaskRetryPolicy {
+    /// The maximum number of retries of attempts whose command failed (i.e.
+    /// exited with a status code not permitted by the task's `return_codes`
+    /// requirement or exceeded its timeout).
+    ///
+    /// This value caps the task's `max_retries` requirement (or `task.retries`
+    /// if the task does not specify it); set it to `0` to never retry a failed
+    /// command.
+    ///
+    /// Defaults to `None` (the task's `max_retries` requirement applies).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<u64>,
+    /// The maximum number of retries of attempts the backend failed to execute
+    /// (e.g. the task's container could not be pulled, the task was preempted,
+    /// or a TES server reported a system error).
+    ///
+    /// These retries do not count against the task's `max_retries`
+    /// requirement.
+    ///
+    /// Defaults to 0 (no retries).
+    #[serde(default)]
+    pub system: u64,
+}
+
+impl TaskRetryPolicy {
+    /// Validates the task retry policy.
+    pub fn validate(&self) -> Result<()> {
+        if self.command.unwrap_or(0) > MAX_RETRIES {
+            bail!("configuration value `task.retry_policy.command` cannot exceed {MAX_RETRIES}");
+        }
+
+        if self.system > MAX_RETRIES {
+            bail!("configuration value `task.retry_policy.system` cannot exceed {MAX_RETRIES}");
+        }
+
         Ok(())
     }
 }
@@ -660,6 +890,168 @@ pub enum TaskResourceLimitBehavior {
     Deny,
 }
 
+/// The policy for pinning task containers to references by digest.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum ContainerDigestPolicy {
+    /// Resolve each task container to a reference by digest before the task is
+    /// first executed.
+    ///
+    /// Containers are only resolved by backends that support it; the same
+    /// digest is used for every task using the same container within an
+    /// evaluation.
+    Resolve,
+    /// Require each task container to be specified by digest (e.g.
+    /// `ubuntu@sha256:...`) or to be a local image file (e.g. an Apptainer
+    /// SIF file); a task with any other container fails.
+    ///
+    /// This policy only applies to backends that execute tasks in containers.
+    Require,
+    /// Do not resolve task containers.
+    ///
+    /// This is the default policy.
+    #[default]
+    Ignore,
+}
+
+/// The strategy for localizing task inputs into an attempt directory.
+///
+/// Inputs from the same directory are localized into the same directory so
+/// that files such as indexes remain co-located with the files they index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum LocalizationStrategy {
+    /// Localize inputs as symbolic links to the original inputs.
+    Symlink,
+    /// Localize inputs as hard links to the original inputs.
+    ///
+    /// The files of an input on a different file system than the attempt
+    /// directory are copied.
+    Hardlink,
+    /// Localize inputs as copy-on-write clones of the original inputs.
+    ///
+    /// The files of an input are copied if the file system does not support
+    /// cloning.
+    Reflink,
+    /// Localize inputs as copies of the original inputs.
+    Copy,
+}
+
+impl LocalizationStrategy {
+    /// Determines if inputs localized with the strategy may be written to
+    /// without modifying the original inputs.
+    pub fn is_writable(&self) -> bool {
+        matches!(self, Self::Reflink | Self::Copy)
+    }
+}
+
+/// The behavior when a task exceeds its timeout.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum TaskTimeoutBehavior {
+    /// Retry a task that exceeds its timeout.
+    ///
+    /// A timed out attempt counts against the task's `max_retries`
+    /// requirement.
+    ///
+    /// This is the default behavior.
+    #[default]
+    Retry,
+    /// Fail a task that exceeds its timeout without retrying it.
+    Fail,
+}
+
+/// Represents call cache configuration.
+///
+/// When enabled, the engine reuses the results of a previous successful task
+/// execution if the task's evaluated command, requirements, container, and the
+/// digests of its inputs are unchanged.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct CallCacheConfig {
+    /// Whether or not call caching is enabled.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+    /// The call cache directory.
+    ///
+    /// Defaults to using the system cache directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+    /// The call cache mode.
+    #[serde(default)]
+    pub mode: CallCacheMode,
+    /// The strategy used to localize the results of a cached execution into
+    /// the attempt directory of a call that reuses them.
+    ///
+    /// Defaults to `hardlink` so that the reused results remain available if
+    /// the previous execution's output directory is deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub localization: Option<LocalizationStrategy>,
+}
+
+impl CallCacheConfig {
+    /// Validates the call cache configuration.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(dir) = &self.dir
+            && dir.as_os_str().is_empty()
+        {
+            bail!("configuration value `call_cache.dir` cannot be empty");
+        }
+
+        Ok(())
+    }
+}
+
+/// Represents the mode of the call cache.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum CallCacheMode {
+    /// The call cache is both read from and written to.
+    ///
+    /// This is the default mode.
+    #[default]
+    ReadWrite,
+    /// The call cache is only read from.
+    ///
+    /// Successful task executions are not added to the cache.
+    ReadOnly,
+}
+
+/// Represents run history configuration.
+///
+/// When enabled, the engine records every workflow and task run, along with
+/// their calls, attempts, inputs, outputs, and statuses, into a SQLite
+/// database that can be queried with [`History`](crate::history::History).
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Whether or not run history is recorded.
+    ///
+    /// Defaults to `false`.
+    #[serde(default)]
+    pub enabled: bool,
+    /// The path to the history database.
+    ///
+    /// Defaults to a database in the system data directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+impl HistoryConfig {
+    /// Validates the run history configuration.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(path) = &self.path
+            && path.as_os_str().is_empty()
+        {
+            bail!("configuration value `history.path` cannot be empty");
+        }
+
+        Ok(())
+    }
+}
+
 /// Represents supported task execution backends.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
@@ -670,6 +1062,12 @@ pub enum BackendConfig {
     Docker(DockerBackendConfig),
     /// Use the TES task execution backend.
     Tes(Box<TesBackendConfig>),
+    /// Use the Slurm task execution backend.
+    Slurm(SlurmBackendConfig),
+    /// Use the generic HPC scheduler task execution backend.
+    Scheduler(SchedulerBackendConfig),
+    /// Use the Apptainer task execution backend.
+    Apptainer(ApptainerBackendConfig),
 }
 
 impl Default for BackendConfig {
@@ -685,6 +1083,9 @@ impl BackendConfig {
             Self::Local(config) => config.validate(),
             Self::Docker(config) => config.validate(),
             Self::Tes(config) => config.validate(),
+            Self::Slurm(config) => config.validate(),
+            Self::Scheduler(config) => config.validate(),
+            Self::Apptainer(config) => config.validate(),
         }
     }
 
@@ -718,10 +1119,44 @@ impl BackendConfig {
         }
     }
 
+    /// Converts the backend configuration into a Slurm backend configuration
+    ///
+    /// Returns `None` if the backend configuration is not Slurm.
+    pub fn as_slurm(&self) -> Option<&SlurmBackendConfig> {
+        match self {
+            Self::Slurm(config) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Converts the backend configuration into a generic HPC scheduler backend
+    /// configuration
+    ///
+    /// Returns `None` if the backend configuration is not a generic HPC
+    /// scheduler.
+    pub fn as_scheduler(&self) -> Option<&SchedulerBackendConfig> {
+        match self {
+            Self::Scheduler(config) => Some(config),
+            _ => None,
+        }
+    }
+
+    /// Converts the backend configuration into an Apptainer backend
+    /// configuration
+    ///
+    /// Returns `None` if the backend configuration is not Apptainer.
+    pub fn as_apptainer(&self) -> Option<&ApptainerBackendConfig> {
+        match self {
+            Self::Apptainer(config) => Some(config),
+            _ => None,
+        }
+    }
+
     /// Redacts the secrets contained in the backend configuration.
     pub fn redact(&mut self) {
         match self {
-            Self::Local(_) | Self::Docker(_) => {}
+            Self::Local(_) | Self::Slurm(_) | Self::Scheduler(_) | Self::Apptainer(_) => {}
+            Self::Docker(config) => config.redact(),
             Self::Tes(config) => config.redact(),
         }
     }
@@ -729,7 +1164,8 @@ impl BackendConfig {
     /// Unredacts the secrets contained in the backend configuration.
     pub fn unredact(&mut self) {
         match self {
-            Self::Local(_) | Self::Docker(_) => {}
+            Self::Local(_) | Self::Slurm(_) | Self::Scheduler(_) | Self::Apptainer(_) => {}
+            Self::Docker(config) => config.unredact(),
             Self::Tes(config) => config.unredact(),
         }
     }
@@ -765,39 +1201,103 @@ pub struct LocalBackendConfig {
 impl LocalBackendConfig {
     /// Validates the local task execution backend configuration.
     pub fn validate(&self) -> Result<()> {
-        if let Some(cpu) = self.cpu {
-            if cpu == 0 {
-                bail!("local backend configuration value `cpu` cannot be zero");
-            }
+        validate_host_resources("local", self.cpu, self.memory.as_deref())
+    }
+}
 
-            let total = SYSTEM.cpus().len() as u64;
-            if cpu > total {
-                bail!(
-                    "local backend configuration value `cpu` cannot exceed the virtual CPUs \
-                     available to the host ({total})"
-                );
-            }
+/// Validates the host CPU and memory configuration values of a backend that
+/// executes tasks on the host.
+fn validate_host_resources(backend: &str, cpu: Option<u64>, memory: Option<&str>) -> Result<()> {
+    if let Some(cpu) = cpu {
+        if cpu == 0 {
+            bail!("{backend} backend configuration value `cpu` cannot be zero");
         }
 
-        if let Some(memory) = &self.memory {
-            let memory = convert_unit_string(memory).with_context(|| {
-                format!("local backend configuration value `memory` has invalid value `{memory}`")
-            })?;
+        let total = SYSTEM.cpus().len() as u64;
+        if cpu > total {
+            bail!(
+                "{backend} backend configuration value `cpu` cannot exceed the virtual CPUs \
+                 available to the host ({total})"
+            );
+        }
+    }
 
-            if memory == 0 {
-                bail!("local backend configuration value `memory` cannot be zero");
-            }
+    if let Some(memory) = memory {
+        let memory = convert_unit_string(memory).with_context(|| {
+            format!("{backend} backend configuration value `memory` has invalid value `{memory}`")
+        })?;
 
-            let total = SYSTEM.total_memory();
-            if memory > total {
-                bail!(
-                    "local backend configuration value `memory` cannot exceed the total memory of \
-                     the host ({total} bytes)"
-                );
-            }
+        if memory == 0 {
+            bail!("{backend} backend configuration value `memory` cannot be zero");
         }
 
-        Ok(())
+        let total = SYSTEM.total_memory();
+        if memory > total {
+            bail!(
+                "{backend} backend configuration value `memory` cannot exceed the total memory of \
+                 the host ({total} bytes)"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Represents configuration for the Apptainer (formerly Singularity) task
+/// execution backend.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct ApptainerBackendConfig {
+    /// The Apptainer executable to use (e.g. `singularity`).
+    ///
+    /// Defaults to `apptainer`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub executable: Option<String>,
+
+    /// The directory to cache images pulled from remote registries in.
+    ///
+    /// Defaults to using the system cache directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_cache_dir: Option<PathBuf>,
+
+    /// Additional arguments to pass to `apptainer exec` when executing tasks.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+
+    /// Set the number of CPUs available for task execution.
+    ///
+    /// Defaults to the number of logical CPUs for the host.
+    ///
+    /// The value cannot be zero or exceed the host's number of CPUs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<u64>,
+
+    /// Set the total amount of memory for task execution as a unit string (e.g.
+    /// `2 GiB`).
+    ///
+    /// Defaults to the total amount of memory for the host.
+    ///
+    /// The value cannot be zero or exceed the host's total amount of memory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+}
+
+impl ApptainerBackendConfig {
+    /// Validates the Apptainer task execution backend configuration.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(executable) = &self.executable
+            && executable.is_empty()
+        {
+            bail!("Apptainer backend configuration value `executable` cannot be empty");
+        }
+
+        if let Some(dir) = &self.image_cache_dir
+            && dir.as_os_str().is_empty()
+        {
+            bail!("Apptainer backend configuration value `image_cache_dir` cannot be empty");
+        }
+
+        validate_host_resources("Apptainer", self.cpu, self.memory.as_deref())
     }
 }
 
@@ -815,18 +1315,436 @@ pub struct DockerBackendConfig {
     /// Defaults to `true`.
     #[serde(default = "cleanup_default")]
     pub cleanup: bool,
+    /// The identifiers of the GPU devices available to tasks.
+    ///
+    /// Tasks with a `gpu` requirement are assigned devices from this list in
+    /// order.
+    ///
+    /// Defaults to the GPUs reported by `nvidia-smi` when the Docker daemon has
+    /// an `nvidia` runtime.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpus: Option<Vec<String>>,
+    /// The path to the socket of a Docker-compatible daemon to use, such as a
+    /// rootless Podman socket (e.g. `/run/user/1000/podman/podman.sock`).
+    ///
+    /// Tasks are always run as containers on the daemon when a socket is
+    /// configured, even if the daemon is part of a swarm.
+    ///
+    /// Defaults to the Docker daemon specified by the `DOCKER_HOST`
+    /// environment variable or the default Docker socket.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<String>,
+    /// The user namespace mode for task containers (e.g. `keep-id` for
+    /// rootless Podman to run containers as the current user).
+    ///
+    /// Defaults to the daemon's default user namespace mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub userns_mode: Option<String>,
+    /// The authentication to use when pulling images from container
+    /// registries.
+    ///
+    /// The key is the registry host (e.g. `ghcr.io` or `localhost:5000`);
+    /// images without a registry host are pulled from `docker.io`.
+    ///
+    /// This option is specific to the Docker backend; TES servers pull images
+    /// with their own credentials.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub registries: IndexMap<String, RegistryAuthConfig>,
 }
 
 impl DockerBackendConfig {
     /// Validates the Docker backend configuration.
     pub fn validate(&self) -> Result<()> {
+        if let Some(gpus) = &self.gpus
+            && gpus.iter().any(|id| id.trim().is_empty())
+        {
+            bail!("Docker backend configuration value `gpus` cannot contain an empty identifier");
+        }
+
+        if let Some(socket) = &self.socket
+            && socket.trim().is_empty()
+        {
+            bail!("Docker backend configuration value `socket` cannot be empty");
+        }
+
+        if let Some(mode) = &self.userns_mode
+            && mode.trim().is_empty()
+        {
+            bail!("Docker backend configuration value `userns_mode` cannot be empty");
+        }
+
+        for (registry, auth) in &self.registries {
+            if registry.trim().is_empty() || registry.contains('/') {
+                bail!(
+                    "Docker backend configuration value `registries` contains an invalid registry \
+                     host `{registry}`"
+                );
+            }
+
+            auth.validate()?;
+        }
+
         Ok(())
     }
-}
+
+    /// Redacts the secrets contained in the Docker backend configuration.
+    pub fn redact(&mut self) {
+        for auth in self.registries.values_mut() {
+            auth.redact();
+        }
+    }
+
+    /// Unredacts the secrets contained in the Docker backend configuration.
+    pub fn unredact(&mut self) {
+        for auth in self.registries.values_mut() {
+            auth.unredact();
+        }
+    }
+}
 
 impl Default for DockerBackendConfig {
     fn default() -> Self {
-        Self { cleanup: true }
+        Self {
+            cleanup: true,
+            gpus: None,
+            socket: None,
+            userns_mode: None,
+            registries: Default::default(),
+        }
+    }
+}
+
+/// Represents the kind of authentication for a container registry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum RegistryAuthConfig {
+    /// Use basic authentication for the registry.
+    Basic(BasicAuthConfig),
+    /// Use bearer token authentication for the registry.
+    Bearer(BearerAuthConfig),
+}
+
+impl RegistryAuthConfig {
+    /// Validates the registry authentication configuration.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::Basic(config) => config.validate(),
+            Self::Bearer(config) => config.validate(),
+        }
+    }
+
+    /// Redacts the secrets contained in the registry authentication
+    /// configuration.
+    pub fn redact(&mut self) {
+        match self {
+            Self::Basic(auth) => auth.redact(),
+            Self::Bearer(auth) => auth.redact(),
+        }
+    }
+
+    /// Unredacts the secrets contained in the registry authentication
+    /// configuration.
+    pub fn unredact(&mut self) {
+        match self {
+            Self::Basic(auth) => auth.unredact(),
+            Self::Bearer(auth) => auth.unredact(),
+        }
+    }
+}
+
+/// Represents configuration for the Slurm task execution backend.
+///
+/// <div class="warning">
+/// Warning: the Slurm task execution backend runs tasks on the cluster's nodes
+/// directly without the use of a container; only use this backend on trusted
+/// WDL. </div>
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct SlurmBackendConfig {
+    /// The partition to submit jobs to.
+    ///
+    /// Defaults to the cluster's default partition.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partition: Option<String>,
+
+    /// Additional arguments to pass to `sbatch` when submitting jobs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+
+    /// The polling interval, in seconds, for checking job status.
+    ///
+    /// Defaults to 30 seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+
+    /// The maximum task concurrency for the backend.
+    ///
+    /// Defaults to unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u64>,
+
+    /// The maximum number of CPUs of any one node in the cluster.
+    ///
+    /// Defaults to unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpu: Option<u64>,
+
+    /// The maximum amount of memory of any one node in the cluster as a unit
+    /// string (e.g. `2 GiB`).
+    ///
+    /// Defaults to unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory: Option<String>,
+}
+
+impl SlurmBackendConfig {
+    /// Validates the Slurm task execution backend configuration.
+    pub fn validate(&self) -> Result<()> {
+        if self.interval == Some(0) {
+            bail!("Slurm backend configuration value `interval` cannot be zero");
+        }
+
+        if self.max_concurrency == Some(0) {
+            bail!("Slurm backend configuration value `max_concurrency` cannot be zero");
+        }
+
+        if self.max_cpu == Some(0) {
+            bail!("Slurm backend configuration value `max_cpu` cannot be zero");
+        }
+
+        if let Some(memory) = &self.max_memory {
+            let memory = convert_unit_string(memory).with_context(|| {
+                format!(
+                    "Slurm backend configuration value `max_memory` has invalid value `{memory}`"
+                )
+            })?;
+
+            if memory == 0 {
+                bail!("Slurm backend configuration value `max_memory` cannot be zero");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Represents configuration for the generic HPC scheduler task execution
+/// backend.
+///
+/// The backend submits tasks to a scheduler such as LSF, PBS/Torque, or SGE
+/// with configured command templates. Each command is a program followed by
+/// its arguments; variables in the form `{name}` are expanded in each argument
+/// and `{{` and `}}` expand to literal braces.
+///
+/// The following variables are available to the `submit`, `cpu_args`,
+/// `memory_args`, `disk_args`, and `args` templates:
+///
+/// * `name` - the unique name of the task's job.
+/// * `attempt_dir` - the path to the task's attempt directory.
+/// * `work_dir` - the path to the task's working directory.
+/// * `cpu` - the number of CPUs requested, rounded up.
+/// * `memory` - the memory requested, in bytes.
+/// * `memory_mb` - the memory requested, in mebibytes (rounded up).
+/// * `memory_gb` - the memory requested, in gibibytes (rounded up).
+/// * `disk_gb` - the total disk space requested, in gibibytes.
+///
+/// The `status` and `cancel` templates may use the `name` and `job_id`
+/// variables.
+///
+/// For example, LSF may be configured with:
+///
+/// ```toml
+/// [backends.default]
+/// type = "scheduler"
+/// scheduler = "LSF"
+/// submit = ["bsub", "-J", "{name}", "-o", "{attempt_dir}/job.log"]
+/// job_id_pattern = "Job <(\\d+)>"
+/// status = ["bjobs", "-noheader", "-o", "stat", "{job_id}"]
+/// finished_states = ["DONE", "EXIT"]
+/// unknown_job_pattern = "Job <\\d+> is not found"
+/// cancel = ["bkill", "{job_id}"]
+/// cpu_args = ["-n", "{cpu}"]
+/// memory_args = ["-M", "{memory_mb}M"]
+/// ```
+///
+/// <div class="warning">
+/// Warning: the generic HPC scheduler task execution backend runs tasks on the
+/// cluster's nodes directly without the use of a container; only use this
+/// backend on trusted WDL. </div>
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct SchedulerBackendConfig {
+    /// The name of the scheduler used in log and error messages (e.g. `LSF`).
+    ///
+    /// Defaults to `scheduler`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheduler: Option<String>,
+
+    /// The command template for submitting a job.
+    ///
+    /// The path to the job script is appended as the final argument.
+    pub submit: Vec<String>,
+
+    /// The regular expression used to extract the job identifier from the
+    /// output of the submit command.
+    ///
+    /// If the expression has a capture group, the first group is the job
+    /// identifier; otherwise, the entire match is.
+    ///
+    /// Defaults to the first line of the output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id_pattern: Option<String>,
+
+    /// The command template for querying the state of a job.
+    ///
+    /// A job is finished when the command outputs nothing, outputs one of the
+    /// `finished_states`, or fails with output matching the
+    /// `unknown_job_pattern`.
+    ///
+    /// Any other failure of the command is retried at the next interval; the
+    /// task fails after three consecutive failures unless the job script has
+    /// written the command's exit code.
+    pub status: Vec<String>,
+
+    /// The job states output by the status command that indicate a job is
+    /// finished.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub finished_states: Vec<String>,
+
+    /// The regular expression matched against the stdout and stderr of a
+    /// failed status command that indicates the job is no longer known to the
+    /// scheduler and has therefore finished.
+    ///
+    /// Defaults to treating every failure of the status command as transient.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unknown_job_pattern: Option<String>,
+
+    /// The command template for cancelling a job.
+    pub cancel: Vec<String>,
+
+    /// The argument templates for requesting a task's CPUs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cpu_args: Vec<String>,
+
+    /// The argument templates for requesting a task's memory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub memory_args: Vec<String>,
+
+    /// The argument templates for requesting a task's disk space.
+    ///
+    /// These arguments are only passed if the task requests disks.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disk_args: Vec<String>,
+
+    /// Additional argument templates to pass to the submit command.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+
+    /// The polling interval, in seconds, for checking job status.
+    ///
+    /// Defaults to 30 seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+
+    /// The maximum task concurrency for the backend.
+    ///
+    /// Defaults to unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u64>,
+
+    /// The maximum number of CPUs of any one node in the cluster.
+    ///
+    /// Defaults to unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpu: Option<u64>,
+
+    /// The maximum amount of memory of any one node in the cluster as a unit
+    /// string (e.g. `2 GiB`).
+    ///
+    /// Defaults to unlimited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory: Option<String>,
+}
+
+impl SchedulerBackendConfig {
+    /// Gets the name of the scheduler used in log and error messages.
+    pub fn scheduler_name(&self) -> &str {
+        self.scheduler.as_deref().unwrap_or("scheduler")
+    }
+
+    /// Validates the generic HPC scheduler task execution backend
+    /// configuration.
+    pub fn validate(&self) -> Result<()> {
+        for (name, command) in [
+            ("submit", &self.submit),
+            ("status", &self.status),
+            ("cancel", &self.cancel),
+        ] {
+            if command.is_empty() {
+                bail!("scheduler backend configuration value `{name}` cannot be empty");
+            }
+        }
+
+        for (name, templates, variables) in [
+            ("submit", &self.submit, SUBMIT_TEMPLATE_VARIABLES),
+            ("cpu_args", &self.cpu_args, SUBMIT_TEMPLATE_VARIABLES),
+            ("memory_args", &self.memory_args, SUBMIT_TEMPLATE_VARIABLES),
+            ("disk_args", &self.disk_args, SUBMIT_TEMPLATE_VARIABLES),
+            ("args", &self.args, SUBMIT_TEMPLATE_VARIABLES),
+            ("status", &self.status, JOB_TEMPLATE_VARIABLES),
+            ("cancel", &self.cancel, JOB_TEMPLATE_VARIABLES),
+        ] {
+            for template in templates {
+                expand_template(template, |v| variables.contains(&v).then(String::new))
+                    .with_context(|| {
+                        format!(
+                            "scheduler backend configuration value `{name}` has invalid template \
+                             `{template}`"
+                        )
+                    })?;
+            }
+        }
+
+        for (name, pattern) in [
+            ("job_id_pattern", &self.job_id_pattern),
+            ("unknown_job_pattern", &self.unknown_job_pattern),
+        ] {
+            if let Some(pattern) = pattern {
+                Regex::new(pattern).with_context(|| {
+                    format!(
+                        "scheduler backend configuration value `{name}` has invalid pattern \
+                         `{pattern}`"
+                    )
+                })?;
+            }
+        }
+
+        if self.interval == Some(0) {
+            bail!("scheduler backend configuration value `interval` cannot be zero");
+        }
+
+        if self.max_concurrency == Some(0) {
+            bail!("scheduler backend configuration value `max_concurrency` cannot be zero");
+        }
+
+        if self.max_cpu == Some(0) {
+            bail!("scheduler backend configuration value `max_cpu` cannot be zero");
+        }
+
+        if let Some(memory) = &self.max_memory {
+            let memory = convert_unit_string(memory).with_context(|| {
+                format!(
+                    "scheduler backend configuration value `max_memory` has invalid value \
+                     `{memory}`"
+                )
+            })?;
+
+            if memory == 0 {
+                bail!("scheduler backend configuration value `max_memory` cannot be zero");
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -924,6 +1842,10 @@ impl TesBackendAuthConfig {
 }
 
 /// Represents configuration for the Task Execution Service (TES) backend.
+///
+/// The TES API cannot pass container registry credentials to the server, so
+/// there is no `registries` option for the backend; the TES server must be
+/// configured with the credentials needed to pull private images itself.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub struct TesBackendConfig {
@@ -1090,6 +2012,20 @@ mod test {
                         .into(),
                     ),
                 ),
+                (
+                    "third".to_string(),
+                    BackendConfig::Docker(DockerBackendConfig {
+                        registries: [(
+                            "ghcr.io".to_string(),
+                            RegistryAuthConfig::Basic(BasicAuthConfig {
+                                username: "foo".into(),
+                                password: "secret".into(),
+                            }),
+                        )]
+                        .into(),
+                        ..Default::default()
+                    }),
+                ),
             ]
             .into(),
             storage: StorageConfig {
@@ -1127,6 +2063,13 @@ mod test {
             "configuration value `task.retries` cannot exceed 100"
         );
 
+        let mut config = Config::default();
+        config.task.timeout = Some(0);
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "configuration value `task.timeout` cannot be zero"
+        );
+
         // Test invalid scatter concurrency config
         let mut config = Config::default();
         config.workflow.scatter.concurrency = Some(0);
@@ -1135,6 +2078,51 @@ mod test {
             "configuration value `workflow.scatter.concurrency` cannot be zero"
         );
 
+        // Test invalid memory retry multiplier config
+        let mut config = Config::default();
+        config.task.memory_retry_multiplier = Some(1.0);
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "configuration value `task.memory_retry_multiplier` must be greater than 1"
+        );
+        config.task.memory_retry_multiplier = Some(f64::NAN);
+        assert!(config.validate().is_err());
+        config.task.memory_retry_multiplier = Some(1.5);
+        config.validate().expect("config should validate");
+
+        // Test invalid task retry policy config
+        let mut config = Config::default();
+        config.task.retry_policy.command = Some(MAX_RETRIES + 1);
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "configuration value `task.retry_policy.command` cannot exceed 100"
+        );
+        config.task.retry_policy.command = Some(0);
+        config.task.retry_policy.system = MAX_RETRIES + 1;
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "configuration value `task.retry_policy.system` cannot exceed 100"
+        );
+
+        // Test invalid workflow outputs config
+        let mut config = Config::default();
+        config.workflow.outputs = Some(OutputsConfig {
+            dir: String::new(),
+            layout: OutputLayout::Flat,
+            strategy: None,
+        });
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "configuration value `workflow.outputs.dir` cannot be empty"
+        );
+        config.workflow.outputs.as_mut().unwrap().dir = "file:///tmp/outputs".into();
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "configuration value `workflow.outputs.dir` is not a supported cloud storage URL"
+        );
+        config.workflow.outputs.as_mut().unwrap().dir = "s3://bucket/outputs".into();
+        config.validate().expect("config should validate");
+
         // Test invalid backend name
         let config = Config {
             backend: Some("foo".into()),
@@ -1240,6 +2228,64 @@ mod test {
             "local backend configuration value `memory` cannot exceed the total memory of the host"
         ));
 
+        // Test invalid Docker backend GPU config
+        let config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Docker(DockerBackendConfig {
+                    gpus: Some(vec!["0".to_string(), " ".to_string()]),
+                    ..Default::default()
+                }),
+            )]
+            .into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "Docker backend configuration value `gpus` cannot contain an empty identifier"
+        );
+
+        // Test invalid Docker backend socket config
+        let config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Docker(DockerBackendConfig {
+                    socket: Some(String::new()),
+                    ..Default::default()
+                }),
+            )]
+            .into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "Docker backend configuration value `socket` cannot be empty"
+        );
+
+        // Test invalid Docker backend registry config
+        let config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Docker(DockerBackendConfig {
+                    registries: [(
+                        "ghcr.io/foo".to_string(),
+                        RegistryAuthConfig::Bearer(BearerAuthConfig {
+                            token: "secret".into(),
+                        }),
+                    )]
+                    .into(),
+                    ..Default::default()
+                }),
+            )]
+            .into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "Docker backend configuration value `registries` contains an invalid registry host \
+             `ghcr.io/foo`"
+        );
+
         // Test missing TES URL
         let config = Config {
             backends: [(
@@ -1314,5 +2360,82 @@ mod test {
         let mut config = Config::default();
         config.http.parallelism = None;
         assert!(config.validate().is_ok(), "should pass for default (None)");
+
+        // Test invalid scheduler backend config
+        let scheduler = SchedulerBackendConfig {
+            submit: vec!["bsub".into(), "-J".into(), "{name}".into()],
+            status: vec!["bjobs".into(), "{job_id}".into()],
+            cancel: vec!["bkill".into(), "{job_id}".into()],
+            cpu_args: vec!["-n".into(), "{cpu}".into()],
+            ..Default::default()
+        };
+        scheduler.validate().expect("config should validate");
+        assert_eq!(
+            SchedulerBackendConfig {
+                cancel: Default::default(),
+                ..scheduler.clone()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string(),
+            "scheduler backend configuration value `cancel` cannot be empty"
+        );
+        assert_eq!(
+            SchedulerBackendConfig {
+                status: vec!["bjobs".into(), "{cpu}".into()],
+                ..scheduler.clone()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string(),
+            "scheduler backend configuration value `status` has invalid template `{cpu}`"
+        );
+        assert_eq!(
+            SchedulerBackendConfig {
+                job_id_pattern: Some("Job <(\\d+>".into()),
+                ..scheduler.clone()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string(),
+            "scheduler backend configuration value `job_id_pattern` has invalid pattern `Job \
+             <(\\d+>`"
+        );
+        assert_eq!(
+            SchedulerBackendConfig {
+                unknown_job_pattern: Some("(".into()),
+                ..scheduler.clone()
+            }
+            .validate()
+            .unwrap_err()
+            .to_string(),
+            "scheduler backend configuration value `unknown_job_pattern` has invalid pattern `(`"
+        );
+        assert_eq!(
+            SchedulerBackendConfig {
+                interval: Some(0),
+                ..scheduler
+            }
+            .validate()
+            .unwrap_err()
+            .to_string(),
+            "scheduler backend configuration value `interval` cannot be zero"
+        );
+
+        // Test invalid call cache directory
+        let mut config = Config::default();
+        config.call_cache.dir = Some(PathBuf::new());
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "configuration value `call_cache.dir` cannot be empty"
+        );
+
+        // Test invalid history path
+        let mut config = Config::default();
+        config.history.path = Some(PathBuf::new());
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "configuration value `history.path` cannot be empty"
+        );
     }
 }
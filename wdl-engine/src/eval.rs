@@ -8,14 +8,18 @@ use std::io::BufRead;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use anyhow::bail;
+use chrono::DateTime;
+use chrono::Utc;
 use cloud_copy::TransferEvent;
 use crankshaft::events::Event as CrankshaftEvent;
 use indexmap::IndexMap;
 use itertools::Itertools;
 use rev_buf_reader::RevBufReader;
+use serde::Serialize;
 use tokio::sync::broadcast;
 use wdl_analysis::Document;
 use wdl_analysis::document::Task;
@@ -29,6 +33,7 @@ use wdl_ast::v1::TASK_REQUIREMENT_RETURN_CODES_ALIAS;
 use crate::CompoundValue;
 use crate::Outputs;
 use crate::PrimitiveValue;
+use crate::ResourceUsage;
 use crate::TaskExecutionResult;
 use crate::Value;
 use crate::http::Location;
@@ -58,6 +63,10 @@ pub struct Events {
     ///
     /// This is `None` when transfer events are not enabled.
     transfer: Option<broadcast::Sender<TransferEvent>>,
+    /// The engine events channel.
+    ///
+    /// This is `None` when engine events are not enabled.
+    engine: Option<broadcast::Sender<EngineEvent>>,
 }
 
 impl Events {
@@ -66,6 +75,7 @@ impl Events {
         Self {
             crankshaft: Some(broadcast::Sender::new(capacity)),
             transfer: Some(broadcast::Sender::new(capacity)),
+            engine: Some(broadcast::Sender::new(capacity)),
         }
     }
 
@@ -80,6 +90,7 @@ impl Events {
         Self {
             crankshaft: Some(broadcast::Sender::new(capacity)),
             transfer: None,
+            engine: None,
         }
     }
 
@@ -89,6 +100,17 @@ impl Events {
         Self {
             crankshaft: None,
             transfer: Some(broadcast::Sender::new(capacity)),
+            engine: None,
+        }
+    }
+
+    /// Constructs a new `Events` and enable subscribing to only the engine
+    /// events channel.
+    pub fn engine_only(capacity: usize) -> Self {
+        Self {
+            crankshaft: None,
+            transfer: None,
+            engine: Some(broadcast::Sender::new(capacity)),
         }
     }
 
@@ -106,6 +128,13 @@ impl Events {
         self.transfer.as_ref().map(|s| s.subscribe())
     }
 
+    /// Subscribes to the engine events channel.
+    ///
+    /// Returns `None` if engine events are not enabled.
+    pub fn subscribe_engine(&self) -> Option<broadcast::Receiver<EngineEvent>> {
+        self.engine.as_ref().map(|s| s.subscribe())
+    }
+
     /// Gets the sender for the Crankshaft events.
     pub(crate) fn crankshaft(&self) -> &Option<broadcast::Sender<CrankshaftEvent>> {
         &self.crankshaft
@@ -115,6 +144,162 @@ impl Events {
     pub(crate) fn transfer(&self) -> &Option<broadcast::Sender<TransferEvent>> {
         &self.transfer
     }
+
+    /// Gets the sender for the engine events.
+    pub(crate) fn engine(&self) -> &Option<broadcast::Sender<EngineEvent>> {
+        &self.engine
+    }
+}
+
+/// Represents the source of a reused task execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReuseSource {
+    /// The execution was resumed from a previous evaluation in the same output
+    /// directory.
+    Resumed,
+    /// The execution was retrieved from the call cache.
+    Cached,
+}
+
+/// Represents the kind of an engine event.
+#[derive(Debug, Clone)]
+pub enum EngineEventKind {
+    /// A workflow has started evaluation.
+    WorkflowStarted {
+        /// The root directory of the workflow's evaluation.
+        root_dir: PathBuf,
+    },
+    /// A workflow has completed evaluation successfully.
+    WorkflowCompleted {
+        /// The time taken to evaluate the workflow.
+        elapsed: Duration,
+    },
+    /// A workflow has failed evaluation.
+    WorkflowFailed {
+        /// The time taken until the workflow failed.
+        elapsed: Duration,
+    },
+    /// A call has been scheduled for evaluation.
+    ///
+    /// This is sent once the call's inputs have been evaluated.
+    CallScheduled {
+        /// The root directory of the call's evaluation.
+        root_dir: PathBuf,
+    },
+    /// A call has completed evaluation successfully.
+    CallCompleted {
+        /// The time taken to evaluate the call.
+        elapsed: Duration,
+    },
+    /// A call has failed evaluation.
+    CallFailed {
+        /// The time taken until the call failed.
+        elapsed: Duration,
+    },
+    /// An attempt to execute a task has started.
+    TaskStarted {
+        /// The attempt number, starting at zero.
+        attempt: u64,
+        /// The attempt directory.
+        attempt_dir: PathBuf,
+    },
+    /// A task reused the result of a previous execution instead of executing.
+    TaskReused {
+        /// The attempt directory of the previous execution.
+        attempt_dir: PathBuf,
+        /// The exit code of the previous execution.
+        exit_code: i32,
+        /// The source of the previous execution.
+        source: ReuseSource,
+    },
+    /// An attempt to execute a task failed and the task will be retried.
+    TaskRetried {
+        /// The attempt number of the failed attempt.
+        attempt: u64,
+        /// The attempt directory of the failed attempt.
+        attempt_dir: PathBuf,
+        /// The exit code of the failed attempt.
+        ///
+        /// This is `None` if the attempt did not exit (e.g. it timed out).
+        exit_code: Option<i32>,
+        /// The resources used by the failed attempt.
+        ///
+        /// This is `None` if the attempt did not exit or the backend did not
+        /// report its resource usage.
+        usage: Option<ResourceUsage>,
+    },
+    /// An attempt to execute a task exceeded its timeout and was cancelled.
+    TaskTimedOut {
+        /// The attempt number.
+        attempt: u64,
+        /// The attempt directory.
+        attempt_dir: PathBuf,
+        /// The timeout that was exceeded.
+        timeout: Duration,
+    },
+    /// An attempt to execute a task completed successfully.
+    TaskCompleted {
+        /// The attempt number.
+        attempt: u64,
+        /// The attempt directory.
+        attempt_dir: PathBuf,
+        /// The exit code of the task.
+        exit_code: i32,
+        /// The resources used by the attempt.
+        ///
+        /// This is `None` if the backend did not report the attempt's resource
+        /// usage.
+        usage: Option<ResourceUsage>,
+        /// The time taken to execute the attempt.
+        elapsed: Duration,
+    },
+    /// The last attempt to execute a task failed.
+    TaskFailed {
+        /// The attempt number.
+        attempt: u64,
+        /// The attempt directory.
+        attempt_dir: PathBuf,
+        /// The exit code of the task.
+        ///
+        /// This is `None` if the task failed to execute.
+        exit_code: Option<i32>,
+        /// The resources used by the attempt.
+        ///
+        /// This is `None` if the task failed to execute or the backend did not
+        /// report its resource usage.
+        usage: Option<ResourceUsage>,
+        /// The time taken to execute the attempt.
+        elapsed: Duration,
+    },
+}
+
+/// Represents a lifecycle event of a workflow, call, or task sent by the
+/// engine during evaluation.
+#[derive(Debug, Clone)]
+pub struct EngineEvent {
+    /// The identifier of the workflow, call, or task.
+    pub id: String,
+    /// The scatter index of the call.
+    ///
+    /// This is `None` if the call is not within a scatter statement.
+    pub scatter_index: Option<String>,
+    /// The time the event occurred.
+    pub time: DateTime<Utc>,
+    /// The kind of the event.
+    pub kind: EngineEventKind,
+}
+
+impl EngineEvent {
+    /// Constructs a new engine event occurring now.
+    pub(crate) fn new(id: &str, scatter_index: Option<&str>, kind: EngineEventKind) -> Self {
+        Self {
+            id: id.to_string(),
+            scatter_index: scatter_index.map(ToString::to_string),
+            time: Utc::now(),
+            kind,
+        }
+    }
 }
 
 /// Represents the location of a call in an evaluation error.
@@ -140,6 +325,38 @@ pub struct SourceError {
     ///
     /// The call locations are stored as most recent to least recent.
     pub backtrace: Vec<CallLocation>,
+    /// The kind of task execution failure that caused the error.
+    ///
+    /// This is `None` if the error was not caused by a failed task execution.
+    pub failure: Option<FailureKind>,
+}
+
+/// The kind of a task execution failure.
+///
+/// Retries of failed task attempts are budgeted separately for each kind of
+/// failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The task's command failed.
+    ///
+    /// The command either exited with a status code not permitted by the
+    /// task's `return_codes` requirement or exceeded its timeout.
+    Command,
+    /// The execution backend failed to execute the task.
+    ///
+    /// For example, the task's container could not be pulled, the task was
+    /// preempted, or a TES server reported a system error.
+    System,
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Command => write!(f, "command"),
+            Self::System => write!(f, "system"),
+        }
+    }
 }
 
 /// Represents an error that may occur when evaluating a workflow or task.
@@ -158,9 +375,32 @@ impl EvaluationError {
             document,
             diagnostic,
             backtrace: Default::default(),
+            failure: None,
         }))
     }
 
+    /// Sets the kind of task execution failure that caused the error.
+    ///
+    /// This has no effect on errors that did not come from WDL source
+    /// evaluation.
+    pub(crate) fn with_failure(mut self, kind: FailureKind) -> Self {
+        if let Self::Source(e) = &mut self {
+            e.failure = Some(kind);
+        }
+
+        self
+    }
+
+    /// Gets the kind of task execution failure that caused the error.
+    ///
+    /// Returns `None` if the error was not caused by a failed task execution.
+    pub fn failure(&self) -> Option<FailureKind> {
+        match self {
+            Self::Source(e) => e.failure,
+            Self::Other(_) => None,
+        }
+    }
+
     /// Helper for tests for converting an evaluation error to a string.
     #[cfg(feature = "codespan-reporting")]
     #[allow(clippy::inherent_to_string)]
@@ -729,7 +969,11 @@ pub struct Input {
 
 impl Input {
     /// Creates a new input with the given path and access.
-    fn new(kind: InputKind, path: EvaluationPath, guest_path: Option<GuestPath>) -> Self {
+    pub(crate) fn new(
+        kind: InputKind,
+        path: EvaluationPath,
+        guest_path: Option<GuestPath>,
+    ) -> Self {
         Self {
             kind,
             path,
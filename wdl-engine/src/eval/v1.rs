@@ -1,6 +1,7 @@
 //! Implementation of evaluation for V1 documents.
 
 mod expr;
+mod plan;
 mod task;
 mod workflow;
 
@@ -11,6 +12,7 @@ use std::path::Path;
 use anyhow::Context;
 use anyhow::Result;
 pub use expr::*;
+pub use plan::*;
 use serde::Serialize;
 pub use task::*;
 pub use workflow::*;
@@ -23,8 +25,12 @@ const INPUTS_FILE: &str = "inputs.json";
 /// outputs directory.
 const OUTPUTS_FILE: &str = "outputs.json";
 
+/// The name of the completion record file to write for each task in the
+/// outputs directory.
+pub(crate) const COMPLETION_FILE: &str = "completion.json";
+
 /// Serializes a value into a JSON file.
-fn write_json_file(path: impl AsRef<Path>, value: &impl Serialize) -> Result<()> {
+pub(crate) fn write_json_file(path: impl AsRef<Path>, value: &impl Serialize) -> Result<()> {
     let path = path.as_ref();
     let file = File::create(path)
         .with_context(|| format!("failed to create file `{path}`", path = path.display()))?;
@@ -769,7 +769,7 @@ impl<C: EvaluationContext> ExprEvaluator<C> {
             members.insert(name.text().to_string(), value);
         }
 
-        Ok(Object::new(members).into())
+        Ok(Value::Hints(Object::new(members).into()))
     }
 
     /// Evaluates a hints item, whether in task `hints` section or a `hints`
@@ -815,7 +815,7 @@ impl<C: EvaluationContext> ExprEvaluator<C> {
             members.insert(name, value);
         }
 
-        Ok(Object::new(members).into())
+        Ok(Value::Input(Object::new(members).into()))
     }
 
     /// Evaluates a literal output expression.
@@ -831,7 +831,7 @@ impl<C: EvaluationContext> ExprEvaluator<C> {
             members.insert(name, value);
         }
 
-        Ok(Object::new(members).into())
+        Ok(Value::Output(Object::new(members).into()))
     }
 
     /// Evaluates a literal input/output item.
@@ -0,0 +1,934 @@
+//! Implementation of execution plans for V1 workflows.
+//!
+//! An execution plan describes the calls a workflow would make without
+//! executing any of them.
+//!
+//! Expressions are evaluated from the workflow's inputs where possible; values
+//! that are only known once calls execute (e.g. call outputs) are left
+//! unresolved in the plan.
+//!
+//! # Differences from evaluation
+//!
+//! The planner walks the same workflow and task evaluation graphs as
+//! [`WorkflowEvaluator`](crate::v1::WorkflowEvaluator), but as it does not
+//! execute calls or expand scatter statements into shards, a plan differs from
+//! an evaluation in the following ways:
+//!
+//! * The body of a scatter statement is planned once rather than once per
+//!   shard, so call identifiers do not have a scatter index and the scatter
+//!   variable and anything depending on it are unresolved.
+//! * Declarations within a scatter or conditional statement are not gathered
+//!   into arrays or optional values for use outside of the statement, so
+//!   expressions outside of the statement that reference them are unresolved.
+//! * Call outputs are never known.
+//! * Calls within a conditional statement whose condition is `false` are still
+//!   planned, but with zero executions.
+//! * An expression that fails to evaluate (e.g. an out of bounds array index)
+//!   is unresolved rather than failing the plan.
+//! * Task requirements and hints are evaluated once, without retries, memory
+//!   escalation, or container pinning; the container of a planned call is the
+//!   one the backend determines from the requirements and configuration.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::bail;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use indexmap::IndexMap;
+use petgraph::Direction;
+use petgraph::algo::toposort;
+use serde::Serialize;
+use wdl_analysis::Document;
+use wdl_analysis::diagnostics::unknown_name;
+use wdl_analysis::document::Task;
+use wdl_analysis::eval::v1::TaskGraphBuilder;
+use wdl_analysis::eval::v1::TaskGraphNode;
+use wdl_analysis::eval::v1::WorkflowGraphBuilder;
+use wdl_analysis::eval::v1::WorkflowGraphNode;
+use wdl_analysis::types::Optional;
+use wdl_analysis::types::Type;
+use wdl_analysis::types::v1::task_hint_types;
+use wdl_analysis::types::v1::task_requirement_types;
+use wdl_ast::Ast;
+use wdl_ast::AstNode;
+use wdl_ast::AstToken;
+use wdl_ast::Diagnostic;
+use wdl_ast::Span;
+use wdl_ast::SupportedVersion;
+use wdl_ast::SyntaxKind;
+use wdl_ast::TreeNode;
+use wdl_ast::v1::Decl;
+use wdl_ast::v1::Expr;
+
+use crate::Coercible;
+use crate::EvaluationContext;
+use crate::Inputs;
+use crate::TaskExecutionBackend;
+use crate::TaskInputs;
+use crate::Value;
+use crate::ValueSerializer;
+use crate::WorkflowInputs;
+use crate::http::Transferer;
+use crate::path::EvaluationPath;
+use crate::tree::SyntaxNode;
+use crate::v1::ExprEvaluator;
+
+/// Represents the kind of a planned call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannedCallKind {
+    /// The call is to a task.
+    Task,
+    /// The call is to a workflow.
+    Workflow,
+}
+
+/// Represents a scatter statement enclosing a planned call.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PlannedScatter {
+    /// The name of the scatter variable.
+    pub variable: String,
+    /// The number of shards of the scatter.
+    ///
+    /// This is `None` if the scatter's array could not be determined from the
+    /// inputs.
+    pub shards: Option<usize>,
+}
+
+/// Represents a conditional statement enclosing a planned call.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PlannedConditional {
+    /// The source of the conditional's expression.
+    pub expr: String,
+    /// The value of the conditional's expression.
+    ///
+    /// This is `None` if the value could not be determined from the inputs.
+    pub value: Option<bool>,
+}
+
+/// Represents a call in an execution plan.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PlannedCall {
+    /// The identifier of the call.
+    ///
+    /// Calls made by a called workflow are identified by the path of call
+    /// names from the planned workflow (e.g. `sub.task`).
+    pub id: String,
+    /// The namespace of the call's target.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// The name of the called task or workflow.
+    pub target: String,
+    /// The kind of the call.
+    pub kind: PlannedCallKind,
+    /// The scatter statements enclosing the call, from outermost to innermost.
+    pub scatters: Vec<PlannedScatter>,
+    /// The conditional statements enclosing the call, from outermost to
+    /// innermost.
+    pub conditionals: Vec<PlannedConditional>,
+    /// The number of times the call will execute.
+    ///
+    /// This is `None` if the number could not be determined from the inputs.
+    pub executions: Option<usize>,
+    /// The container the task will run in, as resolved by the execution
+    /// backend.
+    ///
+    /// This is `None` for workflow calls, for tasks with unresolved
+    /// requirements or hints, or if the backend runs tasks on the host.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    /// The number of CPUs allocated to the task by the execution backend.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<f64>,
+    /// The memory allocated to the task by the execution backend, in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<i64>,
+    /// The evaluated requirements of the task.
+    pub requirements: BTreeMap<String, serde_json::Value>,
+    /// The evaluated hints of the task.
+    pub hints: BTreeMap<String, serde_json::Value>,
+    /// The names of the requirements and hints of the task that could not be
+    /// determined from the inputs.
+    pub unresolved: Vec<String>,
+}
+
+impl PlannedCall {
+    /// Constructs a new planned call with nothing yet resolved.
+    fn new(
+        id: String,
+        namespace: Option<&str>,
+        target: &str,
+        kind: PlannedCallKind,
+        scatters: Vec<PlannedScatter>,
+        conditionals: Vec<PlannedConditional>,
+    ) -> Self {
+        let executions = executions(&scatters, &conditionals);
+        Self {
+            id,
+            namespace: namespace.map(Into::into),
+            target: target.to_string(),
+            kind,
+            scatters,
+            conditionals,
+            executions,
+            container: None,
+            cpu: None,
+            memory: None,
+            requirements: Default::default(),
+            hints: Default::default(),
+            unresolved: Default::default(),
+        }
+    }
+}
+
+/// Represents a dependency between two calls in an execution plan.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PlannedDependency {
+    /// The identifier of the call that is depended upon.
+    pub from: String,
+    /// The identifier of the dependent call.
+    pub to: String,
+}
+
+/// Represents the execution plan of a workflow.
+///
+/// A plan is serialized to JSON with `serde` or to a Graphviz DOT graph with
+/// [`Plan::to_dot`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Plan {
+    /// The name of the planned workflow.
+    pub workflow: String,
+    /// The calls of the workflow, including the calls of called workflows.
+    pub calls: Vec<PlannedCall>,
+    /// The dependencies between the calls.
+    pub dependencies: Vec<PlannedDependency>,
+}
+
+impl Plan {
+    /// Formats the plan as a Graphviz DOT graph.
+    pub fn to_dot(&self) -> String {
+        /// Escapes a string for use in a quoted DOT string.
+        fn escape(s: &str) -> String {
+            s.replace('\\', r"\\").replace('"', "\\\"")
+        }
+
+        /// Quotes a string for use as a DOT identifier.
+        fn quote(s: &str) -> String {
+            format!("\"{s}\"", s = escape(s))
+        }
+
+        let mut dot = String::new();
+        writeln!(&mut dot, "digraph {name} {{", name = quote(&self.workflow)).unwrap();
+        for call in &self.calls {
+            let mut label = vec![call.id.clone()];
+            if let Some(container) = &call.container {
+                label.push(container.clone());
+            }
+
+            if !call.scatters.is_empty() || !call.conditionals.is_empty() {
+                label.push(match call.executions {
+                    Some(n) => format!("executions: {n}"),
+                    None => "executions: unknown".to_string(),
+                });
+            }
+
+            writeln!(
+                &mut dot,
+                "  {id} [label=\"{label}\", shape={shape}{style}];",
+                id = quote(&call.id),
+                label = label
+                    .iter()
+                    .map(|l| escape(l))
+                    .collect::<Vec<_>>()
+                    .join(r"\n"),
+                shape = match call.kind {
+                    PlannedCallKind::Task => "box",
+                    PlannedCallKind::Workflow => "component",
+                },
+                style = if call.executions == Some(0) {
+                    ", style=dashed"
+                } else {
+                    ""
+                },
+            )
+            .unwrap();
+        }
+
+        for dependency in &self.dependencies {
+            writeln!(
+                &mut dot,
+                "  {from} -> {to};",
+                from = quote(&dependency.from),
+                to = quote(&dependency.to)
+            )
+            .unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Computes the number of times a call executes given its enclosing scatter
+/// and conditional statements.
+///
+/// Returns `None` if the number cannot be determined.
+fn executions(scatters: &[PlannedScatter], conditionals: &[PlannedConditional]) -> Option<usize> {
+    if conditionals.iter().any(|c| c.value == Some(false))
+        || scatters.iter().any(|s| s.shards == Some(0))
+    {
+        return Some(0);
+    }
+
+    if conditionals.iter().any(|c| c.value.is_none()) {
+        return None;
+    }
+
+    scatters
+        .iter()
+        .try_fold(1usize, |n, s| Some(n.saturating_mul(s.shards?)))
+}
+
+/// Gets the scatter and conditional statements enclosing the given node.
+///
+/// The statements are identified by their starting offsets and are ordered
+/// from outermost to innermost.
+fn enclosing_statements(node: &SyntaxNode) -> Vec<usize> {
+    let mut statements: Vec<_> = node
+        .ancestors()
+        .skip(1)
+        .filter(|n| {
+            matches!(
+                n.kind(),
+                SyntaxKind::ScatterStatementNode | SyntaxKind::ConditionalStatementNode
+            )
+        })
+        .map(|n| n.span().start())
+        .collect();
+    statements.reverse();
+    statements
+}
+
+/// Gets the base directory for evaluating expressions in the given document.
+fn base_dir(document: &Document) -> Result<EvaluationPath> {
+    let path = document.path();
+    let mut base_dir = EvaluationPath::parent_of(&path)
+        .with_context(|| format!("document `{path}` does not have a parent directory"))?;
+    base_dir.make_absolute();
+    Ok(base_dir)
+}
+
+/// Serializes evaluated requirements or hints.
+fn serialize(values: &HashMap<String, Value>) -> Result<BTreeMap<String, serde_json::Value>> {
+    values
+        .iter()
+        .map(|(k, v)| {
+            Ok((
+                k.clone(),
+                serde_json::to_value(ValueSerializer::new(v, true))?,
+            ))
+        })
+        .collect::<Result<_>>()
+        .context("failed to serialize task requirements and hints")
+}
+
+/// The values known during planning.
+///
+/// Each value is paired with the scatter and conditional statements enclosing
+/// its declaration; a value is only visible to expressions enclosed by the same
+/// statements as outside of them the value is gathered.
+type Values = HashMap<String, (Vec<usize>, Value)>;
+
+/// Represents an evaluated scatter or conditional statement.
+enum Statement {
+    /// The statement is a scatter statement.
+    Scatter(PlannedScatter),
+    /// The statement is a conditional statement.
+    Conditional(PlannedConditional),
+}
+
+/// Used to evaluate expressions when planning.
+///
+/// Names that are not known during planning fail to resolve.
+#[derive(Clone, Copy)]
+struct PlanEvaluationContext<'a> {
+    /// The document being evaluated.
+    document: &'a Document,
+    /// The base directory for evaluation.
+    base_dir: &'a EvaluationPath,
+    /// The temp directory for evaluation.
+    temp_dir: &'a Path,
+    /// The transferer for evaluation.
+    transferer: &'a dyn Transferer,
+    /// The known values.
+    values: &'a Values,
+    /// The statements enclosing the expression being evaluated.
+    scope: &'a [usize],
+    /// The task associated with the evaluation.
+    ///
+    /// This is only `Some` when evaluating a task's hints section.
+    task: Option<&'a Task>,
+}
+
+impl<'a> PlanEvaluationContext<'a> {
+    /// Sets the associated task for evaluation.
+    fn with_task(mut self, task: &'a Task) -> Self {
+        self.task = Some(task);
+        self
+    }
+}
+
+impl EvaluationContext for PlanEvaluationContext<'_> {
+    fn version(&self) -> SupportedVersion {
+        self.document
+            .version()
+            .expect("document should have a version")
+    }
+
+    fn resolve_name(&self, name: &str, span: Span) -> Result<Value, Diagnostic> {
+        match self.values.get(name) {
+            Some((scope, value)) if self.scope.starts_with(scope) => Ok(value.clone()),
+            _ => Err(unknown_name(name, span)),
+        }
+    }
+
+    fn resolve_type_name(&self, name: &str, span: Span) -> Result<Type, Diagnostic> {
+        crate::resolve_type_name(self.document, name, span)
+    }
+
+    fn base_dir(&self) -> &EvaluationPath {
+        self.base_dir
+    }
+
+    fn temp_dir(&self) -> &Path {
+        self.temp_dir
+    }
+
+    fn task(&self) -> Option<&Task> {
+        self.task
+    }
+
+    fn transferer(&self) -> &dyn Transferer {
+        self.transferer
+    }
+}
+
+/// Plans the execution of a workflow.
+pub(crate) struct Planner<'a> {
+    /// The task execution backend used to resolve task constraints.
+    backend: &'a dyn TaskExecutionBackend,
+    /// The transferer for expression evaluation.
+    transferer: &'a dyn Transferer,
+    /// The temp directory for expression evaluation.
+    temp_dir: &'a Path,
+    /// The planned calls.
+    calls: Vec<PlannedCall>,
+    /// The planned dependencies between calls.
+    dependencies: Vec<PlannedDependency>,
+}
+
+impl<'a> Planner<'a> {
+    /// Constructs a new planner.
+    pub fn new(
+        backend: &'a dyn TaskExecutionBackend,
+        transferer: &'a dyn Transferer,
+        temp_dir: &'a Path,
+    ) -> Self {
+        Self {
+            backend,
+            transferer,
+            temp_dir,
+            calls: Default::default(),
+            dependencies: Default::default(),
+        }
+    }
+
+    /// Plans the execution of the workflow of the given document.
+    ///
+    /// The inputs are expected to have been validated.
+    pub async fn plan(mut self, document: &Document, inputs: &WorkflowInputs) -> Result<Plan> {
+        let workflow = document
+            .workflow()
+            .context("document does not contain a workflow")?;
+
+        self.plan_workflow(
+            document,
+            None,
+            inputs
+                .iter()
+                .map(|(name, value)| (name.to_string(), Some(value.clone())))
+                .collect(),
+            inputs.calls(),
+            &[],
+            &[],
+        )
+        .await?;
+
+        Ok(Plan {
+            workflow: workflow.name().to_string(),
+            calls: self.calls,
+            dependencies: self.dependencies,
+        })
+    }
+
+    /// Creates an evaluation context for the given document and scope.
+    fn context<'b>(
+        &'b self,
+        document: &'b Document,
+        base_dir: &'b EvaluationPath,
+        values: &'b Values,
+        scope: &'b [usize],
+    ) -> PlanEvaluationContext<'b> {
+        PlanEvaluationContext {
+            document,
+            base_dir,
+            temp_dir: self.temp_dir,
+            transferer: self.transferer,
+            values,
+            scope,
+            task: None,
+        }
+    }
+
+    /// Evaluates an expression.
+    ///
+    /// Returns `None` if the expression's value cannot be determined.
+    async fn evaluate(
+        context: PlanEvaluationContext<'_>,
+        expr: &Expr<SyntaxNode>,
+    ) -> Option<Value> {
+        ExprEvaluator::new(context).evaluate_expr(expr).await.ok()
+    }
+
+    /// Evaluates a declaration, coercing its value to the declared type.
+    ///
+    /// The provided value is used in place of the declaration's expression;
+    /// a provided value of `None` indicates the value is not yet known.
+    ///
+    /// Returns `None` if the declaration's value cannot be determined.
+    async fn evaluate_decl(
+        context: PlanEvaluationContext<'_>,
+        decl: &Decl<SyntaxNode>,
+        provided: Option<Option<&Value>>,
+    ) -> Option<Value> {
+        let ty = crate::convert_ast_type_v1(context.document, &decl.ty()).ok()?;
+        let value = match provided {
+            Some(value) => value?.clone(),
+            None => match decl.expr() {
+                Some(expr) => Self::evaluate(context, &expr).await?,
+                None if ty.is_optional() => Value::new_none(ty.clone()),
+                None => return None,
+            },
+        };
+
+        value.coerce(None, &ty).ok()
+    }
+
+    /// Plans the execution of the workflow of the given document.
+    ///
+    /// Note that this method is not `async` because it is recursive.
+    fn plan_workflow<'b>(
+        &'b mut self,
+        document: &'b Document,
+        prefix: Option<&'b str>,
+        inputs: HashMap<String, Option<Value>>,
+        calls: &'b HashMap<String, Inputs>,
+        scatters: &'b [PlannedScatter],
+        conditionals: &'b [PlannedConditional],
+    ) -> BoxFuture<'b, Result<()>> {
+        async move {
+            let ast = match document.root().morph().ast() {
+                Ast::V1(ast) => ast,
+                _ => bail!("workflow planning is only supported for WDL 1.x documents"),
+            };
+
+            let definition = ast
+                .workflows()
+                .next()
+                .expect("workflow should exist in the AST");
+
+            let mut diagnostics = Vec::new();
+            let graph =
+                WorkflowGraphBuilder::default().build(&definition, &mut diagnostics, |name| {
+                    inputs.contains_key(name)
+                });
+            assert!(
+                diagnostics.is_empty(),
+                "workflow evaluation graph should have no diagnostics"
+            );
+
+            let base_dir = base_dir(document)?;
+            let mut values = Values::new();
+            let mut statements = HashMap::new();
+            let mut ids = IndexMap::new();
+            for node in toposort(&graph, None).expect("graph should be acyclic") {
+                match &graph[node] {
+                    WorkflowGraphNode::Input(decl) => {
+                        let context = self.context(document, &base_dir, &values, &[]);
+                        let name = decl.name();
+                        let provided = inputs.get(name.text()).map(Option::as_ref);
+                        if let Some(value) = Self::evaluate_decl(context, decl, provided).await {
+                            values.insert(name.text().to_string(), (Vec::new(), value));
+                        }
+                    }
+                    WorkflowGraphNode::Decl(decl) => {
+                        let scope = enclosing_statements(decl.inner());
+                        let context = self.context(document, &base_dir, &values, &scope);
+                        if let Some(value) = Self::evaluate_decl(context, decl, None).await {
+                            values.insert(decl.name().text().to_string(), (scope, value));
+                        }
+                    }
+                    WorkflowGraphNode::Conditional(stmt, _) => {
+                        let scope = enclosing_statements(stmt.inner());
+                        let context = self.context(document, &base_dir, &values, &scope);
+                        let expr = stmt.expr();
+                        let value = Self::evaluate(context, &expr)
+                            .await
+                            .and_then(|v| v.as_boolean());
+                        statements.insert(
+                            stmt.span().start(),
+                            Statement::Conditional(PlannedConditional {
+                                expr: expr.text().to_string(),
+                                value,
+                            }),
+                        );
+                    }
+                    WorkflowGraphNode::Scatter(stmt, _) => {
+                        let scope = enclosing_statements(stmt.inner());
+                        let context = self.context(document, &base_dir, &values, &scope);
+                        let shards = Self::evaluate(context, &stmt.expr())
+                            .await
+                            .and_then(|v| v.as_array().map(|a| a.len()));
+                        statements.insert(
+                            stmt.span().start(),
+                            Statement::Scatter(PlannedScatter {
+                                variable: stmt.variable().text().to_string(),
+                                shards,
+                            }),
+                        );
+                    }
+                    WorkflowGraphNode::Call(stmt) => {
+                        let scope = enclosing_statements(stmt.inner());
+                        let mut call_scatters = scatters.to_vec();
+                        let mut call_conditionals = conditionals.to_vec();
+                        for start in &scope {
+                            match &statements[start] {
+                                Statement::Scatter(s) => call_scatters.push(s.clone()),
+                                Statement::Conditional(c) => call_conditionals.push(c.clone()),
+                            }
+                        }
+
+                        // Resolve the namespace and target of the call
+                        let mut names: Vec<_> = stmt.target().names().collect();
+                        let target = names.pop().expect("expected at least one name");
+                        let namespace = names.pop();
+                        let callee = namespace
+                            .as_ref()
+                            .map(|ns| {
+                                document
+                                    .namespace(ns.text())
+                                    .expect("namespace should exist")
+                                    .document()
+                            })
+                            .unwrap_or(document);
+
+                        let alias = stmt
+                            .alias()
+                            .map(|a| a.name())
+                            .unwrap_or_else(|| target.clone());
+                        let id = match prefix {
+                            Some(prefix) => format!("{prefix}.{alias}", alias = alias.text()),
+                            None => alias.text().to_string(),
+                        };
+
+                        // Determine the call's inputs from the inputs to the workflow and the
+                        // call statement
+                        let specified = calls.get(alias.text());
+                        let mut provided: HashMap<_, _> = match specified {
+                            Some(Inputs::Task(inputs)) => inputs
+                                .iter()
+                                .map(|(name, value)| (name.to_string(), Some(value.clone())))
+                                .collect(),
+                            Some(Inputs::Workflow(inputs)) => inputs
+                                .iter()
+                                .map(|(name, value)| (name.to_string(), Some(value.clone())))
+                                .collect(),
+                            None => HashMap::new(),
+                        };
+
+                        let context = self.context(document, &base_dir, &values, &scope);
+                        for input in stmt.inputs() {
+                            let name = input.name();
+                            let value = match input.expr() {
+                                Some(expr) => Self::evaluate(context, &expr).await,
+                                None => context.resolve_name(name.text(), name.span()).ok(),
+                            };
+
+                            provided.insert(name.text().to_string(), value);
+                        }
+
+                        match callee.task_by_name(target.text()) {
+                            Some(task) => {
+                                let mut call = PlannedCall::new(
+                                    id.clone(),
+                                    namespace.as_ref().map(|ns| ns.text()),
+                                    target.text(),
+                                    PlannedCallKind::Task,
+                                    call_scatters,
+                                    call_conditionals,
+                                );
+
+                                self.plan_task(
+                                    &mut call,
+                                    callee,
+                                    task,
+                                    &provided,
+                                    specified.and_then(Inputs::as_task_inputs),
+                                )
+                                .await?;
+                                self.calls.push(call);
+                            }
+                            None => {
+                                self.calls.push(PlannedCall::new(
+                                    id.clone(),
+                                    namespace.as_ref().map(|ns| ns.text()),
+                                    target.text(),
+                                    PlannedCallKind::Workflow,
+                                    call_scatters.clone(),
+                                    call_conditionals.clone(),
+                                ));
+
+                                let empty = HashMap::new();
+                                self.plan_workflow(
+                                    callee,
+                                    Some(&id),
+                                    provided,
+                                    specified
+                                        .and_then(Inputs::as_workflow_inputs)
+                                        .map(WorkflowInputs::calls)
+                                        .unwrap_or(&empty),
+                                    &call_scatters,
+                                    &call_conditionals,
+                                )
+                                .await?;
+                            }
+                        }
+
+                        ids.insert(node, id);
+                    }
+                    WorkflowGraphNode::Output(_)
+                    | WorkflowGraphNode::ExitConditional(_)
+                    | WorkflowGraphNode::ExitScatter(_) => {
+                        // Outputs are not planned and exits have nothing to
+                        // evaluate
+                    }
+                }
+            }
+
+            // A call depends on the calls reachable by walking the graph's edges backwards
+            // through nodes that are not calls
+            for (node, id) in &ids {
+                let mut stack: Vec<_> = graph
+                    .neighbors_directed(*node, Direction::Incoming)
+                    .collect();
+                let mut seen = HashSet::new();
+                let mut dependencies = BTreeSet::new();
+                while let Some(node) = stack.pop() {
+                    if !seen.insert(node) {
+                        continue;
+                    }
+
+                    match ids.get(&node) {
+                        Some(dependency) => {
+                            dependencies.insert(dependency);
+                        }
+                        None => stack.extend(graph.neighbors_directed(node, Direction::Incoming)),
+                    }
+                }
+
+                self.dependencies
+                    .extend(dependencies.into_iter().map(|from| PlannedDependency {
+                        from: from.clone(),
+                        to: id.clone(),
+                    }));
+            }
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    /// Plans the execution of a task call.
+    ///
+    /// The given overrides are the task inputs that may override the task's
+    /// requirements and hints.
+    async fn plan_task(
+        &self,
+        call: &mut PlannedCall,
+        document: &Document,
+        task: &Task,
+        inputs: &HashMap<String, Option<Value>>,
+        overrides: Option<&TaskInputs>,
+    ) -> Result<()> {
+        let ast = match document.root().morph().ast() {
+            Ast::V1(ast) => ast,
+            _ => bail!("task planning is only supported for WDL 1.x documents"),
+        };
+
+        let definition = ast
+            .tasks()
+            .find(|t| t.name().text() == task.name())
+            .expect("task should exist in the AST");
+
+        let version = document.version().expect("document should have version");
+        let mut diagnostics = Vec::new();
+        let graph = TaskGraphBuilder::default().build(version, &definition, &mut diagnostics);
+        assert!(
+            diagnostics.is_empty(),
+            "task evaluation graph should have no diagnostics"
+        );
+
+        let base_dir = base_dir(document)?;
+        let mut values = Values::new();
+        let mut requirements = HashMap::new();
+        let mut hints = HashMap::new();
+        let mut unresolved = Vec::new();
+        for node in toposort(&graph, None).expect("graph should be acyclic") {
+            let context = self.context(document, &base_dir, &values, &[]);
+            match &graph[node] {
+                TaskGraphNode::Input(decl) => {
+                    let name = decl.name();
+                    let provided = inputs.get(name.text()).map(Option::as_ref);
+                    if let Some(value) = Self::evaluate_decl(context, decl, provided).await {
+                        values.insert(name.text().to_string(), (Vec::new(), value));
+                    }
+                }
+                TaskGraphNode::Decl(decl) => {
+                    if let Some(value) = Self::evaluate_decl(context, decl, None).await {
+                        values.insert(decl.name().text().to_string(), (Vec::new(), value));
+                    }
+                }
+                TaskGraphNode::Runtime(section) => {
+                    for item in section.items() {
+                        let name = item.name();
+                        if let Some(value) = overrides.and_then(|i| i.requirement(name.text())) {
+                            requirements.insert(name.text().to_string(), value.clone());
+                            continue;
+                        }
+
+                        if let Some(value) = overrides.and_then(|i| i.hint(name.text())) {
+                            hints.insert(name.text().to_string(), value.clone());
+                            continue;
+                        }
+
+                        let (types, requirement) =
+                            match task_requirement_types(version, name.text()) {
+                                Some(types) => (Some(types), true),
+                                None => (task_hint_types(version, name.text(), false), false),
+                            };
+
+                        let value =
+                            Self::evaluate(context, &item.expr())
+                                .await
+                                .and_then(|v| match types {
+                                    Some(types) => types
+                                        .iter()
+                                        .find_map(|ty| v.coerce(Some(&context), ty).ok()),
+                                    None => Some(v),
+                                });
+
+                        match value {
+                            Some(value) if requirement => {
+                                requirements.insert(name.text().to_string(), value);
+                            }
+                            Some(value) => {
+                                hints.insert(name.text().to_string(), value);
+                            }
+                            None => unresolved.push(name.text().to_string()),
+                        }
+                    }
+                }
+                TaskGraphNode::Requirements(section) => {
+                    for item in section.items() {
+                        let name = item.name();
+                        if let Some(value) = overrides.and_then(|i| i.requirement(name.text())) {
+                            requirements.insert(name.text().to_string(), value.clone());
+                            continue;
+                        }
+
+                        let types = task_requirement_types(version, name.text())
+                            .expect("requirement should be known");
+                        match Self::evaluate(context, &item.expr()).await.and_then(|v| {
+                            types
+                                .iter()
+                                .find_map(|ty| v.coerce(Some(&context), ty).ok())
+                        }) {
+                            Some(value) => {
+                                requirements.insert(name.text().to_string(), value);
+                            }
+                            None => unresolved.push(name.text().to_string()),
+                        }
+                    }
+                }
+                TaskGraphNode::Hints(section) => {
+                    for item in section.items() {
+                        let name = item.name();
+                        if let Some(value) = overrides.and_then(|i| i.hint(name.text())) {
+                            hints.insert(name.text().to_string(), value.clone());
+                            continue;
+                        }
+
+                        match ExprEvaluator::new(context.with_task(task))
+                            .evaluate_hints_item(&name, &item.expr())
+                            .await
+                        {
+                            Ok(value) => {
+                                hints.insert(name.text().to_string(), value);
+                            }
+                            Err(_) => unresolved.push(name.text().to_string()),
+                        }
+                    }
+                }
+                TaskGraphNode::Command(_) | TaskGraphNode::Output(_) => {
+                    // The command and outputs are not planned
+                }
+            }
+        }
+
+        call.requirements = serialize(&requirements)?;
+        call.hints = serialize(&hints)?;
+        unresolved.sort();
+        call.unresolved = unresolved;
+
+        // The constraints can only be determined once everything is resolved
+        if call.unresolved.is_empty() {
+            let constraints = self
+                .backend
+                .constraints(&requirements, &hints)
+                .with_context(|| {
+                    format!(
+                        "failed to get constraints for task `{task}`",
+                        task = task.name()
+                    )
+                })?;
+
+            call.container = constraints.container;
+            call.cpu = Some(constraints.cpu);
+            call.memory = Some(constraints.memory);
+        }
+
+        Ok(())
+    }
+}
@@ -2,20 +2,27 @@
 
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fs;
+use std::iter;
 use std::mem;
 use std::path::Path;
 use std::path::absolute;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
 use anyhow::Context;
 use anyhow::Result;
 use anyhow::anyhow;
 use anyhow::bail;
 use bimap::BiHashMap;
+use chrono::Utc;
+use crankshaft::events::send_event;
 use indexmap::IndexMap;
 use petgraph::algo::toposort;
+use tokio::sync::broadcast;
 use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
 use tracing::Level;
@@ -45,10 +52,15 @@ use wdl_ast::SupportedVersion;
 use wdl_ast::v1::CommandPart;
 use wdl_ast::v1::CommandSection;
 use wdl_ast::v1::Decl;
+use wdl_ast::v1::Expr;
 use wdl_ast::v1::RequirementsSection;
 use wdl_ast::v1::RuntimeSection;
 use wdl_ast::v1::StrippedCommandPart;
 use wdl_ast::v1::TASK_HINT_DISKS;
+use wdl_ast::v1::TASK_HINT_GPU;
+use wdl_ast::v1::TASK_HINT_INPUTS;
+use wdl_ast::v1::TASK_HINT_LOCALIZATION_OPTIONAL;
+use wdl_ast::v1::TASK_HINT_LOCALIZATION_OPTIONAL_ALIAS;
 use wdl_ast::v1::TASK_HINT_MAX_CPU;
 use wdl_ast::v1::TASK_HINT_MAX_CPU_ALIAS;
 use wdl_ast::v1::TASK_HINT_MAX_MEMORY;
@@ -57,6 +69,7 @@ use wdl_ast::v1::TASK_REQUIREMENT_CONTAINER;
 use wdl_ast::v1::TASK_REQUIREMENT_CONTAINER_ALIAS;
 use wdl_ast::v1::TASK_REQUIREMENT_CPU;
 use wdl_ast::v1::TASK_REQUIREMENT_DISKS;
+use wdl_ast::v1::TASK_REQUIREMENT_GPU;
 use wdl_ast::v1::TASK_REQUIREMENT_MAX_RETRIES;
 use wdl_ast::v1::TASK_REQUIREMENT_MAX_RETRIES_ALIAS;
 use wdl_ast::v1::TASK_REQUIREMENT_MEMORY;
@@ -65,10 +78,13 @@ use wdl_ast::v1::TaskHintsSection;
 use wdl_ast::version::V1;
 
 use crate::Coercible;
+use crate::EngineEvent;
+use crate::EngineEventKind;
 use crate::EvaluationContext;
 use crate::EvaluationError;
 use crate::EvaluationResult;
 use crate::Events;
+use crate::FailureKind;
 use crate::GuestPath;
 use crate::HostPath;
 use crate::Input;
@@ -76,6 +92,7 @@ use crate::InputKind;
 use crate::ONE_GIBIBYTE;
 use crate::Outputs;
 use crate::PrimitiveValue;
+use crate::ReuseSource;
 use crate::Scope;
 use crate::ScopeIndex;
 use crate::ScopeRef;
@@ -84,10 +101,16 @@ use crate::TaskExecutionBackend;
 use crate::TaskInputs;
 use crate::TaskSpawnInfo;
 use crate::TaskSpawnRequest;
+use crate::TaskTimeoutError;
 use crate::TaskValue;
 use crate::Value;
+use crate::cache::CallCache;
+use crate::cache::CallCacheEntry;
+use crate::cache::CompletionRecord;
 use crate::config::Config;
+use crate::config::ContainerDigestPolicy;
 use crate::config::MAX_RETRIES;
+use crate::config::TaskTimeoutBehavior;
 use crate::convert_unit_string;
 use crate::diagnostics::decl_evaluation_failed;
 use crate::diagnostics::runtime_type_mismatch;
@@ -95,11 +118,21 @@ use crate::diagnostics::task_execution_failed;
 use crate::diagnostics::task_localization_failed;
 use crate::eval::EvaluatedTask;
 use crate::eval::trie::InputTrie;
+use crate::hash::InputDigests;
+use crate::history;
+use crate::history::History;
+use crate::history::RunRecorder;
 use crate::http::HttpTransferer;
 use crate::http::Transferer;
+use crate::localization;
 use crate::path::EvaluationPath;
 use crate::path::is_file_url;
 use crate::path::is_url;
+use crate::pinning::ContainerRecord;
+use crate::pinning::ContainerResolver;
+use crate::pinning::is_digest_reference;
+use crate::pinning::is_local_image;
+use crate::provenance::ProvenanceRecorder;
 use crate::tree::SyntaxNode;
 use crate::v1::ExprEvaluator;
 use crate::v1::INPUTS_FILE;
@@ -117,6 +150,12 @@ pub const DEFAULT_TASK_REQUIREMENT_MAX_RETRIES: u64 = 0;
 /// The default value for the `disks` requirement (in GiB).
 pub const DEFAULT_TASK_REQUIREMENT_DISKS: f64 = 1.0;
 
+/// The exit code of a task that was killed for exceeding its memory.
+///
+/// This is the status code of a process terminated by `SIGKILL`, which is how
+/// the Linux out-of-memory killer terminates processes.
+const OOM_KILLED_EXIT_CODE: i32 = 137;
+
 /// The index of a task's root scope.
 const ROOT_SCOPE_INDEX: ScopeIndex = ScopeIndex::new(0);
 /// The index of a task's output scope.
@@ -178,6 +217,19 @@ pub(crate) fn cpu(requirements: &HashMap<String, Value>) -> f64 {
         .unwrap_or(DEFAULT_TASK_REQUIREMENT_CPU)
 }
 
+/// Gets the `max_retries` requirement from a requirements map.
+///
+/// Falls back to the configured default if the requirement is not present.
+pub(crate) fn max_retries(requirements: &HashMap<String, Value>, config: &Config) -> u64 {
+    requirements
+        .get(TASK_REQUIREMENT_MAX_RETRIES)
+        .or_else(|| requirements.get(TASK_REQUIREMENT_MAX_RETRIES_ALIAS))
+        .cloned()
+        .map(|v| v.unwrap_integer() as u64)
+        .or(config.task.retries)
+        .unwrap_or(DEFAULT_TASK_REQUIREMENT_MAX_RETRIES)
+}
+
 /// Gets the `max_cpu` hint from a hints map.
 pub(crate) fn max_cpu(hints: &HashMap<String, Value>) -> Option<f64> {
     hints
@@ -236,6 +288,36 @@ pub(crate) fn max_memory(hints: &HashMap<String, Value>) -> Result<Option<i64>>
         .transpose()
 }
 
+/// Gets the number of GPUs requested by a task from its requirements and
+/// hints.
+///
+/// Returns zero if the `gpu` requirement is not `true`.
+///
+/// Otherwise, the number of GPUs is taken from the `gpu` hint, which may be an
+/// integer or a string containing an integer; defaults to one GPU if the hint
+/// is not present or does not specify a count.
+pub(crate) fn gpu(requirements: &HashMap<String, Value>, hints: &HashMap<String, Value>) -> u64 {
+    let required = requirements
+        .get(TASK_REQUIREMENT_GPU)
+        .and_then(|v| v.as_boolean())
+        .unwrap_or(false);
+    if !required {
+        return 0;
+    }
+
+    hints
+        .get(TASK_HINT_GPU)
+        .and_then(|v| {
+            if let Some(v) = v.as_integer() {
+                return v.try_into().ok();
+            }
+
+            v.as_string()?.trim().parse().ok()
+        })
+        .filter(|count| *count > 0)
+        .unwrap_or(1)
+}
+
 /// Represents the type of a disk.
 ///
 /// Disk types are specified via hints.
@@ -449,6 +531,55 @@ pub(crate) fn preemptible(hints: &HashMap<String, Value>) -> i64 {
         .unwrap_or(DEFAULT_TASK_HINT_PREEMPTIBLE)
 }
 
+/// Gets the `timeout` hint from a hints map.
+///
+/// The hint may be an integer number of seconds or a string containing an
+/// integer followed by an optional unit suffix of `s`, `m`, `h`, or `d`.
+///
+/// This hint is not part of the WDL standard.
+///
+/// Falls back to the configured default if the hint is not present.
+pub(crate) fn timeout(hints: &HashMap<String, Value>, config: &Config) -> Result<Option<Duration>> {
+    const TASK_HINT_TIMEOUT: &str = "timeout";
+
+    /// Parses a timeout string into a number of seconds.
+    fn parse_timeout(s: &str) -> Option<i64> {
+        let s = s.trim();
+        let (value, multiplier) = match s.char_indices().last()? {
+            (i, 's') => (&s[..i], 1),
+            (i, 'm') => (&s[..i], 60),
+            (i, 'h') => (&s[..i], 60 * 60),
+            (i, 'd') => (&s[..i], 24 * 60 * 60),
+            _ => (s, 1),
+        };
+
+        value
+            .trim_end()
+            .parse::<i64>()
+            .ok()?
+            .checked_mul(multiplier)
+    }
+
+    let Some(v) = hints.get(TASK_HINT_TIMEOUT) else {
+        return Ok(config.task.timeout.map(Duration::from_secs));
+    };
+
+    let secs = if let Some(v) = v.as_integer() {
+        v
+    } else if let Some(s) = v.as_string() {
+        parse_timeout(s)
+            .with_context(|| format!("task specifies an invalid `timeout` hint `{s}`"))?
+    } else {
+        bail!("task `timeout` hint must be an integer or a string");
+    };
+
+    if secs <= 0 {
+        bail!("task `timeout` hint must be greater than zero");
+    }
+
+    Ok(Some(Duration::from_secs(secs as u64)))
+}
+
 /// Used to evaluate expressions in tasks.
 struct TaskEvaluationContext<'a, 'b> {
     /// The associated evaluation state.
@@ -610,6 +741,24 @@ struct State<'a> {
     backend_inputs: InputTrie,
     /// A bi-map of host paths and guest paths.
     path_map: BiHashMap<HostPath, GuestPath>,
+    /// The host paths of backend inputs that are not mapped to guest paths,
+    /// keyed by the index of the input.
+    ///
+    /// These are mapped to their localized paths when a localization strategy
+    /// is configured.
+    host_paths: HashMap<usize, HostPath>,
+    /// The names of the inputs that are not required to be localized.
+    ///
+    /// Remote inputs with these names are passed to the task as URLs.
+    unlocalized_inputs: HashSet<String>,
+    /// The authenticated URLs of remote inputs passed to the task as URLs,
+    /// mapped to the URLs before authentication was applied.
+    ///
+    /// Authenticated URLs may contain secrets (e.g. signed query parameters),
+    /// so they are replaced with their unauthenticated URLs in everything the
+    /// engine persists about a task other than the command file and re-run
+    /// script of an attempt.
+    authenticated_urls: HashMap<String, String>,
 }
 
 impl<'a> State<'a> {
@@ -656,9 +805,25 @@ impl<'a> State<'a> {
             env: Default::default(),
             backend_inputs,
             path_map: Default::default(),
+            host_paths: Default::default(),
+            unlocalized_inputs: Default::default(),
+            authenticated_urls: Default::default(),
         })
     }
 
+    /// Replaces the authenticated URLs of remote inputs in the given string
+    /// with their unauthenticated URLs.
+    fn unauthenticated<'b>(&self, s: &'b str) -> Cow<'b, str> {
+        let mut s = Cow::Borrowed(s);
+        for (authenticated, url) in &self.authenticated_urls {
+            if s.contains(authenticated.as_str()) {
+                s = Cow::Owned(s.replace(authenticated.as_str(), url));
+            }
+        }
+
+        s
+    }
+
     /// Adds backend inputs to the state for any `File` or `Directory` values
     /// referenced by the given value.
     ///
@@ -668,6 +833,10 @@ impl<'a> State<'a> {
     /// If the backend does use containers, remote inputs are localized during
     /// the call to `localize_inputs`.
     ///
+    /// If localization of the value is optional, remote inputs are not added as
+    /// backend inputs and are instead mapped to their URLs with any required
+    /// authentication applied.
+    ///
     /// This method also ensures that a `File` or `Directory` paths exist for
     /// WDL 1.2+.
     async fn add_backend_inputs(
@@ -676,8 +845,10 @@ impl<'a> State<'a> {
         value: &mut Value,
         transferer: &Arc<dyn Transferer>,
         needs_local_inputs: bool,
+        localization_optional: bool,
     ) -> Result<()> {
         let mut urls = Vec::new();
+        let mut unlocalized = Vec::new();
         value.visit_paths_mut(is_optional, &mut |optional, value| {
             // Ensure the path exists before we translate it (1.2+ behavior)
             if self
@@ -698,6 +869,12 @@ impl<'a> State<'a> {
                 _ => unreachable!("only file and directory values should be visited"),
             };
 
+            // Remote inputs that are not required to be localized are passed as URLs
+            if localization_optional && is_url(path.as_str()) && !is_file_url(path.as_str()) {
+                unlocalized.push(path.clone());
+                return Ok(true);
+            }
+
             // Insert a backend input for the path
             if let Some(index) = self.insert_backend_input(kind, path)? {
                 // Check to see if there's no guest path for a remote URL that needs to be
@@ -714,6 +891,20 @@ impl<'a> State<'a> {
             Ok(true)
         })?;
 
+        for path in unlocalized {
+            let url = path
+                .as_str()
+                .parse()
+                .with_context(|| format!("invalid URL `{path}`"))?;
+            let url = transferer.apply_auth(&url)?;
+            if url.as_str() != path.as_str() {
+                self.authenticated_urls
+                    .insert(url.to_string(), path.as_str().to_string());
+            }
+
+            self.path_map.insert(path, GuestPath::new(url.as_str()));
+        }
+
         if urls.is_empty() {
             return Ok(());
         }
@@ -768,8 +959,13 @@ impl<'a> State<'a> {
         {
             // If the input has a guest path, map it
             let input = &self.backend_inputs.as_slice()[index];
-            if let Some(guest_path) = &input.guest_path {
-                self.path_map.insert(path.clone(), guest_path.clone());
+            match &input.guest_path {
+                Some(guest_path) => {
+                    self.path_map.insert(path.clone(), guest_path.clone());
+                }
+                None => {
+                    self.host_paths.entry(index).or_insert_with(|| path.clone());
+                }
             }
 
             return Ok(Some(index));
@@ -777,6 +973,102 @@ impl<'a> State<'a> {
 
         Ok(None)
     }
+
+    /// Maps the host paths of backend inputs that are not mapped to guest
+    /// paths to the paths they are localized to in the given attempt
+    /// directory.
+    fn map_localized_inputs(&mut self, attempt_dir: &Path) -> Result<()> {
+        let localized = localization::localized_paths(attempt_dir, self.backend_inputs.as_slice())?;
+        for (index, path) in &self.host_paths {
+            let localized = &localized[*index];
+            self.path_map.insert(
+                path.clone(),
+                GuestPath::new(localized.to_str().with_context(|| {
+                    format!(
+                        "localized path `{path}` is not UTF-8",
+                        path = localized.display()
+                    )
+                })?),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Gets the names of the inputs of a task that are not required to be
+/// localized.
+///
+/// An input is not required to be localized if the `localization_optional`
+/// hint is `true` for the input in the `inputs` hint or, if not specified for
+/// the input, for the task.
+///
+/// As inputs are localized before the hints section is evaluated, only hints
+/// with literal values are honored.
+fn unlocalized_inputs(definition: &TaskDefinition<SyntaxNode>) -> HashSet<String> {
+    /// Determines if the given hint name is `localization_optional`.
+    fn is_localization_optional(name: &str) -> bool {
+        name == TASK_HINT_LOCALIZATION_OPTIONAL || name == TASK_HINT_LOCALIZATION_OPTIONAL_ALIAS
+    }
+
+    /// Gets the value of a literal `Boolean` expression.
+    fn literal_boolean(expr: &Expr<SyntaxNode>) -> Option<bool> {
+        expr.as_literal()?.as_boolean().map(|b| b.value())
+    }
+
+    let Some(section) = definition.hints() else {
+        return Default::default();
+    };
+
+    let mut task = false;
+    let mut inputs = HashMap::new();
+    for item in section.items() {
+        let name = item.name();
+        let expr = item.expr();
+        if is_localization_optional(name.text()) {
+            task = literal_boolean(&expr).unwrap_or(task);
+            continue;
+        }
+
+        if name.text() != TASK_HINT_INPUTS {
+            continue;
+        }
+
+        let Some(input) = expr.as_literal().and_then(|l| l.as_input()) else {
+            continue;
+        };
+
+        for item in input.items() {
+            // Only hints for an input itself, and not a member of an input, are honored
+            let mut names = item.names();
+            let (Some(name), None) = (names.next(), names.next()) else {
+                continue;
+            };
+
+            let expr = item.expr();
+            let Some(hints) = expr.as_literal().and_then(|l| l.as_hints()) else {
+                continue;
+            };
+
+            for hint in hints.items() {
+                if is_localization_optional(hint.name().text())
+                    && let Some(value) = literal_boolean(&hint.expr())
+                {
+                    inputs.insert(name.text().to_string(), value);
+                }
+            }
+        }
+    }
+
+    let Some(section) = definition.input() else {
+        return Default::default();
+    };
+
+    section
+        .declarations()
+        .map(|d| d.name().text().to_string())
+        .filter(|n| inputs.get(n).copied().unwrap_or(task))
+        .collect()
 }
 
 /// Represents the result of evaluating task sections before execution.
@@ -790,6 +1082,9 @@ struct EvaluatedSections {
 }
 
 /// Represents a WDL V1 task evaluator.
+///
+/// This type is cheaply cloned.
+#[derive(Clone)]
 pub struct TaskEvaluator {
     /// The associated evaluation configuration.
     config: Arc<Config>,
@@ -799,6 +1094,26 @@ pub struct TaskEvaluator {
     token: CancellationToken,
     /// The transferer to use for expression evaluation.
     transferer: Arc<dyn Transferer>,
+    /// The call cache to use.
+    ///
+    /// This is `None` when call caching is disabled.
+    cache: Option<Arc<CallCache>>,
+    /// The sender for engine events.
+    events: Option<broadcast::Sender<EngineEvent>>,
+    /// The recorder of call provenance.
+    ///
+    /// This is `None` when provenance is not being recorded.
+    provenance: Option<Arc<ProvenanceRecorder>>,
+    /// The run history database.
+    ///
+    /// This is `None` when run history is disabled.
+    history: Option<Arc<History>>,
+    /// The recorder of the history of the run being evaluated.
+    ///
+    /// This is `None` when not evaluating a run with history enabled.
+    run: Option<RunRecorder>,
+    /// The resolver of task containers to references by digest.
+    containers: Arc<ContainerResolver>,
 }
 
 impl TaskEvaluator {
@@ -811,32 +1126,70 @@ impl TaskEvaluator {
 
         let config = Arc::new(config);
         let backend = config.create_backend(events.crankshaft().clone()).await?;
+        Self::with_backend(config, backend, token, events)
+    }
+
+    /// Constructs a new task evaluator with the given evaluation
+    /// configuration, task execution backend, cancellation token, and events.
+    ///
+    /// The backend is expected to have been created from the configuration
+    /// with [`Config::create_backend`]; evaluators sharing a backend share its
+    /// concurrency and resource limits.
+    ///
+    /// This method does not validate the configuration.
+    pub fn with_backend(
+        config: Arc<Config>,
+        backend: Arc<dyn TaskExecutionBackend>,
+        token: CancellationToken,
+        events: Events,
+    ) -> Result<Self> {
         let transferer =
             HttpTransferer::new(config.clone(), token.clone(), events.transfer().clone())?;
+        let cache = CallCache::new(&config.call_cache)?.map(Arc::new);
+        let history = History::new(&config.history)?.map(Arc::new);
 
         Ok(Self {
             config,
             backend,
             token,
             transferer: Arc::new(transferer),
+            cache,
+            events: events.engine().clone(),
+            provenance: None,
+            history,
+            run: None,
+            containers: Default::default(),
         })
     }
 
     /// Creates a new task evaluator with the given configuration, backend,
-    /// cancellation token, and transferer.
+    /// cancellation token, transferer, call cache, engine events sender,
+    /// provenance recorder, run history recorder, and container resolver.
     ///
     /// This method does not validate the configuration.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new_unchecked(
         config: Arc<Config>,
         backend: Arc<dyn TaskExecutionBackend>,
         token: CancellationToken,
         transferer: Arc<dyn Transferer>,
+        cache: Option<Arc<CallCache>>,
+        events: Option<broadcast::Sender<EngineEvent>>,
+        provenance: Option<Arc<ProvenanceRecorder>>,
+        run: Option<RunRecorder>,
+        containers: Arc<ContainerResolver>,
     ) -> Self {
         Self {
             config,
             backend,
             token,
             transferer,
+            cache,
+            events,
+            provenance,
+            history: run.as_ref().map(|r| r.history().clone()),
+            run,
+            containers,
         }
     }
 
@@ -855,8 +1208,40 @@ impl TaskEvaluator {
             return Err(anyhow!("cannot evaluate a document with errors").into());
         }
 
-        self.perform_evaluation(document, task, inputs, root.as_ref(), task.name())
-            .await
+        let root = root.as_ref();
+        let Some(history) = &self.history else {
+            return self
+                .perform_evaluation(document, task, inputs, root, task.name(), None)
+                .await;
+        };
+
+        // Record the run, and the run's single call, into the history database
+        let run = history.start_run(history::Kind::Task, task.name(), document, root, inputs);
+        run.start_call(
+            task.name(),
+            None,
+            history::Kind::Task,
+            task.name(),
+            root,
+            inputs,
+        );
+
+        let evaluator = Self {
+            run: Some(run.clone()),
+            ..self.clone()
+        };
+        let result = evaluator
+            .perform_evaluation(document, task, inputs, root, task.name(), None)
+            .await;
+
+        let outputs = match &result {
+            Ok(evaluated) => evaluated.outputs.as_ref(),
+            Err(e) => Err(e),
+        };
+        let canceled = self.token.is_cancelled();
+        run.finish_call(root, outputs, canceled);
+        run.finish(outputs, canceled);
+        result
     }
 
     /// Performs the evaluation of the given task.
@@ -870,6 +1255,7 @@ impl TaskEvaluator {
         inputs: &TaskInputs,
         root: &Path,
         id: &str,
+        scatter_index: Option<&str>,
     ) -> EvaluationResult<EvaluatedTask> {
         inputs.validate(document, task, None).with_context(|| {
             format!(
@@ -917,6 +1303,12 @@ impl TaskEvaluator {
             )
         })?;
 
+        // The record of the call's provenance; the call is recorded when dropped
+        let mut record = self
+            .provenance
+            .as_deref()
+            .map(|p| p.start(id, scatter_index, task.name(), document, &root_dir));
+
         // Create the temp directory now as it may be needed for task evaluation
         let temp_dir = root_dir.join("tmp");
         fs::create_dir_all(&temp_dir).with_context(|| {
@@ -930,6 +1322,7 @@ impl TaskEvaluator {
         write_json_file(root_dir.join(INPUTS_FILE), inputs)?;
 
         let mut state = State::new(document, task, &temp_dir, self.backend.guest_inputs_dir())?;
+        state.unlocalized_inputs = unlocalized_inputs(&definition);
         let nodes = toposort(&graph, None).expect("graph should be acyclic");
         let mut current = 0;
         while current < nodes.len() {
@@ -961,26 +1354,93 @@ impl TaskEvaluator {
         }
 
         let env = Arc::new(mem::take(&mut state.env));
+
+        // The environment variables without authentication for the call cache key
+        let unauthenticated_env: IndexMap<_, _> = env
+            .iter()
+            .map(|(k, v)| (k.clone(), state.unauthenticated(v).into_owned()))
+            .collect();
+        // The digest of the task's source, including the struct definitions it may use
+        let source = CompletionRecord::source_digest(
+            iter::once(definition.text().to_string())
+                .chain(ast.structs().map(|s| s.text().to_string())),
+        );
+        if let Some(record) = &mut record {
+            record.set_source(&source);
+        }
+
+        // The fingerprint of the task's inputs; this is calculated for the first
+        // attempt
+        let mut fingerprint = None;
+        // The call cache key for the task; this is calculated for the first attempt
+        let mut cache_key = None;
+        // The source of the execution result if it was reused from a previous
+        // execution
+        let mut reused = None;
+        // The escalated memory of the task after an attempt was killed for exceeding
+        // its memory
+        let mut escalated_memory = None;
+        // The number of retries of attempts whose command failed
+        let mut command_retries = 0;
+        // The number of retries of attempts the backend failed to execute
+        let mut system_retries = 0;
         // Spawn the task in a retry loop
         let mut attempt = 0;
         let mut evaluated = loop {
+            let mut attempt_dir = root_dir.clone();
+            attempt_dir.push("attempts");
+            attempt_dir.push(attempt.to_string());
+
+            // Backends that do not use containers observe the localized paths of inputs
+            // directly, so map the inputs before the command is evaluated
+            if self.config.task.localization.is_some()
+                && self.backend.needs_local_inputs()
+                && self.backend.guest_inputs_dir().is_none()
+            {
+                state.map_localized_inputs(&attempt_dir)?;
+            }
+
             let EvaluatedSections {
                 command,
-                requirements,
+                mut requirements,
                 hints,
             } = self
-                .evaluate_sections(id, &mut state, &definition, inputs, attempt)
+                .evaluate_sections(
+                    id,
+                    &mut state,
+                    &definition,
+                    inputs,
+                    attempt,
+                    escalated_memory,
+                )
                 .await?;
 
+            // The command without authentication is used for everything persisted about
+            // the task; only the executed command contains authenticated URLs
+            let unauthenticated_command = state.unauthenticated(&command).into_owned();
+
+            // Pin the task's container for backends that execute tasks in containers
+            let pinned = if self.backend.guest_inputs_dir().is_some() {
+                let requested =
+                    container(&requirements, self.config.task.container.as_deref()).into_owned();
+                let resolved = self.pin_container(task.name(), &requested).await?;
+                if let Some(resolved) = &resolved {
+                    let requirements = Arc::make_mut(&mut requirements);
+                    requirements.remove(TASK_REQUIREMENT_CONTAINER_ALIAS);
+                    requirements.insert(
+                        TASK_REQUIREMENT_CONTAINER.to_string(),
+                        PrimitiveValue::new_string(resolved).into(),
+                    );
+                }
+
+                Some((requested, resolved))
+            } else {
+                None
+            };
+
             // Get the maximum number of retries, either from the task's requirements or
             // from configuration
-            let max_retries = requirements
-                .get(TASK_REQUIREMENT_MAX_RETRIES)
-                .or_else(|| requirements.get(TASK_REQUIREMENT_MAX_RETRIES_ALIAS))
-                .cloned()
-                .map(|v| v.unwrap_integer() as u64)
-                .or_else(|| self.config.task.retries)
-                .unwrap_or(DEFAULT_TASK_REQUIREMENT_MAX_RETRIES);
+            let max_retries = max_retries(&requirements, &self.config);
 
             if max_retries > MAX_RETRIES {
                 return Err(anyhow!(
@@ -989,43 +1449,356 @@ impl TaskEvaluator {
                 .into());
             }
 
-            let mut attempt_dir = root_dir.clone();
-            attempt_dir.push("attempts");
-            attempt_dir.push(attempt.to_string());
+            // The retry policy's command retries cap the requirement
+            let max_retries = self
+                .config
+                .task
+                .retry_policy
+                .command
+                .map_or(max_retries, |command| max_retries.min(command));
 
-            let request = TaskSpawnRequest::new(
-                id.to_string(),
-                TaskSpawnInfo::new(
-                    command,
-                    self.localize_inputs(id, &mut state).await?,
-                    requirements.clone(),
-                    hints.clone(),
-                    env.clone(),
-                    self.transferer.clone(),
-                ),
-                attempt,
-                attempt_dir.clone(),
-            );
+            let mut backend_inputs = self.localize_inputs(id, &mut state).await?;
 
-            let result = self
-                .backend
-                .spawn(request, self.token.clone())
+            // The digests of the inputs are calculated once for both the provenance and
+            // the call cache key of the task
+            let digests = if attempt == 0 && (record.is_some() || self.cache.is_some()) {
+                InputDigests::calculate(&backend_inputs).await
+            } else {
+                InputDigests::default()
+            };
+
+            if let Some(record) = &mut record {
+                record.set_execution(
+                    &unauthenticated_command,
+                    &container(&requirements, self.config.task.container.as_deref()),
+                    &requirements,
+                )?;
+                record.set_inputs(&backend_inputs, &digests);
+            }
+
+            // Look for a previous execution to reuse before the first attempt
+            let mut entry = None;
+            let mut reuse_source = ReuseSource::Resumed;
+            if attempt == 0 && fingerprint.is_none() {
+                let digest = CompletionRecord::fingerprint(
+                    inputs,
+                    &unauthenticated_command,
+                    &requirements,
+                    &backend_inputs,
+                )
                 .with_context(|| {
                     format!(
-                        "failed to spawn task `{name}` in `{path}` (task id `{id}`)",
-                        name = task.name(),
-                        path = document.path(),
-                    )
-                })?
-                .await
-                .expect("failed to receive response from spawned task")
-                .map_err(|e| {
-                    EvaluationError::new(
-                        state.document.clone(),
-                        task_execution_failed(e, task.name(), id, task.name_span()),
+                        "failed to calculate the inputs fingerprint of task `{name}`",
+                        name = task.name()
                     )
                 })?;
 
+                // Resume from a previous successful execution in the output directory
+                if self.config.task.resume
+                    && let Some(record) = CompletionRecord::read(&root_dir)?
+                    && let Some(execution) = record.into_execution(&source, &digest)
+                {
+                    info!(
+                        "resuming from previous execution of task `{name}` in `{path}`",
+                        name = task.name(),
+                        path = execution.attempt_dir.display()
+                    );
+                    entry = Some(execution);
+                }
+
+                fingerprint = Some(digest);
+
+                // Consult the call cache
+                if entry.is_none()
+                    && let Some(cache) = &self.cache
+                {
+                    let key = CallCache::key(
+                        &unauthenticated_command,
+                        &requirements,
+                        &hints,
+                        &container(&requirements, self.config.task.container.as_deref()),
+                        &unauthenticated_env,
+                        &backend_inputs,
+                        &digests,
+                    )
+                    .with_context(|| {
+                        format!(
+                            "failed to calculate call cache key for task `{name}`",
+                            name = task.name()
+                        )
+                    })?;
+
+                    entry = cache.get(&key)?;
+                    reuse_source = ReuseSource::Cached;
+                    if let Some(entry) = &entry {
+                        info!(
+                            "reusing cached execution of task `{name}` from `{path}`",
+                            name = task.name(),
+                            path = entry.attempt_dir.display()
+                        );
+                    }
+
+                    cache_key = Some(key);
+                }
+
+                // Remove any stale completion record as the task will execute again
+                if entry.is_none() {
+                    CompletionRecord::remove(&root_dir)?;
+                }
+            }
+
+            let (attempt_dir, result, elapsed) = match entry {
+                Some(entry) => {
+                    reused = Some(reuse_source);
+
+                    // Localize a cached execution into this call's attempt directory so that
+                    // the outputs do not depend on another evaluation's output directory
+                    let entry = match (reuse_source, &self.cache) {
+                        (ReuseSource::Cached, Some(cache)) => {
+                            cache.localize(entry, &attempt_dir).await.with_context(|| {
+                                format!(
+                                    "failed to localize cached execution of task `{name}`",
+                                    name = task.name()
+                                )
+                            })?
+                        }
+                        _ => entry,
+                    };
+
+                    let (attempt_dir, result) = entry.into_result()?;
+                    (attempt_dir, result, None)
+                }
+                None => {
+                    if let Some((requested, resolved)) = &pinned {
+                        fs::create_dir_all(&attempt_dir).with_context(|| {
+                            format!(
+                                "failed to create directory `{path}`",
+                                path = attempt_dir.display()
+                            )
+                        })?;
+
+                        ContainerRecord {
+                            requested,
+                            resolved: resolved.as_deref(),
+                        }
+                        .write(&attempt_dir)?;
+                    }
+
+                    // Localize the inputs into the attempt directory after the inputs have been
+                    // fingerprinted so that the localized paths do not affect reuse
+                    if let Some(strategy) = self.config.task.localization
+                        && self.backend.needs_local_inputs()
+                    {
+                        localization::localize_inputs(strategy, &attempt_dir, &mut backend_inputs)
+                            .await
+                            .map_err(|e| {
+                                EvaluationError::new(
+                                    state.document.clone(),
+                                    task_localization_failed(
+                                        e,
+                                        state.task.name(),
+                                        state.task.name_span(),
+                                    ),
+                                )
+                            })?;
+                    }
+
+                    let request = TaskSpawnRequest::new(
+                        id.to_string(),
+                        TaskSpawnInfo::new(
+                            command,
+                            backend_inputs,
+                            requirements.clone(),
+                            hints.clone(),
+                            env.clone(),
+                            self.transferer.clone(),
+                        ),
+                        attempt,
+                        attempt_dir.clone(),
+                    );
+
+                    send_event!(
+                        self.events,
+                        EngineEvent::new(
+                            id,
+                            scatter_index,
+                            EngineEventKind::TaskStarted {
+                                attempt,
+                                attempt_dir: attempt_dir.clone(),
+                            },
+                        ),
+                    );
+
+                    let start = Instant::now();
+                    let started = Utc::now();
+                    // Use a child token so that cancelling a timed out attempt does not
+                    // cancel the entire evaluation
+                    let result = match self
+                        .backend
+                        .spawn(request, self.token.child_token())
+                        .with_context(|| {
+                            format!(
+                                "failed to spawn task `{name}` in `{path}` (task id `{id}`)",
+                                name = task.name(),
+                                path = document.path(),
+                            )
+                        })?
+                        .await
+                        .expect("failed to receive response from spawned task")
+                    {
+                        Ok(result) => {
+                            if let Some(record) = &mut record {
+                                record.add_attempt(
+                                    attempt,
+                                    &attempt_dir,
+                                    Some(result.exit_code),
+                                    result.usage,
+                                    started,
+                                );
+                            }
+
+                            if let Some(run) = &self.run {
+                                run.add_attempt(
+                                    &root_dir,
+                                    attempt,
+                                    &attempt_dir,
+                                    Some(result.exit_code),
+                                    started,
+                                );
+                            }
+
+                            if let Some(usage) = &result.usage {
+                                usage.write(&attempt_dir)?;
+                            }
+
+                            result
+                        }
+                        Err(e) => {
+                            if let Some(record) = &mut record {
+                                record.add_attempt(attempt, &attempt_dir, None, None, started);
+                            }
+
+                            if let Some(run) = &self.run {
+                                run.add_attempt(&root_dir, attempt, &attempt_dir, None, started);
+                            }
+
+                            let kind = if let Some(e) = e.downcast_ref::<TaskTimeoutError>() {
+                                warn!(
+                                    "task `{name}` (task id `{id}`) {e}",
+                                    name = state.task.name()
+                                );
+
+                                send_event!(
+                                    self.events,
+                                    EngineEvent::new(
+                                        id,
+                                        scatter_index,
+                                        EngineEventKind::TaskTimedOut {
+                                            attempt,
+                                            attempt_dir: attempt_dir.clone(),
+                                            timeout: e.timeout,
+                                        },
+                                    ),
+                                );
+
+                                if let TaskTimeoutBehavior::Retry =
+                                    self.config.task.timeout_behavior
+                                    && command_retries < max_retries
+                                {
+                                    send_event!(
+                                        self.events,
+                                        EngineEvent::new(
+                                            id,
+                                            scatter_index,
+                                            EngineEventKind::TaskRetried {
+                                                attempt,
+                                                attempt_dir,
+                                                exit_code: None,
+                                                usage: None,
+                                            },
+                                        ),
+                                    );
+
+                                    attempt += 1;
+                                    command_retries += 1;
+
+                                    info!(
+                                        "retrying execution of task `{name}` (retry {attempt})",
+                                        name = state.task.name()
+                                    );
+                                    continue;
+                                }
+
+                                FailureKind::Command
+                            } else {
+                                // An attempt that failed as evaluation was cancelled is not
+                                // retried
+                                if self.token.is_cancelled() {
+                                    return Err(
+                                        anyhow!("task evaluation has been cancelled").into()
+                                    );
+                                }
+
+                                if system_retries < self.config.task.retry_policy.system {
+                                    warn!(
+                                        "task `{name}` (task id `{id}`) failed to execute: {e:#}",
+                                        name = state.task.name()
+                                    );
+
+                                    send_event!(
+                                        self.events,
+                                        EngineEvent::new(
+                                            id,
+                                            scatter_index,
+                                            EngineEventKind::TaskRetried {
+                                                attempt,
+                                                attempt_dir,
+                                                exit_code: None,
+                                                usage: None,
+                                            },
+                                        ),
+                                    );
+
+                                    attempt += 1;
+                                    system_retries += 1;
+
+                                    info!(
+                                        "retrying execution of task `{name}` (retry {attempt})",
+                                        name = state.task.name()
+                                    );
+                                    continue;
+                                }
+
+                                FailureKind::System
+                            };
+
+                            send_event!(
+                                self.events,
+                                EngineEvent::new(
+                                    id,
+                                    scatter_index,
+                                    EngineEventKind::TaskFailed {
+                                        attempt,
+                                        attempt_dir: attempt_dir.clone(),
+                                        exit_code: None,
+                                        usage: None,
+                                        elapsed: start.elapsed(),
+                                    },
+                                ),
+                            );
+
+                            return Err(EvaluationError::new(
+                                state.document.clone(),
+                                task_execution_failed(e, task.name(), id, task.name_span()),
+                            )
+                            .with_failure(kind));
+                        }
+                    };
+
+                    (attempt_dir, result, Some(start.elapsed()))
+                }
+            };
+
             // Update the task variable
             let evaluated = EvaluatedTask::new(attempt_dir, result)?;
             if version >= SupportedVersion::V1(V1::Two) {
@@ -1042,20 +1815,82 @@ impl TaskEvaluator {
                     )
                 })?);
                 task.set_return_code(evaluated.result.exit_code);
+
+                // GPU devices are reserved when the task is spawned, so the devices are
+                // only known once the task has executed
+                if !evaluated.result.gpus.is_empty() {
+                    task.set_gpu(evaluated.result.gpus.clone());
+                }
             }
 
+            let exit_code = evaluated.result.exit_code;
             if let Err(e) = evaluated
                 .handle_exit(&requirements, self.transferer.as_ref())
                 .await
             {
-                if attempt >= max_retries {
+                if let Some(source) = reused.take() {
+                    // The reused execution is no longer considered successful (e.g. the
+                    // task's return codes changed); execute the task instead
+                    CompletionRecord::remove(&root_dir)?;
+                    if source == ReuseSource::Cached {
+                        fs::remove_dir_all(&evaluated.attempt_dir).with_context(|| {
+                            format!(
+                                "failed to remove directory `{path}`",
+                                path = evaluated.attempt_dir.display()
+                            )
+                        })?;
+                    }
+
+                    continue;
+                }
+
+                if command_retries >= max_retries {
+                    send_event!(
+                        self.events,
+                        EngineEvent::new(
+                            id,
+                            scatter_index,
+                            EngineEventKind::TaskFailed {
+                                attempt,
+                                attempt_dir: evaluated.attempt_dir,
+                                exit_code: Some(exit_code),
+                                usage: evaluated.result.usage,
+                                elapsed: elapsed.unwrap_or_default(),
+                            },
+                        ),
+                    );
+
                     return Err(EvaluationError::new(
                         state.document.clone(),
                         task_execution_failed(e, task.name(), id, task.name_span()),
-                    ));
+                    )
+                    .with_failure(FailureKind::Command));
+                }
+
+                if exit_code == OOM_KILLED_EXIT_CODE
+                    && let Some(multiplier) = self.config.task.memory_retry_multiplier
+                {
+                    escalated_memory = self
+                        .escalate_memory(state.task.name(), &requirements, &hints, multiplier)?
+                        .or(escalated_memory);
                 }
 
+                send_event!(
+                    self.events,
+                    EngineEvent::new(
+                        id,
+                        scatter_index,
+                        EngineEventKind::TaskRetried {
+                            attempt,
+                            attempt_dir: evaluated.attempt_dir,
+                            exit_code: Some(exit_code),
+                            usage: evaluated.result.usage,
+                        },
+                    ),
+                );
+
                 attempt += 1;
+                command_retries += 1;
 
                 info!(
                     "retrying execution of task `{name}` (retry {attempt})",
@@ -1064,6 +1899,22 @@ impl TaskEvaluator {
                 continue;
             }
 
+            let kind = match reused {
+                Some(source) => EngineEventKind::TaskReused {
+                    attempt_dir: evaluated.attempt_dir.clone(),
+                    exit_code,
+                    source,
+                },
+                None => EngineEventKind::TaskCompleted {
+                    attempt,
+                    attempt_dir: evaluated.attempt_dir.clone(),
+                    exit_code,
+                    usage: evaluated.result.usage,
+                    elapsed: elapsed.unwrap_or_default(),
+                },
+            };
+
+            send_event!(self.events, EngineEvent::new(id, scatter_index, kind));
             break evaluated;
         };
 
@@ -1110,6 +1961,31 @@ impl TaskEvaluator {
         // Write the outputs to the task's root directory
         write_json_file(root_dir.join(OUTPUTS_FILE), &outputs)?;
 
+        // Record the successful execution in the output directory and the call cache
+        match CallCacheEntry::new(&evaluated.attempt_dir, &evaluated.result) {
+            Some(entry) => {
+                if reused.is_none()
+                    && let (Some(cache), Some(key)) = (&self.cache, &cache_key)
+                {
+                    cache.put(key, &entry)?;
+                }
+
+                if let Some(fingerprint) = &fingerprint {
+                    CompletionRecord::new(&source, fingerprint, entry).write(&root_dir)?;
+                }
+            }
+            None => warn!(
+                "execution of task `{name}` cannot be recorded as it has non-UTF-8 paths",
+                name = task.name()
+            ),
+        }
+
+        if let Some(record) = &mut record {
+            record.set_reused(reused);
+            record.set_outputs(&outputs).await?;
+            record.succeeded();
+        }
+
         evaluated.outputs = Ok(outputs);
         Ok(evaluated)
     }
@@ -1172,6 +2048,7 @@ impl TaskEvaluator {
                 &mut value,
                 &self.transferer,
                 self.backend.needs_local_inputs(),
+                state.unlocalized_inputs.contains(name.text()),
             )
             .await
             .map_err(|e| {
@@ -1250,6 +2127,7 @@ impl TaskEvaluator {
                 &mut value,
                 &self.transferer,
                 self.backend.needs_local_inputs(),
+                false,
             )
             .await
             .map_err(|e| {
@@ -1535,6 +2413,88 @@ impl TaskEvaluator {
         Ok(command)
     }
 
+    /// Pins the given container of a task according to the configured
+    /// container digest policy.
+    ///
+    /// Returns the reference by digest to use for the container or `None` if
+    /// the container was not resolved.
+    async fn pin_container(&self, name: &str, container: &str) -> Result<Option<String>> {
+        match self.config.task.container_digest_policy {
+            ContainerDigestPolicy::Resolve => {
+                self.containers
+                    .resolve(self.backend.as_ref(), container)
+                    .await
+            }
+            ContainerDigestPolicy::Require => {
+                // Local image files are not pulled from a registry and have no
+                // digest to require
+                if is_local_image(container) {
+                    return Ok(None);
+                }
+
+                if !is_digest_reference(container) {
+                    bail!(
+                        "container `{container}` of task `{name}` is not specified by digest as \
+                         required by the configured container digest policy"
+                    );
+                }
+
+                Ok(Some(container.to_string()))
+            }
+            ContainerDigestPolicy::Ignore => Ok(None),
+        }
+    }
+
+    /// Escalates the memory of a task after an attempt was killed for exceeding
+    /// its memory.
+    ///
+    /// The escalated memory is capped by the task's `max_memory` hint and by
+    /// the execution constraints of the backend.
+    ///
+    /// Returns `Ok(None)` if the memory cannot be escalated.
+    fn escalate_memory(
+        &self,
+        name: &str,
+        requirements: &HashMap<String, Value>,
+        hints: &HashMap<String, Value>,
+        multiplier: f64,
+    ) -> Result<Option<i64>> {
+        let current = memory(requirements)?;
+        let mut escalated = (current as f64 * multiplier).ceil() as i64;
+        if let Some(max) = max_memory(hints)? {
+            escalated = escalated.min(max);
+        }
+
+        if escalated <= current {
+            info!(
+                "task `{name}` was killed for exceeding its memory, but its memory cannot be \
+                 escalated beyond {current} bytes"
+            );
+            return Ok(None);
+        }
+
+        let mut requirements = requirements.clone();
+        requirements.insert(TASK_REQUIREMENT_MEMORY.to_string(), escalated.into());
+        match self.backend.constraints(&requirements, hints) {
+            Ok(constraints) if constraints.memory > current => {
+                info!(
+                    "task `{name}` was killed for exceeding its memory; escalating its memory \
+                     from {current} to {memory} bytes",
+                    memory = constraints.memory
+                );
+                Ok(Some(constraints.memory))
+            }
+            Ok(_) => Ok(None),
+            Err(e) => {
+                warn!(
+                    "task `{name}` was killed for exceeding its memory, but its memory cannot be \
+                     escalated: {e:#}"
+                );
+                Ok(None)
+            }
+        }
+    }
+
     /// Evaluates sections prior to spawning the command.
     ///
     /// This method evaluates the following sections:
@@ -1549,9 +2509,10 @@ impl TaskEvaluator {
         definition: &TaskDefinition<SyntaxNode>,
         inputs: &TaskInputs,
         attempt: u64,
+        memory: Option<i64>,
     ) -> EvaluationResult<EvaluatedSections> {
         // Start by evaluating requirements and hints
-        let (requirements, hints) = match definition.runtime() {
+        let (mut requirements, hints) = match definition.runtime() {
             Some(section) => self
                 .evaluate_runtime_section(id, state, &section, inputs)
                 .await
@@ -1574,6 +2535,12 @@ impl TaskEvaluator {
             ),
         };
 
+        // Override the memory requirement with any escalated memory from a previous
+        // attempt
+        if let Some(memory) = memory {
+            requirements.insert(TASK_REQUIREMENT_MEMORY.to_string(), memory.into());
+        }
+
         // Update or insert the `task` variable in the task scope
         // TODO: if task variables become visible in `requirements` or `hints` section,
         // this needs to be relocated to before we evaluate those sections
@@ -1738,6 +2705,10 @@ impl TaskEvaluator {
 
     /// Localizes inputs for execution.
     ///
+    /// Remote inputs are downloaded for backends that need local inputs; when
+    /// a localization strategy is configured, the inputs are localized into
+    /// the attempt directory immediately before the task is spawned.
+    ///
     /// Returns the inputs to pass to the backend.
     async fn localize_inputs(
         &self,
@@ -8,11 +8,14 @@ use std::mem;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 
 use anyhow::Context;
 use anyhow::Result;
 use anyhow::anyhow;
 use anyhow::bail;
+use chrono::Utc;
+use crankshaft::events::send_event;
 use futures::FutureExt;
 use futures::future::BoxFuture;
 use indexmap::IndexMap;
@@ -21,7 +24,9 @@ use petgraph::graph::DiGraph;
 use petgraph::graph::NodeIndex;
 use petgraph::visit::Bfs;
 use petgraph::visit::EdgeRef;
+use tempfile::TempDir;
 use tokio::sync::RwLock;
+use tokio::sync::broadcast;
 use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
 use tracing::debug;
@@ -61,11 +66,14 @@ use crate::Array;
 use crate::CallLocation;
 use crate::CallValue;
 use crate::Coercible;
+use crate::EngineEvent;
+use crate::EngineEventKind;
 use crate::EvaluationContext;
 use crate::EvaluationError;
 use crate::EvaluationResult;
 use crate::Events;
 use crate::Inputs;
+use crate::NoopBackend;
 use crate::Outputs;
 use crate::PrimitiveValue;
 use crate::Scope;
@@ -74,19 +82,29 @@ use crate::ScopeRef;
 use crate::TaskExecutionBackend;
 use crate::Value;
 use crate::WorkflowInputs;
+use crate::cache::CallCache;
 use crate::config::Config;
 use crate::diagnostics::decl_evaluation_failed;
 use crate::diagnostics::if_conditional_mismatch;
 use crate::diagnostics::runtime_type_mismatch;
+use crate::history;
+use crate::history::History;
+use crate::history::RunRecorder;
 use crate::http::HttpTransferer;
 use crate::http::Transferer;
 use crate::path;
 use crate::path::EvaluationPath;
+use crate::pinning::ContainerResolver;
+use crate::provenance::ProvenanceRecorder;
+use crate::provenance::Status;
+use crate::relocation::relocate_outputs;
 use crate::tree::SyntaxNode;
 use crate::tree::SyntaxToken;
 use crate::v1::ExprEvaluator;
 use crate::v1::INPUTS_FILE;
 use crate::v1::OUTPUTS_FILE;
+use crate::v1::Plan;
+use crate::v1::Planner;
 use crate::v1::TaskEvaluator;
 use crate::v1::write_json_file;
 
@@ -562,6 +580,22 @@ struct State {
     calls_dir: PathBuf,
     /// The transferer for expression evaluation.
     transferer: Arc<dyn Transferer>,
+    /// The call cache to use.
+    ///
+    /// This is `None` when call caching is disabled.
+    cache: Option<Arc<CallCache>>,
+    /// The sender for engine events.
+    events: Option<broadcast::Sender<EngineEvent>>,
+    /// The recorder of call provenance.
+    ///
+    /// This is `None` when provenance is not being recorded.
+    provenance: Option<Arc<ProvenanceRecorder>>,
+    /// The recorder of the run's history.
+    ///
+    /// This is `None` when run history is not being recorded.
+    history: Option<RunRecorder>,
+    /// The resolver of task containers to references by digest.
+    containers: Arc<ContainerResolver>,
 }
 
 /// Represents a WDL V1 workflow evaluator.
@@ -577,6 +611,20 @@ pub struct WorkflowEvaluator {
     token: CancellationToken,
     /// The transferer for expression evaluation.
     transferer: Arc<dyn Transferer>,
+    /// The call cache to use.
+    ///
+    /// This is `None` when call caching is disabled.
+    cache: Option<Arc<CallCache>>,
+    /// The sender for engine events.
+    events: Option<broadcast::Sender<EngineEvent>>,
+    /// The run history database.
+    ///
+    /// This is `None` when run history is disabled.
+    history: Option<Arc<History>>,
+    /// The recorder of the history of the run being evaluated.
+    ///
+    /// This is `None` when not evaluating a run with history enabled.
+    run: Option<RunRecorder>,
 }
 
 impl WorkflowEvaluator {
@@ -591,17 +639,105 @@ impl WorkflowEvaluator {
 
         let config = Arc::new(config);
         let backend = config.create_backend(events.crankshaft().clone()).await?;
+        Self::with_backend(config, backend, token, events)
+    }
+
+    /// Constructs a new workflow evaluator with the given evaluation
+    /// configuration, task execution backend, cancellation token, and events.
+    ///
+    /// The backend is expected to have been created from the configuration
+    /// with [`Config::create_backend`]; evaluators sharing a backend share its
+    /// concurrency and resource limits.
+    ///
+    /// This method does not validate the configuration.
+    pub fn with_backend(
+        config: Arc<Config>,
+        backend: Arc<dyn TaskExecutionBackend>,
+        token: CancellationToken,
+        events: Events,
+    ) -> Result<Self> {
         let transferer =
             HttpTransferer::new(config.clone(), token.clone(), events.transfer().clone())?;
+        let cache = CallCache::new(&config.call_cache)?.map(Arc::new);
+        let history = History::new(&config.history)?.map(Arc::new);
+
+        Ok(Self {
+            config,
+            backend,
+            token,
+            transferer: Arc::new(transferer),
+            cache,
+            events: events.engine().clone(),
+            history,
+            run: None,
+        })
+    }
+
+    /// Constructs a new workflow evaluator for planning the execution of
+    /// workflows with [`WorkflowEvaluator::plan`].
+    ///
+    /// The evaluator uses a no-op task execution backend; evaluating a workflow
+    /// that calls a task with the evaluator will fail.
+    ///
+    /// Returns an error if the configuration isn't valid.
+    pub fn new_dry_run(config: Config, token: CancellationToken) -> Result<Self> {
+        config.validate()?;
+
+        let config = Arc::new(config);
+        let backend = Arc::new(NoopBackend::new(config.clone()));
+        let transferer = HttpTransferer::new(config.clone(), token.clone(), None)?;
 
         Ok(Self {
             config,
             backend,
             token,
             transferer: Arc::new(transferer),
+            cache: None,
+            events: None,
+            history: None,
+            run: None,
         })
     }
 
+    /// Plans the execution of the workflow of the given document without
+    /// executing any of its calls.
+    ///
+    /// Scatter shard counts, task requirements, and task constraints are
+    /// determined from the given inputs where possible; anything that depends
+    /// on the outputs of calls is left unresolved.
+    pub async fn plan(
+        &self,
+        document: &Document,
+        inputs: &WorkflowInputs,
+    ) -> EvaluationResult<Plan> {
+        let workflow = document
+            .workflow()
+            .context("document does not contain a workflow")?;
+
+        // We cannot plan a document with errors
+        if document.has_errors() {
+            return Err(anyhow!("cannot plan a document with errors").into());
+        }
+
+        inputs.validate(document, workflow, None).with_context(|| {
+            format!(
+                "failed to validate the inputs to workflow `{workflow}`",
+                workflow = workflow.name()
+            )
+        })?;
+
+        // Functions evaluated while planning may write files; use a temp directory that
+        // is removed once planning completes
+        let temp_dir = TempDir::new().context("failed to create temporary directory")?;
+        Ok(Planner::new(
+            self.backend.as_ref(),
+            self.transferer.as_ref(),
+            temp_dir.path(),
+        )
+        .plan(document, inputs)
+        .await?)
+    }
+
     /// Evaluates the workflow of the given document.
     ///
     /// Upon success, returns the outputs of the workflow.
@@ -620,8 +756,97 @@ impl WorkflowEvaluator {
             return Err(anyhow!("cannot evaluate a document with errors").into());
         }
 
-        self.perform_evaluation(document, inputs, root_dir.as_ref(), workflow.name())
+        let root_dir = root_dir.as_ref();
+        send_event!(
+            self.events,
+            EngineEvent::new(
+                workflow.name(),
+                None,
+                EngineEventKind::WorkflowStarted {
+                    root_dir: root_dir.to_path_buf(),
+                },
+            ),
+        );
+
+        let start = Instant::now();
+        let started = Utc::now();
+        let provenance = self
+            .config
+            .workflow
+            .provenance
+            .then(|| Arc::new(ProvenanceRecorder::default()));
+
+        // Record the run into the history database, if enabled
+        let evaluator = match &self.history {
+            Some(history) => &Self {
+                run: Some(history.start_run(
+                    history::Kind::Workflow,
+                    workflow.name(),
+                    document,
+                    root_dir,
+                    &inputs,
+                )),
+                ..self.clone()
+            },
+            None => self,
+        };
+
+        let mut result = evaluator
+            .perform_evaluation(
+                document,
+                inputs,
+                root_dir,
+                workflow.name(),
+                provenance.clone(),
+                Default::default(),
+            )
+            .await;
+
+        // Relocate the outputs and rewrite the outputs file with the relocated paths
+        if let (Ok(outputs), Some(config)) = (&mut result, &self.config.workflow.outputs)
+            && let Err(e) = async {
+                relocate_outputs(config, self.transferer.as_ref(), root_dir, outputs).await?;
+                write_json_file(root_dir.join(OUTPUTS_FILE), &*outputs)
+            }
             .await
+        {
+            result = Err(e.into());
+        }
+
+        // Write the provenance document regardless of the evaluation's success
+        if let Some(provenance) = provenance {
+            let status = if result.is_ok() {
+                Status::Succeeded
+            } else {
+                Status::Failed
+            };
+
+            if let Err(e) = provenance.write(root_dir, document, workflow.name(), started, status)
+                && result.is_ok()
+            {
+                result = Err(e.into());
+            }
+        }
+
+        if let Some(run) = &evaluator.run {
+            run.finish(result.as_ref(), self.token.is_cancelled());
+        }
+
+        let elapsed = start.elapsed();
+        send_event!(
+            self.events,
+            EngineEvent::new(
+                workflow.name(),
+                None,
+                if result.is_ok() {
+                    EngineEventKind::WorkflowCompleted { elapsed }
+                } else {
+                    EngineEventKind::WorkflowFailed { elapsed }
+                },
+            ),
+        );
+
+        result
     }
 
     /// Performs the evaluation of the workflow of the given document.
@@ -634,6 +859,8 @@ impl WorkflowEvaluator {
         inputs: WorkflowInputs,
         root_dir: &Path,
         id: &str,
+        provenance: Option<Arc<ProvenanceRecorder>>,
+        containers: Arc<ContainerResolver>,
     ) -> EvaluationResult<Outputs> {
         // Validate the inputs for the workflow
         let workflow = document
@@ -721,7 +948,9 @@ impl WorkflowEvaluator {
         let state = Arc::new(State {
             config: self.config.clone(),
             backend: self.backend.clone(),
-            token: self.token.clone(),
+            // Use a child token so that a failed evaluation only cancels its own calls and not
+            // other evaluations sharing this evaluator
+            token: self.token.child_token(),
             document: document.clone(),
             inputs,
             scopes: Default::default(),
@@ -731,6 +960,11 @@ impl WorkflowEvaluator {
             temp_dir,
             calls_dir,
             transferer: self.transferer.clone(),
+            cache: self.cache.clone(),
+            events: self.events.clone(),
+            provenance,
+            history: self.run.clone(),
+            containers,
         });
 
         // Evaluate the root graph to completion
@@ -1426,8 +1660,13 @@ impl WorkflowEvaluator {
         enum Evaluator<'a> {
             /// Used to evaluate a task call.
             Task(&'a Task, TaskEvaluator),
-            /// Used to evaluate a workflow call.
-            Workflow(WorkflowEvaluator),
+            /// Used to evaluate a workflow call with the provenance recorder
+            /// and container resolver to use.
+            Workflow(
+                WorkflowEvaluator,
+                Option<Arc<ProvenanceRecorder>>,
+                Arc<ContainerResolver>,
+            ),
         }
 
         impl Evaluator<'_> {
@@ -1441,6 +1680,7 @@ impl WorkflowEvaluator {
                 inputs: Inputs,
                 root_dir: &Path,
                 callee_id: &str,
+                scatter_index: Option<&str>,
             ) -> EvaluationResult<Outputs> {
                 match self {
                     Evaluator::Task(task, evaluator) => {
@@ -1452,11 +1692,12 @@ impl WorkflowEvaluator {
                                 &inputs.unwrap_task_inputs(),
                                 root_dir,
                                 callee_id,
+                                scatter_index,
                             )
                             .await?
                             .outputs
                     }
-                    Evaluator::Workflow(evaluator) => {
+                    Evaluator::Workflow(evaluator, provenance, containers) => {
                         debug!(caller_id, callee_id, "evaluating call to workflow");
                         evaluator
                             .perform_evaluation(
@@ -1464,6 +1705,8 @@ impl WorkflowEvaluator {
                                 inputs.unwrap_workflow_inputs(),
                                 root_dir,
                                 callee_id,
+                                provenance,
+                                containers,
                             )
                             .await
                     }
@@ -1544,18 +1787,31 @@ impl WorkflowEvaluator {
                         state.backend.clone(),
                         state.token.clone(),
                         state.transferer.clone(),
+                        state.cache.clone(),
+                        state.events.clone(),
+                        state.provenance.clone(),
+                        state.history.clone(),
+                        state.containers.clone(),
                     ),
                 ),
             ),
             _ => match document.workflow() {
                 Some(workflow) if workflow.name() == target.text() => (
                     inputs.unwrap_or_else(|| Inputs::Workflow(Default::default())),
-                    Evaluator::Workflow(WorkflowEvaluator {
-                        config: state.config.clone(),
-                        backend: state.backend.clone(),
-                        token: state.token.clone(),
-                        transferer: state.transferer.clone(),
-                    }),
+                    Evaluator::Workflow(
+                        WorkflowEvaluator {
+                            config: state.config.clone(),
+                            backend: state.backend.clone(),
+                            token: state.token.clone(),
+                            transferer: state.transferer.clone(),
+                            cache: state.cache.clone(),
+                            events: state.events.clone(),
+                            history: state.history.as_ref().map(|r| r.history().clone()),
+                            run: state.history.clone(),
+                        },
+                        state.provenance.clone(),
+                        state.containers.clone(),
+                    ),
                 ),
                 _ => {
                     return Err(EvaluationError::new(
@@ -1588,10 +1844,58 @@ impl WorkflowEvaluator {
             &scatter_index,
         );
 
+        let root_dir = state.calls_dir.join(&dir);
+        let scatter_index = (!scatter_index.is_empty()).then_some(scatter_index.as_str());
+        send_event!(
+            state.events,
+            EngineEvent::new(
+                &call_id,
+                scatter_index,
+                EngineEventKind::CallScheduled {
+                    root_dir: root_dir.clone(),
+                },
+            ),
+        );
+
+        if let Some(run) = &state.history {
+            run.start_call(
+                &call_id,
+                scatter_index,
+                match &evaluator {
+                    Evaluator::Task(..) => history::Kind::Task,
+                    Evaluator::Workflow(..) => history::Kind::Workflow,
+                },
+                target.text(),
+                &root_dir,
+                &inputs,
+            );
+        }
+
         // Finally, evaluate the task or workflow and return the outputs
-        let outputs = evaluator
-            .evaluate(id, document, inputs, &state.calls_dir.join(&dir), &call_id)
-            .await
+        let start = Instant::now();
+        let result = evaluator
+            .evaluate(id, document, inputs, &root_dir, &call_id, scatter_index)
+            .await;
+
+        if let Some(run) = &state.history {
+            run.finish_call(&root_dir, result.as_ref(), state.token.is_cancelled());
+        }
+
+        let elapsed = start.elapsed();
+        send_event!(
+            state.events,
+            EngineEvent::new(
+                &call_id,
+                scatter_index,
+                if result.is_ok() {
+                    EngineEventKind::CallCompleted { elapsed }
+                } else {
+                    EngineEventKind::CallFailed { elapsed }
+                },
+            ),
+        );
+
+        let outputs = result
             .map_err(|mut e| {
                 if let EvaluationError::Source(e) = &mut e {
                     e.backtrace.push(CallLocation {
@@ -1696,7 +2000,17 @@ mod test {
     use wdl_analysis::DiagnosticsConfig;
 
     use super::*;
+    use crate::FailureKind;
+    use crate::TaskExecutionConstraints;
+    use crate::TaskExecutionResult;
+    use crate::TaskSpawnRequest;
+    use crate::config::AzureStorageConfig;
     use crate::config::BackendConfig;
+    use crate::config::CallCacheConfig;
+    use crate::config::OutputLayout;
+    use crate::config::OutputsConfig;
+    use crate::config::StorageConfig;
+    use crate::config::WorkflowConfig;
 
     #[tokio::test]
     async fn it_writes_input_and_output_files() {
@@ -1978,4 +2292,1473 @@ workflow w {
         assert_eq!(state.tasks_started.load(Ordering::SeqCst), 10);
         assert_eq!(state.tasks_completed.load(Ordering::SeqCst), 10);
     }
+
+    #[tokio::test]
+    async fn it_reuses_cached_calls() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.1
+
+task t {
+  input {
+    String s
+  }
+
+  command <<<
+    echo ~{s} > out.txt
+  >>>
+
+  output {
+    File out = "out.txt"
+  }
+}
+
+workflow w {
+  call t as cached { input: s = "cached" }
+
+  output {
+    File out = cached.out
+  }
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+
+        // Analyze the source file
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        assert_eq!(results.len(), 1, "expected only one result");
+
+        let config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Local(Default::default()),
+            )]
+            .into(),
+            call_cache: CallCacheConfig {
+                enabled: true,
+                dir: Some(root_dir.path().join("cache")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let evaluator = WorkflowEvaluator::new(config, CancellationToken::new(), Events::none())
+            .await
+            .unwrap();
+
+        // Evaluate the workflow twice using different output directories
+        let mut outputs = Vec::new();
+        for dir in ["first", "second"] {
+            outputs.push(
+                evaluator
+                    .evaluate(
+                        results.first().expect("should have result").document(),
+                        WorkflowInputs::default(),
+                        root_dir.path().join(dir),
+                    )
+                    .await
+                    .map_err(|e| e.to_string())
+                    .expect("failed to evaluate workflow"),
+            );
+        }
+
+        // The second evaluation should reuse the output of the first, localized into
+        // its own output directory
+        let first = outputs[0].get("out").unwrap().as_file().unwrap().as_str();
+        let second = outputs[1].get("out").unwrap().as_file().unwrap().as_str();
+        assert!(first.starts_with(root_dir.path().join("first").to_str().unwrap()));
+        assert!(second.starts_with(root_dir.path().join("second").to_str().unwrap()));
+        assert!(
+            !root_dir
+                .path()
+                .join("second/calls/cached/attempts/0/command")
+                .exists(),
+            "the cached call should not have been executed"
+        );
+
+        // Deleting the first evaluation should not affect the outputs of the second
+        fs::remove_dir_all(root_dir.path().join("first")).unwrap();
+        assert_eq!(fs::read_to_string(second).unwrap().trim(), "cached");
+    }
+
+    #[tokio::test]
+    async fn it_does_not_reuse_calls_with_different_env() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.2
+
+task t {
+  input {
+    env String MODE
+  }
+
+  command <<<
+    echo "$MODE" > out.txt
+  >>>
+
+  output {
+    String out = read_string("out.txt")
+  }
+}
+
+workflow w {
+  call t as fast { MODE = "fast" }
+  call t as slow after fast { MODE = "slow" }
+
+  output {
+    String fast_out = fast.out
+    String slow_out = slow.out
+  }
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+
+        // Analyze the source file
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        assert_eq!(results.len(), 1, "expected only one result");
+
+        let config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Local(Default::default()),
+            )]
+            .into(),
+            call_cache: CallCacheConfig {
+                enabled: true,
+                dir: Some(root_dir.path().join("cache")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let evaluator = WorkflowEvaluator::new(config, CancellationToken::new(), Events::none())
+            .await
+            .unwrap();
+
+        let outputs = evaluator
+            .evaluate(
+                results.first().expect("should have result").document(),
+                WorkflowInputs::default(),
+                root_dir.path().join("out"),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .expect("failed to evaluate workflow");
+
+        // The calls differ only in an environment variable, so neither should be
+        // a cache hit for the other
+        assert_eq!(
+            outputs
+                .get("fast_out")
+                .unwrap()
+                .as_string()
+                .unwrap()
+                .as_str(),
+            "fast"
+        );
+        assert_eq!(
+            outputs
+                .get("slow_out")
+                .unwrap()
+                .as_string()
+                .unwrap()
+                .as_str(),
+            "slow"
+        );
+        for call in ["fast", "slow"] {
+            assert!(
+                root_dir
+                    .path()
+                    .join(format!("out/calls/{call}/attempts/0/command"))
+                    .exists(),
+                "the `{call}` call should have been executed"
+            );
+        }
+    }
+
+    /// A backend that reports reserved GPU devices without executing tasks.
+    struct GpuBackend;
+
+    impl TaskExecutionBackend for GpuBackend {
+        fn max_concurrency(&self) -> u64 {
+            1
+        }
+
+        fn constraints(
+            &self,
+            _: &HashMap<String, Value>,
+            _: &HashMap<String, Value>,
+        ) -> Result<TaskExecutionConstraints> {
+            Ok(TaskExecutionConstraints {
+                container: None,
+                cpu: 1.0,
+                memory: 1024,
+                gpu: vec!["nvidia".to_string(); 2],
+                fpga: Default::default(),
+                disks: Default::default(),
+            })
+        }
+
+        fn guest_inputs_dir(&self) -> Option<&'static str> {
+            None
+        }
+
+        fn needs_local_inputs(&self) -> bool {
+            true
+        }
+
+        fn spawn(
+            &self,
+            request: TaskSpawnRequest,
+            _: CancellationToken,
+        ) -> Result<tokio::sync::oneshot::Receiver<Result<TaskExecutionResult>>> {
+            let work_dir = request.attempt_dir().join("work");
+            fs::create_dir_all(&work_dir)?;
+            let stdout = request.attempt_dir().join("stdout");
+            let stderr = request.attempt_dir().join("stderr");
+            fs::write(&stdout, "")?;
+            fs::write(&stderr, "")?;
+
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            let _ = tx.send(Ok(TaskExecutionResult {
+                exit_code: 0,
+                work_dir: EvaluationPath::Local(work_dir),
+                stdout: PrimitiveValue::new_file(stdout.to_str().unwrap()).into(),
+                stderr: PrimitiveValue::new_file(stderr.to_str().unwrap()).into(),
+                usage: None,
+                gpus: vec!["2".to_string(), "3".to_string()],
+            }));
+            Ok(rx)
+        }
+    }
+
+    #[tokio::test]
+    async fn it_reports_reserved_gpus() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.2
+
+task t {
+  command <<<>>>
+
+  requirements {
+    gpu: true
+  }
+
+  output {
+    Array[String] gpus = task.gpu
+  }
+}
+
+workflow w {
+  call t
+
+  output {
+    Array[String] gpus = t.gpus
+  }
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+
+        // Analyze the source file
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        assert_eq!(results.len(), 1, "expected only one result");
+
+        let evaluator = WorkflowEvaluator::with_backend(
+            Default::default(),
+            Arc::new(GpuBackend),
+            CancellationToken::new(),
+            Events::none(),
+        )
+        .unwrap();
+        let outputs = evaluator
+            .evaluate(
+                results.first().expect("should have result").document(),
+                WorkflowInputs::default(),
+                root_dir.path().join("out"),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .expect("failed to evaluate workflow");
+
+        // The outputs should see the reserved devices rather than the constraints
+        let gpus: Vec<_> = outputs
+            .get("gpus")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .as_slice()
+            .iter()
+            .map(|v| v.as_string().unwrap().to_string())
+            .collect();
+        assert_eq!(gpus, ["2", "3"]);
+    }
+
+    #[tokio::test]
+    async fn it_does_not_persist_authenticated_urls() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.2
+
+task t {
+  input {
+    File bam
+  }
+
+  command <<<
+    echo '~{bam}'
+  >>>
+
+  output {
+    String out = read_string(stdout())
+  }
+
+  hints {
+    localization_optional: true
+  }
+}
+
+workflow w {
+  call t { bam = "https://account.blob.core.windows.net/data/sample.bam" }
+
+  output {
+    String out = t.out
+  }
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+
+        // Analyze the source file
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        assert_eq!(results.len(), 1, "expected only one result");
+
+        let config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Local(Default::default()),
+            )]
+            .into(),
+            workflow: WorkflowConfig {
+                provenance: true,
+                ..Default::default()
+            },
+            storage: StorageConfig {
+                azure: AzureStorageConfig {
+                    auth: [(
+                        "account".to_string(),
+                        [("data".to_string(), "sig=secret".into())].into(),
+                    )]
+                    .into(),
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let evaluator = WorkflowEvaluator::new(config, CancellationToken::new(), Events::none())
+            .await
+            .unwrap();
+        let outputs = evaluator
+            .evaluate(
+                results.first().expect("should have result").document(),
+                WorkflowInputs::default(),
+                root_dir.path().join("out"),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .expect("failed to evaluate workflow");
+
+        // The executed command should use the authenticated URL
+        assert_eq!(
+            outputs.get("out").unwrap().as_string().unwrap().trim(),
+            "https://account.blob.core.windows.net/data/sample.bam?sig=secret"
+        );
+
+        // The provenance document should only contain the unauthenticated URL
+        let provenance = read_to_string(
+            root_dir
+                .path()
+                .join("out")
+                .join(crate::provenance::PROVENANCE_FILE),
+        )
+        .unwrap();
+        assert!(
+            provenance.contains("echo 'https://account.blob.core.windows.net/data/sample.bam'")
+        );
+        assert!(!provenance.contains("sig=secret"));
+    }
+
+    #[tokio::test]
+    async fn it_resumes_from_completed_calls() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.1
+
+task t {
+  input {
+    String s
+    String counter
+  }
+
+  command <<<
+    echo ~{s} >> '~{counter}'
+    echo ~{s} > out.txt
+  >>>
+
+  output {
+    File out = "out.txt"
+  }
+}
+
+task check {
+  input {
+    String flag
+    Array[File] files
+  }
+
+  command <<<
+    test -f '~{flag}'
+  >>>
+}
+
+workflow w {
+  input {
+    String suffix
+    String counter
+    String flag
+  }
+
+  scatter (i in [1, 2]) {
+    call t { input: s = "~{i}~{suffix}", counter = counter }
+  }
+
+  call check { input: flag = flag, files = t.out }
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+
+        // Analyze the source file
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        assert_eq!(results.len(), 1, "expected only one result");
+        let document = results.first().expect("should have result").document();
+
+        let mut config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Local(Default::default()),
+            )]
+            .into(),
+            ..Default::default()
+        };
+        config.task.resume = true;
+
+        let counter = root_dir.path().join("counter.txt");
+        let flag = root_dir.path().join("flag");
+        let inputs = |suffix: &str| {
+            let mut inputs = WorkflowInputs::default();
+            inputs.set("suffix", PrimitiveValue::new_string(suffix));
+            inputs.set(
+                "counter",
+                PrimitiveValue::new_string(counter.to_str().unwrap()),
+            );
+            inputs.set("flag", PrimitiveValue::new_string(flag.to_str().unwrap()));
+            inputs
+        };
+        let output_dir = root_dir.path().join("outputs");
+        // Each evaluation uses a new evaluator so that it resumes from the output
+        // directory as a separate invocation of the engine would
+        let evaluate = async |suffix| {
+            WorkflowEvaluator::new(config.clone(), CancellationToken::new(), Events::none())
+                .await
+                .unwrap()
+                .evaluate(document, inputs(suffix), &output_dir)
+                .await
+                .map_err(|e| e.to_string())
+        };
+        let executions = || read_to_string(&counter).unwrap().lines().count();
+
+        // The first evaluation should fail after the scattered calls complete
+        evaluate("a").await.expect_err("evaluation should fail");
+        assert_eq!(executions(), 2);
+
+        // Resuming the evaluation should not execute the scattered calls again
+        fs::write(&flag, "").unwrap();
+        evaluate("a").await.expect("failed to evaluate workflow");
+        assert_eq!(executions(), 2);
+
+        // Changing the inputs should execute the scattered calls again
+        evaluate("b").await.expect("failed to evaluate workflow");
+        assert_eq!(executions(), 4);
+    }
+
+    #[tokio::test]
+    async fn it_sends_engine_events() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.1
+
+task t {
+  input {
+    String marker
+  }
+
+  command <<<
+    if [ ! -f '~{marker}' ]; then
+      touch '~{marker}'
+      exit 1
+    fi
+  >>>
+
+  runtime {
+    maxRetries: 1
+  }
+}
+
+workflow w {
+  input {
+    String prefix
+  }
+
+  scatter (i in [0, 1]) {
+    call t { input: marker = "~{prefix}~{i}" }
+  }
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+
+        // Analyze the source file
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        assert_eq!(results.len(), 1, "expected only one result");
+
+        let config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Local(Default::default()),
+            )]
+            .into(),
+            ..Default::default()
+        };
+        let events = Events::engine_only(100);
+        let mut rx = events.subscribe_engine().unwrap();
+        let evaluator = WorkflowEvaluator::new(config, CancellationToken::new(), events)
+            .await
+            .unwrap();
+
+        let mut inputs = WorkflowInputs::default();
+        inputs.set(
+            "prefix",
+            PrimitiveValue::new_string(root_dir.path().join("marker-").to_str().unwrap()),
+        );
+
+        let output_dir = root_dir.path().join("outputs");
+        evaluator
+            .evaluate(
+                results.first().expect("should have result").document(),
+                inputs,
+                &output_dir,
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .expect("failed to evaluate workflow");
+
+        drop(evaluator);
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+
+        assert!(matches!(
+            events.first().map(|e| (e.id.as_str(), &e.kind)),
+            Some(("w", EngineEventKind::WorkflowStarted { .. }))
+        ));
+        assert!(matches!(
+            events.last().map(|e| (e.id.as_str(), &e.kind)),
+            Some(("w", EngineEventKind::WorkflowCompleted { .. }))
+        ));
+
+        // Each call should be scheduled, fail once, and then succeed on retry
+        for index in ["0", "1"] {
+            let id = format!("t-{index}");
+            let call_events: Vec<_> = events.iter().filter(|e| e.id == id).collect();
+            assert!(
+                call_events
+                    .iter()
+                    .all(|e| e.scatter_index.as_deref() == Some(index))
+            );
+
+            let call_dir = output_dir.join("calls").join(&id);
+            match call_events
+                .iter()
+                .map(|e| &e.kind)
+                .collect::<Vec<_>>()
+                .as_slice()
+            {
+                [
+                    EngineEventKind::CallScheduled { root_dir },
+                    EngineEventKind::TaskStarted {
+                        attempt: 0,
+                        attempt_dir: first,
+                    },
+                    EngineEventKind::TaskRetried {
+                        attempt: 0,
+                        attempt_dir: retried,
+                        exit_code: Some(1),
+                        usage: Some(_),
+                    },
+                    EngineEventKind::TaskStarted {
+                        attempt: 1,
+                        attempt_dir: second,
+                    },
+                    EngineEventKind::TaskCompleted {
+                        attempt: 1,
+                        attempt_dir: completed,
+                        exit_code: 0,
+                        usage: Some(_),
+                        ..
+                    },
+                    EngineEventKind::CallCompleted { .. },
+                ] => {
+                    assert_eq!(root_dir, &call_dir);
+                    assert_eq!(first, &call_dir.join("attempts").join("0"));
+                    assert_eq!(retried, first);
+                    assert_eq!(second, &call_dir.join("attempts").join("1"));
+                    assert_eq!(completed, second);
+                    assert!(first.join("usage.json").is_file());
+                    assert!(second.join("usage.json").is_file());
+                }
+                kinds => panic!("unexpected events for call `{id}`: {kinds:#?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn it_writes_provenance() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.1
+
+task t {
+  input {
+    File f
+  }
+
+  command <<<
+    cat '~{f}' > out.txt
+    if [ ! -f '~{f}.marker' ]; then
+      touch '~{f}.marker'
+      exit 1
+    fi
+  >>>
+
+  runtime {
+    container: "ubuntu@sha256:0123"
+    maxRetries: 1
+  }
+
+  output {
+    File out = "out.txt"
+  }
+}
+
+workflow w {
+  input {
+    File f
+  }
+
+  call t { input: f = f }
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+        fs::write(root_dir.path().join("input.txt"), "hello").unwrap();
+
+        // Analyze the source file
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        assert_eq!(results.len(), 1, "expected only one result");
+
+        let mut config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Local(Default::default()),
+            )]
+            .into(),
+            ..Default::default()
+        };
+        config.workflow.provenance = true;
+        let evaluator = WorkflowEvaluator::new(config, CancellationToken::new(), Events::none())
+            .await
+            .unwrap();
+
+        let mut inputs = WorkflowInputs::default();
+        inputs.set(
+            "f",
+            PrimitiveValue::new_file(root_dir.path().join("input.txt").to_str().unwrap()),
+        );
+
+        let output_dir = root_dir.path().join("outputs");
+        evaluator
+            .evaluate(
+                results.first().expect("should have result").document(),
+                inputs,
+                &output_dir,
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .expect("failed to evaluate workflow");
+
+        let provenance: serde_json::Value = serde_json::from_str(
+            &read_to_string(output_dir.join(crate::provenance::PROVENANCE_FILE)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(provenance["workflow"], "w");
+        assert_eq!(provenance["status"], "succeeded");
+
+        let calls = provenance["calls"].as_array().unwrap();
+        assert_eq!(calls.len(), 1);
+        let call = &calls[0];
+        assert_eq!(call["id"], "t");
+        assert_eq!(call["status"], "succeeded");
+        assert_eq!(call["container"], "ubuntu@sha256:0123");
+        assert_eq!(call["container_digest"], "sha256:0123");
+        assert_eq!(call["requirements"]["maxRetries"], 1);
+        assert!(call["command"].as_str().unwrap().contains("out.txt"));
+
+        // Both the input and output have the same contents and therefore digest
+        let digest = blake3::hash(b"hello").to_hex().to_string();
+        assert_eq!(call["inputs"][0]["kind"], "file");
+        assert_eq!(call["inputs"][0]["digest"], digest.as_str());
+        assert_eq!(call["outputs"][0]["name"], "out");
+        assert_eq!(call["outputs"][0]["digest"], digest.as_str());
+
+        let attempts = call["attempts"].as_array().unwrap();
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[0]["exit_code"], 1);
+        assert_eq!(attempts[1]["exit_code"], 0);
+    }
+
+    #[tokio::test]
+    async fn it_records_history() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.1
+
+task align_reads {
+  input {
+    String sample
+  }
+
+  command <<<
+    if [ '~{sample}' = 'b' ] && [ ! -f ../../marker ]; then
+      touch ../../marker
+      exit 1
+    fi
+    echo '~{sample}.bam'
+  >>>
+
+  runtime {
+    maxRetries: 1
+  }
+
+  output {
+    String bam = read_string(stdout())
+  }
+}
+
+workflow w {
+  input {
+    Array[String] samples
+  }
+
+  scatter (sample in samples) {
+    call align_reads { input: sample }
+  }
+
+  output {
+    Array[String] bams = align_reads.bam
+  }
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+
+        // Analyze the source file
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        assert_eq!(results.len(), 1, "expected only one result");
+        let document = results.first().expect("should have result").document();
+
+        let mut config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Local(Default::default()),
+            )]
+            .into(),
+            ..Default::default()
+        };
+        config.history.enabled = true;
+        config.history.path = Some(root_dir.path().join("history.db"));
+        let evaluator = WorkflowEvaluator::new(config, CancellationToken::new(), Events::none())
+            .await
+            .unwrap();
+
+        let mut inputs = WorkflowInputs::default();
+        inputs.set(
+            "samples",
+            serde_json::from_str::<Value>("[\"a\", \"b\"]").unwrap(),
+        );
+        evaluator
+            .evaluate(document, inputs, root_dir.path().join("first"))
+            .await
+            .map_err(|e| e.to_string())
+            .expect("failed to evaluate workflow");
+
+        // A second run with a missing input fails
+        evaluator
+            .evaluate(
+                document,
+                WorkflowInputs::default(),
+                root_dir.path().join("second"),
+            )
+            .await
+            .expect_err("evaluation should fail");
+
+        let history = History::open(root_dir.path().join("history.db")).unwrap();
+        let runs = history.runs(&Default::default()).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].status, history::Status::Failed);
+        assert!(runs[0].outputs.is_none());
+        assert!(
+            runs[0].error.as_deref().unwrap().contains("samples"),
+            "unexpected error: {error:?}",
+            error = runs[0].error
+        );
+
+        let runs = history
+            .runs(&history::RunFilter {
+                name: Some("w".to_string()),
+                status: Some(history::Status::Succeeded),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(runs.len(), 1);
+        let run = &runs[0];
+        assert_eq!(run.kind, history::Kind::Workflow);
+        assert_eq!(run.inputs, serde_json::json!({ "samples": ["a", "b"] }));
+        assert_eq!(
+            run.outputs,
+            Some(serde_json::json!({ "bams": ["a.bam", "b.bam"] }))
+        );
+        assert!(run.ended.unwrap() >= run.started);
+
+        // Find the last successful call of `align_reads` on sample `b`
+        let calls = history
+            .calls(&history::CallFilter {
+                target: Some("align_reads".to_string()),
+                status: Some(history::Status::Succeeded),
+                inputs: vec![("sample".to_string(), serde_json::json!("b"))],
+                limit: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(calls.len(), 1);
+        let call = &calls[0];
+        assert_eq!(call.run, run.id);
+        assert_eq!(call.id, "align_reads-1");
+        assert_eq!(call.scatter_index.as_deref(), Some("1"));
+        assert_eq!(call.outputs, Some(serde_json::json!({ "bam": "b.bam" })));
+
+        let attempts = history.attempts(call).unwrap();
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[0].exit_code, Some(1));
+        assert_eq!(attempts[1].exit_code, Some(0));
+        assert!(attempts[1].attempt_dir.starts_with(&call.root_dir));
+
+        let calls = history
+            .calls(&history::CallFilter {
+                run: Some(run.id.clone()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(calls.len(), 2);
+        assert!(history.run(&run.id).unwrap().is_some());
+        assert!(history.run("missing").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn it_relocates_outputs() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.1
+
+task t {
+  input {
+    String s
+  }
+
+  command <<<
+    mkdir out
+    echo '~{s}' > out/out.txt
+  >>>
+
+  output {
+    File out = "out/out.txt"
+  }
+}
+
+workflow w {
+  input {
+    File f
+  }
+
+  scatter (s in ["a", "b"]) {
+    call t { input: s }
+  }
+
+  output {
+    Array[File] outs = t.out
+    File input = f
+  }
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+        fs::write(root_dir.path().join("input.txt"), "hello").unwrap();
+
+        // Analyze the source file
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        assert_eq!(results.len(), 1, "expected only one result");
+        let document = results.first().expect("should have result").document();
+
+        let relocated_dir = root_dir.path().join("relocated");
+        for layout in [OutputLayout::Flat, OutputLayout::Call] {
+            let mut config = Config {
+                backends: [(
+                    "default".to_string(),
+                    BackendConfig::Local(Default::default()),
+                )]
+                .into(),
+                ..Default::default()
+            };
+            config.workflow.outputs = Some(OutputsConfig {
+                dir: relocated_dir.to_str().unwrap().to_string(),
+                layout,
+                strategy: None,
+            });
+            let evaluator =
+                WorkflowEvaluator::new(config, CancellationToken::new(), Events::none())
+                    .await
+                    .unwrap();
+
+            let mut inputs = WorkflowInputs::default();
+            inputs.set(
+                "f",
+                PrimitiveValue::new_file(root_dir.path().join("input.txt").to_str().unwrap()),
+            );
+
+            let output_dir = root_dir.path().join("outputs");
+            let outputs = evaluator
+                .evaluate(document, inputs, &output_dir)
+                .await
+                .map_err(|e| e.to_string())
+                .unwrap();
+
+            // Both shards output a file named `out.txt`, so the flat layout prefixes
+            // the names with the shard's call directory
+            let (a, b) = match layout {
+                OutputLayout::Flat => (
+                    relocated_dir.join("t-0-out.txt"),
+                    relocated_dir.join("t-1-out.txt"),
+                ),
+                OutputLayout::Call => (
+                    relocated_dir.join("t-0").join("out").join("out.txt"),
+                    relocated_dir.join("t-1").join("out").join("out.txt"),
+                ),
+            };
+            let input = relocated_dir.join("input.txt");
+            assert_eq!(read_to_string(&a).unwrap().trim(), "a");
+            assert_eq!(read_to_string(&b).unwrap().trim(), "b");
+            assert_eq!(read_to_string(&input).unwrap(), "hello");
+
+            let outs = outputs.get("outs").unwrap().as_array().unwrap().as_slice();
+            assert_eq!(outs[0].as_file().unwrap().as_str(), a.to_str().unwrap());
+            assert_eq!(outs[1].as_file().unwrap().as_str(), b.to_str().unwrap());
+
+            // The outputs file is rewritten with the relocated paths
+            let written: serde_json::Value =
+                serde_json::from_str(&read_to_string(output_dir.join(OUTPUTS_FILE)).unwrap())
+                    .unwrap();
+            assert_eq!(written["input"], input.to_str().unwrap());
+            assert_eq!(written["outs"][1], b.to_str().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn it_classifies_task_failures() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.2
+
+task t {
+  input {
+    Boolean kill
+  }
+
+  command <<<
+    if ~{kill}; then
+      kill -9 $$
+    fi
+
+    exit 1
+  >>>
+
+  requirements {
+    max_retries: 2
+  }
+}
+
+workflow w {
+  input {
+    Boolean kill
+  }
+
+  call t { input: kill }
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+
+        // Analyze the source file
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        assert_eq!(results.len(), 1, "expected only one result");
+        let document = results.first().expect("should have result").document();
+
+        for (kill, expected) in [(false, FailureKind::Command), (true, FailureKind::System)] {
+            let mut config = Config {
+                backends: [(
+                    "default".to_string(),
+                    BackendConfig::Local(Default::default()),
+                )]
+                .into(),
+                ..Default::default()
+            };
+            config.task.retry_policy.command = Some(1);
+            config.task.retry_policy.system = 2;
+            let evaluator =
+                WorkflowEvaluator::new(config, CancellationToken::new(), Events::none())
+                    .await
+                    .unwrap();
+
+            let mut inputs = WorkflowInputs::default();
+            inputs.set("kill", kill);
+
+            let output_dir = root_dir.path().join(format!("outputs-{expected}"));
+            let e = evaluator
+                .evaluate(document, inputs, &output_dir)
+                .await
+                .map(|_| ())
+                .unwrap_err();
+            assert_eq!(e.failure(), Some(expected));
+
+            // The failed command is retried once as the retry policy caps the
+            // `max_retries` requirement, but the attempt the backend failed to execute is
+            // retried twice
+            let attempts = fs::read_dir(output_dir.join("calls").join("t").join("attempts"))
+                .unwrap()
+                .count();
+            assert_eq!(attempts, if kill { 3 } else { 2 });
+        }
+
+        // An attempt that failed as evaluation was cancelled is not retried
+        let mut config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Local(Default::default()),
+            )]
+            .into(),
+            ..Default::default()
+        };
+        config.task.retry_policy.system = 2;
+        let token = CancellationToken::new();
+        let evaluator = WorkflowEvaluator::new(config, token.clone(), Events::none())
+            .await
+            .unwrap();
+        token.cancel();
+
+        let mut inputs = WorkflowInputs::default();
+        inputs.set("kill", false);
+
+        let output_dir = root_dir.path().join("outputs-cancelled");
+        let e = evaluator
+            .evaluate(document, inputs, &output_dir)
+            .await
+            .map(|_| ())
+            .unwrap_err();
+        assert!(
+            format!("{e:?}").contains("task evaluation has been cancelled"),
+            "unexpected error `{e:?}`"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_plans_workflow_execution() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.1
+
+task t {
+  input {
+    String image
+    Int n
+  }
+
+  command <<<
+    echo ~{n}
+  >>>
+
+  runtime {
+    container: image
+    cpu: n
+    memory: "~{n} GiB"
+  }
+
+  output {
+    Int out = n
+  }
+}
+
+workflow w {
+  input {
+    Array[Int] xs
+    Boolean skip = true
+  }
+
+  scatter (x in xs) {
+    call t as a { input: image = "ubuntu:22.04", n = 2 }
+  }
+
+  call t as b { input: image = "alpine", n = length(a.out) }
+
+  if (!skip) {
+    call t as c { input: image = "alpine", n = 1 }
+  }
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+
+        // Analyze the source file
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        assert_eq!(results.len(), 1, "expected only one result");
+
+        let evaluator =
+            WorkflowEvaluator::new_dry_run(Config::default(), CancellationToken::new()).unwrap();
+
+        let mut inputs = WorkflowInputs::default();
+        inputs.set(
+            "xs",
+            Array::new(None, ArrayType::new(PrimitiveType::Integer), [1, 2, 3]).unwrap(),
+        );
+
+        let plan = evaluator
+            .plan(
+                results.first().expect("should have result").document(),
+                &inputs,
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .expect("failed to plan workflow");
+
+        assert_eq!(plan.workflow, "w");
+        assert_eq!(plan.calls.len(), 3);
+
+        // The scattered call is fully resolved
+        let a = plan.calls.iter().find(|c| c.id == "a").unwrap();
+        assert_eq!(a.target, "t");
+        assert_eq!(a.scatters.len(), 1);
+        assert_eq!(a.scatters[0].variable, "x");
+        assert_eq!(a.scatters[0].shards, Some(3));
+        assert_eq!(a.executions, Some(3));
+        assert_eq!(a.container.as_deref(), Some("ubuntu:22.04"));
+        assert_eq!(a.cpu, Some(2.0));
+        assert_eq!(a.memory, Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(a.requirements["memory"], "2 GiB");
+        assert!(a.unresolved.is_empty());
+
+        // The call's requirements depend on the outputs of another call
+        let b = plan.calls.iter().find(|c| c.id == "b").unwrap();
+        assert_eq!(b.executions, Some(1));
+        assert_eq!(b.container, None);
+        assert_eq!(b.requirements["container"], "alpine");
+        assert_eq!(b.unresolved, ["cpu", "memory"]);
+
+        // The conditional call will not execute
+        let c = plan.calls.iter().find(|c| c.id == "c").unwrap();
+        assert_eq!(c.conditionals.len(), 1);
+        assert_eq!(c.conditionals[0].expr, "!skip");
+        assert_eq!(c.conditionals[0].value, Some(false));
+        assert_eq!(c.executions, Some(0));
+
+        assert_eq!(plan.dependencies.len(), 1);
+        assert_eq!(plan.dependencies[0].from, "a");
+        assert_eq!(plan.dependencies[0].to, "b");
+
+        let dot = plan.to_dot();
+        assert!(dot.starts_with("digraph \"w\" {\n"));
+        assert!(dot.contains("  \"a\" [label=\"a\\nubuntu:22.04\\nexecutions: 3\", shape=box];"));
+        assert!(
+            dot.contains(
+                "  \"c\" [label=\"c\\nalpine\\nexecutions: 0\", shape=box, style=dashed];"
+            )
+        );
+        assert!(dot.contains("  \"a\" -> \"b\";"));
+    }
+
+    #[tokio::test]
+    async fn it_plans_with_known_differences() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.1
+
+task t {
+  input {
+    Int n
+    String image = "ubuntu:22.04"
+  }
+
+  command <<<
+    echo ~{n}
+  >>>
+
+  runtime {
+    container: image
+    cpu: n
+  }
+
+  output {
+    Int out = n
+  }
+}
+
+workflow w {
+  input {
+    Array[Int] xs = [1, 2]
+    Boolean run = true
+  }
+
+  # The scatter variable is not known
+  scatter (x in xs) {
+    Int doubled = x * 2
+    call t as per_shard { input: n = doubled }
+  }
+
+  # Declarations in scatter and conditional statements are not gathered
+  call t as gathered { input: n = length(doubled) }
+
+  if (run) {
+    Int one = 1
+  }
+
+  call t as optional { input: n = select_first([one, 2]) }
+
+  # Call outputs are never known
+  call t as from_output { input: n = gathered.out }
+
+  # Evaluation failures are unresolved
+  call t as failed { input: n = xs[5] }
+
+  # Calls that do not depend on the differences are resolved
+  call t as resolved { input: n = length(xs) }
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+
+        // Analyze the source file
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        assert_eq!(results.len(), 1, "expected only one result");
+
+        let evaluator =
+            WorkflowEvaluator::new_dry_run(Config::default(), CancellationToken::new()).unwrap();
+        let plan = evaluator
+            .plan(
+                results.first().expect("should have result").document(),
+                &WorkflowInputs::default(),
+            )
+            .await
+            .map_err(|e| e.to_string())
+            .expect("failed to plan workflow");
+
+        for id in ["per_shard", "gathered", "optional", "from_output", "failed"] {
+            let call = plan.calls.iter().find(|c| c.id == id).unwrap();
+            assert_eq!(call.unresolved, ["cpu"], "call `{id}`");
+            assert_eq!(call.requirements["container"], "ubuntu:22.04");
+            assert_eq!(call.cpu, None, "call `{id}`");
+        }
+
+        // The scattered call is planned once
+        let per_shard = plan.calls.iter().find(|c| c.id == "per_shard").unwrap();
+        assert_eq!(per_shard.scatters[0].shards, Some(2));
+        assert_eq!(per_shard.executions, Some(2));
+
+        let resolved = plan.calls.iter().find(|c| c.id == "resolved").unwrap();
+        assert!(resolved.unresolved.is_empty());
+        assert_eq!(resolved.cpu, Some(2.0));
+    }
 }
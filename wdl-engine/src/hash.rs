@@ -16,6 +16,8 @@ use tokio::task::spawn_blocking;
 use url::Url;
 use walkdir::WalkDir;
 
+use crate::Input;
+
 /// Represents a calculated [Blake3](https://github.com/BLAKE3-team/BLAKE3) digest of a file or directory.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Digest {
@@ -35,6 +37,45 @@ pub enum Digest {
 static DIGESTS: LazyLock<Mutex<HashMap<PathBuf, Arc<OnceCell<Digest>>>>> =
     LazyLock::new(Mutex::default);
 
+/// Represents the digests of the local paths of a task's inputs.
+///
+/// The digests are calculated once per task and shared by the call cache key
+/// and the task's provenance.
+#[derive(Debug, Default)]
+pub struct InputDigests(Vec<Result<Option<Digest>, String>>);
+
+impl InputDigests {
+    /// Calculates the digests of the given inputs.
+    ///
+    /// The digest of an input without a local path is `None`; a failure to
+    /// calculate a digest is kept as an error message so that each user of the
+    /// digests can decide how to handle it.
+    pub async fn calculate(inputs: &[Input]) -> Self {
+        let mut digests = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            digests.push(match input.local_path() {
+                Some(path) => calculate_path_digest(path).await.map(Some).map_err(|e| {
+                    format!(
+                        "failed to calculate digest of input `{path}`: {e:#}",
+                        path = path.display()
+                    )
+                }),
+                None => Ok(None),
+            });
+        }
+
+        Self(digests)
+    }
+
+    /// Iterates over the digests in the order of the inputs they were
+    /// calculated from.
+    pub fn iter(&self) -> impl Iterator<Item = Result<Option<Digest>, &str>> {
+        self.0
+            .iter()
+            .map(|d| d.as_ref().copied().map_err(String::as_str))
+    }
+}
+
 /// An extension trait for joining a digest to a URL.
 pub trait UrlDigestExt: Sized {
     /// Joins the given digest to the URL.
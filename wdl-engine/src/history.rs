@@ -0,0 +1,874 @@
+//! Implementation of the run history database.
+//!
+//! When enabled, the engine records each workflow or task run it evaluates
+//! into a SQLite database along with the calls made by the run and the
+//! attempts made to execute its tasks.
+//!
+//! Calls are identified by the same identifiers used by engine events. As the
+//! calls of nested workflows may share an identifier with the calls of their
+//! caller, a call is uniquely identified within a run by its root directory.
+//!
+//! Statements that record history are executed by a dedicated writer thread
+//! so that evaluation does not block on the database; queries wait for
+//! previously recorded statements to be executed.
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::Context;
+use anyhow::Result;
+use chrono::DateTime;
+use chrono::Utc;
+use rusqlite::Connection;
+use rusqlite::OptionalExtension;
+use rusqlite::Row;
+use rusqlite::ToSql;
+use rusqlite::params;
+use rusqlite::params_from_iter;
+use rusqlite::types::FromSql;
+use rusqlite::types::FromSqlError;
+use rusqlite::types::FromSqlResult;
+use rusqlite::types::ToSqlOutput;
+use rusqlite::types::Value;
+use rusqlite::types::ValueRef;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::warn;
+use wdl_analysis::Document;
+
+use crate::EvaluationError;
+use crate::Outputs;
+use crate::config::HistoryConfig;
+
+/// The default history database path that is appended to the system data
+/// directory.
+const DEFAULT_HISTORY_PATH: &str = "wdl/history.db";
+
+/// The amount of time to wait for another process to release a lock on the
+/// database.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The schema of the history database.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id TEXT PRIMARY KEY NOT NULL,
+    kind TEXT NOT NULL,
+    name TEXT NOT NULL,
+    document TEXT NOT NULL,
+    root_dir TEXT NOT NULL,
+    inputs TEXT NOT NULL,
+    outputs TEXT,
+    error TEXT,
+    status TEXT NOT NULL,
+    started TEXT NOT NULL,
+    ended TEXT
+);
+CREATE INDEX IF NOT EXISTS runs_by_name ON runs (name, started);
+CREATE TABLE IF NOT EXISTS calls (
+    run TEXT NOT NULL,
+    id TEXT NOT NULL,
+    scatter_index TEXT,
+    kind TEXT NOT NULL,
+    target TEXT NOT NULL,
+    root_dir TEXT NOT NULL,
+    inputs TEXT NOT NULL,
+    outputs TEXT,
+    error TEXT,
+    status TEXT NOT NULL,
+    started TEXT NOT NULL,
+    ended TEXT,
+    PRIMARY KEY (run, root_dir)
+);
+CREATE INDEX IF NOT EXISTS calls_by_target ON calls (target, started);
+CREATE TABLE IF NOT EXISTS attempts (
+    run TEXT NOT NULL,
+    call_dir TEXT NOT NULL,
+    attempt INTEGER NOT NULL,
+    attempt_dir TEXT NOT NULL,
+    exit_code INTEGER,
+    started TEXT NOT NULL,
+    ended TEXT NOT NULL,
+    PRIMARY KEY (run, call_dir, attempt)
+);
+";
+
+/// The columns selected when querying runs.
+const RUN_COLUMNS: &str =
+    "id, kind, name, document, root_dir, inputs, outputs, error, status, started, ended";
+
+/// The columns selected when querying calls.
+const CALL_COLUMNS: &str = "run, id, scatter_index, kind, target, root_dir, inputs, outputs, \
+                            error, status, started, ended";
+
+/// Represents the kind of a run or call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// The run or call is of a workflow.
+    Workflow,
+    /// The run or call is of a task.
+    Task,
+}
+
+impl Kind {
+    /// Gets the string representation of the kind.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Workflow => "workflow",
+            Self::Task => "task",
+        }
+    }
+}
+
+impl ToSql for Kind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for Kind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "workflow" => Ok(Self::Workflow),
+            "task" => Ok(Self::Task),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+/// Represents the status of a run or call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// The run or call is being evaluated.
+    ///
+    /// A run is left in this status if the engine stopped before the run
+    /// finished.
+    Running,
+    /// The run or call succeeded.
+    Succeeded,
+    /// The run or call failed.
+    Failed,
+    /// The run or call was canceled.
+    Canceled,
+}
+
+impl Status {
+    /// Gets the status of a finished run or call from its result.
+    fn new<T>(result: Result<T, &EvaluationError>, canceled: bool) -> Self {
+        match result {
+            Ok(_) => Self::Succeeded,
+            Err(_) if canceled => Self::Canceled,
+            Err(_) => Self::Failed,
+        }
+    }
+
+    /// Gets the string representation of the status.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::Canceled => "canceled",
+        }
+    }
+}
+
+impl ToSql for Status {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for Status {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "running" => Ok(Self::Running),
+            "succeeded" => Ok(Self::Succeeded),
+            "failed" => Ok(Self::Failed),
+            "canceled" => Ok(Self::Canceled),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+/// Represents a recorded run of a workflow or task.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RunRecord {
+    /// The identifier of the run.
+    pub id: String,
+    /// Whether the run is of a workflow or a task.
+    pub kind: Kind,
+    /// The name of the workflow or task.
+    pub name: String,
+    /// The URI of the document containing the workflow or task.
+    pub document: String,
+    /// The root directory of the run.
+    pub root_dir: PathBuf,
+    /// The inputs of the run.
+    pub inputs: serde_json::Value,
+    /// The outputs of the run.
+    ///
+    /// This is `None` unless the run succeeded.
+    pub outputs: Option<serde_json::Value>,
+    /// The error message of a failed run.
+    pub error: Option<String>,
+    /// The status of the run.
+    pub status: Status,
+    /// The time the run started.
+    pub started: DateTime<Utc>,
+    /// The time the run ended.
+    ///
+    /// This is `None` if the run has not ended.
+    pub ended: Option<DateTime<Utc>>,
+}
+
+impl RunRecord {
+    /// Reads a run record from a row selecting [`RUN_COLUMNS`].
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            kind: row.get(1)?,
+            name: row.get(2)?,
+            document: row.get(3)?,
+            root_dir: row.get::<_, String>(4)?.into(),
+            inputs: json_column(row, 5)?,
+            outputs: row
+                .get::<_, Option<String>>(6)?
+                .map(|_| json_column(row, 6))
+                .transpose()?,
+            error: row.get(7)?,
+            status: row.get(8)?,
+            started: time_column(row, 9)?,
+            ended: row
+                .get::<_, Option<String>>(10)?
+                .map(|_| time_column(row, 10))
+                .transpose()?,
+        })
+    }
+}
+
+/// Represents a recorded call to a workflow or task.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct CallRecord {
+    /// The identifier of the run that made the call.
+    pub run: String,
+    /// The identifier of the call.
+    pub id: String,
+    /// The scatter index of the call.
+    pub scatter_index: Option<String>,
+    /// Whether the call is to a workflow or a task.
+    pub kind: Kind,
+    /// The name of the called workflow or task.
+    pub target: String,
+    /// The root directory of the call.
+    pub root_dir: PathBuf,
+    /// The inputs of the call.
+    pub inputs: serde_json::Value,
+    /// The outputs of the call.
+    ///
+    /// This is `None` unless the call succeeded.
+    pub outputs: Option<serde_json::Value>,
+    /// The error message of a failed call.
+    pub error: Option<String>,
+    /// The status of the call.
+    pub status: Status,
+    /// The time the call started.
+    pub started: DateTime<Utc>,
+    /// The time the call ended.
+    ///
+    /// This is `None` if the call has not ended.
+    pub ended: Option<DateTime<Utc>>,
+}
+
+impl CallRecord {
+    /// Reads a call record from a row selecting [`CALL_COLUMNS`].
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            run: row.get(0)?,
+            id: row.get(1)?,
+            scatter_index: row.get(2)?,
+            kind: row.get(3)?,
+            target: row.get(4)?,
+            root_dir: row.get::<_, String>(5)?.into(),
+            inputs: json_column(row, 6)?,
+            outputs: row
+                .get::<_, Option<String>>(7)?
+                .map(|_| json_column(row, 7))
+                .transpose()?,
+            error: row.get(8)?,
+            status: row.get(9)?,
+            started: time_column(row, 10)?,
+            ended: row
+                .get::<_, Option<String>>(11)?
+                .map(|_| time_column(row, 11))
+                .transpose()?,
+        })
+    }
+}
+
+/// Represents a recorded attempt to execute a task.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct AttemptRecord {
+    /// The attempt number, starting at zero.
+    pub attempt: u64,
+    /// The attempt directory.
+    pub attempt_dir: PathBuf,
+    /// The exit code of the attempt.
+    ///
+    /// This is `None` if the attempt failed to execute.
+    pub exit_code: Option<i32>,
+    /// The time the attempt started.
+    pub started: DateTime<Utc>,
+    /// The time the attempt ended.
+    pub ended: DateTime<Utc>,
+}
+
+/// Represents a filter of recorded runs.
+///
+/// Runs are returned most recently started first.
+#[derive(Debug, Default, Clone)]
+pub struct RunFilter {
+    /// Only include runs of the workflow or task with the given name.
+    pub name: Option<String>,
+    /// Only include runs with the given status.
+    pub status: Option<Status>,
+    /// Only include runs started at or after the given time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only include runs started before the given time.
+    pub until: Option<DateTime<Utc>>,
+    /// Only include runs with the given input values.
+    ///
+    /// Input names are not prefixed with the name of the workflow or task.
+    pub inputs: Vec<(String, serde_json::Value)>,
+    /// The maximum number of runs to include.
+    pub limit: Option<usize>,
+}
+
+/// Represents a filter of recorded calls.
+///
+/// Calls are returned most recently started first.
+#[derive(Debug, Default, Clone)]
+pub struct CallFilter {
+    /// Only include calls made by the run with the given identifier.
+    pub run: Option<String>,
+    /// Only include calls with the given call identifier.
+    pub id: Option<String>,
+    /// Only include calls to the workflow or task with the given name.
+    pub target: Option<String>,
+    /// Only include calls with the given status.
+    pub status: Option<Status>,
+    /// Only include calls started at or after the given time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only include calls started before the given time.
+    pub until: Option<DateTime<Utc>>,
+    /// Only include calls with the given input values.
+    pub inputs: Vec<(String, serde_json::Value)>,
+    /// The maximum number of calls to include.
+    pub limit: Option<usize>,
+}
+
+/// Builds the conditions of a query.
+#[derive(Default)]
+struct Conditions {
+    /// The SQL expressions of the conditions.
+    clauses: Vec<&'static str>,
+    /// The parameters of the conditions, in order.
+    params: Vec<rusqlite::types::Value>,
+}
+
+impl Conditions {
+    /// Adds a condition with a single parameter if the parameter is present.
+    fn add<T: Into<rusqlite::types::Value>>(&mut self, clause: &'static str, param: Option<T>) {
+        if let Some(param) = param {
+            self.clauses.push(clause);
+            self.params.push(param.into());
+        }
+    }
+
+    /// Adds the conditions common to runs and calls.
+    fn add_common(
+        &mut self,
+        status: Option<Status>,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        inputs: &[(String, serde_json::Value)],
+    ) {
+        self.add("status = ?", status.map(|s| s.as_str().to_string()));
+        self.add("started >= ?", since.map(format_time));
+        self.add("started < ?", until.map(format_time));
+        for (name, value) in inputs {
+            self.clauses
+                .push("json_extract(inputs, ?) IS json_extract(?, '$')");
+            self.params.push(format!("$.\"{name}\"").into());
+            self.params.push(value.to_string().into());
+        }
+    }
+
+    /// Formats the `WHERE` and `LIMIT` clauses of a query.
+    fn finish(mut self, limit: Option<usize>) -> (String, Vec<rusqlite::types::Value>) {
+        let mut sql = String::new();
+        if !self.clauses.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&self.clauses.join(" AND "));
+        }
+
+        sql.push_str(" ORDER BY started DESC, rowid DESC LIMIT ?");
+        self.params.push(
+            limit
+                .and_then(|l| i64::try_from(l).ok())
+                .unwrap_or(-1)
+                .into(),
+        );
+        (sql, self.params)
+    }
+}
+
+/// Formats a time as stored in the database.
+///
+/// The format sorts lexicographically in time order.
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string()
+}
+
+/// Reads a JSON column of a row.
+fn json_column(row: &Row<'_>, index: usize) -> rusqlite::Result<serde_json::Value> {
+    let text: String = row.get(index)?;
+    serde_json::from_str(&text).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+/// Reads a time column of a row.
+fn time_column(row: &Row<'_>, index: usize) -> rusqlite::Result<DateTime<Utc>> {
+    let text: String = row.get(index)?;
+    DateTime::parse_from_rfc3339(&text)
+        .map(|t| t.to_utc())
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into())
+        })
+}
+
+/// Serializes a value to JSON for the database.
+///
+/// Values that fail to serialize are recorded as `null`.
+fn to_json(value: &impl Serialize) -> String {
+    serde_json::to_string(value).unwrap_or_else(|e| {
+        warn!("failed to serialize value for run history: {e}");
+        "null".to_string()
+    })
+}
+
+/// Formats a path for the database.
+///
+/// Paths are made absolute so that the root directory of a call matches the
+/// directory used by its task's attempts.
+fn format_path(path: &Path) -> String {
+    std::path::absolute(path)
+        .as_deref()
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// Gets the message of an evaluation error.
+fn error_message(e: &EvaluationError) -> String {
+    match e {
+        EvaluationError::Source(e) => e.diagnostic.message().to_string(),
+        EvaluationError::Other(e) => format!("{e:#}"),
+    }
+}
+
+/// Converts the parameters of a statement into owned values so that the
+/// statement can be sent to the writer thread.
+fn owned_params(params: &[&dyn ToSql]) -> rusqlite::Result<Vec<Value>> {
+    params
+        .iter()
+        .map(|p| match p.to_sql()? {
+            ToSqlOutput::Borrowed(v) => Ok(v.into()),
+            ToSqlOutput::Owned(v) => Ok(v),
+            _ => Err(rusqlite::Error::ToSqlConversionFailure(
+                "unsupported statement parameter".into(),
+            )),
+        })
+        .collect()
+}
+
+/// Represents a message sent to the history writer thread.
+#[derive(Debug)]
+enum WriterMessage {
+    /// Executes a statement that records history with the given parameters.
+    Record(&'static str, Vec<Value>),
+    /// Notifies the given sender once the previously sent statements have been
+    /// executed.
+    Flush(mpsc::Sender<()>),
+}
+
+/// Executes the statements received by the history writer thread until every
+/// sender has been dropped.
+///
+/// Failing to record history does not fail evaluation, so errors are logged
+/// as warnings.
+fn write_history(conn: &Mutex<Connection>, receiver: mpsc::Receiver<WriterMessage>) {
+    for message in receiver {
+        match message {
+            WriterMessage::Record(sql, params) => {
+                let conn = conn.lock().expect("failed to lock history database");
+                if let Err(e) = conn.execute(sql, params_from_iter(params)) {
+                    warn!("failed to record run history: {e}");
+                }
+            }
+            WriterMessage::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+/// Represents a run history database.
+#[derive(Debug)]
+pub struct History {
+    /// The connection to the database.
+    conn: Arc<Mutex<Connection>>,
+    /// The sender of messages to the writer thread.
+    ///
+    /// This is only `None` when the history is dropped.
+    sender: Option<mpsc::Sender<WriterMessage>>,
+    /// The handle of the writer thread.
+    ///
+    /// This is only `None` when the history is dropped.
+    writer: Option<JoinHandle<()>>,
+}
+
+impl History {
+    /// Opens the history database at the given path.
+    ///
+    /// The database is created if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!(
+                    "failed to create directory `{parent}`",
+                    parent = parent.display()
+                )
+            })?;
+        }
+
+        let conn = Connection::open(path).with_context(|| {
+            format!(
+                "failed to open history database `{path}`",
+                path = path.display()
+            )
+        })?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch(&format!("PRAGMA journal_mode = WAL;{SCHEMA}"))
+            .with_context(|| {
+                format!(
+                    "failed to initialize history database `{path}`",
+                    path = path.display()
+                )
+            })?;
+
+        let conn = Arc::new(Mutex::new(conn));
+        let (sender, receiver) = mpsc::channel();
+        let writer = thread::Builder::new()
+            .name("wdl-history".to_string())
+            .spawn({
+                let conn = conn.clone();
+                move || write_history(&conn, receiver)
+            })
+            .context("failed to spawn history writer thread")?;
+
+        Ok(Self {
+            conn,
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    /// Opens the history database from the given configuration.
+    ///
+    /// Returns `Ok(None)` if run history is disabled.
+    pub(crate) fn new(config: &HistoryConfig) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        let path = match &config.path {
+            Some(path) => path.clone(),
+            None => dirs::data_dir()
+                .context("failed to determine system data directory")?
+                .join(DEFAULT_HISTORY_PATH),
+        };
+
+        Self::open(path).map(Some)
+    }
+
+    /// Gets the recorded run with the given identifier.
+    ///
+    /// Returns `Ok(None)` if the run does not exist.
+    pub fn run(&self, id: &str) -> Result<Option<RunRecord>> {
+        self.flush();
+        let conn = self.conn.lock().expect("failed to lock history database");
+        conn.query_row(
+            &format!("SELECT {RUN_COLUMNS} FROM runs WHERE id = ?"),
+            [id],
+            RunRecord::from_row,
+        )
+        .optional()
+        .with_context(|| format!("failed to query run `{id}`"))
+    }
+
+    /// Lists the recorded runs that match the given filter.
+    pub fn runs(&self, filter: &RunFilter) -> Result<Vec<RunRecord>> {
+        let mut conditions = Conditions::default();
+        conditions.add("name = ?", filter.name.clone());
+        conditions.add_common(filter.status, filter.since, filter.until, &filter.inputs);
+        let (clauses, params) = conditions.finish(filter.limit);
+
+        self.flush();
+        let conn = self.conn.lock().expect("failed to lock history database");
+        conn.prepare(&format!("SELECT {RUN_COLUMNS} FROM runs{clauses}"))?
+            .query_map(params_from_iter(params), RunRecord::from_row)?
+            .collect::<rusqlite::Result<_>>()
+            .context("failed to query runs")
+    }
+
+    /// Lists the recorded calls that match the given filter.
+    pub fn calls(&self, filter: &CallFilter) -> Result<Vec<CallRecord>> {
+        let mut conditions = Conditions::default();
+        conditions.add("run = ?", filter.run.clone());
+        conditions.add("id = ?", filter.id.clone());
+        conditions.add("target = ?", filter.target.clone());
+        conditions.add_common(filter.status, filter.since, filter.until, &filter.inputs);
+        let (clauses, params) = conditions.finish(filter.limit);
+
+        self.flush();
+        let conn = self.conn.lock().expect("failed to lock history database");
+        conn.prepare(&format!("SELECT {CALL_COLUMNS} FROM calls{clauses}"))?
+            .query_map(params_from_iter(params), CallRecord::from_row)?
+            .collect::<rusqlite::Result<_>>()
+            .context("failed to query calls")
+    }
+
+    /// Lists the recorded attempts to execute the task of the given call in
+    /// attempt order.
+    pub fn attempts(&self, call: &CallRecord) -> Result<Vec<AttemptRecord>> {
+        self.flush();
+        let conn = self.conn.lock().expect("failed to lock history database");
+        conn.prepare(
+            "SELECT attempt, attempt_dir, exit_code, started, ended FROM attempts WHERE run = ? \
+             AND call_dir = ? ORDER BY attempt",
+        )?
+        .query_map(
+            params![call.run, call.root_dir.display().to_string()],
+            |row| {
+                Ok(AttemptRecord {
+                    attempt: row.get(0)?,
+                    attempt_dir: row.get::<_, String>(1)?.into(),
+                    exit_code: row.get(2)?,
+                    started: time_column(row, 3)?,
+                    ended: time_column(row, 4)?,
+                })
+            },
+        )?
+        .collect::<rusqlite::Result<_>>()
+        .with_context(|| format!("failed to query attempts of call `{id}`", id = call.id))
+    }
+
+    /// Starts recording a run of a workflow or task.
+    pub(crate) fn start_run(
+        self: &Arc<Self>,
+        kind: Kind,
+        name: &str,
+        document: &Document,
+        root_dir: &Path,
+        inputs: &impl Serialize,
+    ) -> RunRecorder {
+        let id = format!("{id:032x}", id = rand::random::<u128>());
+        self.record(
+            "INSERT INTO runs (id, kind, name, document, root_dir, inputs, status, started) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                id,
+                kind,
+                name,
+                document.uri().as_str(),
+                format_path(root_dir),
+                to_json(inputs),
+                Status::Running,
+                format_time(Utc::now()),
+            ],
+        );
+
+        RunRecorder {
+            history: self.clone(),
+            id: id.into(),
+        }
+    }
+
+    /// Sends a statement that records history to the writer thread.
+    ///
+    /// Failing to record history does not fail evaluation, so errors are
+    /// logged as warnings.
+    fn record(&self, sql: &'static str, params: &[&dyn ToSql]) {
+        let params = match owned_params(params) {
+            Ok(params) => params,
+            Err(e) => {
+                warn!("failed to record run history: {e}");
+                return;
+            }
+        };
+
+        let sender = self.sender.as_ref().expect("history should have a sender");
+        if sender.send(WriterMessage::Record(sql, params)).is_err() {
+            warn!("failed to record run history: the writer thread has stopped");
+        }
+    }
+
+    /// Waits for the writer thread to execute the previously recorded
+    /// statements.
+    fn flush(&self) {
+        let (done, wait) = mpsc::channel();
+        let sender = self.sender.as_ref().expect("history should have a sender");
+        if sender.send(WriterMessage::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
+
+impl Drop for History {
+    fn drop(&mut self) {
+        // Dropping the sender stops the writer thread once it has executed the
+        // remaining statements
+        drop(self.sender.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Records the calls and attempts of a run into the history database.
+///
+/// This type is cheaply cloned.
+#[derive(Debug, Clone)]
+pub(crate) struct RunRecorder {
+    /// The history database.
+    history: Arc<History>,
+    /// The identifier of the run.
+    id: Arc<str>,
+}
+
+impl RunRecorder {
+    /// Gets the history database being recorded into.
+    pub fn history(&self) -> &Arc<History> {
+        &self.history
+    }
+
+    /// Records the end of the run.
+    pub fn finish(&self, result: Result<&Outputs, &EvaluationError>, canceled: bool) {
+        let (outputs, error) = match result {
+            Ok(outputs) => (Some(to_json(outputs)), None),
+            Err(e) => (None, Some(error_message(e))),
+        };
+
+        self.history.record(
+            "UPDATE runs SET outputs = ?, error = ?, status = ?, ended = ? WHERE id = ?",
+            params![
+                outputs,
+                error,
+                Status::new(result, canceled),
+                format_time(Utc::now()),
+                &*self.id,
+            ],
+        );
+    }
+
+    /// Records the start of a call made by the run.
+    pub fn start_call(
+        &self,
+        id: &str,
+        scatter_index: Option<&str>,
+        kind: Kind,
+        target: &str,
+        root_dir: &Path,
+        inputs: &impl Serialize,
+    ) {
+        self.history.record(
+            "INSERT OR REPLACE INTO calls (run, id, scatter_index, kind, target, root_dir, \
+             inputs, status, started) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &*self.id,
+                id,
+                scatter_index,
+                kind,
+                target,
+                format_path(root_dir),
+                to_json(inputs),
+                Status::Running,
+                format_time(Utc::now()),
+            ],
+        );
+    }
+
+    /// Records the end of a call made by the run.
+    pub fn finish_call(
+        &self,
+        root_dir: &Path,
+        result: Result<&Outputs, &EvaluationError>,
+        canceled: bool,
+    ) {
+        let (outputs, error) = match result {
+            Ok(outputs) => (Some(to_json(outputs)), None),
+            Err(e) => (None, Some(error_message(e))),
+        };
+
+        self.history.record(
+            "UPDATE calls SET outputs = ?, error = ?, status = ?, ended = ? WHERE run = ? AND \
+             root_dir = ?",
+            params![
+                outputs,
+                error,
+                Status::new(result, canceled),
+                format_time(Utc::now()),
+                &*self.id,
+                format_path(root_dir),
+            ],
+        );
+    }
+
+    /// Records an attempt to execute the task of a call that ended now.
+    pub fn add_attempt(
+        &self,
+        call_dir: &Path,
+        attempt: u64,
+        attempt_dir: &Path,
+        exit_code: Option<i32>,
+        started: DateTime<Utc>,
+    ) {
+        self.history.record(
+            "INSERT OR REPLACE INTO attempts (run, call_dir, attempt, attempt_dir, exit_code, \
+             started, ended) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &*self.id,
+                format_path(call_dir),
+                attempt,
+                format_path(attempt_dir),
+                exit_code,
+                format_time(started),
+                format_time(Utc::now()),
+            ],
+        );
+    }
+}
@@ -17,6 +17,7 @@ use serde_yaml_ng::Value as YamlValue;
 use wdl_analysis::Document;
 use wdl_analysis::document::Task;
 use wdl_analysis::document::Workflow;
+use wdl_analysis::schema::AnnotatedInput;
 use wdl_analysis::types::CallKind;
 use wdl_analysis::types::Coercible as _;
 use wdl_analysis::types::PrimitiveType;
@@ -682,6 +683,18 @@ pub enum Inputs {
     Workflow(WorkflowInputs),
 }
 
+impl Serialize for Inputs {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Self::Task(inputs) => inputs.serialize(serializer),
+            Self::Workflow(inputs) => inputs.serialize(serializer),
+        }
+    }
+}
+
 impl Inputs {
     /// Parses an inputs file from the given file path.
     ///
@@ -786,6 +799,80 @@ impl Inputs {
         Self::parse_object(document, object)
     }
 
+    /// Generates a JSON inputs file template for the workflow or task with the
+    /// given name.
+    ///
+    /// Required inputs are given a placeholder of the input's type (e.g.
+    /// `"Array[File]"`) and optional inputs are given their default value
+    /// where the default is constant; optional inputs without a default
+    /// expression are given `null`.
+    ///
+    /// Inputs with a default expression that is not constant are omitted from
+    /// the template.
+    pub fn template_json(document: &Document, name: &str) -> Result<String> {
+        let template: IndexMap<_, _> = Self::template_values(document, name)?
+            .into_iter()
+            .map(|(input, value)| (input.path().to_string(), value))
+            .collect();
+
+        let mut json = serde_json::to_string_pretty(&template)?;
+        json.push('\n');
+        Ok(json)
+    }
+
+    /// Generates a YAML inputs file template for the workflow or task with the
+    /// given name.
+    ///
+    /// The template contains the same inputs as [`Inputs::template_json`] with
+    /// the `parameter_meta` description of each input written as a comment.
+    pub fn template_yaml(document: &Document, name: &str) -> Result<String> {
+        let mut yaml = String::new();
+        for (input, value) in Self::template_values(document, name)? {
+            if let Some(description) = input.description() {
+                for line in description.lines() {
+                    yaml.push_str(format!("# {line}").trim_end());
+                    yaml.push('\n');
+                }
+            }
+
+            let mut mapping = serde_yaml_ng::Mapping::new();
+            mapping.insert(
+                input.path().into(),
+                serde_yaml_ng::to_value(value).context("failed to convert JSON to YAML")?,
+            );
+            yaml.push_str(&serde_yaml_ng::to_string(&mapping)?);
+        }
+
+        Ok(yaml)
+    }
+
+    /// Gets the inputs and values of an inputs file template for the workflow
+    /// or task with the given name.
+    fn template_values(
+        document: &Document,
+        name: &str,
+    ) -> Result<Vec<(AnnotatedInput, JsonValue)>> {
+        let inputs = wdl_analysis::schema::inputs(document, name).with_context(|| {
+            format!("a task or workflow named `{name}` does not exist in the document")
+        })?;
+
+        Ok(inputs
+            .into_iter()
+            .filter_map(|input| {
+                // Optional inputs without a default expression default to `null`
+                let value = if input.required() {
+                    input.ty().to_string().into()
+                } else if !input.has_default() {
+                    JsonValue::Null
+                } else {
+                    input.default()?.clone()
+                };
+
+                Some((input, value))
+            })
+            .collect())
+    }
+
     /// Gets an input value.
     pub fn get(&self, name: &str) -> Option<&Value> {
         match self {
@@ -975,3 +1062,117 @@ impl From<WorkflowInputs> for Inputs {
         Self::Workflow(inputs)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+    use wdl_analysis::Analyzer;
+    use wdl_analysis::Config as AnalysisConfig;
+    use wdl_analysis::DiagnosticsConfig;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_generates_input_templates() {
+        let root_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(
+            root_dir.path().join("source.wdl"),
+            r#"
+version 1.2
+
+task greet {
+    input {
+        String name
+        Int count = 1 + 1
+        String? greeting
+    }
+
+    command <<<>>>
+}
+
+workflow test {
+    input {
+        Array[File]+ files
+        Map[String, Int] counts = { "a": 1 }
+        Float ratio = -0.5
+    }
+
+    parameter_meta {
+        files: "The files to process"
+        ratio: { description: "The ratio to use\nwhen processing" }
+    }
+
+    hints {
+        allow_nested_inputs: true
+    }
+
+    call greet
+}
+"#,
+        )
+        .expect("failed to write WDL source file");
+
+        let analyzer = Analyzer::new(
+            AnalysisConfig::default().with_diagnostics_config(DiagnosticsConfig::except_all()),
+            |(), _, _, _| async {},
+        );
+        analyzer
+            .add_directory(root_dir.path().to_path_buf())
+            .await
+            .expect("failed to add directory");
+        let results = analyzer
+            .analyze(())
+            .await
+            .expect("failed to analyze document");
+        let document = results[0].document();
+
+        assert_eq!(
+            Inputs::template_json(document, "test").unwrap(),
+            r#"{
+  "test.files": "Array[File]+",
+  "test.counts": {
+    "a": 1
+  },
+  "test.ratio": -0.5,
+  "test.greet.name": "String",
+  "test.greet.greeting": null
+}
+"#
+        );
+
+        assert_eq!(
+            Inputs::template_yaml(document, "test").unwrap(),
+            r#"# The files to process
+test.files: Array[File]+
+test.counts:
+  a: 1
+# The ratio to use
+# when processing
+test.ratio: -0.5
+test.greet.name: String
+test.greet.greeting: null
+"#
+        );
+
+        // Optional inputs in the template should parse
+        let path = root_dir.path().join("inputs.yaml");
+        fs::write(&path, Inputs::template_yaml(document, "greet").unwrap()).unwrap();
+        let (name, inputs) = Inputs::parse(document, &path).unwrap().unwrap();
+        assert_eq!(name, "greet");
+        assert_eq!(
+            inputs.get("name").unwrap().as_string().unwrap().as_str(),
+            "String"
+        );
+        assert!(inputs.get("greeting").unwrap().is_none());
+
+        assert_eq!(
+            Inputs::template_json(document, "missing")
+                .unwrap_err()
+                .to_string(),
+            "a task or workflow named `missing` does not exist in the document"
+        );
+    }
+}
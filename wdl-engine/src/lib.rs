@@ -1,14 +1,20 @@
 //! Execution engine for Workflow Description Language (WDL) documents.
 
 mod backend;
+pub(crate) mod cache;
 pub mod config;
 pub mod diagnostics;
 mod eval;
 pub(crate) mod hash;
+pub mod history;
 pub(crate) mod http;
 mod inputs;
+pub(crate) mod localization;
 mod outputs;
 pub mod path;
+pub(crate) mod pinning;
+pub(crate) mod provenance;
+pub(crate) mod relocation;
 mod stdlib;
 pub(crate) mod tree;
 mod units;
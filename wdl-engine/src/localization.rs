@@ -0,0 +1,322 @@
+//! Implementation of task input localization.
+//!
+//! When a localization strategy is configured, the inputs of a task are
+//! localized into an `inputs` directory of each attempt directory before the
+//! task is executed rather than being used where they are.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use walkdir::WalkDir;
+
+use crate::Input;
+use crate::InputKind;
+use crate::config::LocalizationStrategy;
+use crate::http::Location;
+use crate::path::EvaluationPath;
+
+/// The name of the directory, within an attempt directory, that inputs are
+/// localized to.
+pub(crate) const INPUTS_DIR_NAME: &str = "inputs";
+
+/// Gets the paths the given inputs are localized to within an attempt
+/// directory.
+///
+/// The inputs from each parent directory are localized to a directory of
+/// their own so that co-located inputs remain co-located.
+pub(crate) fn localized_paths(attempt_dir: &Path, inputs: &[Input]) -> Result<Vec<PathBuf>> {
+    let dir = attempt_dir.join(INPUTS_DIR_NAME);
+    let mut parents = HashMap::new();
+    inputs
+        .iter()
+        .map(|input| {
+            let path = input.path();
+            let parent = match path {
+                EvaluationPath::Local(path) => path
+                    .parent()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default(),
+                EvaluationPath::Remote(url) => url
+                    .join(".")
+                    .map(Into::into)
+                    .unwrap_or_else(|_| url.to_string()),
+            };
+
+            let name = path.file_name()?.unwrap_or("input");
+            let len = parents.len();
+            let index = *parents.entry(parent).or_insert(len);
+            Ok(dir.join(index.to_string()).join(name))
+        })
+        .collect()
+}
+
+/// Localizes the given inputs into an attempt directory with the given
+/// strategy.
+///
+/// The location of each input is updated to its localized path.
+///
+/// Inputs without a local path are not localized.
+pub(crate) async fn localize_inputs(
+    strategy: LocalizationStrategy,
+    attempt_dir: &Path,
+    inputs: &mut [Input],
+) -> Result<()> {
+    let localized = localized_paths(attempt_dir, inputs)?;
+    let paths: Vec<_> = inputs
+        .iter()
+        .zip(localized)
+        .enumerate()
+        .filter_map(|(index, (input, target))| {
+            Some((index, input.local_path()?.to_path_buf(), target))
+        })
+        .collect();
+
+    let paths = tokio::task::spawn_blocking(move || {
+        for (_, source, target) in &paths {
+            localize(strategy, source, target).with_context(|| {
+                format!(
+                    "failed to localize input `{source}` to `{target}`",
+                    source = source.display(),
+                    target = target.display()
+                )
+            })?;
+        }
+
+        anyhow::Ok(paths)
+    })
+    .await
+    .context("input localization task failed")??;
+
+    for (index, _, target) in paths {
+        inputs[index].set_location(Location::Path(target));
+    }
+
+    Ok(())
+}
+
+/// Localizes a file or directory to the given target path with the given
+/// strategy.
+///
+/// Any existing file or directory at the target path is replaced.
+pub(crate) fn localize(strategy: LocalizationStrategy, source: &Path, target: &Path) -> Result<()> {
+    if let Ok(metadata) = target.symlink_metadata() {
+        if metadata.is_dir() {
+            fs::remove_dir_all(target)?;
+        } else {
+            fs::remove_file(target)?;
+        }
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let kind = if source.is_dir() {
+        InputKind::Directory
+    } else {
+        InputKind::File
+    };
+
+    match strategy {
+        LocalizationStrategy::Symlink => symlink(kind, source, target)?,
+        LocalizationStrategy::Hardlink => {
+            localize_tree(kind, source, target, |s, t| match fs::hard_link(s, t) {
+                Err(e) if e.kind() == io::ErrorKind::CrossesDevices => fs::copy(s, t).map(|_| ()),
+                r => r,
+            })?
+        }
+        LocalizationStrategy::Reflink => localize_tree(kind, source, target, reflink)?,
+        LocalizationStrategy::Copy => {
+            localize_tree(kind, source, target, |s, t| fs::copy(s, t).map(|_| ()))?
+        }
+    }
+
+    Ok(())
+}
+
+/// Localizes a file or directory by applying the given operation to the
+/// file, or to each file within the directory.
+///
+/// The directories within a directory are created at the target path.
+fn localize_tree(
+    kind: InputKind,
+    source: &Path,
+    target: &Path,
+    op: impl Fn(&Path, &Path) -> io::Result<()>,
+) -> Result<()> {
+    if kind == InputKind::File {
+        return Ok(op(source, target)?);
+    }
+
+    for entry in WalkDir::new(source) {
+        let entry = entry?;
+        let path = target.join(
+            entry
+                .path()
+                .strip_prefix(source)
+                .expect("entry should be within the source directory"),
+        );
+
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&path)?;
+        } else {
+            op(entry.path(), &path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates a symbolic link to a file or directory.
+fn symlink(kind: InputKind, source: &Path, target: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let _ = kind;
+        std::os::unix::fs::symlink(source, target)
+    }
+
+    #[cfg(windows)]
+    match kind {
+        InputKind::File => std::os::windows::fs::symlink_file(source, target),
+        InputKind::Directory => std::os::windows::fs::symlink_dir(source, target),
+    }
+}
+
+/// Clones a file with copy-on-write semantics.
+///
+/// The file is copied if the file system does not support cloning.
+#[cfg(target_os = "linux")]
+fn reflink(source: &Path, target: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let src = fs::File::open(source)?;
+    let dest = fs::File::create(target)?;
+
+    // SAFETY: both file descriptors remain open for the duration of the call
+    if unsafe { libc::ioctl(dest.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) } == 0 {
+        return dest.set_permissions(src.metadata()?.permissions());
+    }
+
+    drop(dest);
+    fs::copy(source, target).map(|_| ())
+}
+
+/// Clones a file with copy-on-write semantics.
+///
+/// Copying a file clones it on platforms and file systems that support it.
+#[cfg(not(target_os = "linux"))]
+fn reflink(source: &Path, target: &Path) -> io::Result<()> {
+    fs::copy(source, target).map(|_| ())
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::eval::trie::InputTrie;
+
+    /// Creates the inputs for the test.
+    fn inputs(dir: &Path) -> Vec<Input> {
+        fs::create_dir_all(dir.join("data").join("nested")).unwrap();
+        fs::write(dir.join("data").join("sample.bam"), "bam").unwrap();
+        fs::write(dir.join("data").join("sample.bam.bai"), "bai").unwrap();
+        fs::write(dir.join("data").join("nested").join("file.txt"), "nested").unwrap();
+        fs::write(dir.join("other.txt"), "other").unwrap();
+
+        let base_dir = EvaluationPath::Local(dir.to_path_buf());
+        let mut trie = InputTrie::new();
+        trie.insert(InputKind::File, "data/sample.bam", &base_dir)
+            .unwrap();
+        trie.insert(InputKind::File, "data/sample.bam.bai", &base_dir)
+            .unwrap();
+        trie.insert(InputKind::File, "other.txt", &base_dir)
+            .unwrap();
+        trie.insert(InputKind::Directory, "data/nested", &base_dir)
+            .unwrap();
+        trie.as_slice().into()
+    }
+
+    #[test]
+    fn it_colocates_inputs() {
+        let dir = TempDir::new().unwrap();
+        let inputs = inputs(dir.path());
+        let attempt_dir = dir.path().join("attempts").join("0");
+        let paths = localized_paths(&attempt_dir, &inputs).unwrap();
+        let paths: Vec<_> = paths
+            .iter()
+            .map(|p| {
+                p.strip_prefix(&attempt_dir)
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .replace('\\', "/")
+            })
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "inputs/0/sample.bam",
+                "inputs/0/sample.bam.bai",
+                "inputs/1/other.txt",
+                "inputs/0/nested",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_localizes_inputs() {
+        for strategy in [
+            LocalizationStrategy::Symlink,
+            LocalizationStrategy::Hardlink,
+            LocalizationStrategy::Reflink,
+            LocalizationStrategy::Copy,
+        ] {
+            let dir = TempDir::new().unwrap();
+            let mut inputs = inputs(dir.path());
+            let attempt_dir = dir.path().join("attempts").join("0");
+            localize_inputs(strategy, &attempt_dir, &mut inputs)
+                .await
+                .unwrap();
+
+            let inputs_dir = attempt_dir.join(INPUTS_DIR_NAME);
+            let bam = inputs_dir.join("0").join("sample.bam");
+            assert_eq!(inputs[0].local_path(), Some(bam.as_path()));
+            assert_eq!(fs::read_to_string(&bam).unwrap(), "bam");
+            assert_eq!(
+                fs::read_to_string(inputs_dir.join("0").join("sample.bam.bai")).unwrap(),
+                "bai"
+            );
+            assert_eq!(
+                fs::read_to_string(inputs_dir.join("0").join("nested").join("file.txt")).unwrap(),
+                "nested"
+            );
+            assert_eq!(
+                bam.symlink_metadata().unwrap().is_symlink(),
+                strategy == LocalizationStrategy::Symlink
+            );
+
+            // Writing to a writable input must not modify the original input
+            if strategy.is_writable() {
+                fs::write(&bam, "modified").unwrap();
+                assert_eq!(
+                    fs::read_to_string(dir.path().join("data").join("sample.bam")).unwrap(),
+                    "bam"
+                );
+            }
+
+            // Localizing again replaces the localized inputs
+            let mut inputs = self::inputs(dir.path());
+            localize_inputs(strategy, &attempt_dir, &mut inputs)
+                .await
+                .unwrap();
+            assert_eq!(fs::read_to_string(&bam).unwrap(), "bam");
+        }
+    }
+}
@@ -39,6 +39,11 @@ impl Outputs {
         self.values.iter().map(|(k, v)| (k.as_str(), v))
     }
 
+    /// Mutably iterates over the outputs in the collection.
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (&str, &mut Value)> + use<'_> {
+        self.values.iter_mut().map(|(k, v)| (k.as_str(), v))
+    }
+
     /// Gets an output of the collection by name.
     ///
     /// Returns `None` if an output with the given name doesn't exist.
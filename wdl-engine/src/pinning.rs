@@ -0,0 +1,264 @@
+//! Implementation of container image pinning.
+//!
+//! Before a task is executed, its container is resolved to an immutable
+//! reference by digest so that every attempt, and every shard of a scatter,
+//! within an evaluation executes with the same image.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use anyhow::Result;
+use serde::Serialize;
+use tokio::sync::OnceCell;
+use tracing::info;
+
+use crate::TaskExecutionBackend;
+use crate::v1::write_json_file;
+
+/// The name of the file recording a task's container that is written to each
+/// attempt directory.
+pub(crate) const CONTAINER_FILE_NAME: &str = "container.json";
+
+/// Determines if the given container is a reference by digest.
+pub(crate) fn is_digest_reference(container: &str) -> bool {
+    container
+        .rsplit_once('@')
+        .and_then(|(_, digest)| digest.split_once(':'))
+        .is_some_and(|(algorithm, hex)| !algorithm.is_empty() && !hex.is_empty())
+}
+
+/// Determines if the given container refers to a local image file rather than
+/// an image in a registry (e.g. an Apptainer SIF file).
+pub(crate) fn is_local_image(container: &str) -> bool {
+    container.starts_with("file://") || container.ends_with(".sif")
+}
+
+/// Gets the repository name of the given container without its tag or digest.
+///
+/// A `:` only separates a tag if it is after the last `/`, as the registry
+/// host of an image may include a port.
+pub(crate) fn repository(container: &str) -> &str {
+    let container = container
+        .split_once('@')
+        .map(|(name, _)| name)
+        .unwrap_or(container);
+    match container.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => name,
+        _ => container,
+    }
+}
+
+/// Represents the record of a task's container written to an attempt
+/// directory.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) struct ContainerRecord<'a> {
+    /// The container requested by the task.
+    pub requested: &'a str,
+    /// The reference by digest the container resolved to.
+    ///
+    /// This is `None` if the backend does not support resolving containers.
+    pub resolved: Option<&'a str>,
+}
+
+impl ContainerRecord<'_> {
+    /// Writes the record to the given attempt directory.
+    pub fn write(&self, attempt_dir: &Path) -> Result<()> {
+        write_json_file(attempt_dir.join(CONTAINER_FILE_NAME), self)
+    }
+}
+
+/// Resolves task containers to references by digest.
+///
+/// Each container is resolved at most once so that every task using the same
+/// container executes with the same image.
+#[derive(Debug, Default)]
+pub(crate) struct ContainerResolver {
+    /// The map of requested container to its resolved reference by digest.
+    ///
+    /// The resolved reference is `None` if the backend does not support
+    /// resolving containers.
+    resolved: Mutex<HashMap<String, Arc<OnceCell<Option<String>>>>>,
+}
+
+impl ContainerResolver {
+    /// Resolves the given container to a reference by digest using the given
+    /// backend.
+    ///
+    /// A container that is already a reference by digest is returned as-is.
+    ///
+    /// Returns `Ok(None)` if the backend does not support resolving
+    /// containers.
+    pub async fn resolve(
+        &self,
+        backend: &dyn TaskExecutionBackend,
+        container: &str,
+    ) -> Result<Option<String>> {
+        if is_digest_reference(container) {
+            return Ok(Some(container.to_string()));
+        }
+
+        let cell = self
+            .resolved
+            .lock()
+            .expect("failed to lock resolved containers")
+            .entry(container.to_string())
+            .or_default()
+            .clone();
+
+        cell.get_or_try_init(|| async {
+            let Some(resolve) = backend.resolve_container(container) else {
+                return Ok(None);
+            };
+
+            let resolved = resolve
+                .await
+                .with_context(|| format!("failed to resolve digest of container `{container}`"))?;
+            info!("resolved container `{container}` to `{resolved}`");
+            Ok(Some(resolved))
+        })
+        .await
+        .cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use anyhow::bail;
+    use futures::FutureExt;
+    use futures::future::BoxFuture;
+    use tokio::sync::oneshot::Receiver;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::TaskExecutionConstraints;
+    use crate::TaskExecutionResult;
+    use crate::TaskSpawnRequest;
+    use crate::Value;
+
+    /// A backend that resolves containers to a fixed digest.
+    #[derive(Default)]
+    struct ResolvingBackend {
+        /// The number of containers resolved.
+        resolved: AtomicUsize,
+    }
+
+    impl TaskExecutionBackend for ResolvingBackend {
+        fn max_concurrency(&self) -> u64 {
+            1
+        }
+
+        fn constraints(
+            &self,
+            _: &HashMap<String, Value>,
+            _: &HashMap<String, Value>,
+        ) -> Result<TaskExecutionConstraints> {
+            unimplemented!()
+        }
+
+        fn guest_inputs_dir(&self) -> Option<&'static str> {
+            None
+        }
+
+        fn needs_local_inputs(&self) -> bool {
+            false
+        }
+
+        fn spawn(
+            &self,
+            _: TaskSpawnRequest,
+            _: CancellationToken,
+        ) -> Result<Receiver<Result<TaskExecutionResult>>> {
+            bail!("not supported")
+        }
+
+        fn resolve_container<'a>(
+            &'a self,
+            container: &'a str,
+        ) -> Option<BoxFuture<'a, Result<String>>> {
+            Some(
+                async move {
+                    self.resolved.fetch_add(1, Ordering::SeqCst);
+                    Ok(format!("{name}@sha256:abc", name = repository(container)))
+                }
+                .boxed(),
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn it_resolves_containers_once() {
+        let backend = ResolvingBackend::default();
+        let resolver = ContainerResolver::default();
+
+        let (first, second) = tokio::join!(
+            resolver.resolve(&backend, "ubuntu:latest"),
+            resolver.resolve(&backend, "ubuntu:latest")
+        );
+        assert_eq!(first.unwrap().as_deref(), Some("ubuntu@sha256:abc"));
+        assert_eq!(second.unwrap().as_deref(), Some("ubuntu@sha256:abc"));
+        assert_eq!(backend.resolved.load(Ordering::SeqCst), 1);
+
+        // A reference by digest is not resolved
+        assert_eq!(
+            resolver
+                .resolve(&backend, "alpine@sha256:def")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("alpine@sha256:def")
+        );
+        assert_eq!(backend.resolved.load(Ordering::SeqCst), 1);
+
+        assert_eq!(
+            resolver
+                .resolve(&backend, "alpine")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("alpine@sha256:abc")
+        );
+        assert_eq!(backend.resolved.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn digest_references() {
+        assert!(is_digest_reference("ubuntu@sha256:abc"));
+        assert!(is_digest_reference(
+            "localhost:5000/ubuntu:22.04@sha256:abc"
+        ));
+        assert!(!is_digest_reference("ubuntu"));
+        assert!(!is_digest_reference("ubuntu:latest"));
+        assert!(!is_digest_reference("ubuntu@"));
+        assert!(!is_digest_reference("ubuntu@sha256:"));
+    }
+
+    #[test]
+    fn local_images() {
+        assert!(is_local_image("file:///images/ubuntu.sif"));
+        assert!(is_local_image("/images/ubuntu.sif"));
+        assert!(is_local_image("ubuntu.sif"));
+        assert!(!is_local_image("ubuntu"));
+        assert!(!is_local_image("docker://ubuntu:latest"));
+        assert!(!is_local_image("ubuntu@sha256:abc"));
+    }
+
+    #[test]
+    fn repositories() {
+        assert_eq!(repository("ubuntu"), "ubuntu");
+        assert_eq!(repository("ubuntu:latest"), "ubuntu");
+        assert_eq!(repository("ubuntu@sha256:abc"), "ubuntu");
+        assert_eq!(repository("ubuntu:22.04@sha256:abc"), "ubuntu");
+        assert_eq!(repository("localhost:5000/ubuntu"), "localhost:5000/ubuntu");
+        assert_eq!(
+            repository("localhost:5000/ubuntu:22.04"),
+            "localhost:5000/ubuntu"
+        );
+    }
+}
@@ -0,0 +1,493 @@
+//! Implementation of workflow execution provenance.
+//!
+//! When enabled, a provenance document is written to the root directory of a
+//! workflow evaluation upon its completion.
+//!
+//! The document records what was executed for each task call of the workflow,
+//! including the digest of the task's source, the evaluated command,
+//! container, and requirements, the digests of the task's inputs and outputs,
+//! and each attempt made to execute the task.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::mem;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use anyhow::Result;
+use blake3::Hash;
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Serialize;
+use sysinfo::System;
+use tracing::warn;
+use wdl_analysis::Document;
+
+use crate::Input;
+use crate::Outputs;
+use crate::PrimitiveValue;
+use crate::ResourceUsage;
+use crate::ReuseSource;
+use crate::Value;
+use crate::ValueSerializer;
+use crate::hash::Digest;
+use crate::hash::InputDigests;
+use crate::hash::calculate_path_digest;
+use crate::path::is_url;
+use crate::v1::write_json_file;
+
+/// The name of the provenance file written to a workflow's root directory.
+pub(crate) const PROVENANCE_FILE: &str = "provenance.json";
+
+/// The version of the provenance document format.
+///
+/// This must be incremented whenever the format of the document changes.
+const PROVENANCE_VERSION: u32 = 1;
+
+/// Represents the status of a workflow or call.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Status {
+    /// The workflow or call succeeded.
+    Succeeded,
+    /// The workflow or call failed.
+    #[default]
+    Failed,
+}
+
+/// Represents the provenance of a file or directory.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) struct PathProvenance {
+    /// The name of the input or output, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    /// The path or URL of the file or directory.
+    path: String,
+    /// The path of the file or directory inside the task's container.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guest_path: Option<String>,
+    /// Whether the path is a `file` or `directory`.
+    ///
+    /// This is `None` for remote paths.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<&'static str>,
+    /// The hex-encoded blake3 digest of the file or directory.
+    ///
+    /// This is `None` for remote paths.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    digest: Option<String>,
+    /// The error that occurred when calculating the digest of the file or
+    /// directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl PathProvenance {
+    /// Creates the provenance of the given path with the given digest.
+    ///
+    /// A failure to calculate the digest is logged and recorded rather than
+    /// failing the task.
+    fn new(
+        name: Option<&str>,
+        path: &str,
+        guest_path: Option<&str>,
+        digest: Result<Option<Digest>, &str>,
+    ) -> Self {
+        let (kind, digest, error) = match digest {
+            Ok(None) => (None, None, None),
+            Ok(Some(Digest::File(digest))) => {
+                (Some("file"), Some(digest.to_hex().to_string()), None)
+            }
+            Ok(Some(Digest::Directory(digest))) => {
+                (Some("directory"), Some(digest.to_hex().to_string()), None)
+            }
+            Err(e) => {
+                warn!("provenance of `{path}` is incomplete: {e}");
+                (None, None, Some(e.to_string()))
+            }
+        };
+
+        Self {
+            name: name.map(Into::into),
+            path: path.to_string(),
+            guest_path: guest_path.map(Into::into),
+            kind,
+            digest,
+            error,
+        }
+    }
+}
+
+/// Represents the provenance of an attempt to execute a task.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) struct AttemptProvenance {
+    /// The attempt number, starting at zero.
+    attempt: u64,
+    /// The attempt directory.
+    attempt_dir: PathBuf,
+    /// The exit code of the attempt.
+    ///
+    /// This is `None` if the attempt failed to execute.
+    exit_code: Option<i32>,
+    /// The resources used by the attempt.
+    ///
+    /// This is `None` if the backend did not report the attempt's resource
+    /// usage.
+    usage: Option<ResourceUsage>,
+    /// The time the attempt started.
+    started: DateTime<Utc>,
+    /// The time the attempt ended.
+    ended: DateTime<Utc>,
+}
+
+/// Represents the provenance of a task call.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) struct CallProvenance {
+    /// The identifier of the call.
+    id: String,
+    /// The scatter index of the call.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scatter_index: Option<String>,
+    /// The name of the task that was called.
+    task: String,
+    /// The URI of the document containing the task.
+    document: String,
+    /// The root directory of the call.
+    root_dir: PathBuf,
+    /// The hex-encoded blake3 digest of the task's source.
+    source_digest: Option<String>,
+    /// The evaluated command of the last attempt.
+    command: Option<String>,
+    /// The container of the last attempt.
+    container: Option<String>,
+    /// The digest of the container image.
+    ///
+    /// This is `None` unless the container was specified by digest.
+    container_digest: Option<String>,
+    /// The evaluated requirements of the last attempt.
+    requirements: BTreeMap<String, serde_json::Value>,
+    /// The provenance of the task's inputs.
+    inputs: Vec<PathProvenance>,
+    /// The provenance of the task's outputs.
+    outputs: Vec<PathProvenance>,
+    /// The host running the engine.
+    host: Option<String>,
+    /// The time the call started.
+    started: DateTime<Utc>,
+    /// The time the call ended.
+    ended: Option<DateTime<Utc>>,
+    /// The status of the call.
+    status: Status,
+    /// The source of the call's execution if it was reused from a previous
+    /// execution.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reused: Option<ReuseSource>,
+    /// The attempts made to execute the task.
+    attempts: Vec<AttemptProvenance>,
+}
+
+impl CallProvenance {
+    /// Sets the source digest of the task.
+    pub fn set_source(&mut self, source: &Hash) {
+        self.source_digest = Some(source.to_hex().to_string());
+    }
+
+    /// Sets the evaluated command, container, and requirements of the task.
+    pub fn set_execution(
+        &mut self,
+        command: &str,
+        container: &str,
+        requirements: &HashMap<String, Value>,
+    ) -> Result<()> {
+        self.command = Some(command.to_string());
+        self.container = Some(container.to_string());
+        self.container_digest = container
+            .split_once('@')
+            .map(|(_, digest)| digest.to_string());
+        self.requirements = requirements
+            .iter()
+            .map(|(k, v)| {
+                Ok((
+                    k.clone(),
+                    serde_json::to_value(ValueSerializer::new(v, true))?,
+                ))
+            })
+            .collect::<Result<_>>()
+            .context("failed to serialize task requirements")?;
+        Ok(())
+    }
+
+    /// Adds an attempt to execute the task that ended now.
+    pub fn add_attempt(
+        &mut self,
+        attempt: u64,
+        attempt_dir: &Path,
+        exit_code: Option<i32>,
+        usage: Option<ResourceUsage>,
+        started: DateTime<Utc>,
+    ) {
+        self.attempts.push(AttemptProvenance {
+            attempt,
+            attempt_dir: attempt_dir.to_path_buf(),
+            exit_code,
+            usage,
+            started,
+            ended: Utc::now(),
+        });
+    }
+
+    /// Sets the source of the task's execution if it was reused from a
+    /// previous execution.
+    pub fn set_reused(&mut self, reused: Option<ReuseSource>) {
+        self.reused = reused;
+    }
+
+    /// Sets the provenance of the task's inputs.
+    ///
+    /// The inputs are only recorded once as they do not change between
+    /// attempts.
+    pub fn set_inputs(&mut self, inputs: &[Input], digests: &InputDigests) {
+        if !self.inputs.is_empty() {
+            return;
+        }
+
+        for (input, digest) in inputs.iter().zip(digests.iter()) {
+            let path = match input.local_path() {
+                Some(path) => path.display().to_string(),
+                None => input.path().display().to_string(),
+            };
+
+            self.inputs.push(PathProvenance::new(
+                None,
+                &path,
+                input.guest_path().map(|p| p.as_str()),
+                digest,
+            ));
+        }
+    }
+
+    /// Sets the provenance of the task's outputs.
+    pub async fn set_outputs(&mut self, outputs: &Outputs) -> Result<()> {
+        for (name, value) in outputs.iter() {
+            let mut paths = Vec::new();
+            value.clone().visit_paths_mut(false, &mut |_, v| {
+                match v {
+                    PrimitiveValue::File(path) | PrimitiveValue::Directory(path) => {
+                        paths.push(path.as_str().to_string())
+                    }
+                    _ => unreachable!("value should be a path"),
+                }
+
+                Ok(true)
+            })?;
+
+            for path in paths {
+                let digest = if is_url(&path) {
+                    Ok(None)
+                } else {
+                    calculate_path_digest(&path)
+                        .await
+                        .map(Some)
+                        .map_err(|e| format!("failed to calculate digest of `{path}`: {e:#}"))
+                };
+
+                self.outputs.push(PathProvenance::new(
+                    Some(name),
+                    &path,
+                    None,
+                    digest.as_ref().copied().map_err(String::as_str),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Records the provenance of the task calls made during a workflow
+/// evaluation.
+#[derive(Debug, Default)]
+pub(crate) struct ProvenanceRecorder {
+    /// The recorded calls.
+    calls: Mutex<Vec<CallProvenance>>,
+}
+
+impl ProvenanceRecorder {
+    /// Starts recording the provenance of a task call.
+    ///
+    /// The call is recorded as failed unless [`CallRecord::succeeded`] is
+    /// called before the returned record is dropped.
+    pub fn start<'a>(
+        &'a self,
+        id: &str,
+        scatter_index: Option<&str>,
+        task: &str,
+        document: &Document,
+        root_dir: &Path,
+    ) -> CallRecord<'a> {
+        CallRecord {
+            recorder: self,
+            call: CallProvenance {
+                id: id.to_string(),
+                scatter_index: scatter_index.map(Into::into),
+                task: task.to_string(),
+                document: document.uri().to_string(),
+                root_dir: root_dir.to_path_buf(),
+                host: System::host_name(),
+                started: Utc::now(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Writes the provenance document for a workflow evaluation to the given
+    /// root directory.
+    pub fn write(
+        &self,
+        root_dir: &Path,
+        document: &Document,
+        workflow: &str,
+        started: DateTime<Utc>,
+        status: Status,
+    ) -> Result<()> {
+        /// Represents the provenance document of a workflow evaluation.
+        #[derive(Serialize)]
+        #[serde(rename_all = "snake_case")]
+        struct WorkflowProvenance<'a> {
+            /// The version of the document format.
+            version: u32,
+            /// The engine that evaluated the workflow.
+            engine: String,
+            /// The name of the workflow.
+            workflow: &'a str,
+            /// The URI of the document containing the workflow.
+            document: String,
+            /// The host running the engine.
+            host: Option<String>,
+            /// The time the workflow started.
+            started: DateTime<Utc>,
+            /// The time the workflow ended.
+            ended: DateTime<Utc>,
+            /// The status of the workflow.
+            status: Status,
+            /// The task calls made by the workflow.
+            calls: Vec<CallProvenance>,
+        }
+
+        let mut calls = mem::take(&mut *self.calls.lock().expect("failed to lock calls"));
+        calls.sort_by(|a, b| a.started.cmp(&b.started).then_with(|| a.id.cmp(&b.id)));
+
+        write_json_file(
+            root_dir.join(PROVENANCE_FILE),
+            &WorkflowProvenance {
+                version: PROVENANCE_VERSION,
+                engine: format!(
+                    "{name} {version}",
+                    name = env!("CARGO_PKG_NAME"),
+                    version = env!("CARGO_PKG_VERSION")
+                ),
+                workflow,
+                document: document.uri().to_string(),
+                host: System::host_name(),
+                started,
+                ended: Utc::now(),
+                status,
+                calls,
+            },
+        )
+    }
+}
+
+/// Represents the in-progress record of a task call's provenance.
+///
+/// The record is added to its recorder when dropped.
+pub(crate) struct CallRecord<'a> {
+    /// The recorder to add the call to.
+    recorder: &'a ProvenanceRecorder,
+    /// The call being recorded.
+    call: CallProvenance,
+}
+
+impl CallRecord<'_> {
+    /// Marks the call as having succeeded.
+    pub fn succeeded(&mut self) {
+        self.call.status = Status::Succeeded;
+    }
+}
+
+impl Deref for CallRecord<'_> {
+    type Target = CallProvenance;
+
+    fn deref(&self) -> &Self::Target {
+        &self.call
+    }
+}
+
+impl DerefMut for CallRecord<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.call
+    }
+}
+
+impl Drop for CallRecord<'_> {
+    fn drop(&mut self) {
+        let mut call = mem::take(&mut self.call);
+        call.ended = Some(Utc::now());
+        self.recorder
+            .calls
+            .lock()
+            .expect("failed to lock calls")
+            .push(call);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::InputKind;
+    use crate::path::EvaluationPath;
+
+    #[tokio::test]
+    async fn it_records_digest_errors() {
+        let dir = TempDir::new().expect("failed to create temporary directory");
+        let file = dir.path().join("input.txt");
+        std::fs::write(&file, "hello").unwrap();
+        let missing = dir.path().join("missing.txt");
+
+        let inputs = [
+            Input::new(InputKind::File, EvaluationPath::Local(file.clone()), None),
+            Input::new(
+                InputKind::File,
+                EvaluationPath::Local(missing.clone()),
+                None,
+            ),
+        ];
+
+        let mut call = CallProvenance::default();
+        call.set_inputs(&inputs, &InputDigests::calculate(&inputs).await);
+
+        let inputs = serde_json::to_value(&call.inputs).unwrap();
+        assert_eq!(
+            inputs[0]["digest"],
+            blake3::hash(b"hello").to_hex().to_string()
+        );
+        assert!(inputs[0].get("error").is_none());
+        assert!(inputs[1].get("digest").is_none());
+        assert!(
+            inputs[1]["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("failed to calculate digest of input")
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Implementation of workflow output relocation.
+//!
+//! When configured, the final `File` and `Directory` outputs of a workflow are
+//! relocated from the attempt directories of the calls that produced them to
+//! an output directory, which may be a cloud storage URL.
+//!
+//! Outputs from different sources that would be relocated to the same path
+//! (e.g. the outputs of a scattered call with the flat layout) are
+//! disambiguated by prefixing their file names with the name of the directory
+//! of the call that produced them.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::path::absolute;
+
+use anyhow::Context;
+use anyhow::Result;
+use anyhow::anyhow;
+use futures::future::try_join_all;
+use url::Url;
+
+use crate::HostPath;
+use crate::Outputs;
+use crate::PrimitiveValue;
+use crate::WORK_DIR_NAME;
+use crate::config::LocalizationStrategy;
+use crate::config::OutputLayout;
+use crate::config::OutputsConfig;
+use crate::http::Transferer;
+use crate::localization::localize;
+use crate::path;
+use crate::path::EvaluationPath;
+
+/// The name of the directory, within a workflow's root directory, containing
+/// the directories of its calls.
+const CALLS_DIR_NAME: &str = "calls";
+
+/// The name of the directory, within a task's root directory, containing the
+/// directories of its attempts.
+const ATTEMPTS_DIR_NAME: &str = "attempts";
+
+/// Gets the path, relative to the output directory, that an output is
+/// relocated to.
+///
+/// The `root_dir` is the root directory of the workflow that produced the
+/// output.
+fn relative_path(layout: OutputLayout, root_dir: &Path, path: &Path) -> Result<PathBuf> {
+    if layout == OutputLayout::Call
+        && let Some(relative) = call_relative_path(root_dir, path)
+    {
+        return Ok(relative);
+    }
+
+    path.file_name().map(Into::into).with_context(|| {
+        format!(
+            "output path `{path}` does not have a file name",
+            path = path.display()
+        )
+    })
+}
+
+/// Gets the path of an output relative to the directory of the call that
+/// produced it, prefixed with the call's directory name.
+///
+/// The directories of calls made by nested workflows are nested within the
+/// directory of the call to the nested workflow.
+///
+/// Returns `None` if the output was not produced by a call.
+fn call_relative_path(root_dir: &Path, path: &Path) -> Option<PathBuf> {
+    let (call, rest) = split_call_path(root_dir, path)?;
+    Some(call.join(rest))
+}
+
+/// Splits the path of an output into the relative directory of the call that
+/// produced it and the output's path relative to the call's work directory.
+///
+/// Returns `None` if the output was not produced by a call.
+fn split_call_path<'a>(root_dir: &Path, path: &'a Path) -> Option<(PathBuf, &'a Path)> {
+    let mut call = PathBuf::new();
+    let mut rest = path.strip_prefix(root_dir).ok()?;
+    loop {
+        let mut components = rest.strip_prefix(CALLS_DIR_NAME).ok()?.components();
+        call.push(components.next()?);
+        rest = components.as_path();
+
+        // The directory of a call to a nested workflow contains its own calls
+        if rest.starts_with(CALLS_DIR_NAME) {
+            continue;
+        }
+
+        // Skip the attempt number and the work directory
+        let mut components = rest.strip_prefix(ATTEMPTS_DIR_NAME).ok()?.components();
+        components.next()?;
+        rest = components.as_path();
+        return Some((call, rest.strip_prefix(WORK_DIR_NAME).unwrap_or(rest)));
+    }
+}
+
+/// Disambiguates the relative path of an output that collides with the
+/// relative path of an output from a different source.
+///
+/// The file name is prefixed with the name of the directory of the call that
+/// produced the output (e.g. `t-1-out.txt` for the second shard of a scattered
+/// call `t`); the directory names of nested calls are joined with `-`.
+///
+/// The path is returned unchanged if the output was not produced by a call.
+fn disambiguate(root_dir: &Path, source: &Path, relative: &Path) -> PathBuf {
+    let (Some((call, _)), Some(name)) = (split_call_path(root_dir, source), relative.file_name())
+    else {
+        return relative.to_path_buf();
+    };
+
+    let call = call
+        .iter()
+        .map(|c| c.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("-");
+    relative.with_file_name(format!("{call}-{name}", name = name.to_string_lossy()))
+}
+
+/// Joins a relative path to a cloud storage URL.
+fn join_url(url: &Url, relative: &Path) -> Result<Url> {
+    let mut joined = url.clone();
+    joined
+        .path_segments_mut()
+        .map_err(|_| anyhow!("URL `{url}` cannot be used as an output directory"))?
+        .pop_if_empty()
+        .extend(relative.iter().map(|c| c.to_string_lossy()));
+    Ok(joined)
+}
+
+/// Relocates the outputs of a workflow evaluated in the given root directory.
+///
+/// The `File` and `Directory` values of the outputs are updated to their
+/// relocated paths.
+///
+/// Outputs that are already URLs are not relocated.
+pub(crate) async fn relocate_outputs(
+    config: &OutputsConfig,
+    transferer: &dyn Transferer,
+    root_dir: &Path,
+    outputs: &mut Outputs,
+) -> Result<()> {
+    let mut dir: EvaluationPath = config.dir.parse()?;
+    dir.make_absolute();
+
+    let root_dir = absolute(root_dir).with_context(|| {
+        format!(
+            "failed to determine absolute path of `{path}`",
+            path = root_dir.display()
+        )
+    })?;
+
+    // Determine the sources of the outputs relocated to each path so that paths
+    // with more than one source can be disambiguated
+    let mut sources: HashMap<PathBuf, HashSet<PathBuf>> = HashMap::new();
+    for (_, value) in outputs.iter_mut() {
+        value.visit_paths_mut(false, &mut |_, value| {
+            let path = match value {
+                PrimitiveValue::File(path) => path,
+                PrimitiveValue::Directory(path) => path,
+                _ => unreachable!("only file and directory values should be visited"),
+            };
+
+            if !path::is_url(path.as_str()) {
+                let source = Path::new(path.as_str());
+                sources
+                    .entry(relative_path(config.layout, &root_dir, source)?)
+                    .or_default()
+                    .insert(source.to_path_buf());
+            }
+
+            Ok(true)
+        })?;
+    }
+
+    // Map each output to its relocated path, ensuring no two outputs would be
+    // relocated to the same path
+    let mut relocated: HashMap<PathBuf, PathBuf> = HashMap::new();
+    for (_, value) in outputs.iter_mut() {
+        value.visit_paths_mut(false, &mut |_, value| {
+            let path = match value {
+                PrimitiveValue::File(path) => path,
+                PrimitiveValue::Directory(path) => path,
+                _ => unreachable!("only file and directory values should be visited"),
+            };
+
+            if path::is_url(path.as_str()) {
+                return Ok(true);
+            }
+
+            let source = Path::new(path.as_str());
+            let mut relative = relative_path(config.layout, &root_dir, source)?;
+            if sources.get(&relative).is_some_and(|s| s.len() > 1) {
+                relative = disambiguate(&root_dir, source, &relative);
+            }
+
+            // Number the path if it still collides (e.g. for outputs not produced by
+            // a call)
+            let unnumbered = relative.clone();
+            let mut number = 1;
+            while relocated.get(&relative).is_some_and(|e| e != source) {
+                let name = unnumbered
+                    .file_name()
+                    .expect("path should have a file name")
+                    .to_string_lossy();
+                relative = unnumbered.with_file_name(format!("{number}-{name}"));
+                number += 1;
+            }
+
+            let destination = match &dir {
+                EvaluationPath::Local(dir) => dir
+                    .join(&relative)
+                    .into_os_string()
+                    .into_string()
+                    .map_err(|p| anyhow!("path `{p}` is not UTF-8", p = Path::new(&p).display()))?,
+                EvaluationPath::Remote(url) => join_url(url, &relative)?.into(),
+            };
+
+            relocated.insert(relative, source.to_path_buf());
+            *path = HostPath::new(destination);
+            Ok(true)
+        })?;
+    }
+
+    match dir {
+        EvaluationPath::Local(dir) => {
+            let strategy = config.strategy.unwrap_or(LocalizationStrategy::Copy);
+            tokio::task::spawn_blocking(move || {
+                for (relative, source) in relocated {
+                    let target = dir.join(relative);
+                    localize(strategy, &source, &target).with_context(|| {
+                        format!(
+                            "failed to relocate output `{source}` to `{target}`",
+                            source = source.display(),
+                            target = target.display()
+                        )
+                    })?;
+                }
+
+                anyhow::Ok(())
+            })
+            .await
+            .context("output relocation task failed")??;
+        }
+        EvaluationPath::Remote(url) => {
+            try_join_all(relocated.iter().map(|(relative, source)| {
+                let url = &url;
+                async move {
+                    let destination = join_url(url, relative)?;
+                    transferer
+                        .upload(source, &destination)
+                        .await
+                        .with_context(|| {
+                            format!(
+                                "failed to relocate output `{source}` to `{destination}`",
+                                source = source.display()
+                            )
+                        })
+                }
+            }))
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn relative_paths() {
+        let root_dir = Path::new("/runs/run");
+        let relative = |layout, path: &str| {
+            relative_path(layout, root_dir, Path::new(path))
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .replace('\\', "/")
+        };
+
+        let task_output = "/runs/run/calls/align-0/attempts/1/work/out/sample.bam";
+        assert_eq!(relative(OutputLayout::Flat, task_output), "sample.bam");
+        assert_eq!(
+            relative(OutputLayout::Call, task_output),
+            "align-0/out/sample.bam"
+        );
+
+        let stdout = "/runs/run/calls/align-0/attempts/0/stdout";
+        assert_eq!(relative(OutputLayout::Call, stdout), "align-0/stdout");
+
+        let nested = "/runs/run/calls/sub/calls/align/attempts/0/work/sample.bam";
+        assert_eq!(relative(OutputLayout::Call, nested), "sub/align/sample.bam");
+
+        let input = "/data/sample.bam";
+        assert_eq!(relative(OutputLayout::Call, input), "sample.bam");
+    }
+
+    #[test]
+    fn disambiguated_paths() {
+        let root_dir = Path::new("/runs/run");
+        let disambiguated = |path: &str| {
+            let path = Path::new(path);
+            disambiguate(root_dir, path, Path::new(path.file_name().unwrap()))
+                .to_str()
+                .unwrap()
+                .replace('\\', "/")
+        };
+
+        assert_eq!(
+            disambiguated("/runs/run/calls/align-1/attempts/0/work/out/sample.bam"),
+            "align-1-sample.bam"
+        );
+        assert_eq!(
+            disambiguated("/runs/run/calls/sub-0/calls/align/attempts/0/work/sample.bam"),
+            "sub-0-align-sample.bam"
+        );
+        assert_eq!(disambiguated("/data/sample.bam"), "sample.bam");
+    }
+
+    #[test]
+    fn join_urls() {
+        let url: Url = "s3://bucket/outputs".parse().unwrap();
+        assert_eq!(
+            join_url(&url, Path::new("align/sample #1.bam"))
+                .unwrap()
+                .as_str(),
+            "s3://bucket/outputs/align/sample%20%231.bam"
+        );
+
+        let url: Url = "s3://bucket/outputs/".parse().unwrap();
+        assert_eq!(
+            join_url(&url, Path::new("sample.bam")).unwrap().as_str(),
+            "s3://bucket/outputs/sample.bam"
+        );
+    }
+}
@@ -2613,11 +2613,6 @@ struct TaskData {
     cpu: f64,
     /// The allocated memory (in bytes) for the task.
     memory: i64,
-    /// The GPU allocations for the task.
-    ///
-    /// An array with one specification per allocated GPU; the specification is
-    /// execution engine-specific.
-    gpu: Array,
     /// The FPGA allocations for the task.
     ///
     /// An array with one specification per allocated FPGA; the specification is
@@ -2649,6 +2644,11 @@ struct TaskData {
 pub struct TaskValue {
     /// The immutable data for task values.
     data: Arc<TaskData>,
+    /// The GPU allocations for the task.
+    ///
+    /// An array with one specification per allocated GPU; the specification is
+    /// execution engine-specific.
+    gpu: Array,
     /// The current task attempt count.
     ///
     /// The value must be 0 the first time the task is executed and incremented
@@ -2676,14 +2676,6 @@ impl TaskValue {
                 container: constraints.container.map(Into::into),
                 cpu: constraints.cpu,
                 memory: constraints.memory,
-                gpu: Array::new_unchecked(
-                    ANALYSIS_STDLIB.array_string_type().clone(),
-                    constraints
-                        .gpu
-                        .into_iter()
-                        .map(|v| PrimitiveValue::new_string(v).into())
-                        .collect(),
-                ),
                 fpga: Array::new_unchecked(
                     ANALYSIS_STDLIB.array_string_type().clone(),
                     constraints
@@ -2711,6 +2703,14 @@ impl TaskValue {
                     .unwrap_or_else(Object::empty),
                 ext: Object::empty(),
             }),
+            gpu: Array::new_unchecked(
+                ANALYSIS_STDLIB.array_string_type().clone(),
+                constraints
+                    .gpu
+                    .into_iter()
+                    .map(|v| PrimitiveValue::new_string(v).into())
+                    .collect(),
+            ),
             attempt,
             return_code: None,
         }
@@ -2746,7 +2746,7 @@ impl TaskValue {
     /// An array with one specification per allocated GPU; the specification is
     /// execution engine-specific.
     pub fn gpu(&self) -> &Array {
-        &self.data.gpu
+        &self.gpu
     }
 
     /// Gets the FPGA allocations for the task.
@@ -2814,6 +2814,16 @@ impl TaskValue {
         self.attempt = attempt;
     }
 
+    /// Sets the GPU allocations after the task's GPU devices were reserved.
+    pub(crate) fn set_gpu(&mut self, gpu: Vec<String>) {
+        self.gpu = Array::new_unchecked(
+            ANALYSIS_STDLIB.array_string_type().clone(),
+            gpu.into_iter()
+                .map(|v| PrimitiveValue::new_string(v).into())
+                .collect(),
+        );
+    }
+
     /// Accesses a field of the task value by name.
     ///
     /// Returns `None` if the name is not a known field name.
@@ -2837,7 +2847,7 @@ impl TaskValue {
             ),
             n if n == TASK_FIELD_CPU => Some(self.data.cpu.into()),
             n if n == TASK_FIELD_MEMORY => Some(self.data.memory.into()),
-            n if n == TASK_FIELD_GPU => Some(self.data.gpu.clone().into()),
+            n if n == TASK_FIELD_GPU => Some(self.gpu.clone().into()),
             n if n == TASK_FIELD_FPGA => Some(self.data.fpga.clone().into()),
             n if n == TASK_FIELD_DISKS => Some(self.data.disks.clone().into()),
             n if n == TASK_FIELD_ATTEMPT => Some(self.attempt.into()),
@@ -0,0 +1,170 @@
+//! The Apptainer backend tests.
+//!
+//! These tests configure the Apptainer backend to use a stub executable so
+//! that the backend can be tested without Apptainer installed.
+//!
+//! The stub executable records the images it pulls and runs the task's command
+//! on the host using the work directory and command file bind mounts.
+
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
+use wdl_analysis::Analyzer;
+use wdl_engine::Events;
+use wdl_engine::PrimitiveValue;
+use wdl_engine::TaskInputs;
+use wdl_engine::config::ApptainerBackendConfig;
+use wdl_engine::config::BackendConfig;
+use wdl_engine::config::Config;
+use wdl_engine::v1::TaskEvaluator;
+
+/// The stub `apptainer` executable.
+const APPTAINER: &str = r#"#!/bin/sh
+dir="$(dirname "$0")"
+case "$1" in
+  pull)
+    echo "$4" >> "$dir/pulls"
+    echo "image" > "$3"
+    ;;
+  exec)
+    shift
+    echo "$@" > "$dir/exec.args"
+    for arg in "$@"; do
+      case "$arg" in
+        --bind=*:/mnt/task/work) work="${arg#--bind=}"; work="${work%:/mnt/task/work}" ;;
+        --bind=*:/mnt/task/command:ro) command="${arg#--bind=}"; command="${command%:/mnt/task/command:ro}" ;;
+      esac
+    done
+    cd "$work" && exec sh "$command"
+    ;;
+esac
+"#;
+
+/// Evaluates the test task with the given image in the given output directory.
+async fn evaluate(source: &Path, config: &Config, image: &str, output: &Path) -> String {
+    let analyzer = Analyzer::default();
+    analyzer
+        .add_directory(source)
+        .await
+        .expect("failed to add directory");
+    let results = analyzer
+        .analyze(())
+        .await
+        .expect("failed to analyze document");
+    let document = results.first().expect("should have result").document();
+    let task = document.task_by_name("t").expect("should have task");
+
+    let mut inputs = TaskInputs::default();
+    inputs.set("image", PrimitiveValue::new_string(image));
+
+    let evaluator = TaskEvaluator::new(config.clone(), CancellationToken::new(), Events::none())
+        .await
+        .unwrap();
+    let outputs = evaluator
+        .evaluate(document, task, &inputs, output)
+        .await
+        .expect("failed to evaluate task")
+        .into_result()
+        .expect("task should succeed");
+
+    outputs.get("out").unwrap().as_string().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn executes_tasks_in_containers() {
+    let dir = TempDir::new().expect("failed to create temporary directory");
+    let executable = dir.path().join("apptainer");
+    fs::write(&executable, APPTAINER).unwrap();
+    fs::set_permissions(&executable, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let source = dir.path().join("source");
+    fs::create_dir(&source).unwrap();
+    fs::write(
+        source.join("source.wdl"),
+        r#"
+version 1.2
+
+task t {
+  input {
+    String image
+  }
+
+  command <<<
+    echo "hello from ${PWD}"
+  >>>
+
+  requirements {
+    container: image
+  }
+
+  output {
+    String out = read_string(stdout())
+  }
+}
+"#,
+    )
+    .unwrap();
+
+    let config = Config {
+        backends: [(
+            "default".to_string(),
+            BackendConfig::Apptainer(ApptainerBackendConfig {
+                executable: Some(executable.to_str().unwrap().to_string()),
+                image_cache_dir: Some(dir.path().join("images")),
+                ..Default::default()
+            }),
+        )]
+        .into(),
+        ..Default::default()
+    };
+
+    // Remote images should only be pulled once into the image cache
+    for output in ["first", "second"] {
+        let out = evaluate(&source, &config, "ubuntu:22.04", &dir.path().join(output)).await;
+        assert!(out.starts_with("hello from "));
+    }
+
+    let pulls = fs::read_to_string(dir.path().join("pulls")).unwrap();
+    assert_eq!(pulls.lines().collect::<Vec<_>>(), ["docker://ubuntu:22.04"]);
+
+    let args = fs::read_to_string(dir.path().join("exec.args")).unwrap();
+    assert!(args.contains("--containall"));
+    assert!(args.contains(&format!(
+        "--bind={work}:/mnt/task/work",
+        work = dir.path().join("second/attempts/0/work").display()
+    )));
+    assert!(args.contains(&dir.path().join("images").display().to_string()));
+
+    // The re-run script should execute the command with the same arguments
+    let rerun = dir.path().join("second/attempts/0/rerun.sh");
+    let output = std::process::Command::new(&rerun)
+        .output()
+        .expect("failed to run re-run script");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("hello from "));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("exec.args")).unwrap(),
+        args
+    );
+
+    // Local images should be used directly
+    let image = dir.path().join("local.sif");
+    fs::write(&image, "image").unwrap();
+    evaluate(
+        &source,
+        &config,
+        image.to_str().unwrap(),
+        &dir.path().join("third"),
+    )
+    .await;
+
+    let args = fs::read_to_string(dir.path().join("exec.args")).unwrap();
+    assert!(args.contains(&format!(" {image} ", image = image.display())));
+    let pulls = fs::read_to_string(dir.path().join("pulls")).unwrap();
+    assert_eq!(pulls.lines().count(), 1);
+}
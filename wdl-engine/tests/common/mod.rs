@@ -0,0 +1,18 @@
+//! Common functionality for the backend tests.
+
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// Writes an executable stub script.
+///
+/// Returns the path to the stub script.
+pub fn write_stub(dir: &Path, name: &str, contents: &str) -> String {
+    let path = dir.join(name);
+    fs::write(&path, contents).expect("failed to write stub");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+        .expect("failed to set stub permissions");
+    path.to_str().expect("path should be UTF-8").to_string()
+}
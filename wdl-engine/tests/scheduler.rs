@@ -0,0 +1,273 @@
+//! The generic HPC scheduler backend tests.
+//!
+//! These tests configure the backend with stub submit, status, and cancel
+//! scripts that mimic LSF so that the backend can be tested without a cluster.
+//!
+//! The stub submit script records its arguments and runs the submitted job
+//! script synchronously; the stub status script records its arguments and
+//! reports the job as done.
+//!
+//! Other tests submit jobs that never run to test how failures of the status
+//! command are handled.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+
+use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
+use wdl_analysis::Analyzer;
+use wdl_engine::Events;
+use wdl_engine::config::BackendConfig;
+use wdl_engine::config::Config;
+use wdl_engine::config::SchedulerBackendConfig;
+use wdl_engine::v1::TaskEvaluator;
+
+use crate::common::write_stub;
+
+/// The stub submit script.
+const SUBMIT: &str = r#"#!/bin/sh
+echo "$@" > "$(dirname "$0")/submit.args"
+for arg in "$@"; do script="$arg"; done
+"$script"
+echo "Job <42> is submitted to queue <normal>."
+"#;
+
+/// The stub status script.
+const STATUS: &str = r#"#!/bin/sh
+echo "$@" > "$(dirname "$0")/status.args"
+echo "DONE"
+"#;
+
+/// The stub cancel script.
+const CANCEL: &str = "#!/bin/sh\n";
+
+/// The stub submit script that submits a job that never runs.
+const SUBMIT_LOST: &str = "#!/bin/sh\necho \"Job <43> is submitted to queue <normal>.\"\n";
+
+/// The stub status script that fails as the job is not known to the scheduler.
+const STATUS_UNKNOWN: &str = r#"#!/bin/sh
+echo "Job <43> is not found" >&2
+exit 255
+"#;
+
+#[tokio::test]
+async fn submits_tasks_with_templates() {
+    let dir = TempDir::new().expect("failed to create temporary directory");
+    let bin = dir.path().join("bin");
+    fs::create_dir(&bin).unwrap();
+    let submit = write_stub(&bin, "bsub", SUBMIT);
+    let status = write_stub(&bin, "bjobs", STATUS);
+    let cancel = write_stub(&bin, "bkill", CANCEL);
+
+    let source = dir.path().join("source");
+    fs::create_dir(&source).unwrap();
+    fs::write(
+        source.join("source.wdl"),
+        r#"
+version 1.2
+
+task t {
+  input {
+    env String SCHEDULER = "lsf"
+  }
+
+  command <<<
+    echo "hello from $SCHEDULER"
+    exit 3
+  >>>
+
+  requirements {
+    cpu: 1.5
+    memory: "2 GiB"
+    disks: "10 GiB"
+    return_codes: 3
+  }
+
+  output {
+    String out = read_string(stdout())
+  }
+}
+"#,
+    )
+    .unwrap();
+
+    let analyzer = Analyzer::default();
+    analyzer
+        .add_directory(&source)
+        .await
+        .expect("failed to add directory");
+    let results = analyzer
+        .analyze(())
+        .await
+        .expect("failed to analyze document");
+    let document = results.first().expect("should have result").document();
+    let task = document.task_by_name("t").expect("should have task");
+
+    let config = Config {
+        backends: [(
+            "default".to_string(),
+            BackendConfig::Scheduler(SchedulerBackendConfig {
+                scheduler: Some("LSF".to_string()),
+                submit: vec![submit, "-J".to_string(), "{name}".to_string()],
+                job_id_pattern: Some(r"Job <(\d+)>".to_string()),
+                status: vec![
+                    status,
+                    "-noheader".to_string(),
+                    "-o".to_string(),
+                    "stat".to_string(),
+                    "{job_id}".to_string(),
+                ],
+                finished_states: vec!["DONE".to_string(), "EXIT".to_string()],
+                cancel: vec![cancel, "{job_id}".to_string()],
+                cpu_args: vec!["-n".to_string(), "{cpu}".to_string()],
+                memory_args: vec!["-M".to_string(), "{memory_mb}M".to_string()],
+                disk_args: vec!["-R".to_string(), "rusage[tmp={disk_gb}G]".to_string()],
+                args: vec!["-q".to_string(), "normal".to_string()],
+                interval: Some(1),
+                ..Default::default()
+            }),
+        )]
+        .into(),
+        ..Default::default()
+    };
+
+    let evaluator = TaskEvaluator::new(config, CancellationToken::new(), Events::none())
+        .await
+        .unwrap();
+    let evaluated = evaluator
+        .evaluate(
+            document,
+            task,
+            &Default::default(),
+            dir.path().join("outputs"),
+        )
+        .await
+        .expect("failed to evaluate task");
+
+    assert_eq!(evaluated.exit_code(), 3);
+    let outputs = evaluated.into_result().expect("task should succeed");
+    assert_eq!(
+        outputs.get("out").unwrap().as_string().unwrap().as_str(),
+        "hello from lsf"
+    );
+
+    // Check the templates were expanded for the submit command
+    let args = fs::read_to_string(bin.join("submit.args")).unwrap();
+    let args: Vec<_> = args.split_whitespace().collect();
+    assert_eq!(args[0], "-J");
+    assert!(args[1].starts_with("t-"), "unexpected job name `{args:?}`");
+    assert_eq!(
+        &args[2..10],
+        [
+            "-n",
+            "2",
+            "-M",
+            "2048M",
+            "-R",
+            "rusage[tmp=10G]",
+            "-q",
+            "normal"
+        ]
+    );
+    assert!(args[10].ends_with("/outputs/attempts/0/job.sh"));
+
+    // Check the job identifier was passed to the status command
+    assert_eq!(
+        fs::read_to_string(bin.join("status.args")).unwrap(),
+        "-noheader -o stat 42\n"
+    );
+}
+
+#[tokio::test]
+async fn retries_failed_status_commands() {
+    let dir = TempDir::new().expect("failed to create temporary directory");
+    let bin = dir.path().join("bin");
+    fs::create_dir(&bin).unwrap();
+    let submit = write_stub(&bin, "bsub", SUBMIT_LOST);
+    let status = write_stub(&bin, "bjobs", STATUS_UNKNOWN);
+    let cancel = write_stub(&bin, "bkill", CANCEL);
+
+    let source = dir.path().join("source");
+    fs::create_dir(&source).unwrap();
+    fs::write(
+        source.join("source.wdl"),
+        r#"
+version 1.2
+
+task t {
+  command <<<
+    echo "never runs"
+  >>>
+}
+"#,
+    )
+    .unwrap();
+
+    let analyzer = Analyzer::default();
+    analyzer
+        .add_directory(&source)
+        .await
+        .expect("failed to add directory");
+    let results = analyzer
+        .analyze(())
+        .await
+        .expect("failed to analyze document");
+    let document = results.first().expect("should have result").document();
+    let task = document.task_by_name("t").expect("should have task");
+
+    let backend = SchedulerBackendConfig {
+        scheduler: Some("LSF".to_string()),
+        submit: vec![submit],
+        job_id_pattern: Some(r"Job <(\d+)>".to_string()),
+        status: vec![status, "{job_id}".to_string()],
+        finished_states: vec!["DONE".to_string(), "EXIT".to_string()],
+        cancel: vec![cancel, "{job_id}".to_string()],
+        interval: Some(1),
+        ..Default::default()
+    };
+
+    // Without an unknown job pattern, failures of the status command are retried
+    // before the task fails
+    for (pattern, expected) in [
+        (
+            None,
+            "failed to query the state of LSF job 43 3 consecutive times",
+        ),
+        (
+            Some(r"Job <\d+> is not found".to_string()),
+            "LSF job 43 terminated without running the task's command",
+        ),
+    ] {
+        let config = Config {
+            backends: [(
+                "default".to_string(),
+                BackendConfig::Scheduler(SchedulerBackendConfig {
+                    unknown_job_pattern: pattern,
+                    ..backend.clone()
+                }),
+            )]
+            .into(),
+            ..Default::default()
+        };
+
+        let evaluator = TaskEvaluator::new(config, CancellationToken::new(), Events::none())
+            .await
+            .unwrap();
+        let e = evaluator
+            .evaluate(
+                document,
+                task,
+                &Default::default(),
+                dir.path().join("outputs"),
+            )
+            .await
+            .expect_err("task should fail");
+        assert!(
+            format!("{e:?}").contains(expected),
+            "expected `{expected}` in error `{e:?}`"
+        );
+    }
+}
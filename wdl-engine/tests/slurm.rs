@@ -0,0 +1,228 @@
+//! The Slurm backend tests.
+//!
+//! These tests place stub `sbatch` and `squeue` scripts on `PATH` so that the
+//! Slurm backend can be tested without a Slurm cluster.
+//!
+//! The stub `sbatch` script records its arguments and runs the submitted batch
+//! script synchronously; the stub `squeue` script reports an empty queue.
+//!
+//! The stubs are then replaced to test that the backend falls back to `sacct`
+//! when `squeue` fails and fails the task only after repeated failures to
+//! query the state of a job.
+
+#![cfg(unix)]
+
+mod common;
+
+use std::env;
+use std::fs;
+
+use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
+use wdl_analysis::Analyzer;
+use wdl_engine::Events;
+use wdl_engine::config::BackendConfig;
+use wdl_engine::config::Config;
+use wdl_engine::config::SlurmBackendConfig;
+use wdl_engine::v1::TaskEvaluator;
+
+use crate::common::write_stub;
+
+/// The stub `sbatch` script.
+const SBATCH: &str = r#"#!/bin/sh
+echo "$@" > "$(dirname "$0")/sbatch.args"
+for arg in "$@"; do
+  case "$arg" in
+    --chdir=*) dir="${arg#--chdir=}" ;;
+    --output=*) out="${arg#--output=}" ;;
+    --error=*) err="${arg#--error=}" ;;
+    *) script="$arg" ;;
+  esac
+done
+(cd "$dir" && sh "$script" > "$out" 2> "$err")
+echo "1234;cluster"
+"#;
+
+/// The stub `squeue` script.
+const SQUEUE: &str = "#!/bin/sh\n";
+
+/// The stub `sbatch` script that submits a job that never runs.
+const SBATCH_LOST: &str = "#!/bin/sh\necho 1235\n";
+
+/// The stub `squeue` script that fails as if the controller is unavailable.
+const SQUEUE_UNAVAILABLE: &str = r#"#!/bin/sh
+echo "slurm_load_jobs error: Unable to contact slurm controller" >&2
+exit 1
+"#;
+
+/// The stub `sacct` script that reports the job as running for the first
+/// query of its state and as completed thereafter.
+const SACCT: &str = r#"#!/bin/sh
+case "$*" in
+  *--format=State*)
+    count="$(dirname "$0")/sacct.count"
+    echo x >> "$count"
+    if [ "$(wc -l < "$count")" -eq 1 ]; then echo RUNNING; else echo COMPLETED; fi
+    ;;
+esac
+"#;
+
+/// The stub `sacct` script that fails as if job accounting is unavailable.
+const SACCT_UNAVAILABLE: &str = "#!/bin/sh\nexit 1\n";
+
+#[tokio::test]
+async fn submits_tasks_with_sbatch() {
+    let dir = TempDir::new().expect("failed to create temporary directory");
+    let bin = dir.path().join("bin");
+    fs::create_dir(&bin).unwrap();
+    write_stub(&bin, "sbatch", SBATCH);
+    write_stub(&bin, "squeue", SQUEUE);
+
+    // SAFETY: this is the only test in this test binary and the runtime is single
+    // threaded, so no other threads are reading the environment
+    unsafe {
+        env::set_var(
+            "PATH",
+            format!(
+                "{bin}:{path}",
+                bin = bin.display(),
+                path = env::var("PATH").unwrap_or_default()
+            ),
+        );
+    }
+
+    let source = dir.path().join("source");
+    fs::create_dir(&source).unwrap();
+    fs::write(
+        source.join("source.wdl"),
+        r#"
+version 1.2
+
+task t {
+  command <<<
+    echo "hello from slurm"
+    exit 3
+  >>>
+
+  requirements {
+    cpu: 1.5
+    memory: "2 GiB"
+    disks: "10 GiB"
+    max_retries: 1
+    return_codes: 3
+  }
+
+  output {
+    String out = read_string(stdout())
+  }
+}
+"#,
+    )
+    .unwrap();
+
+    let analyzer = Analyzer::default();
+    analyzer
+        .add_directory(&source)
+        .await
+        .expect("failed to add directory");
+    let results = analyzer
+        .analyze(())
+        .await
+        .expect("failed to analyze document");
+    let document = results.first().expect("should have result").document();
+    let task = document.task_by_name("t").expect("should have task");
+
+    let config = Config {
+        backends: [(
+            "default".to_string(),
+            BackendConfig::Slurm(SlurmBackendConfig {
+                partition: Some("short".to_string()),
+                interval: Some(1),
+                ..Default::default()
+            }),
+        )]
+        .into(),
+        ..Default::default()
+    };
+
+    let evaluator = TaskEvaluator::new(config, CancellationToken::new(), Events::none())
+        .await
+        .unwrap();
+    let evaluated = evaluator
+        .evaluate(
+            document,
+            task,
+            &Default::default(),
+            dir.path().join("outputs"),
+        )
+        .await
+        .expect("failed to evaluate task");
+
+    assert_eq!(evaluated.exit_code(), 3);
+    let outputs = evaluated.into_result().expect("task should succeed");
+    assert_eq!(
+        outputs.get("out").unwrap().as_string().unwrap().as_str(),
+        "hello from slurm"
+    );
+
+    // The re-run script should execute the command locally
+    let output = std::process::Command::new(dir.path().join("outputs/attempts/0/rerun.sh"))
+        .output()
+        .expect("failed to run re-run script");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "hello from slurm\n"
+    );
+
+    // Check the requirements were mapped to `sbatch` arguments
+    let args = fs::read_to_string(bin.join("sbatch.args")).unwrap();
+    for arg in [
+        "--parsable",
+        "--cpus-per-task=2",
+        "--mem=2048M",
+        "--tmp=10G",
+        "--requeue",
+        "--partition=short",
+    ] {
+        assert!(
+            args.split_whitespace().any(|a| a == arg),
+            "expected `{arg}` in `sbatch` arguments `{args}`"
+        );
+    }
+
+    // The state of the job should be queried from `sacct` when `squeue` fails
+    write_stub(&bin, "squeue", SQUEUE_UNAVAILABLE);
+    write_stub(&bin, "sacct", SACCT);
+    let evaluated = evaluator
+        .evaluate(
+            document,
+            task,
+            &Default::default(),
+            dir.path().join("accounted"),
+        )
+        .await
+        .expect("failed to evaluate task");
+    assert_eq!(evaluated.exit_code(), 3);
+    assert_eq!(
+        fs::read_to_string(bin.join("sacct.count"))
+            .unwrap()
+            .lines()
+            .count(),
+        2,
+        "expected the job state to be queried until `sacct` reports it completed"
+    );
+
+    // The task should fail only after repeated failures to query the job's state
+    write_stub(&bin, "sbatch", SBATCH_LOST);
+    write_stub(&bin, "sacct", SACCT_UNAVAILABLE);
+    let e = evaluator
+        .evaluate(document, task, &Default::default(), dir.path().join("lost"))
+        .await
+        .expect_err("task should fail");
+    assert!(
+        format!("{e:?}")
+            .contains("failed to query the state of Slurm job 1235 3 consecutive times"),
+        "unexpected error `{e:?}`"
+    );
+}
@@ -162,10 +162,10 @@ impl Visitor for CallInputSpacingRule {
         call.inner()
             .children_with_tokens()
             .for_each(|c| match c.kind() {
-                SyntaxKind::Whitespace => {
-                    if c.as_token().expect("should be token").text().contains('\n') {
-                        newline_seen += 1;
-                    }
+                SyntaxKind::Whitespace
+                    if c.as_token().expect("should be token").text().contains('\n') =>
+                {
+                    newline_seen += 1;
                 }
                 SyntaxKind::CallInputItemNode => {
                     if newline_seen == 0 && inputs > 1 {
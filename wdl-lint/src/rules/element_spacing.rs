@@ -729,12 +729,10 @@ fn skip_preceding_comments(syntax: &SyntaxNode) -> NodeOrToken<SyntaxNode, Synta
                 // else is on that line.
                 if let Some(before_cur) = cur.prev_token() {
                     match before_cur.kind() {
-                        SyntaxKind::Whitespace => {
-                            if before_cur.text().contains('\n') {
-                                // The 'cur' comment is on is on its own line.
-                                // It "belongs" to the current element.
-                                preceding_comments.push(cur.clone());
-                            }
+                        SyntaxKind::Whitespace if before_cur.text().contains('\n') => {
+                            // The 'cur' comment is on is on its own line.
+                            // It "belongs" to the current element.
+                            preceding_comments.push(cur.clone());
                         }
                         _ => {
                             // The 'cur' comment is on the same line as this
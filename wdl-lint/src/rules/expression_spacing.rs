@@ -217,21 +217,20 @@ impl Visitor for ExpressionSpacingRule {
         }
 
         match expr {
-            Expr::LogicalNot(_) | Expr::Negation(_) => {
-                // No following spacing allowed
+            // No following spacing allowed
+            Expr::LogicalNot(_) | Expr::Negation(_)
                 if expr
                     .inner()
                     .children_with_tokens()
                     .filter(|t| t.kind() == SyntaxKind::Whitespace)
                     .count()
-                    > 0
-                {
-                    diagnostics.exceptable_add(
-                        prefix_whitespace(expr.span()),
-                        SyntaxElement::from(expr.inner().clone()),
-                        &self.exceptable_nodes(),
-                    );
-                }
+                    > 0 =>
+            {
+                diagnostics.exceptable_add(
+                    prefix_whitespace(expr.span()),
+                    SyntaxElement::from(expr.inner().clone()),
+                    &self.exceptable_nodes(),
+                );
             }
             Expr::Parenthesized(_) => {
                 // Find the actual open and close parentheses
@@ -251,43 +251,43 @@ impl Visitor for TrailingCommaRule {
                 LiteralExpr::Array(_)
                 | LiteralExpr::Map(_)
                 | LiteralExpr::Object(_)
-                | LiteralExpr::Struct(_) => {
+                | LiteralExpr::Struct(_)
+                    if l.inner().to_string().contains('\n') && l.inner().children().count() > 1 =>
+                {
                     // Check if array is multi-line
-                    if l.inner().to_string().contains('\n') && l.inner().children().count() > 1 {
-                        let last_child = l.inner().children().last();
-                        if let Some(last_child) = last_child {
-                            let (next_comma, comma_is_next) = find_next_comma(&last_child);
-                            match next_comma {
-                                Some(comma) => {
-                                    if !comma_is_next {
-                                        // Comma found, but not next, extraneous trivia
-                                        diagnostics.exceptable_add(
-                                            extraneous_content(Span::new(
-                                                last_child.text_range().end().into(),
-                                                (comma.text_range().start()
-                                                    - last_child.text_range().end())
-                                                .into(),
-                                            )),
-                                            SyntaxElement::from(l.inner().clone()),
-                                            &self.exceptable_nodes(),
-                                        );
-                                    }
-                                }
-                                _ => {
-                                    // No comma found, report missing
+                    let last_child = l.inner().children().last();
+                    if let Some(last_child) = last_child {
+                        let (next_comma, comma_is_next) = find_next_comma(&last_child);
+                        match next_comma {
+                            Some(comma) => {
+                                if !comma_is_next {
+                                    // Comma found, but not next, extraneous trivia
                                     diagnostics.exceptable_add(
-                                        missing_trailing_comma(
-                                            last_child
-                                                .last_token()
-                                                .expect("item should have tokens")
-                                                .text_range()
-                                                .into(),
-                                        ),
+                                        extraneous_content(Span::new(
+                                            last_child.text_range().end().into(),
+                                            (comma.text_range().start()
+                                                - last_child.text_range().end())
+                                            .into(),
+                                        )),
                                         SyntaxElement::from(l.inner().clone()),
                                         &self.exceptable_nodes(),
                                     );
                                 }
                             }
+                            _ => {
+                                // No comma found, report missing
+                                diagnostics.exceptable_add(
+                                    missing_trailing_comma(
+                                        last_child
+                                            .last_token()
+                                            .expect("item should have tokens")
+                                            .text_range()
+                                            .into(),
+                                    ),
+                                    SyntaxElement::from(l.inner().clone()),
+                                    &self.exceptable_nodes(),
+                                );
+                            }
                         }
                     }
                 }